@@ -0,0 +1,19 @@
+-- Create the "password_history" table to store previous password hashes of each user.
+-- It is used to prevent users from reusing their most recent passwords.
+CREATE TABLE password_history
+(
+    -- Unique identifier for each history entry.
+    id            UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- The user the password belonged to; history is removed together with the user.
+    user_id       UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- The Argon2 PHC string of the previous password.
+    password_hash VARCHAR(255) NOT NULL,
+    -- Timestamp indicating when the password stopped being the current one.
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Index used to load and prune the most recent entries of a user.
+CREATE INDEX password_history_user_id_created_at_idx ON password_history (user_id, created_at DESC);
+
+-- Revoke all default privileges on the password_history table from the PUBLIC role.
+REVOKE ALL ON password_history FROM PUBLIC;
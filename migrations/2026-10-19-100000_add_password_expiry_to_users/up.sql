@@ -0,0 +1,8 @@
+-- Track when the password was last changed, to enforce a maximum password age.
+ALTER TABLE users
+    ADD COLUMN password_changed_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set by an administrator to force the user to change password on next login.
+    ADD COLUMN must_change_password BOOLEAN     NOT NULL DEFAULT FALSE;
+
+-- Existing users are considered to have set their password when the account was created.
+UPDATE users SET password_changed_at = created_at;
@@ -0,0 +1,36 @@
+-- Create the "sessions" table: one row per login, i.e. per refresh-token family.
+-- Access tokens carry the session id in the "sid" claim, so revoking a session
+-- invalidates both its refresh token and its access tokens.
+CREATE TABLE sessions
+(
+    -- Unique identifier for each session.
+    id                          UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    -- The user who owns the session.
+    user_id                     UUID         NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- SHA-256 (hex) of the current refresh token; the token itself is never stored.
+    refresh_token_hash          VARCHAR(64)  NOT NULL UNIQUE,
+    -- SHA-256 (hex) of the previous refresh token, used to detect reuse of a rotated token.
+    previous_refresh_token_hash VARCHAR(64),
+    -- Human readable device description (from the X-Device-Name header or the user agent).
+    device                      VARCHAR(255),
+    -- Raw User-Agent header of the client that created the session.
+    user_agent                  TEXT,
+    -- IP address of the client that created the session.
+    ip_address                  VARCHAR(45),
+    -- Timestamp indicating when the session was created.
+    created_at                  TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- Timestamp of the last request or refresh made with the session.
+    last_seen_at                TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- The refresh token cannot be used after this timestamp.
+    expires_at                  TIMESTAMPTZ  NOT NULL,
+    -- Set when the session is revoked (logout, remote revoke, refresh token reuse).
+    revoked_at                  TIMESTAMPTZ
+);
+
+-- Index used to list the sessions of a user.
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+-- Index used to detect reuse of rotated refresh tokens.
+CREATE INDEX sessions_previous_refresh_token_hash_idx ON sessions (previous_refresh_token_hash);
+
+-- Revoke all default privileges on the sessions table from the PUBLIC role.
+REVOKE ALL ON sessions FROM PUBLIC;
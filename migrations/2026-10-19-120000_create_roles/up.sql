@@ -0,0 +1,85 @@
+-- Create the "roles" table: named groups of permissions assigned to users.
+-- Role names are copied into the "roles" claim of the access token.
+CREATE TABLE roles
+(
+    -- Unique identifier for each role.
+    id          UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- Role name used in tokens and guards (e.g. "admin").
+    name        VARCHAR(32) NOT NULL UNIQUE,
+    -- Human readable description of the role.
+    description TEXT        NOT NULL DEFAULT '',
+    -- Timestamp indicating when the role was created.
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Create the "permissions" table: single actions that can be granted to roles.
+CREATE TABLE permissions
+(
+    -- Unique identifier for each permission.
+    id          UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- Permission name in the form "resource:action" (e.g. "users:manage").
+    name        VARCHAR(64) NOT NULL UNIQUE,
+    -- Human readable description of the permission.
+    description TEXT        NOT NULL DEFAULT ''
+);
+
+-- Create the "role_permissions" table: which permissions each role grants.
+CREATE TABLE role_permissions
+(
+    -- The role granting the permission.
+    role_id       UUID NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    -- The granted permission.
+    permission_id UUID NOT NULL REFERENCES permissions (id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+-- Create the "user_roles" table: which roles each user has.
+CREATE TABLE user_roles
+(
+    -- The user holding the role.
+    user_id     UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- The role assigned to the user.
+    role_id     UUID        NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    -- Timestamp indicating when the role was assigned.
+    assigned_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, role_id)
+);
+
+-- Index used to find the users holding a role.
+CREATE INDEX user_roles_role_id_idx ON user_roles (role_id);
+
+-- Default roles.
+INSERT INTO roles (name, description)
+VALUES ('student', 'Default role of every registered user'),
+       ('teacher', 'Can follow the progress of their students'),
+       ('admin', 'Support staff with access to user management');
+
+-- Default permissions.
+INSERT INTO permissions (name, description)
+VALUES ('profile:manage', 'Manage the own account, password and sessions'),
+       ('students:read', 'Read the progress of students'),
+       ('users:read', 'Search and view user accounts'),
+       ('users:manage', 'Disable, enable, unlock and reset user accounts'),
+       ('roles:assign', 'Assign and remove user roles'),
+       ('audit:read', 'Read the audit log');
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON
+    (r.name = 'student' AND p.name IN ('profile:manage')) OR
+    (r.name = 'teacher' AND p.name IN ('profile:manage', 'students:read')) OR
+    (r.name = 'admin');
+
+-- Existing users become students.
+INSERT INTO user_roles (user_id, role_id)
+SELECT u.id, r.id
+FROM users u
+         CROSS JOIN roles r
+WHERE r.name = 'student';
+
+-- Revoke all default privileges on the new tables from the PUBLIC role.
+REVOKE ALL ON roles FROM PUBLIC;
+REVOKE ALL ON permissions FROM PUBLIC;
+REVOKE ALL ON role_permissions FROM PUBLIC;
+REVOKE ALL ON user_roles FROM PUBLIC;
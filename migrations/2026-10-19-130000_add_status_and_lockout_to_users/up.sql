@@ -0,0 +1,8 @@
+ALTER TABLE users
+    -- Account status: 'active' accounts can log in, 'disabled' accounts cannot.
+    ADD COLUMN status                VARCHAR(32) NOT NULL DEFAULT 'active'
+        CONSTRAINT users_status_check CHECK (status IN ('active', 'disabled')),
+    -- Consecutive failed logins since the last successful login or lockout.
+    ADD COLUMN failed_login_attempts INTEGER     NOT NULL DEFAULT 0,
+    -- Logins are refused until this timestamp after too many failed attempts.
+    ADD COLUMN locked_until          TIMESTAMPTZ;
@@ -0,0 +1,9 @@
+UPDATE users
+SET status = 'disabled'
+WHERE status IN ('suspended', 'pending_verification');
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS status_expires_at,
+    DROP COLUMN IF EXISTS status_reason,
+    DROP CONSTRAINT users_status_check,
+    ADD CONSTRAINT users_status_check CHECK (status IN ('active', 'disabled'));
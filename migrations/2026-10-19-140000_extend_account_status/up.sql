@@ -0,0 +1,10 @@
+-- Account status can now also be 'suspended' (temporary, usually with an expiry)
+-- and 'pending_verification' (the account exists but cannot log in yet).
+ALTER TABLE users
+    DROP CONSTRAINT users_status_check,
+    ADD CONSTRAINT users_status_check
+        CHECK (status IN ('active', 'suspended', 'disabled', 'pending_verification')),
+    -- Why the account is not active, shown to the user when a login is refused.
+    ADD COLUMN status_reason     TEXT,
+    -- When set, the account becomes active again after this timestamp.
+    ADD COLUMN status_expires_at TIMESTAMPTZ;
@@ -0,0 +1,62 @@
+-- Create the "audit_events" table: append-only security log (logins, password
+-- changes, admin actions, token revocations). User ids are stored without foreign
+-- keys so events outlive the accounts they mention.
+CREATE TABLE audit_events
+(
+    -- Unique identifier for each event.
+    id             UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- Timestamp indicating when the event happened.
+    occurred_at    TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- What happened, as "area.action" (e.g. "login.failure", "admin.users.set_status").
+    action         VARCHAR(64) NOT NULL,
+    -- Whether the attempted action succeeded ('success') or was refused ('failure').
+    outcome        VARCHAR(16) NOT NULL
+        CONSTRAINT audit_events_outcome_check CHECK (outcome IN ('success', 'failure')),
+    -- The user who performed the action, if known.
+    actor_id       UUID,
+    -- The user the action was performed on, if any.
+    target_user_id UUID,
+    -- IP address of the client that made the request.
+    ip_address     VARCHAR(45),
+    -- Raw User-Agent header of the client that made the request.
+    user_agent     TEXT,
+    -- Request id (X-Request-Id) correlating the event with application logs.
+    request_id     VARCHAR(64),
+    -- Event specific data.
+    details        JSONB       NOT NULL DEFAULT '{}'::jsonb
+);
+
+-- Indexes used by the admin query endpoint.
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at);
+CREATE INDEX audit_events_actor_id_idx ON audit_events (actor_id);
+CREATE INDEX audit_events_target_user_id_idx ON audit_events (target_user_id);
+CREATE INDEX audit_events_action_idx ON audit_events (action);
+
+-- Audit events can only be inserted. Updates and deletes are refused unless the
+-- transaction explicitly opts in with SET LOCAL app.audit_maintenance = 'on'
+-- (used only to anonymize the events of erased accounts).
+CREATE FUNCTION audit_events_append_only() RETURNS trigger AS
+$$
+BEGIN
+    IF current_setting('app.audit_maintenance', true) = 'on' THEN
+        RETURN COALESCE(NEW, OLD);
+    END IF;
+    RAISE EXCEPTION 'audit_events is append-only (% refused)', TG_OP
+        USING ERRCODE = 'insufficient_privilege';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE
+    ON audit_events
+    FOR EACH ROW
+EXECUTE FUNCTION audit_events_append_only();
+
+CREATE TRIGGER audit_events_no_truncate
+    BEFORE TRUNCATE
+    ON audit_events
+    FOR EACH STATEMENT
+EXECUTE FUNCTION audit_events_append_only();
+
+-- Revoke all default privileges on the audit_events table from the PUBLIC role.
+REVOKE ALL ON audit_events FROM PUBLIC;
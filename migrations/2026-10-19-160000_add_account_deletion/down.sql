@@ -0,0 +1,13 @@
+DROP TABLE IF EXISTS outbox_events;
+
+UPDATE users
+SET status = 'disabled'
+WHERE status = 'deleted';
+
+DROP INDEX IF EXISTS users_purge_after_idx;
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS purge_after,
+    DROP CONSTRAINT users_status_check,
+    ADD CONSTRAINT users_status_check
+        CHECK (status IN ('active', 'suspended', 'disabled', 'pending_verification'));
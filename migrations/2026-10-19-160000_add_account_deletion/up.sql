@@ -0,0 +1,36 @@
+-- Accounts deleted by their owner get status 'deleted' and are erased for good
+-- once "purge_after" has passed; until then support staff can restore them.
+ALTER TABLE users
+    DROP CONSTRAINT users_status_check,
+    ADD CONSTRAINT users_status_check
+        CHECK (status IN ('active', 'suspended', 'disabled', 'pending_verification', 'deleted')),
+    -- When a deleted account (and its data) is permanently erased.
+    ADD COLUMN purge_after TIMESTAMPTZ;
+
+-- Index used by the purge job to find accounts whose grace period is over.
+CREATE INDEX users_purge_after_idx ON users (purge_after) WHERE purge_after IS NOT NULL;
+
+-- Create the "outbox_events" table: events for other services (transactional
+-- outbox). Rows are written in the same transaction as the change they describe
+-- and marked as published by the relay that forwards them.
+CREATE TABLE outbox_events
+(
+    -- Unique identifier for each event.
+    id           UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- Event type (e.g. "user.deletion_scheduled", "user.deleted").
+    event_type   VARCHAR(64) NOT NULL,
+    -- Id of the entity the event is about (for user events, the user id).
+    aggregate_id UUID        NOT NULL,
+    -- Event payload.
+    payload      JSONB       NOT NULL DEFAULT '{}'::jsonb,
+    -- Timestamp indicating when the event was created.
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set once the event has been delivered.
+    published_at TIMESTAMPTZ
+);
+
+-- Index used by the relay to find the events still to publish.
+CREATE INDEX outbox_events_unpublished_idx ON outbox_events (created_at) WHERE published_at IS NULL;
+
+-- Revoke all default privileges on the outbox_events table from the PUBLIC role.
+REVOKE ALL ON outbox_events FROM PUBLIC;
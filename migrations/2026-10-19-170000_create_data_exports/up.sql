@@ -0,0 +1,27 @@
+-- Create the "data_exports" table: personal data archives generated in the
+-- background when they are too large to be returned directly.
+CREATE TABLE data_exports
+(
+    -- Unique identifier for each export.
+    id           UUID PRIMARY KEY     DEFAULT gen_random_uuid(),
+    -- The user the archive belongs to; exports are deleted with the user.
+    user_id      UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Generation status.
+    status       VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'ready', 'failed')),
+    -- The generated archive, once ready.
+    archive      JSONB,
+    -- Timestamp indicating when the export was requested.
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Timestamp indicating when generation finished (successfully or not).
+    completed_at TIMESTAMPTZ,
+    -- After this instant the archive can no longer be downloaded and is removed.
+    expires_at   TIMESTAMPTZ NOT NULL
+);
+
+-- Index used to list a user's exports and to remove the expired ones.
+CREATE INDEX data_exports_user_id_idx ON data_exports (user_id);
+CREATE INDEX data_exports_expires_at_idx ON data_exports (expires_at);
+
+-- Revoke all default privileges on the data_exports table from the PUBLIC role.
+REVOKE ALL ON data_exports FROM PUBLIC;
@@ -0,0 +1,13 @@
+REVOKE ALL ON users FROM ketchapp_app_user;
+
+-- The role is shared by all databases of the cluster: it is kept if another
+-- database still grants it privileges.
+DO
+$$
+BEGIN
+    DROP ROLE IF EXISTS ketchapp_app_user;
+EXCEPTION
+    WHEN dependent_objects_still_exist THEN
+        RAISE NOTICE 'ketchapp_app_user is still used by another database, not dropped';
+END;
+$$;
@@ -0,0 +1,18 @@
+-- Create the non-privileged role used for queries run on behalf of an
+-- authenticated user. The application switches to it with SET LOCAL ROLE after
+-- setting "app.current_user_id", so the row-level security policies on "users"
+-- apply (superusers and table owners bypass them).
+DO
+$$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'ketchapp_app_user') THEN
+        CREATE ROLE ketchapp_app_user NOLOGIN NOINHERIT;
+    END IF;
+END;
+$$;
+
+-- The role the application connects with must be allowed to switch to it.
+GRANT ketchapp_app_user TO CURRENT_USER;
+
+-- The role can only see and change the rows allowed by the policies of "users".
+GRANT SELECT, UPDATE, DELETE ON users TO ketchapp_app_user;
@@ -0,0 +1,80 @@
+-- Create the "oauth_clients" table: applications allowed to obtain tokens for
+-- users through the OAuth 2.0 authorization code flow. The row id is the
+-- client_id.
+CREATE TABLE oauth_clients
+(
+    -- Unique identifier for each client, used as the OAuth client_id.
+    id                 UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    -- Name shown to users on the consent screen.
+    name               VARCHAR(100) NOT NULL,
+    -- SHA-256 (hex) of the client secret; NULL for public clients (SPAs, mobile apps).
+    client_secret_hash VARCHAR(64),
+    -- Redirect URIs accepted for the client, compared by exact match.
+    redirect_uris      TEXT[]       NOT NULL,
+    -- Scopes the client may request.
+    allowed_scopes     TEXT[]       NOT NULL DEFAULT '{}',
+    -- First-party clients (KetchApp's own apps) do not ask users for consent.
+    first_party        BOOLEAN      NOT NULL DEFAULT FALSE,
+    -- The admin who registered the client.
+    created_by         UUID         REFERENCES users (id) ON DELETE SET NULL,
+    -- Timestamp indicating when the client was registered.
+    created_at         TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+-- Create the "oauth_authorization_codes" table: short-lived, single-use codes
+-- issued by /oauth/authorize and exchanged at /oauth/token.
+CREATE TABLE oauth_authorization_codes
+(
+    -- SHA-256 (hex) of the code; the code itself is never stored.
+    code_hash      VARCHAR(64) PRIMARY KEY,
+    -- The client the code was issued to.
+    client_id      UUID         NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    -- The user who authorized the client.
+    user_id        UUID         NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- The redirect URI of the authorization request; the token request must repeat it.
+    redirect_uri   TEXT         NOT NULL,
+    -- Space separated scopes granted to the client.
+    scope          TEXT         NOT NULL DEFAULT '',
+    -- PKCE code challenge (S256) sent with the authorization request.
+    code_challenge VARCHAR(128) NOT NULL,
+    -- Timestamp indicating when the code was issued.
+    created_at     TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- The code cannot be exchanged after this timestamp.
+    expires_at     TIMESTAMPTZ  NOT NULL,
+    -- Set when the code is exchanged; a second exchange revokes the issued session.
+    consumed_at    TIMESTAMPTZ,
+    -- The session created by the exchange.
+    session_id     UUID
+);
+
+-- Index used to remove expired codes.
+CREATE INDEX oauth_authorization_codes_expires_at_idx ON oauth_authorization_codes (expires_at);
+
+-- Create the "oauth_consents" table: clients a user has allowed to act on their behalf.
+CREATE TABLE oauth_consents
+(
+    -- The user who gave consent.
+    user_id    UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- The client the consent was given to.
+    client_id  UUID        NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    -- Scopes the user agreed to.
+    scopes     TEXT[]      NOT NULL DEFAULT '{}',
+    -- Timestamp indicating when consent was first given.
+    granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Timestamp indicating when the consented scopes last changed.
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, client_id)
+);
+
+-- Sessions created through OAuth belong to a client and carry the granted scopes;
+-- their refresh tokens can only be used at /oauth/token by the same client.
+ALTER TABLE sessions
+    -- The OAuth client the session was issued to (NULL for first-party logins).
+    ADD COLUMN oauth_client_id UUID REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    -- Space separated scopes granted to the client.
+    ADD COLUMN scope           TEXT;
+
+-- Revoke all default privileges on the new tables from the PUBLIC role.
+REVOKE ALL ON oauth_clients FROM PUBLIC;
+REVOKE ALL ON oauth_authorization_codes FROM PUBLIC;
+REVOKE ALL ON oauth_consents FROM PUBLIC;
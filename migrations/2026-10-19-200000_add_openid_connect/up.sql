@@ -0,0 +1,13 @@
+-- OpenID Connect: the authorization request may carry a nonce that is echoed in
+-- the ID token, and ID tokens report when the user actually logged in.
+ALTER TABLE oauth_authorization_codes
+    -- Nonce sent by the client with the authorization request.
+    ADD COLUMN nonce     VARCHAR(255),
+    -- Timestamp of the login that authorized the request (auth_time claim).
+    ADD COLUMN auth_time TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+ALTER TABLE sessions
+    -- For OAuth sessions, the timestamp of the user's login that authorized the
+    -- client; ID tokens issued on refresh keep reporting it. NULL for first-party
+    -- logins, where it is created_at.
+    ADD COLUMN auth_time TIMESTAMPTZ;
@@ -0,0 +1,35 @@
+-- Create the "oauth_device_codes" table: pending authorizations of the device
+-- authorization grant (RFC 8628). The device polls /oauth/token with the device
+-- code while the user confirms the user code from another, logged-in device.
+CREATE TABLE oauth_device_codes
+(
+    -- SHA-256 (hex) of the device code; the code itself is never stored.
+    device_code_hash VARCHAR(64) PRIMARY KEY,
+    -- Short code the user types on the verification page, without separators.
+    user_code        VARCHAR(16) NOT NULL UNIQUE,
+    -- The client that started the flow.
+    client_id        UUID        NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    -- Space separated scopes requested by the client.
+    scope            TEXT        NOT NULL DEFAULT '',
+    -- Authorization status.
+    status           VARCHAR(16) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'approved', 'denied', 'consumed')),
+    -- The user who approved or denied the request.
+    user_id          UUID REFERENCES users (id) ON DELETE CASCADE,
+    -- Timestamp of the login of the approving user (auth_time claim).
+    auth_time        TIMESTAMPTZ,
+    -- Minimum number of seconds between two polls; grows on slow_down.
+    interval_secs    INTEGER     NOT NULL,
+    -- Timestamp of the last poll at the token endpoint.
+    last_polled_at   TIMESTAMPTZ,
+    -- Timestamp indicating when the flow was started.
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- The codes cannot be used after this timestamp.
+    expires_at       TIMESTAMPTZ NOT NULL
+);
+
+-- Index used to remove expired codes.
+CREATE INDEX oauth_device_codes_expires_at_idx ON oauth_device_codes (expires_at);
+
+-- Revoke all default privileges on the oauth_device_codes table from the PUBLIC role.
+REVOKE ALL ON oauth_device_codes FROM PUBLIC;
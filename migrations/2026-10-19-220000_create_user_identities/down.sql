@@ -0,0 +1,9 @@
+DROP TABLE IF EXISTS external_login_states;
+DROP TABLE IF EXISTS user_identities;
+
+-- Accounts without a password get an unusable one: they can no longer sign in
+-- but keep their data.
+UPDATE users SET password = '!' WHERE password IS NULL;
+
+ALTER TABLE users
+    ALTER COLUMN password SET NOT NULL;
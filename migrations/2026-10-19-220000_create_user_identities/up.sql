@@ -0,0 +1,56 @@
+-- Accounts created through an upstream identity provider have no local
+-- password until the user sets one.
+ALTER TABLE users
+    -- Argon2 hash of the password, NULL for accounts that only sign in through
+    -- an external identity provider.
+    ALTER COLUMN password DROP NOT NULL;
+
+-- Create the "user_identities" table: accounts at upstream identity providers
+-- (Google, Microsoft, ...) linked to a local user.
+CREATE TABLE user_identities
+(
+    -- Unique identifier of the link.
+    id            UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    -- The local user the identity belongs to.
+    user_id       UUID         NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Identifier of the provider in the configuration (e.g. "google").
+    provider      VARCHAR(64)  NOT NULL,
+    -- Stable identifier of the account at the provider (the "sub" claim).
+    subject       VARCHAR(255) NOT NULL,
+    -- Email address reported by the provider at the last login.
+    email         VARCHAR(255),
+    -- Timestamp indicating when the identity was linked.
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- Timestamp of the last login through this identity.
+    last_login_at TIMESTAMPTZ,
+    -- An account at a provider can be linked to a single user.
+    UNIQUE (provider, subject)
+);
+
+-- Index used to list the identities of a user.
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
+
+-- Create the "external_login_states" table: logins started towards an upstream
+-- provider and not yet completed. The row is consumed by the callback.
+CREATE TABLE external_login_states
+(
+    -- SHA-256 (hex) of the state parameter sent to the provider.
+    state_hash    VARCHAR(64) PRIMARY KEY,
+    -- Identifier of the provider the login was started with.
+    provider      VARCHAR(64)  NOT NULL,
+    -- PKCE code verifier sent to the provider's token endpoint.
+    code_verifier VARCHAR(128) NOT NULL,
+    -- Nonce expected in the provider's ID token.
+    nonce         VARCHAR(64)  NOT NULL,
+    -- Timestamp indicating when the login was started.
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- The callback is rejected after this timestamp.
+    expires_at    TIMESTAMPTZ  NOT NULL
+);
+
+-- Index used to remove expired states.
+CREATE INDEX external_login_states_expires_at_idx ON external_login_states (expires_at);
+
+-- Revoke all default privileges on the new tables from the PUBLIC role.
+REVOKE ALL ON user_identities FROM PUBLIC;
+REVOKE ALL ON external_login_states FROM PUBLIC;
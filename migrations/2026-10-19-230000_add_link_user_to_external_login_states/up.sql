@@ -0,0 +1,5 @@
+-- A login started from the account settings links the provider's account to
+-- the signed-in user instead of signing in.
+ALTER TABLE external_login_states
+    -- The user the identity will be linked to, NULL when the login signs in.
+    ADD COLUMN link_user_id UUID REFERENCES users (id) ON DELETE CASCADE;
@@ -0,0 +1,31 @@
+-- Create the "personal_access_tokens" table: long-lived API keys created by users
+-- for their scripts. The key is shown once at creation and only its hash is kept.
+CREATE TABLE personal_access_tokens
+(
+    -- Unique identifier of the token.
+    id           UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    -- The user the token acts for.
+    user_id      UUID         NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Name chosen by the user to recognise the token.
+    name         VARCHAR(100) NOT NULL,
+    -- First characters of the key (e.g. "kapp_pat_1a2b3c4d"), shown in listings.
+    token_prefix VARCHAR(32)  NOT NULL,
+    -- SHA-256 (hex) of the key; the key itself is never stored.
+    token_hash   VARCHAR(64)  NOT NULL UNIQUE,
+    -- Scopes granted to the token.
+    scopes       TEXT[]       NOT NULL DEFAULT '{}',
+    -- Timestamp indicating when the token was created.
+    created_at   TIMESTAMPTZ  NOT NULL DEFAULT NOW(),
+    -- The token is rejected after this timestamp; NULL if it never expires.
+    expires_at   TIMESTAMPTZ,
+    -- Timestamp of the last request authenticated with the token.
+    last_used_at TIMESTAMPTZ,
+    -- Timestamp indicating when the token was revoked, NULL while it is valid.
+    revoked_at   TIMESTAMPTZ
+);
+
+-- Index used to list the tokens of a user.
+CREATE INDEX personal_access_tokens_user_id_idx ON personal_access_tokens (user_id);
+
+-- Revoke all default privileges on the personal_access_tokens table from the PUBLIC role.
+REVOKE ALL ON personal_access_tokens FROM PUBLIC;
@@ -0,0 +1,5 @@
+ALTER TABLE oauth_clients
+    DROP COLUMN IF EXISTS idle_timeout_secs,
+    DROP COLUMN IF EXISTS refresh_token_ttl_secs,
+    DROP COLUMN IF EXISTS access_token_ttl_secs,
+    DROP COLUMN IF EXISTS audience;
@@ -0,0 +1,10 @@
+-- Token settings of each client. NULL falls back to the global configuration.
+ALTER TABLE oauth_clients
+    -- Audience (aud) of the access tokens issued to the client.
+    ADD COLUMN audience VARCHAR(255),
+    -- Lifetime of the access tokens, in seconds.
+    ADD COLUMN access_token_ttl_secs INTEGER CHECK (access_token_ttl_secs > 0),
+    -- Lifetime of the sessions (refresh tokens) granted to the client, in seconds.
+    ADD COLUMN refresh_token_ttl_secs INTEGER CHECK (refresh_token_ttl_secs > 0),
+    -- Sessions unused for longer than this many seconds cannot be refreshed.
+    ADD COLUMN idle_timeout_secs INTEGER CHECK (idle_timeout_secs > 0);
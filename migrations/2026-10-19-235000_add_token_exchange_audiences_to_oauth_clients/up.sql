@@ -0,0 +1,4 @@
+-- Audiences a client may request with the token exchange grant (RFC 8693) when it
+-- calls a downstream service on behalf of a user. Empty: no exchange allowed.
+ALTER TABLE oauth_clients
+    ADD COLUMN token_exchange_audiences TEXT[] NOT NULL DEFAULT '{}';
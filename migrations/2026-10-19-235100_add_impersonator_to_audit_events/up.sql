@@ -0,0 +1,7 @@
+-- Admin who performed the action while impersonating the user (actor_id and
+-- target_user_id then name the impersonated user). NULL for ordinary events.
+ALTER TABLE audit_events
+    ADD COLUMN impersonator_id UUID;
+
+CREATE INDEX audit_events_impersonator_id_idx ON audit_events (impersonator_id)
+    WHERE impersonator_id IS NOT NULL;
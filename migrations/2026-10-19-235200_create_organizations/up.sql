@@ -0,0 +1,42 @@
+-- Create the "organizations" table: schools (or other customers) KetchApp is sold to.
+CREATE TABLE organizations
+(
+    -- Unique identifier of the organization.
+    id         UUID PRIMARY KEY      DEFAULT gen_random_uuid(),
+    -- Display name (e.g. "Liceo Galilei").
+    name       VARCHAR(255) NOT NULL,
+    -- Unique, URL-friendly identifier (e.g. "liceo-galilei").
+    slug       VARCHAR(64)  NOT NULL UNIQUE,
+    -- The user who created the organization, NULL once that account is deleted.
+    created_by UUID         REFERENCES users (id) ON DELETE SET NULL,
+    -- Timestamp indicating when the organization was created.
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+-- Create the "memberships" table: the users of an organization and the roles they
+-- have in it. Organization roles are independent of the global roles in user_roles.
+CREATE TABLE memberships
+(
+    -- The organization.
+    organization_id UUID        NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+    -- The member.
+    user_id         UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- Roles of the member in the organization.
+    roles           TEXT[]      NOT NULL
+        CONSTRAINT memberships_roles_check
+            CHECK (cardinality(roles) > 0 AND roles <@ ARRAY ['owner', 'teacher', 'student']),
+    -- Timestamp indicating when the user joined the organization.
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+-- Index used to list the organizations of a user.
+CREATE INDEX memberships_user_id_idx ON memberships (user_id);
+
+-- Organization the tokens of a session are issued for (org_id claim), NULL for none.
+ALTER TABLE sessions
+    ADD COLUMN organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+-- Revoke all default privileges on the new tables from the PUBLIC role.
+REVOKE ALL ON organizations FROM PUBLIC;
+REVOKE ALL ON memberships FROM PUBLIC;
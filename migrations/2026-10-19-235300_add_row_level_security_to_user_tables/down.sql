@@ -0,0 +1,31 @@
+REVOKE ALL ON outbox_events FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_outbox_events ON outbox_events;
+ALTER TABLE outbox_events
+    DISABLE ROW LEVEL SECURITY;
+
+REVOKE ALL ON audit_events FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_audit_events ON audit_events;
+ALTER TABLE audit_events
+    DISABLE ROW LEVEL SECURITY;
+
+REVOKE ALL ON password_history FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_password_history ON password_history;
+ALTER TABLE password_history
+    DISABLE ROW LEVEL SECURITY;
+
+REVOKE SELECT (id, name) ON oauth_clients FROM ketchapp_app_user;
+
+REVOKE ALL ON oauth_consents FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_oauth_consents ON oauth_consents;
+ALTER TABLE oauth_consents
+    DISABLE ROW LEVEL SECURITY;
+
+REVOKE ALL ON personal_access_tokens FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_personal_access_tokens ON personal_access_tokens;
+ALTER TABLE personal_access_tokens
+    DISABLE ROW LEVEL SECURITY;
+
+REVOKE ALL ON sessions FROM ketchapp_app_user;
+DROP POLICY IF EXISTS own_sessions ON sessions;
+ALTER TABLE sessions
+    DISABLE ROW LEVEL SECURITY;
@@ -0,0 +1,50 @@
+-- Row-level security for the tables that queries run on behalf of a user
+-- (as "ketchapp_app_user", see 2026-10-19-180000_create_app_user_role) read and
+-- change: sessions, personal access tokens, consents and password history of
+-- that user, plus the audit and outbox events written in the same transaction.
+-- The role the application connects with owns the tables and is not affected.
+
+-- Sessions: listed and revoked by their owner.
+ALTER TABLE sessions
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_sessions ON sessions
+    USING (user_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, UPDATE ON sessions TO ketchapp_app_user;
+
+-- Personal access tokens: listed, created and revoked by their owner.
+ALTER TABLE personal_access_tokens
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_personal_access_tokens ON personal_access_tokens
+    USING (user_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, INSERT, UPDATE ON personal_access_tokens TO ketchapp_app_user;
+
+-- Consents: listed and revoked by the user who granted them.
+ALTER TABLE oauth_consents
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_oauth_consents ON oauth_consents
+    USING (user_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, DELETE ON oauth_consents TO ketchapp_app_user;
+
+-- Names of the clients shown next to the consents; secrets stay out of reach.
+GRANT SELECT (id, name) ON oauth_clients TO ketchapp_app_user;
+
+-- Password history: checked, extended and pruned when the user changes password.
+ALTER TABLE password_history
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_password_history ON password_history
+    USING (user_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, INSERT, DELETE ON password_history TO ketchapp_app_user;
+
+-- Audit events: only events about the user can be written (and read back).
+ALTER TABLE audit_events
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_audit_events ON audit_events
+    USING (target_user_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, INSERT ON audit_events TO ketchapp_app_user;
+
+-- Outbox events: only events about the user can be queued (and read back).
+ALTER TABLE outbox_events
+    ENABLE ROW LEVEL SECURITY;
+CREATE POLICY own_outbox_events ON outbox_events
+    USING (aggregate_id::text = current_setting('app.current_user_id'));
+GRANT SELECT, INSERT ON outbox_events TO ketchapp_app_user;
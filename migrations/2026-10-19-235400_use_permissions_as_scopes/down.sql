@@ -0,0 +1,21 @@
+DELETE
+FROM permissions
+WHERE name IN ('admin', 'study.read', 'study.write');
+
+INSERT INTO permissions (name, description)
+VALUES ('profile:manage', 'Manage the own account, password and sessions'),
+       ('students:read', 'Read the progress of students'),
+       ('users:read', 'Search and view user accounts'),
+       ('users:manage', 'Disable, enable, unlock and reset user accounts'),
+       ('roles:assign', 'Assign and remove user roles'),
+       ('audit:read', 'Read the audit log')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON
+    (r.name = 'student' AND p.name IN ('profile:manage')) OR
+    (r.name = 'teacher' AND p.name IN ('profile:manage', 'students:read')) OR
+    (r.name = 'admin')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,23 @@
+-- Permissions become the scopes of the access tokens: role_permissions decides which
+-- roles grant each scope, and the whole table is the scope catalogue published in the
+-- OpenAPI document. The "resource:action" permissions seeded with the roles were never
+-- checked by the API and are replaced by the scopes it enforces.
+DELETE
+FROM permissions
+WHERE name IN ('profile:manage', 'students:read', 'users:read', 'users:manage', 'roles:assign',
+               'audit:read');
+
+-- Scopes granted through roles.
+INSERT INTO permissions (name, description)
+VALUES ('admin', 'Manage users, OAuth clients and the audit log'),
+       ('study.read', 'Read study sessions, timers and statistics'),
+       ('study.write', 'Record study sessions and timers');
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+         JOIN permissions p ON
+    (r.name = 'student' AND p.name IN ('study.read', 'study.write')) OR
+    (r.name = 'teacher' AND p.name IN ('study.read')) OR
+    (r.name = 'admin' AND p.name IN ('admin'));
+
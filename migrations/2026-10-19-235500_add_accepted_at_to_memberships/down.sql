@@ -0,0 +1,7 @@
+-- Pending invitations would become memberships without the column.
+DELETE
+FROM memberships
+WHERE accepted_at IS NULL;
+
+ALTER TABLE memberships
+    DROP COLUMN IF EXISTS accepted_at;
@@ -0,0 +1,8 @@
+-- Timestamp indicating when the user accepted the invitation to the organization.
+-- NULL while the invitation is pending: the user is not a member until then.
+ALTER TABLE memberships
+    ADD COLUMN accepted_at TIMESTAMPTZ;
+
+-- Memberships created before invitations existed count as accepted.
+UPDATE memberships
+SET accepted_at = created_at;
@@ -0,0 +1,66 @@
+//! Builds the breached-password bloom filter loaded by the API at startup.
+//!
+//! Usage: `build_breach_filter <hibp-sha1-dump.txt> <output.bin> [false_positive_rate]`
+//!
+//! The input is the "SHA-1, ordered by hash" dump from haveibeenpwned.com,
+//! one `SHA1HEX:COUNT` entry per line.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::process::ExitCode;
+
+use ketchapp_auth_api::services::breached_passwords::{parse_hibp_line, BreachedPasswordFilter};
+
+const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!(
+            "Usage: {} <hibp-sha1-dump.txt> <output.bin> [false_positive_rate]",
+            args[0]
+        );
+        return ExitCode::FAILURE;
+    }
+    let false_positive_rate = match args.get(3).map(|rate| rate.parse::<f64>()) {
+        None => DEFAULT_FALSE_POSITIVE_RATE,
+        Some(Ok(rate)) if rate > 0.0 && rate < 1.0 => rate,
+        Some(_) => {
+            eprintln!("false_positive_rate must be a number between 0 and 1");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match build(&args[1], &args[2], false_positive_rate) {
+        Ok((inserted, skipped)) => {
+            println!(
+                "Wrote {} with {} hashes ({} malformed lines skipped)",
+                args[2], inserted, skipped
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to build breached password filter: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(input: &str, output: &str, false_positive_rate: f64) -> io::Result<(u64, u64)> {
+    // * Primo passaggio: conta le righe per dimensionare il filtro
+    let expected = BufReader::new(File::open(input)?).lines().count() as u64;
+    let mut filter = BreachedPasswordFilter::with_capacity(expected, false_positive_rate);
+
+    let (mut inserted, mut skipped) = (0, 0);
+    for line in BufReader::new(File::open(input)?).lines() {
+        match parse_hibp_line(&line?) {
+            Some(digest) => {
+                filter.insert_digest(&digest);
+                inserted += 1;
+            }
+            None => skipped += 1,
+        }
+    }
+    filter.save(output)?;
+    Ok((inserted, skipped))
+}
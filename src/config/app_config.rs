@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -5,10 +7,228 @@ pub struct AppConfig {
     pub port: u16,
     pub host: String,
     pub rust_log: Option<String>,
+    // * Reverse proxy di cui ci si fida per l'header X-Forwarded-For; senza, l'IP del client è
+    // * quello della connessione
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
     pub database_url: String,
     pub jwt_issuer: String,
     pub jwt_audience: String,
     pub jwt_exp_secs: u64,
+    #[serde(default = "default_refresh_token_exp_secs")]
+    pub refresh_token_exp_secs: u64,
+    // * Le sessioni inutilizzate più a lungo non si possono rinnovare (assente = nessun limite)
+    pub session_idle_timeout_secs: Option<u64>,
+    #[serde(default = "default_jwt_private_key_path")]
+    pub jwt_private_key_path: String,
+    #[serde(default)]
+    pub password_peppers: Vec<PepperConfig>,
+    pub breached_password_filter: Option<String>,
+    // * Login falliti consecutivi prima del blocco temporaneo dell'account (0 = mai)
+    #[serde(default = "default_max_failed_logins")]
+    pub max_failed_logins: u32,
+    #[serde(default = "default_lockout_secs")]
+    pub lockout_secs: u64,
+    // * Giorni in cui un account cancellato può ancora essere ripristinato prima della cancellazione definitiva
+    #[serde(default = "default_account_deletion_grace_days")]
+    pub account_deletion_grace_days: u32,
+    #[serde(default = "default_account_purge_interval_secs")]
+    pub account_purge_interval_secs: u64,
+    // * Endpoint che riceve gli eventi dell'outbox; senza, gli eventi restano in coda
+    pub outbox_webhook_url: Option<String>,
+    #[serde(default = "default_outbox_relay_interval_secs")]
+    pub outbox_relay_interval_secs: u64,
+    // * Oltre questo numero di eventi di audit l'esportazione dei dati viene generata in background
+    #[serde(default = "default_data_export_sync_max_events")]
+    pub data_export_sync_max_events: i64,
+    #[serde(default = "default_data_export_ttl_hours")]
+    pub data_export_ttl_hours: u32,
+    // * Validità dei codici di autorizzazione OAuth
+    #[serde(default = "default_oauth_code_ttl_secs")]
+    pub oauth_code_ttl_secs: u64,
+    // * Pagine del frontend a cui /oauth/authorize rimanda per il login e il consenso
+    pub oauth_login_url: Option<String>,
+    pub oauth_consent_url: Option<String>,
+    // * Device authorization grant: validità del device code, intervallo minimo di polling e
+    // * pagina del frontend dove l'utente inserisce il codice (default /oauth/device)
+    #[serde(default = "default_device_code_ttl_secs")]
+    pub device_code_ttl_secs: u64,
+    #[serde(default = "default_device_code_interval_secs")]
+    pub device_code_interval_secs: u32,
+    pub oauth_device_url: Option<String>,
+    // * URL pubblico del servizio: issuer degli ID token OpenID Connect e base degli endpoint
+    // * pubblicati nel discovery
+    #[serde(default = "default_oidc_issuer")]
+    pub oidc_issuer: String,
+    // * Audience dei token emessi con il grant client_credentials (default jwt_audience)
+    pub client_credentials_audience: Option<String>,
+    // * Social login: identity provider esterni, pagina del frontend a cui si torna dopo il
+    // * login e validità dei login avviati e non completati
+    #[serde(default)]
+    pub identity_providers: Vec<IdentityProviderConfig>,
+    pub external_login_redirect_url: Option<String>,
+    #[serde(default = "default_external_login_ttl_secs")]
+    pub external_login_ttl_secs: u64,
+    // * Proxy di uscita per le chiamate ai provider (CONNECT per gli https)
+    pub identity_provider_proxy: Option<String>,
+    // * Per le operazioni sensibili gli account senza password devono aver fatto il login
+    // * entro questo intervallo
+    #[serde(default = "default_reauthentication_max_age_secs")]
+    pub reauthentication_max_age_secs: u64,
+    // * Durata dei token con cui il supporto impersona un utente
+    #[serde(default = "default_impersonation_token_ttl_secs")]
+    pub impersonation_token_ttl_secs: u64,
+    // * Scope che gli utenti possono assegnare ai propri personal access token
+    #[serde(default)]
+    pub personal_access_token_scopes: Vec<String>,
+    #[serde(default)]
+    pub password_policy: PasswordPolicyConfig,
+}
+
+fn default_refresh_token_exp_secs() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_max_failed_logins() -> u32 {
+    5
+}
+
+fn default_lockout_secs() -> u64 {
+    15 * 60
+}
+
+fn default_account_deletion_grace_days() -> u32 {
+    30
+}
+
+fn default_account_purge_interval_secs() -> u64 {
+    3600
+}
+
+fn default_outbox_relay_interval_secs() -> u64 {
+    10
+}
+
+fn default_data_export_sync_max_events() -> i64 {
+    1000
+}
+
+fn default_data_export_ttl_hours() -> u32 {
+    24
+}
+
+fn default_oauth_code_ttl_secs() -> u64 {
+    60
+}
+
+fn default_device_code_ttl_secs() -> u64 {
+    600
+}
+
+fn default_device_code_interval_secs() -> u32 {
+    5
+}
+
+fn default_external_login_ttl_secs() -> u64 {
+    600
+}
+
+fn default_reauthentication_max_age_secs() -> u64 {
+    300
+}
+
+fn default_impersonation_token_ttl_secs() -> u64 {
+    15 * 60
+}
+
+fn default_oidc_issuer() -> String {
+    "http://localhost:8083".into()
+}
+
+fn default_jwt_private_key_path() -> String {
+    "./private_key.pem".into()
+}
+
+// * Pepper segreto mescolato in Argon2; la versione più alta è quella usata per i nuovi hash
+#[derive(Debug, Clone, Deserialize)]
+pub struct PepperConfig {
+    pub version: u32,
+    pub file: Option<String>,
+    pub env: Option<String>,
+}
+
+// * Identity provider esterno (OpenID Connect o OAuth 2.0) per il social login
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdentityProviderConfig {
+    // * Identificativo negli URL e nella tabella user_identities (es. "google")
+    pub id: String,
+    pub name: String,
+    // * Issuer OpenID Connect: gli endpoint non indicati vengono letti dal suo discovery
+    pub issuer: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+    pub client_id: String,
+    // * Client secret letto da file o da variabile d'ambiente; senza, il client è pubblico (solo PKCE)
+    pub client_secret_file: Option<String>,
+    pub client_secret_env: Option<String>,
+    #[serde(default = "default_identity_provider_scopes")]
+    pub scopes: Vec<String>,
+    // * Claim con l'identificativo dell'account presso il provider (es. "id" per OAuth 2.0)
+    #[serde(default = "default_subject_claim")]
+    pub subject_claim: String,
+    // * Collega il login all'account con la stessa email, se il provider la dichiara verificata
+    #[serde(default = "default_link_verified_email")]
+    pub link_verified_email: bool,
+}
+
+fn default_identity_provider_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+fn default_subject_claim() -> String {
+    "sub".into()
+}
+
+fn default_link_verified_email() -> bool {
+    true
+}
+
+// * Regole della password policy; i valori di default valgono se la sezione manca
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicyConfig {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    // * Punteggio minimo di robustezza, da 0 (debolissima) a 4 (molto robusta)
+    pub min_strength_score: u8,
+    pub forbid_user_info: bool,
+    // * Numero di password precedenti che non possono essere riutilizzate (0 = nessun controllo)
+    pub history_depth: usize,
+    // * Età massima della password in giorni, dopo la quale il cambio è obbligatorio
+    pub max_age_days: Option<u32>,
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            min_strength_score: 2,
+            forbid_user_info: true,
+            history_depth: 5,
+            max_age_days: None,
+        }
+    }
 }
 
 impl AppConfig {
@@ -18,6 +238,25 @@ impl AppConfig {
             .build()?
             .try_deserialize()
     }
+    pub fn client_credentials_audience(&self) -> &str {
+        self.client_credentials_audience
+            .as_deref()
+            .unwrap_or(&self.jwt_audience)
+    }
+
+    // * Audience accettate dalla verifica dei token: quella degli utenti e quella dei servizi
+    pub fn accepted_audiences(&self) -> Vec<&str> {
+        let mut audiences = vec![self.jwt_audience.as_str()];
+        if self.client_credentials_audience() != self.jwt_audience {
+            audiences.push(self.client_credentials_audience());
+        }
+        audiences
+    }
+
+    // * URL assoluto di un endpoint del servizio, per il discovery OpenID Connect
+    pub fn oidc_url(&self, path: &str) -> String {
+        format!("{}{}", self.oidc_issuer.trim_end_matches('/'), path)
+    }
     pub fn is_production(&self) -> bool {
         self.rust_log.as_deref() == Some("info")
     }
@@ -5,16 +5,136 @@ use utoipa::OpenApi;
     paths(
         crate::handlers::register::register_handler,
         crate::handlers::login::login_handler,
+        crate::handlers::external_login::list_providers_handler,
+        crate::handlers::external_login::external_login_handler,
+        crate::handlers::external_login::external_callback_handler,
+        crate::handlers::external_login::list_identities_handler,
+        crate::handlers::external_login::link_identity_handler,
+        crate::handlers::external_login::unlink_identity_handler,
+        crate::handlers::password::change_password_handler,
+        crate::handlers::account::delete_account_handler,
+        crate::handlers::data_export::export_personal_data_handler,
+        crate::handlers::data_export::get_data_export_handler,
+        crate::handlers::data_export::download_data_export_handler,
+        crate::handlers::sessions::refresh_handler,
+        crate::handlers::sessions::list_sessions_handler,
+        crate::handlers::sessions::revoke_session_handler,
+        crate::handlers::sessions::revoke_other_sessions_handler,
+        crate::handlers::personal_access_tokens::list_tokens_handler,
+        crate::handlers::personal_access_tokens::create_token_handler,
+        crate::handlers::personal_access_tokens::revoke_token_handler,
+        crate::handlers::organizations::create_organization_handler,
+        crate::handlers::organizations::list_my_organizations_handler,
+        crate::handlers::organizations::switch_organization_handler,
+        crate::handlers::organizations::list_members_handler,
+        crate::handlers::organizations::invite_member_handler,
+        crate::handlers::organizations::accept_invitation_handler,
+        crate::handlers::organizations::update_member_handler,
+        crate::handlers::organizations::remove_member_handler,
+        crate::handlers::admin_users::list_users_handler,
+        crate::handlers::admin_users::get_user_handler,
+        crate::handlers::admin_users::set_status_handler,
+        crate::handlers::admin_users::disable_user_handler,
+        crate::handlers::admin_users::enable_user_handler,
+        crate::handlers::admin_users::force_password_reset_handler,
+        crate::handlers::admin_users::unlock_user_handler,
+        crate::handlers::admin_users::set_roles_handler,
+        crate::handlers::admin_users::impersonate_user_handler,
+        crate::handlers::admin_users::list_roles_handler,
+        crate::handlers::admin_audit::list_audit_events_handler,
+        crate::handlers::admin_audit::export_audit_events_handler,
+        crate::handlers::admin_oauth_clients::list_clients_handler,
+        crate::handlers::admin_oauth_clients::create_client_handler,
+        crate::handlers::admin_oauth_clients::update_token_settings_handler,
+        crate::handlers::admin_oauth_clients::delete_client_handler,
+        crate::handlers::oauth::authorize_handler,
+        crate::handlers::oauth::consent_handler,
+        crate::handlers::oauth::token_handler,
+        crate::handlers::oauth::userinfo_handler,
+        crate::handlers::oauth::device_authorization_handler,
+        crate::handlers::oauth::device_verification_handler,
+        crate::handlers::oauth::device_decision_handler,
+        crate::handlers::oidc::openid_configuration_handler,
+        crate::handlers::oidc::jwks_handler,
+        crate::handlers::consents::list_consents_handler,
+        crate::handlers::consents::revoke_consent_handler,
     ),
     components(
         schemas(
             crate::models::register::RegisterUser,
             crate::models::login::LoginUser,
+            crate::models::change_password::ChangePassword,
+            crate::models::delete_account::DeleteAccount,
+            crate::models::delete_account::AccountDeletionResponse,
+            crate::models::data_export::PersonalDataArchive,
+            crate::models::data_export::ExportedProfile,
+            crate::models::data_export::ExportedMembership,
+            crate::models::data_export::ExportedSession,
+            crate::models::data_export::ExportedIdentity,
+            crate::models::data_export::ExportedPersonalAccessToken,
+            crate::models::data_export::ExportedAuditEvent,
+            crate::models::data_export::DataExportResponse,
+            crate::models::data_export::DataExportStatus,
+            crate::models::auth_response_model::AuthResponse,
+            crate::models::session::SessionResponse,
+            crate::models::session::RefreshTokenRequest,
+            crate::models::personal_access_token::CreatePersonalAccessToken,
+            crate::models::personal_access_token::PersonalAccessTokenResponse,
+            crate::models::organization::CreateOrganization,
+            crate::models::organization::OrganizationResponse,
+            crate::models::organization::SwitchOrganization,
+            crate::models::organization::SwitchOrganizationResponse,
+            crate::models::organization::InviteMember,
+            crate::models::organization::UpdateMember,
+            crate::models::organization::MemberResponse,
             crate::models::user::User,
+            crate::models::user::AccountStatus,
+            crate::models::user_identity::IdentityProviderResponse,
+            crate::models::user_identity::UserIdentityResponse,
+            crate::models::user_identity::LinkIdentity,
+            crate::models::user_identity::LinkIdentityResponse,
+            crate::models::admin_user::AdminUserResponse,
+            crate::models::admin_user::AdminUserPage,
+            crate::models::admin_user::AssignRoles,
+            crate::models::admin_user::AccountStatusChange,
+            crate::models::admin_user::StatusReason,
+            crate::models::admin_user::ImpersonationRequest,
+            crate::models::admin_user::ImpersonationResponse,
+            crate::models::role::Role,
+            crate::models::audit_event::AuditLogEntry,
+            crate::models::audit_event::AuditEventPage,
+            crate::models::audit_event::AuditOutcome,
+            crate::models::audit_event::ExportFormat,
+            crate::models::oauth::RegisterOAuthClient,
+            crate::models::oauth::OAuthClientResponse,
+            crate::models::oauth::ClientTokenSettings,
+            crate::models::oauth::ConsentResponse,
+            crate::models::oauth::ConsentPrompt,
+            crate::models::oauth::ConsentDecision,
+            crate::models::oauth::ConsentChoice,
+            crate::models::oauth::TokenRequest,
+            crate::models::oauth::TokenResponse,
+            crate::models::oauth::DeviceAuthorizationRequest,
+            crate::models::oauth::DeviceAuthorizationResponse,
+            crate::models::oauth::DeviceVerificationPrompt,
+            crate::models::oauth::DeviceVerification,
+            crate::models::oidc::OpenIdConfiguration,
+            crate::models::oidc::JwkSet,
+            crate::models::oidc::Jwk,
+            crate::models::oidc::UserInfo,
+            crate::errors::ErrorResponse,
+            crate::errors::OAuthErrorResponse,
+            crate::services::password_policy::PolicyViolation,
+            crate::services::password_policy::PolicyRule,
         )
     ),
     tags(
         (name = "authentication", description = "User authentication and management"),
+        (name = "sessions", description = "Logged-in devices, refresh tokens and remote logout"),
+        (name = "personal access tokens", description = "Long-lived kapp_pat_ API keys users create for their scripts"),
+        (name = "organizations", description = "Schools and other organizations, their members and the organization roles carried in tokens (org_id, org_roles)"),
+        (name = "oauth", description = "OAuth 2.0 authorization code flow with PKCE and OpenID Connect for KetchApp and third-party applications"),
+        (name = "admin", description = "User management for support staff (admin role required)"),
     ),
     info(
         title = "Rust Authentication API",
@@ -36,4 +156,4 @@ use utoipa::OpenApi;
         url = "https://github.com/"
     )
 )]
-pub struct ApiDoc;
\ No newline at end of file
+pub struct ApiDoc;
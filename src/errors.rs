@@ -1,9 +1,13 @@
 use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use chrono::NaiveDateTime;
 use diesel::result::Error as DieselError;
 use serde::Serialize;
 use thiserror::Error;
 use utoipa::ToSchema;
 
+use crate::models::user::AccountStatus;
+use crate::services::password_policy::PolicyViolation;
+
 #[derive(Error, Debug)]
 pub enum ServiceError {
     #[error("Internal Server Error")]
@@ -28,6 +32,45 @@ pub enum ServiceError {
     JwtGenerationError(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Locked: {0}")]
+    Locked(String),
+    #[error("Identity provider error: {0}")]
+    IdentityProvider(String),
+    #[error("Password does not meet the password policy")]
+    PasswordPolicy(Vec<PolicyViolation>),
+    #[error("{}", account_inactive_message(*.status, .reason.as_deref(), *.until))]
+    AccountInactive {
+        status: AccountStatus,
+        reason: Option<String>,
+        until: Option<NaiveDateTime>,
+    },
+}
+
+fn account_inactive_message(
+    status: AccountStatus,
+    reason: Option<&str>,
+    until: Option<NaiveDateTime>,
+) -> String {
+    let mut message = match status {
+        AccountStatus::Suspended => "Account suspended".to_string(),
+        AccountStatus::PendingVerification => "Account pending verification".to_string(),
+        AccountStatus::Deleted => "Account deleted".to_string(),
+        _ => "Account disabled".to_string(),
+    };
+    match (status, until) {
+        (AccountStatus::Deleted, Some(until)) => message.push_str(&format!(
+            ", it will be permanently erased on {} UTC; contact support to restore it",
+            until.format("%Y-%m-%d %H:%M")
+        )),
+        (_, Some(until)) => {
+            message.push_str(&format!(" until {} UTC", until.format("%Y-%m-%d %H:%M")))
+        }
+        (_, None) => {}
+    }
+    if let Some(reason) = reason {
+        message.push_str(&format!(": {}", reason));
+    }
+    message
 }
 
 #[derive(Serialize, ToSchema)]
@@ -35,16 +78,22 @@ pub struct ErrorResponse {
     pub code: u16,
     pub error: String,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<PolicyViolation>>,
 }
 
 impl ResponseError for ServiceError {
     fn status_code(&self) -> StatusCode {
         match self {
             ServiceError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ServiceError::PasswordPolicy(_) => StatusCode::BAD_REQUEST,
             ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
             ServiceError::Conflict(_) => StatusCode::CONFLICT,
             ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
             ServiceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServiceError::AccountInactive { .. } => StatusCode::FORBIDDEN,
+            ServiceError::Locked(_) => StatusCode::LOCKED,
+            ServiceError::IdentityProvider(_) => StatusCode::BAD_GATEWAY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -57,12 +106,28 @@ impl ResponseError for ServiceError {
             ServiceError::Conflict(msg) => ("Conflict", msg.clone()),
             ServiceError::Forbidden(msg) => ("Forbidden", msg.clone()),
             ServiceError::Unauthorized(msg) => ("Unauthorized", msg.clone()),
+            ServiceError::Locked(msg) => ("Locked", msg.clone()),
+            ServiceError::IdentityProvider(msg) => ("Identity Provider Error", msg.clone()),
+            ServiceError::PasswordPolicy(_) => ("Password Policy Violation", self.to_string()),
+            ServiceError::AccountInactive { status, .. } => (
+                match status {
+                    AccountStatus::Suspended => "Account Suspended",
+                    AccountStatus::PendingVerification => "Account Pending Verification",
+                    AccountStatus::Deleted => "Account Deleted",
+                    _ => "Account Disabled",
+                },
+                self.to_string(),
+            ),
             _ => (binding.as_str(), self.to_string()),
         };
         let error_response = ErrorResponse {
             code: self.status_code().as_u16(),
             error: error.to_string(),
             message,
+            violations: match self {
+                ServiceError::PasswordPolicy(violations) => Some(violations.clone()),
+                _ => None,
+            },
         };
         HttpResponse::build(self.status_code()).json(error_response)
     }
@@ -73,3 +138,163 @@ impl From<actix_web::error::BlockingError> for ServiceError {
         ServiceError::BlockingError
     }
 }
+
+/// Error of the OAuth 2.0 endpoints, rendered as required by RFC 6749
+/// (`error` code plus `error_description`) instead of [`ErrorResponse`].
+#[derive(Error, Debug)]
+#[error("{error}: {description}")]
+pub struct OAuthError {
+    pub error: &'static str,
+    pub description: String,
+    status: StatusCode,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: String,
+}
+
+impl OAuthError {
+    fn new(error: &'static str, description: impl Into<String>, status: StatusCode) -> Self {
+        Self {
+            error,
+            description: description.into(),
+            status,
+        }
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        Self::new("invalid_request", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn invalid_client(description: impl Into<String>) -> Self {
+        Self::new("invalid_client", description, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn invalid_grant(description: impl Into<String>) -> Self {
+        Self::new("invalid_grant", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn invalid_scope(description: impl Into<String>) -> Self {
+        Self::new("invalid_scope", description, StatusCode::BAD_REQUEST)
+    }
+
+    // * Audience non consentita nel token exchange (RFC 8693, sezione 2.2.2)
+    pub fn invalid_target(description: impl Into<String>) -> Self {
+        Self::new("invalid_target", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn unauthorized_client(description: impl Into<String>) -> Self {
+        Self::new("unauthorized_client", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn unsupported_grant_type(description: impl Into<String>) -> Self {
+        Self::new(
+            "unsupported_grant_type",
+            description,
+            StatusCode::BAD_REQUEST,
+        )
+    }
+
+    pub fn unsupported_response_type(description: impl Into<String>) -> Self {
+        Self::new(
+            "unsupported_response_type",
+            description,
+            StatusCode::BAD_REQUEST,
+        )
+    }
+
+    pub fn access_denied(description: impl Into<String>) -> Self {
+        Self::new("access_denied", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn login_required(description: impl Into<String>) -> Self {
+        Self::new("login_required", description, StatusCode::UNAUTHORIZED)
+    }
+
+    // * Risposte al polling del device authorization grant (RFC 8628, sezione 3.5)
+    pub fn authorization_pending(description: impl Into<String>) -> Self {
+        Self::new(
+            "authorization_pending",
+            description,
+            StatusCode::BAD_REQUEST,
+        )
+    }
+
+    pub fn slow_down(description: impl Into<String>) -> Self {
+        Self::new("slow_down", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn expired_token(description: impl Into<String>) -> Self {
+        Self::new("expired_token", description, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn consent_required(description: impl Into<String>) -> Self {
+        Self::new("consent_required", description, StatusCode::FORBIDDEN)
+    }
+
+    // * Errori delle risorse protette da Bearer token, come /oauth/userinfo (RFC 6750)
+    pub fn invalid_token(description: impl Into<String>) -> Self {
+        Self::new("invalid_token", description, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn insufficient_scope(description: impl Into<String>) -> Self {
+        Self::new("insufficient_scope", description, StatusCode::FORBIDDEN)
+    }
+
+    pub fn server_error() -> Self {
+        Self::new(
+            "server_error",
+            "The server encountered an unexpected condition",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        response.insert_header(("Cache-Control", "no-store"));
+        match self.error {
+            "invalid_client" => {
+                response.insert_header(("WWW-Authenticate", "Basic realm=\"oauth\""));
+            }
+            "invalid_token" | "insufficient_scope" => {
+                response.insert_header((
+                    "WWW-Authenticate",
+                    format!("Bearer realm=\"oauth\", error=\"{}\"", self.error),
+                ));
+            }
+            _ => {}
+        }
+        response.json(OAuthErrorResponse {
+            error: self.error.to_string(),
+            error_description: self.description.clone(),
+        })
+    }
+}
+
+impl From<DieselError> for OAuthError {
+    fn from(e: DieselError) -> Self {
+        tracing::error!("OAuth database error: {:?}", e);
+        OAuthError::server_error()
+    }
+}
+
+impl From<ServiceError> for OAuthError {
+    fn from(e: ServiceError) -> Self {
+        tracing::error!("OAuth internal error: {:?}", e);
+        OAuthError::server_error()
+    }
+}
+
+impl From<actix_web::error::BlockingError> for OAuthError {
+    fn from(_: actix_web::error::BlockingError) -> Self {
+        OAuthError::server_error()
+    }
+}
@@ -0,0 +1,362 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpMessage, HttpRequest};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    models::claims::Claims,
+    repositories::{
+        oauth_clients_repo, personal_access_tokens_repo, roles_repo, sessions_repo, users_repo,
+    },
+    services::{
+        impersonation, jwt::JwtKeys, password_policy::PasswordPolicy, personal_access_token, scope,
+        session,
+    },
+    DbPool,
+};
+
+pub const AUTH_COOKIE: &str = "auth_token";
+
+// * Intervallo minimo tra due aggiornamenti di last_seen_at della stessa sessione
+const SESSION_TOUCH_INTERVAL_SECS: i64 = 60;
+
+type AuthFuture<T> = Pin<Box<dyn Future<Output = Result<T, ServiceError>>>>;
+
+/// Authenticated caller, extracted from a `Bearer` token or the
+/// `auth_token` cookie set at login. Every token is rejected once the
+/// account stops being active, and tokens bound to a session (`sid`) once
+/// that session is revoked. A `kapp_pat_` personal access token is also
+/// accepted as `Bearer`; its claims are built from the token and the
+/// user's roles.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub session_id: Option<Uuid>,
+    // * Presente se la richiesta è autenticata con un personal access token
+    pub personal_access_token_id: Option<Uuid>,
+    pub claims: Claims,
+}
+
+impl AuthenticatedUser {
+    // * Le credenziali dell'utente (password, token, identità collegate) non si possono
+    // * modificare mentre un admin lo impersona
+    pub fn ensure_not_impersonated(&self) -> Result<(), ServiceError> {
+        match self.claims.impersonator_id() {
+            Some(_) => Err(ServiceError::Forbidden(
+                "Not allowed while impersonating a user".into(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ServiceError;
+    type Future = AuthFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let user = authenticate(&req).await?;
+            if user.claims.password_change_required {
+                return Err(ServiceError::Forbidden(
+                    "Password change required before using this endpoint".into(),
+                ));
+            }
+            Ok(user)
+        })
+    }
+}
+
+/// Like [`AuthenticatedUser`], but also accepts the restricted token issued
+/// when the password is expired or a change was forced. Only the password
+/// change endpoint should use it.
+#[derive(Debug, Clone)]
+pub struct PasswordChangeUser(pub AuthenticatedUser);
+
+impl FromRequest for PasswordChangeUser {
+    type Error = ServiceError;
+    type Future = AuthFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move { authenticate(&req).await.map(PasswordChangeUser) })
+    }
+}
+
+/// Like [`AuthenticatedUser`], but accepts access tokens issued to OAuth
+/// clients whatever their audience, since a client may have its own. Only
+/// `/oauth/userinfo` should use it.
+#[derive(Debug, Clone)]
+pub struct OAuthAccessUser(pub AuthenticatedUser);
+
+impl FromRequest for OAuthAccessUser {
+    type Error = ServiceError;
+    type Future = AuthFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            authenticate_token(&req, Audience::Any)
+                .await
+                .map(OAuthAccessUser)
+        })
+    }
+}
+
+/// Backend service authenticated with a token from the `client_credentials`
+/// grant, for internal APIs. Tokens are rejected once the client is deleted.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedClient {
+    pub client_id: Uuid,
+    pub scopes: Vec<String>,
+    pub claims: Claims,
+}
+
+impl AuthenticatedClient {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+impl FromRequest for AuthenticatedClient {
+    type Error = ServiceError;
+    type Future = AuthFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let token = bearer_token(&req)
+                .ok_or_else(|| ServiceError::Unauthorized("Missing authentication token".into()))?;
+            let (Some(jwt_keys), Some(app_config), Some(pool)) = (
+                req.app_data::<web::Data<JwtKeys>>(),
+                req.app_data::<web::Data<AppConfig>>(),
+                req.app_data::<web::Data<DbPool>>(),
+            ) else {
+                return Err(ServiceError::InternalServerError);
+            };
+
+            let claims = jwt_keys.verify(&token, app_config)?;
+            if !claims.is_client_token() {
+                return Err(ServiceError::Forbidden(
+                    "A client credentials token is required".into(),
+                ));
+            }
+            let client_id = Uuid::parse_str(&claims.sub)
+                .map_err(|_| ServiceError::Unauthorized("Invalid token subject".into()))?;
+            let pool = pool.clone();
+            web::block(move || oauth_clients_repo::get_client(&pool, client_id))
+                .await?
+                .map_err(|_| ServiceError::Unauthorized("Client no longer exists".into()))?;
+
+            Ok(AuthenticatedClient {
+                client_id,
+                scopes: claims
+                    .scope
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect(),
+                claims,
+            })
+        })
+    }
+}
+
+// * Audience richiesta ai JWT: quelle del servizio, o qualsiasi per /oauth/userinfo
+#[derive(Clone, Copy)]
+enum Audience {
+    Service,
+    Any,
+}
+
+async fn authenticate(req: &HttpRequest) -> Result<AuthenticatedUser, ServiceError> {
+    authenticate_token(req, Audience::Service).await
+}
+
+async fn authenticate_token(
+    req: &HttpRequest,
+    audience: Audience,
+) -> Result<AuthenticatedUser, ServiceError> {
+    let token = request_token(req)
+        .ok_or_else(|| ServiceError::Unauthorized("Missing authentication token".into()))?;
+
+    let (Some(jwt_keys), Some(app_config), Some(pool)) = (
+        req.app_data::<web::Data<JwtKeys>>(),
+        req.app_data::<web::Data<AppConfig>>(),
+        req.app_data::<web::Data<DbPool>>(),
+    ) else {
+        return Err(ServiceError::InternalServerError);
+    };
+
+    if personal_access_token::is_personal_access_token(&token) {
+        let Some(password_policy) = req.app_data::<web::Data<PasswordPolicy>>() else {
+            return Err(ServiceError::InternalServerError);
+        };
+        return authenticate_personal_access_token(
+            pool.clone(),
+            app_config,
+            password_policy,
+            token,
+        )
+        .await;
+    }
+    let claims = verified_claims(req, &token, jwt_keys, app_config)?;
+    if matches!(audience, Audience::Service)
+        && !app_config
+            .accepted_audiences()
+            .contains(&claims.aud.as_str())
+    {
+        return Err(ServiceError::Unauthorized(
+            "Invalid or expired token".into(),
+        ));
+    }
+    if claims.is_client_token() {
+        return Err(ServiceError::Unauthorized(
+            "Client credentials tokens do not represent a user".into(),
+        ));
+    }
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| ServiceError::Unauthorized("Invalid token subject".into()))?;
+    let session_id = claims
+        .sid
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| ServiceError::Unauthorized("Invalid token session".into()))?;
+    check_account(pool.clone(), user_id, session_id, claims.impersonator_id()).await?;
+
+    Ok(AuthenticatedUser {
+        user_id,
+        session_id,
+        personal_access_token_id: None,
+        claims,
+    })
+}
+
+// * Un personal access token agisce come l'utente con i suoi ruoli attuali, limitato agli
+// * scope del token che quei ruoli consentono; un cambio password imposto o una password
+// * scaduta bloccano anche i token, come per le sessioni
+async fn authenticate_personal_access_token(
+    pool: web::Data<DbPool>,
+    app_config: &AppConfig,
+    password_policy: &PasswordPolicy,
+    secret: String,
+) -> Result<AuthenticatedUser, ServiceError> {
+    let (token, user, grants) = web::block(move || -> Result<_, ServiceError> {
+        let (token, user) = personal_access_token::authenticate(&pool, &secret)?;
+        let grants = roles_repo::grants_for_user(&pool, user.id)?;
+        if token.last_used_at.is_none_or(|used| {
+            used + Duration::seconds(SESSION_TOUCH_INTERVAL_SECS) < Utc::now().naive_utc()
+        }) {
+            personal_access_tokens_repo::touch(&pool, token.id)?;
+        }
+        Ok((token, user, grants))
+    })
+    .await??;
+
+    let mut claims = Claims::new(user.id.to_string(), app_config);
+    claims.iat = token.created_at.and_utc().timestamp() as usize;
+    if let Some(expires_at) = token.expires_at {
+        claims.exp = expires_at.and_utc().timestamp() as usize;
+    }
+    claims.password_change_required = session::password_change_required(&user, password_policy);
+    claims.set_scopes(&scope::restrict_to_grants(
+        &grants,
+        token.scopes.iter().map(String::as_str),
+    ));
+    claims.roles = grants.roles;
+    Ok(AuthenticatedUser {
+        user_id: user.id,
+        session_id: None,
+        personal_access_token_id: Some(token.id),
+        claims,
+    })
+}
+
+// * Ogni token utente vale solo finché l'account è attivo, anche senza sessione (token di
+// * token exchange). Se il token è legato a una sessione, questa non deve essere stata revocata
+// * e il suo ultimo accesso viene aggiornato. Per un'impersonazione, anche l'admin deve
+// * essere ancora attivo e avere il ruolo admin
+async fn check_account(
+    pool: web::Data<DbPool>,
+    user_id: Uuid,
+    session_id: Option<Uuid>,
+    impersonator_id: Option<Uuid>,
+) -> Result<(), ServiceError> {
+    web::block(move || -> Result<(), ServiceError> {
+        let session = session_id
+            .map(|session_id| {
+                sessions_repo::get_session(&pool, session_id)
+                    .map_err(|_| ServiceError::Unauthorized("Session not found".into()))
+            })
+            .transpose()?;
+        if session
+            .as_ref()
+            .is_some_and(|session| session.user_id != user_id || session.revoked_at.is_some())
+        {
+            return Err(ServiceError::Unauthorized(
+                "Session has been revoked".into(),
+            ));
+        }
+        users_repo::get_own_user(&pool, user_id)
+            .map_err(|_| ServiceError::Unauthorized("User no longer exists".into()))?
+            .ensure_active()?;
+        if let Some(admin_id) = impersonator_id {
+            let admin = users_repo::get_own_user(&pool, admin_id).map_err(|_| {
+                ServiceError::Unauthorized(
+                    "The impersonating administrator no longer exists".into(),
+                )
+            })?;
+            let roles = roles_repo::grants_for_user(&pool, admin_id)?.roles;
+            impersonation::ensure_impersonator_allowed(&admin, &roles)?;
+        }
+        if let Some(session) = session.filter(|session| {
+            session.last_seen_at + Duration::seconds(SESSION_TOUCH_INTERVAL_SECS)
+                < Utc::now().naive_utc()
+        }) {
+            sessions_repo::touch(&pool, session.id)?;
+        }
+        Ok(())
+    })
+    .await?
+}
+
+// * Esito della verifica del JWT della richiesta, conservato nelle estensioni
+#[derive(Clone)]
+struct VerifiedToken(Option<Claims>);
+
+// * Il JWT si verifica una sola volta per richiesta: autenticazione e ClientInfo condividono
+// * l'esito, in qualunque ordine il gestore li estragga. L'audience la controlla chi la richiede
+pub(crate) fn verified_claims(
+    req: &HttpRequest,
+    token: &str,
+    jwt_keys: &JwtKeys,
+    app_config: &AppConfig,
+) -> Result<Claims, ServiceError> {
+    let cached = req.extensions().get::<VerifiedToken>().cloned();
+    let VerifiedToken(claims) = cached.unwrap_or_else(|| {
+        let verified = VerifiedToken(jwt_keys.verify_any_audience(token, app_config).ok());
+        req.extensions_mut().insert(verified.clone());
+        verified
+    });
+    claims.ok_or_else(|| ServiceError::Unauthorized("Invalid or expired token".into()))
+}
+
+// * Token dell'header Authorization o, in mancanza, del cookie impostato al login
+pub(crate) fn request_token(req: &HttpRequest) -> Option<String> {
+    bearer_token(req).or_else(|| req.cookie(AUTH_COOKIE).map(|c| c.value().to_string()))
+}
+
+pub(crate) fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
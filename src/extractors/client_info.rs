@@ -0,0 +1,139 @@
+use std::future::{ready, Ready};
+use std::net::IpAddr;
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpMessage, HttpRequest};
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    extractors::{auth, request_id::RequestId},
+    services::{jwt::JwtKeys, personal_access_token},
+};
+
+pub const DEVICE_NAME_HEADER: &str = "X-Device-Name";
+pub const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Information about the calling client, recorded on sessions and audit
+/// events.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    // * Sempre un indirizzo valido: gli header della richiesta non finiscono mai qui così come sono
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub device: Option<String>,
+    // * Id assegnato dal middleware request_id
+    pub request_id: Option<String>,
+    // * Admin che impersona l'utente del token, riportato su ogni evento di audit
+    pub impersonator_id: Option<Uuid>,
+}
+
+impl FromRequest for ClientInfo {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let header_value = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let user_agent = header_value(header::USER_AGENT.as_str());
+        let device = header_value(DEVICE_NAME_HEADER)
+            .map(|name| name.chars().take(255).collect())
+            .or_else(|| user_agent.as_deref().map(describe_device));
+        // * Prima di prendere in prestito le estensioni: la lettura dei cookie le modifica
+        let impersonator_id = impersonator_id(req);
+        let trusted_proxies = req
+            .app_data::<web::Data<AppConfig>>()
+            .map(|config| config.trusted_proxies.as_slice())
+            .unwrap_or_default();
+        let forwarded_for = req
+            .headers()
+            .get(FORWARDED_FOR_HEADER)
+            .and_then(|value| value.to_str().ok());
+        ready(Ok(ClientInfo {
+            ip_address: client_ip(
+                req.peer_addr().map(|addr| addr.ip()),
+                forwarded_for,
+                trusted_proxies,
+            ),
+            user_agent,
+            device,
+            request_id: req.extensions().get::<RequestId>().map(|id| id.0.clone()),
+            impersonator_id,
+        }))
+    }
+}
+
+// * Letto dal token della richiesta, se valido: la verifica è condivisa con l'autenticazione,
+// * quindi l'estrattore non dipende dall'ordine in cui il gestore estrae l'utente autenticato
+fn impersonator_id(req: &HttpRequest) -> Option<Uuid> {
+    let token = auth::request_token(req)
+        .filter(|token| !personal_access_token::is_personal_access_token(token))?;
+    let jwt_keys = req.app_data::<web::Data<JwtKeys>>()?;
+    let app_config = req.app_data::<web::Data<AppConfig>>()?;
+    auth::verified_claims(req, &token, jwt_keys, app_config)
+        .ok()?
+        .impersonator_id()
+}
+
+// * Indirizzo del client: quello della connessione, a meno che arrivi da un proxy fidato. In quel
+// * caso si risale X-Forwarded-For da destra, saltando i proxy fidati: le voci più a sinistra le
+// * scrive il client e non sono attendibili. Una voce che non è un IP fa tenere l'ultimo proxy
+pub fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let mut ip = peer?;
+    let Some(forwarded_for) = forwarded_for else {
+        return Some(ip);
+    };
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+        match hop.trim().parse::<IpAddr>() {
+            Ok(hop) => ip = hop,
+            Err(_) => break,
+        }
+    }
+    Some(ip)
+}
+
+// * Descrizione sintetica del dispositivo ricavata dallo User-Agent, es. "Firefox on Linux"
+pub fn describe_device(user_agent: &str) -> String {
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+        ("okhttp", "Android app"),
+        ("CFNetwork", "iOS app"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => "Unknown device".to_string(),
+    }
+}
@@ -0,0 +1,5 @@
+pub mod auth;
+pub mod client_info;
+pub mod request_id;
+pub mod role;
+pub mod scope;
@@ -0,0 +1,71 @@
+use std::{future::Future, marker::PhantomData, ops::Deref, pin::Pin};
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+
+use crate::{
+    errors::ServiceError,
+    extractors::auth::AuthenticatedUser,
+    models::role::{ADMIN_ROLE, STUDENT_ROLE, TEACHER_ROLE},
+};
+
+/// Role checked by [`RequireRole`], as a type so it can be named in a
+/// handler signature.
+pub trait RoleName {
+    const NAME: &'static str;
+}
+
+pub struct Student;
+pub struct Teacher;
+pub struct Admin;
+
+impl RoleName for Student {
+    const NAME: &'static str = STUDENT_ROLE;
+}
+
+impl RoleName for Teacher {
+    const NAME: &'static str = TEACHER_ROLE;
+}
+
+impl RoleName for Admin {
+    const NAME: &'static str = ADMIN_ROLE;
+}
+
+/// Authenticated caller whose token carries the role `R`, e.g.
+/// `RequireRole<Admin>`. Other callers get 403 Forbidden.
+///
+/// Roles are read from the `roles` claim, so a role change takes effect
+/// when the access token is next refreshed.
+pub struct RequireRole<R: RoleName> {
+    pub user: AuthenticatedUser,
+    _role: PhantomData<R>,
+}
+
+impl<R: RoleName> Deref for RequireRole<R> {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
+impl<R: RoleName + 'static> FromRequest for RequireRole<R> {
+    type Error = ServiceError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let authenticated = AuthenticatedUser::from_request(req, payload);
+        Box::pin(async move {
+            let user = authenticated.await?;
+            if !user.claims.has_role(R::NAME) {
+                return Err(ServiceError::Forbidden(format!(
+                    "The {} role is required",
+                    R::NAME
+                )));
+            }
+            Ok(RequireRole {
+                user,
+                _role: PhantomData,
+            })
+        })
+    }
+}
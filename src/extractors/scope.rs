@@ -0,0 +1,84 @@
+use std::{future::Future, marker::PhantomData, ops::Deref, pin::Pin};
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+
+use crate::{
+    errors::ServiceError,
+    extractors::{
+        auth::AuthenticatedUser,
+        role::{RequireRole, RoleName},
+    },
+    services::scope::ADMIN_SCOPE,
+};
+
+/// Scope checked by [`RequireScope`], as a type so it can be named in a
+/// handler signature.
+pub trait ScopeName {
+    const NAME: &'static str;
+}
+
+pub struct AdminScope;
+
+impl ScopeName for AdminScope {
+    const NAME: &'static str = ADMIN_SCOPE;
+}
+
+/// Extractor that resolves to an authenticated user, so [`RequireScope`]
+/// can wrap other guards.
+pub trait UserGuard: FromRequest<Error = ServiceError> {
+    fn authenticated_user(&self) -> &AuthenticatedUser;
+}
+
+impl UserGuard for AuthenticatedUser {
+    fn authenticated_user(&self) -> &AuthenticatedUser {
+        self
+    }
+}
+
+impl<R: RoleName + 'static> UserGuard for RequireRole<R> {
+    fn authenticated_user(&self) -> &AuthenticatedUser {
+        &self.user
+    }
+}
+
+/// Caller extracted by `G` whose token carries the scope `S`, e.g.
+/// `RequireScope<AdminScope, RequireRole<Admin>>`. Other callers get 403
+/// Forbidden.
+///
+/// Scopes are read from the `scope` claim: direct logins get the scopes of
+/// the user's roles, OAuth clients and personal access tokens only the ones
+/// they were granted.
+pub struct RequireScope<S: ScopeName, G: UserGuard = AuthenticatedUser> {
+    pub guard: G,
+    _scope: PhantomData<S>,
+}
+
+impl<S: ScopeName, G: UserGuard> Deref for RequireScope<S, G> {
+    type Target = G;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<S: ScopeName + 'static, G: UserGuard + 'static> FromRequest for RequireScope<S, G> {
+    type Error = ServiceError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let guarded = G::from_request(req, payload);
+        Box::pin(async move {
+            let guard = guarded.await?;
+            if !guard.authenticated_user().claims.has_scope(S::NAME) {
+                return Err(ServiceError::Forbidden(format!(
+                    "The {} scope is required",
+                    S::NAME
+                )));
+            }
+            Ok(RequireScope {
+                guard,
+                _scope: PhantomData,
+            })
+        })
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::{delete, web, HttpResponse};
+use serde_json::json;
+use tracing::info;
+use validator::Validate;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    handlers::cookies,
+    models::{
+        audit_event::AuditOutcome,
+        delete_account::{AccountDeletionResponse, DeleteAccount},
+    },
+    repositories::{as_user, users_repo},
+    services::{
+        account_deletion,
+        audit::{self, AuditEvent},
+        password::PasswordService,
+        session,
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        delete,
+        path = "/api/me",
+        request_body = DeleteAccount,
+        responses(
+            (status = 202, description = "Account deleted and all sessions revoked; it is permanently erased, with its audit trail anonymized, once the grace period ends", body = AccountDeletionResponse),
+            (status = 400, description = "Bad Request: invalid input", body = ErrorResponse),
+            (status = 401, description = "Unauthorized: missing token, wrong password, or no recent sign-in for an account without a password", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[delete("/me")]
+pub async fn delete_account_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    password_service: web::Data<PasswordService>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<DeleteAccount>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+
+    // * 1. La cancellazione va confermata con la password attuale o, per gli account senza
+    // *    password, con un login recente
+    let body = body.into_inner();
+    let (user, failure) = web::block({
+        let (pool, app_config) = (pool.clone(), app_config.clone());
+        move || -> Result<_, ServiceError> {
+            let user = users_repo::get_own_user(&pool, auth.user_id)
+                .map_err(|_| ServiceError::Unauthorized("User no longer exists".into()))?;
+            let failure = session::reauthentication_failure(
+                &pool,
+                &password_service,
+                &user,
+                body.password.as_deref(),
+                auth.session_id,
+                &app_config,
+            )?;
+            Ok((user, failure))
+        }
+    })
+    .await??;
+    if let Some(reason) = failure {
+        audit::record(
+            &pool,
+            AuditEvent {
+                action: "user.deletion_requested",
+                outcome: AuditOutcome::Failure,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(&client),
+                details: json!({ "reason": reason }),
+            },
+        )
+        .await;
+        return Err(ServiceError::Unauthorized(match reason {
+            "password_missing" => "The current password is required".into(),
+            "invalid_password" => "Password is incorrect".into(),
+            _ => "Sign in again to delete the account".into(),
+        }));
+    }
+
+    // * 2. L'account viene segnato come cancellato e tutte le sessioni chiuse; l'eliminazione
+    // *    definitiva avviene al termine del periodo di ripristino
+    let grace_days = app_config.account_deletion_grace_days;
+    let deleted = web::block(move || -> Result<_, ServiceError> {
+        let deleted = as_user(&pool, user.id, |conn| {
+            account_deletion::schedule_with_connection(conn, &user, &client, grace_days)
+        })?;
+        Ok(deleted)
+    })
+    .await??;
+
+    let purge_after = deleted
+        .purge_after
+        .ok_or(ServiceError::InternalServerError)?;
+    info!(
+        "User {} deleted their account, erasing after {}",
+        deleted.id, purge_after
+    );
+    let [auth_cookie, refresh_cookie] = cookies::removal_cookies(&app_config);
+    Ok(HttpResponse::Accepted()
+        .cookie(auth_cookie)
+        .cookie(refresh_cookie)
+        .json(AccountDeletionResponse { purge_after }))
+}
@@ -0,0 +1,171 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+use validator::Validate;
+
+use crate::{
+    errors::{ErrorResponse, ServiceError},
+    extractors::{
+        client_info::ClientInfo,
+        role::{Admin, RequireRole},
+        scope::{AdminScope, RequireScope},
+    },
+    models::audit_event::{
+        AuditEventPage, AuditEventQuery, AuditLogEntry, AuditOutcome, ExportFormat,
+        ExportFormatQuery,
+    },
+    repositories::audit_events_repo,
+    services::audit::{self, AuditEvent},
+    DbPool,
+};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+// * Numero massimo di eventi in un singolo export; per periodi più lunghi si restringe from/to
+const EXPORT_LIMIT: i64 = 10_000;
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/audit-events",
+        params(AuditEventQuery),
+        responses(
+            (status = 200, description = "Matching audit events, newest first", body = AuditEventPage),
+            (status = 400, description = "Bad Request: invalid filters", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("/admin/audit-events")]
+pub async fn list_audit_events_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    query: web::Query<AuditEventQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    query
+        .validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let query = query.into_inner();
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+    let (items, total, query) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            let (items, total) =
+                audit_events_repo::search(&pool, &query, per_page, (page - 1) * per_page)?;
+            Ok((items, total, query))
+        }
+    })
+    .await??;
+
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.audit.query",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: query.target_user_id,
+            client: Some(&client),
+            details: json!({ "filters": filters(&query), "results": total }),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(AuditEventPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/audit-events/export",
+        params(AuditEventQuery, ExportFormatQuery),
+        responses(
+            (status = 200, description = "Matching audit events (at most 10000, newest first) as a JSON array or CSV attachment", content(
+                ([AuditLogEntry] = "application/json"),
+                (String = "text/csv")
+            )),
+            (status = 400, description = "Bad Request: invalid filters", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("/admin/audit-events/export")]
+pub async fn export_audit_events_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    query: web::Query<AuditEventQuery>,
+    format: web::Query<ExportFormatQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let query = query.into_inner();
+    let format = format.format.unwrap_or_default();
+
+    let (entries, total, query) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            let (entries, total) = audit_events_repo::search(&pool, &query, EXPORT_LIMIT, 0)?;
+            Ok((entries, total, query))
+        }
+    })
+    .await??;
+
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.audit.export",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: query.target_user_id,
+            client: Some(&client),
+            details: json!({
+                "filters": filters(&query),
+                "format": format,
+                "exported": entries.len(),
+                "total": total,
+            }),
+        },
+    )
+    .await;
+
+    let filename = format!("audit-events-{}", Utc::now().format("%Y%m%dT%H%M%S"));
+    let mut response = HttpResponse::Ok();
+    response.insert_header(("X-Total-Count", total.to_string()));
+    Ok(match format {
+        ExportFormat::Json => response
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.json\"", filename),
+            ))
+            .json(entries),
+        ExportFormat::Csv => response
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.csv\"", filename),
+            ))
+            .content_type("text/csv; charset=utf-8")
+            .body(audit::to_csv(&entries)),
+    })
+}
+
+// * Filtri usati nella richiesta, registrati nell'evento di audit della consultazione
+fn filters(query: &AuditEventQuery) -> serde_json::Value {
+    json!({
+        "actor_id": query.actor_id,
+        "target_user_id": query.target_user_id,
+        "action": query.action,
+        "outcome": query.outcome,
+        "ip_address": query.ip_address,
+        "request_id": query.request_id,
+        "from": query.from,
+        "to": query.to,
+    })
+}
@@ -0,0 +1,247 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    errors::{ErrorResponse, ServiceError},
+    extractors::{
+        client_info::ClientInfo,
+        role::{Admin, RequireRole},
+        scope::{AdminScope, RequireScope},
+    },
+    models::{
+        audit_event::AuditOutcome,
+        oauth::{ClientTokenSettings, NewOAuthClient, OAuthClientResponse, RegisterOAuthClient},
+    },
+    repositories::oauth_clients_repo,
+    services::{
+        audit::{self, AuditEvent},
+        oauth,
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/oauth/clients",
+        responses(
+            (status = 200, description = "Registered OAuth clients, newest first", body = [OAuthClientResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("")]
+pub async fn list_clients_handler(
+    pool: web::Data<DbPool>,
+    _admin: RequireScope<AdminScope, RequireRole<Admin>>,
+) -> Result<HttpResponse, ServiceError> {
+    let clients = web::block(move || oauth_clients_repo::list_clients(&pool)).await??;
+    let clients: Vec<OAuthClientResponse> = clients
+        .into_iter()
+        .map(|client| OAuthClientResponse::from_client(client, None))
+        .collect();
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/oauth/clients",
+        request_body = RegisterOAuthClient,
+        responses(
+            (status = 201, description = "Client registered; the client_secret of confidential clients is shown only in this response", body = OAuthClientResponse),
+            (status = 400, description = "Bad Request: invalid redirect URI, scope or token settings", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("")]
+pub async fn create_client_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    body: web::Json<RegisterOAuthClient>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let body = body.into_inner();
+    for uri in &body.redirect_uris {
+        oauth::validate_redirect_uri(uri).map_err(ServiceError::ValidationError)?;
+    }
+    if let Some(scope) = body
+        .scopes
+        .iter()
+        .find(|scope| !oauth::is_valid_scope_token(scope))
+    {
+        return Err(ServiceError::ValidationError(format!(
+            "Invalid scope {:?}",
+            scope
+        )));
+    }
+    validate_token_settings(&body.token_settings)?;
+
+    let secret = body.confidential.then(oauth::generate_token);
+    let new_client = NewOAuthClient {
+        name: body.name,
+        client_secret_hash: secret.as_deref().map(oauth::hash_token),
+        redirect_uris: body.redirect_uris,
+        allowed_scopes: body.scopes,
+        first_party: body.first_party,
+        created_by: Some(admin.user_id),
+        audience: body.token_settings.audience,
+        access_token_ttl_secs: body.token_settings.access_token_ttl_secs,
+        refresh_token_ttl_secs: body.token_settings.refresh_token_ttl_secs,
+        idle_timeout_secs: body.token_settings.idle_timeout_secs,
+        token_exchange_audiences: body.token_settings.token_exchange_audiences,
+    };
+    let created = web::block({
+        let pool = pool.clone();
+        move || oauth_clients_repo::create(&pool, new_client)
+    })
+    .await??;
+
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.oauth_clients.create",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: None,
+            client: Some(&client),
+            details: json!({
+                "client_id": created.id,
+                "name": created.name,
+                "confidential": created.is_confidential(),
+                "first_party": created.first_party,
+                "redirect_uris": created.redirect_uris,
+                "scopes": created.allowed_scopes,
+                "token_settings": created.token_settings(),
+            }),
+        },
+    )
+    .await;
+    info!(
+        "OAuth client {} registered by {}",
+        created.id, admin.user_id
+    );
+    Ok(HttpResponse::Created().json(OAuthClientResponse::from_client(created, secret)))
+}
+
+#[utoipa::path(
+        put,
+        path = "/api/admin/oauth/clients/{id}/token-settings",
+        params(("id" = Uuid, Path, description = "Client id")),
+        request_body = ClientTokenSettings,
+        responses(
+            (status = 200, description = "Settings replaced; they apply to the tokens and sessions issued from now on", body = OAuthClientResponse),
+            (status = 400, description = "Bad Request: invalid token settings", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[put("/{id}/token-settings")]
+pub async fn update_token_settings_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: web::Json<ClientTokenSettings>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    validate_token_settings(&body)?;
+    let client_id = path.into_inner();
+    let settings = body.into_inner();
+    let updated = web::block({
+        let pool = pool.clone();
+        let settings = settings.clone();
+        move || oauth_clients_repo::update_token_settings(&pool, client_id, settings)
+    })
+    .await??
+    .ok_or_else(|| ServiceError::NotFound("Client not found".into()))?;
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.oauth_clients.update",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: None,
+            client: Some(&client),
+            details: json!({ "client_id": client_id, "token_settings": settings }),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(OAuthClientResponse::from_client(updated, None)))
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/admin/oauth/clients/{id}",
+        params(("id" = Uuid, Path, description = "Client id")),
+        responses(
+            (status = 204, description = "Client deleted together with its codes, consents and sessions"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[delete("/{id}")]
+pub async fn delete_client_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let client_id = path.into_inner();
+    let deleted = web::block({
+        let pool = pool.clone();
+        move || oauth_clients_repo::delete_client(&pool, client_id)
+    })
+    .await??;
+    if deleted == 0 {
+        return Err(ServiceError::NotFound("Client not found".into()));
+    }
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.oauth_clients.delete",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: None,
+            client: Some(&client),
+            details: json!({ "client_id": client_id }),
+        },
+    )
+    .await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// * Le audience finiscono nel claim aud e vengono confrontate per uguaglianza dalle API
+fn validate_token_settings(settings: &ClientTokenSettings) -> Result<(), ServiceError> {
+    match settings
+        .audience
+        .iter()
+        .chain(&settings.token_exchange_audiences)
+        .find(|audience| audience.is_empty() || audience.contains(char::is_whitespace))
+    {
+        Some(audience) => Err(ServiceError::ValidationError(format!(
+            "Invalid audience {:?}",
+            audience
+        ))),
+        None => Ok(()),
+    }
+}
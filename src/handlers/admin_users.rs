@@ -0,0 +1,648 @@
+use std::collections::{BTreeSet, HashMap};
+
+use actix_web::{get, http::header, post, put, web, HttpResponse};
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{
+        auth::AuthenticatedUser,
+        client_info::ClientInfo,
+        role::{Admin, RequireRole},
+        scope::{AdminScope, RequireScope},
+    },
+    models::{
+        admin_user::{
+            AccountStatusChange, AdminUserPage, AdminUserResponse, AssignRoles,
+            ImpersonationRequest, ImpersonationResponse, StatusReason, UserSearchQuery,
+        },
+        audit_event::AuditOutcome,
+        role::{Role, ADMIN_ROLE},
+        user::AccountStatus,
+    },
+    repositories::{establish_connection, roles_repo, sessions_repo, users_repo},
+    services::{
+        account_deletion,
+        audit::{self, AuditEvent},
+        impersonation,
+        jwt::JwtKeys,
+    },
+    DbPool,
+};
+
+const DEFAULT_PER_PAGE: i64 = 20;
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/users",
+        params(UserSearchQuery),
+        responses(
+            (status = 200, description = "Users whose username or email contains q, newest first", body = AdminUserPage),
+            (status = 400, description = "Bad Request: invalid paging parameters", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("")]
+pub async fn list_users_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    query: web::Query<UserSearchQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    query
+        .validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let query = query.into_inner();
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+    let (users, total, roles) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            let (users, total) = users_repo::search_users(
+                &pool,
+                query.q.as_deref().map(str::trim),
+                per_page,
+                (page - 1) * per_page,
+            )?;
+            let ids: Vec<Uuid> = users.iter().map(|u| u.id).collect();
+            let roles = roles_repo::role_names_for_users(&pool, &ids)?;
+            Ok((users, total, roles))
+        }
+    })
+    .await??;
+
+    let mut roles_by_user: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (user_id, role) in roles {
+        roles_by_user.entry(user_id).or_default().push(role);
+    }
+    let items = users
+        .into_iter()
+        .map(|user| {
+            let roles = roles_by_user.remove(&user.id).unwrap_or_default();
+            AdminUserResponse::from_user(user, roles)
+        })
+        .collect();
+
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.users.search",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: None,
+            client: Some(&client),
+            details: json!({ "page": page, "per_page": per_page, "results": total }),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(AdminUserPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/users/{id}",
+        params(("id" = Uuid, Path, description = "User id")),
+        responses(
+            (status = 200, description = "The user", body = AdminUserResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("/{id}")]
+pub async fn get_user_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = path.into_inner();
+    let user = web::block({
+        let pool = pool.clone();
+        move || load_user(&pool, user_id)
+    })
+    .await??;
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.users.view",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: Some(user_id),
+            client: Some(&client),
+            details: json!({}),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[utoipa::path(
+        put,
+        path = "/api/admin/users/{id}/status",
+        params(("id" = Uuid, Path, description = "User id")),
+        request_body = AccountStatusChange,
+        responses(
+            (status = 200, description = "Status changed; any status other than active also revokes all of the user's sessions", body = AdminUserResponse),
+            (status = 400, description = "Bad Request: invalid input, expiry in the past or status deleted (only the owner can delete an account)", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 409, description = "Conflict: admins cannot deactivate themselves", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[put("/{id}/status")]
+pub async fn set_status_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: web::Json<AccountStatusChange>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    change_status(pool, &admin, &client, path.into_inner(), body.into_inner()).await
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/users/{id}/disable",
+        params(("id" = Uuid, Path, description = "User id")),
+        request_body(content = StatusReason, description = "Optional reason shown to the user"),
+        responses(
+            (status = 200, description = "User disabled and all of their sessions revoked", body = AdminUserResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 409, description = "Conflict: admins cannot disable themselves", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("/{id}/disable")]
+pub async fn disable_user_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: Option<web::Json<StatusReason>>,
+) -> Result<HttpResponse, ServiceError> {
+    let change = AccountStatusChange {
+        status: AccountStatus::Disabled,
+        reason: body.and_then(|body| body.into_inner().reason),
+        expires_at: None,
+    };
+    change
+        .validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    change_status(pool, &admin, &client, path.into_inner(), change).await
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/users/{id}/enable",
+        params(("id" = Uuid, Path, description = "User id")),
+        responses(
+            (status = 200, description = "User active again; reason and expiry are cleared and a deleted account still in its grace period is restored", body = AdminUserResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("/{id}/enable")]
+pub async fn enable_user_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let change = AccountStatusChange {
+        status: AccountStatus::Active,
+        reason: None,
+        expires_at: None,
+    };
+    change_status(pool, &admin, &client, path.into_inner(), change).await
+}
+
+// * Applica il cambio di stato; se l'account non è più attivo tutte le sue sessioni vengono revocate
+async fn change_status(
+    pool: web::Data<DbPool>,
+    admin: &AuthenticatedUser,
+    client: &ClientInfo,
+    user_id: Uuid,
+    change: AccountStatusChange,
+) -> Result<HttpResponse, ServiceError> {
+    if change.status == AccountStatus::Deleted {
+        return Err(ServiceError::ValidationError(
+            "Accounts can only be deleted by their owner".into(),
+        ));
+    }
+    let active = change.status == AccountStatus::Active;
+    if user_id == admin.user_id && !active {
+        return Err(ServiceError::Conflict(
+            "You cannot deactivate your own account".into(),
+        ));
+    }
+    let (reason, expires_at) = if active {
+        (None, None)
+    } else {
+        (change.reason, change.expires_at)
+    };
+    if expires_at.is_some_and(|until| until <= Utc::now().naive_utc()) {
+        return Err(ServiceError::ValidationError(
+            "expires_at must be in the future".into(),
+        ));
+    }
+
+    let admin_id = admin.user_id;
+    let client = client.clone();
+    let user = web::block(move || -> Result<_, ServiceError> {
+        let mut conn = establish_connection(&pool)?;
+        conn.transaction::<(), diesel::result::Error, _>(|conn| {
+            let previous = users_repo::get_user_by_id_with_connection(conn, user_id)?;
+            // * Un account cancellato non è ancora stato eliminato: cambiarne lo stato lo ripristina
+            let restored = previous.account_status() == AccountStatus::Deleted;
+            if restored {
+                account_deletion::restored_with_connection(conn, user_id)?;
+            }
+            users_repo::set_status_with_connection(
+                conn,
+                user_id,
+                change.status,
+                reason.as_deref(),
+                expires_at,
+            )?;
+            let revoked = if active {
+                0
+            } else {
+                sessions_repo::revoke_all_except_with_connection(conn, user_id, None)?
+            };
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "admin.users.set_status",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(admin_id),
+                    target_user_id: Some(user_id),
+                    client: Some(&client),
+                    details: json!({
+                        "status": change.status,
+                        "reason": reason,
+                        "expires_at": expires_at,
+                        "revoked_sessions": revoked,
+                        "restored": restored,
+                    }),
+                },
+            )
+        })
+        .map_err(not_found_as("User not found"))?;
+        load_user(&pool, user_id)
+    })
+    .await??;
+
+    info!(
+        "User {} set to {} by {}",
+        user_id,
+        change.status.as_str(),
+        admin_id
+    );
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/users/{id}/force-password-reset",
+        params(("id" = Uuid, Path, description = "User id")),
+        responses(
+            (status = 200, description = "The user must change password at the next login or token refresh", body = AdminUserResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("/{id}/force-password-reset")]
+pub async fn force_password_reset_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = path.into_inner();
+    let user = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            users_repo::force_password_change(&pool, user_id)
+                .map_err(not_found_as("User not found"))?;
+            load_user(&pool, user_id)
+        }
+    })
+    .await??;
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.users.force_password_reset",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: Some(user_id),
+            client: Some(&client),
+            details: json!({}),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/users/{id}/unlock",
+        params(("id" = Uuid, Path, description = "User id")),
+        responses(
+            (status = 200, description = "Failed login counter reset and lockout lifted", body = AdminUserResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("/{id}/unlock")]
+pub async fn unlock_user_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = path.into_inner();
+    let user = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            users_repo::reset_failed_logins(&pool, user_id)
+                .map_err(not_found_as("User not found"))?;
+            load_user(&pool, user_id)
+        }
+    })
+    .await??;
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.users.unlock",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: Some(user_id),
+            client: Some(&client),
+            details: json!({}),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[utoipa::path(
+        put,
+        path = "/api/admin/users/{id}/roles",
+        params(("id" = Uuid, Path, description = "User id")),
+        request_body = AssignRoles,
+        responses(
+            (status = 200, description = "Roles replaced; they apply from the user's next token refresh", body = AdminUserResponse),
+            (status = 400, description = "Bad Request: unknown role", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 409, description = "Conflict: admins cannot remove their own admin role", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[put("/{id}/roles")]
+pub async fn set_roles_handler(
+    pool: web::Data<DbPool>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: web::Json<AssignRoles>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let user_id = path.into_inner();
+    let wanted: BTreeSet<String> = body.into_inner().roles.into_iter().collect();
+    if user_id == admin.user_id && !wanted.contains(ADMIN_ROLE) {
+        return Err(ServiceError::Conflict(
+            "You cannot remove your own admin role".into(),
+        ));
+    }
+
+    let admin_id = admin.user_id;
+    let user = web::block(move || -> Result<_, ServiceError> {
+        users_repo::get_user_by_id(&pool, user_id).map_err(not_found_as("User not found"))?;
+        let mut conn = establish_connection(&pool)?;
+        conn.transaction::<_, ServiceError, _>(|conn| {
+            let previous = roles_repo::role_names_for_user_with_connection(conn, user_id)?;
+            for role in previous.iter().filter(|r| !wanted.contains(*r)) {
+                roles_repo::remove_role_with_connection(conn, user_id, role)?;
+            }
+            for role in &wanted {
+                if !roles_repo::assign_role_with_connection(conn, user_id, role)? {
+                    return Err(ServiceError::ValidationError(format!(
+                        "Unknown role: {}",
+                        role
+                    )));
+                }
+            }
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "admin.users.set_roles",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(admin_id),
+                    target_user_id: Some(user_id),
+                    client: Some(&client),
+                    details: json!({ "previous": previous, "roles": wanted }),
+                },
+            )?;
+            Ok(())
+        })?;
+        load_user(&pool, user_id)
+    })
+    .await??;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/admin/users/{id}/impersonate",
+        params(("id" = Uuid, Path, description = "User id")),
+        request_body = ImpersonationRequest,
+        responses(
+            (status = 200, description = "Short-lived token acting as the user, with the admin in the act claim. Every audit event recorded with it carries impersonator_id; it cannot change the user's password or other credentials. It is bound to a session of the user that cannot be refreshed, and stops working when that session is revoked or the admin loses the admin role", body = ImpersonationResponse),
+            (status = 400, description = "Bad Request: missing reason", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 409, description = "Conflict: admins, inactive accounts and the caller cannot be impersonated", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[post("/{id}/impersonate")]
+pub async fn impersonate_user_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    jwt_keys: web::Data<JwtKeys>,
+    admin: RequireScope<AdminScope, RequireRole<Admin>>,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: web::Json<ImpersonationRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let user_id = path.into_inner();
+    let reason = body.into_inner().reason;
+    let (user, grants) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            let user = users_repo::get_user_by_id(&pool, user_id)
+                .map_err(not_found_as("User not found"))?;
+            let grants = roles_repo::grants_for_user(&pool, user_id)?;
+            Ok((user, grants))
+        }
+    })
+    .await??;
+
+    if let Err(e) = impersonation::ensure_can_impersonate(admin.user_id, &user, &grants.roles) {
+        audit::record(
+            &pool,
+            AuditEvent {
+                action: "admin.users.impersonate",
+                outcome: AuditOutcome::Failure,
+                actor_id: Some(admin.user_id),
+                target_user_id: Some(user_id),
+                client: Some(&client),
+                details: json!({ "reason": reason, "error": e.to_string() }),
+            },
+        )
+        .await;
+        return Err(e);
+    }
+
+    let session = web::block({
+        let pool = pool.clone();
+        let app_config = app_config.clone();
+        let client = client.clone();
+        move || -> Result<_, ServiceError> {
+            let mut conn = establish_connection(&pool)?;
+            Ok(impersonation::start_session_with_connection(
+                &mut conn,
+                user_id,
+                &client,
+                &app_config,
+            )?)
+        }
+    })
+    .await??;
+    let claims = impersonation::impersonation_claims(
+        user_id,
+        grants,
+        admin.user_id,
+        session.id,
+        &app_config,
+    );
+    let access_token = jwt_keys.sign(&claims)?;
+    let expires_in = claims.exp as i64 - claims.iat as i64;
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "admin.users.impersonate",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(admin.user_id),
+            target_user_id: Some(user_id),
+            client: Some(&client),
+            details: json!({
+                "reason": reason,
+                "expires_in": expires_in,
+                "scope": claims.scope,
+                "session_id": session.id,
+            }),
+        },
+    )
+    .await;
+    info!(
+        "Admin {} is impersonating user {} for {}s",
+        admin.user_id, user_id, expires_in
+    );
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(ImpersonationResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in,
+            user_id,
+            username: user.username,
+        }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/admin/roles",
+        responses(
+            (status = 200, description = "Roles that can be assigned to users", body = [Role]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: admin role and admin scope required", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "admin",
+        security(("oauth2" = ["admin"]))
+    )]
+#[get("/admin/roles")]
+pub async fn list_roles_handler(
+    pool: web::Data<DbPool>,
+    _admin: RequireScope<AdminScope, RequireRole<Admin>>,
+) -> Result<HttpResponse, ServiceError> {
+    let roles = web::block(move || roles_repo::list_roles(&pool)).await??;
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// * Carica l'utente con i suoi ruoli nella forma mostrata agli amministratori
+fn load_user(pool: &DbPool, user_id: Uuid) -> Result<AdminUserResponse, ServiceError> {
+    let user = users_repo::get_user_by_id(pool, user_id).map_err(not_found_as("User not found"))?;
+    let roles = roles_repo::role_names_for_user(pool, user.id)?;
+    Ok(AdminUserResponse::from_user(user, roles))
+}
+
+fn not_found_as(message: &'static str) -> impl Fn(diesel::result::Error) -> ServiceError {
+    move |e| match e {
+        diesel::result::Error::NotFound => ServiceError::NotFound(message.into()),
+        e => ServiceError::DatabaseError(e),
+    }
+}
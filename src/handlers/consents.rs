@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use actix_web::{delete, get, web, HttpResponse};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    models::{audit_event::AuditOutcome, oauth::ConsentResponse},
+    repositories::{as_user, oauth_clients_repo, oauth_consents_repo, sessions_repo},
+    services::audit::{self, AuditEvent},
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/api/me/consents",
+        responses(
+            (status = 200, description = "Applications the current user allowed to act on their behalf", body = [ConsentResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[get("/me/consents")]
+pub async fn list_consents_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+) -> Result<HttpResponse, ServiceError> {
+    let consents = web::block(move || -> Result<_, ServiceError> {
+        let (consents, names) = as_user(&pool, auth.user_id, |conn| {
+            let consents = oauth_consents_repo::list_for_user_with_connection(conn, auth.user_id)?;
+            let client_ids: Vec<Uuid> = consents.iter().map(|c| c.client_id).collect();
+            let names: HashMap<Uuid, String> =
+                oauth_clients_repo::names_with_connection(conn, &client_ids)?
+                    .into_iter()
+                    .collect();
+            Ok((consents, names))
+        })?;
+        Ok(consents
+            .into_iter()
+            .map(|consent| {
+                let name = names.get(&consent.client_id).cloned().unwrap_or_default();
+                ConsentResponse::new(consent, name)
+            })
+            .collect::<Vec<_>>())
+    })
+    .await??;
+    Ok(HttpResponse::Ok().json(consents))
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/me/consents/{client_id}",
+        params(("client_id" = Uuid, Path, description = "OAuth client id")),
+        responses(
+            (status = 204, description = "Consent withdrawn and the application's sessions revoked"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: no consent for this application", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[delete("/me/consents/{client_id}")]
+pub async fn revoke_consent_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    let client_id = path.into_inner();
+    let user_id = auth.user_id;
+    let revoked = web::block(move || -> Result<bool, ServiceError> {
+        Ok(as_user(&pool, user_id, |conn| {
+            if oauth_consents_repo::delete_with_connection(conn, user_id, client_id)? == 0 {
+                return Ok(false);
+            }
+            // * Senza consenso l'applicazione perde anche l'accesso già ottenuto
+            let revoked_sessions =
+                sessions_repo::revoke_for_client_with_connection(conn, user_id, client_id)?;
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "oauth.consent.revoked",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(user_id),
+                    target_user_id: Some(user_id),
+                    client: Some(&client),
+                    details: json!({ "client_id": client_id, "revoked_sessions": revoked_sessions }),
+                },
+            )?;
+            Ok(true)
+        })?)
+    })
+    .await??;
+    if !revoked {
+        return Err(ServiceError::NotFound(
+            "No consent for this application".into(),
+        ));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
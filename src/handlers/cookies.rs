@@ -0,0 +1,58 @@
+use actix_web::cookie::{time, Cookie, SameSite};
+use chrono::Duration;
+
+use crate::{config::app_config::AppConfig, extractors::auth::AUTH_COOKIE};
+
+pub const REFRESH_COOKIE: &str = "refresh_token";
+// * Il refresh token viene inviato solo all'endpoint che lo usa
+pub const REFRESH_COOKIE_PATH: &str = "/api/token";
+
+// * Cookie HTTP-only che contiene il token JWT
+pub fn auth_cookie(token: String, app_config: &AppConfig) -> Cookie<'static> {
+    Cookie::build(AUTH_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .secure(app_config.is_production())
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(Duration::days(1).num_seconds()))
+        .finish()
+}
+
+// * Cookie HTTP-only che contiene il refresh token della sessione
+pub fn refresh_cookie(refresh_token: String, app_config: &AppConfig) -> Cookie<'static> {
+    Cookie::build(REFRESH_COOKIE, refresh_token)
+        .path(REFRESH_COOKIE_PATH)
+        .http_only(true)
+        .secure(app_config.is_production())
+        .same_site(SameSite::Strict)
+        .max_age(time::Duration::seconds(
+            app_config.refresh_token_exp_secs as i64,
+        ))
+        .finish()
+}
+
+pub const EXTERNAL_LOGIN_COOKIE: &str = "external_login_state";
+pub const EXTERNAL_LOGIN_COOKIE_PATH: &str = "/api/auth/providers";
+
+// * Cookie con lo state del login esterno in corso. SameSite=Lax perché il callback arriva
+// * da un redirect del provider
+pub fn external_login_cookie(state: String, app_config: &AppConfig) -> Cookie<'static> {
+    Cookie::build(EXTERNAL_LOGIN_COOKIE, state)
+        .path(EXTERNAL_LOGIN_COOKIE_PATH)
+        .http_only(true)
+        .secure(app_config.is_production())
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(
+            app_config.external_login_ttl_secs as i64,
+        ))
+        .finish()
+}
+
+// * Cookie scaduti che rimuovono dal browser il token JWT e il refresh token
+pub fn removal_cookies(app_config: &AppConfig) -> [Cookie<'static>; 2] {
+    let mut auth = auth_cookie(String::new(), app_config);
+    auth.make_removal();
+    let mut refresh = refresh_cookie(String::new(), app_config);
+    refresh.make_removal();
+    [auth, refresh]
+}
@@ -0,0 +1,200 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    models::{
+        audit_event::AuditOutcome,
+        data_export::{
+            DataExport, DataExportResponse, DataExportStatus, NewDataExport, PersonalDataArchive,
+        },
+    },
+    repositories::{audit_events_repo, data_exports_repo},
+    services::{
+        audit::{self, AuditEvent},
+        data_export,
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/api/me/export",
+        responses(
+            (status = 200, description = "JSON archive of the personal data held about the current user", body = PersonalDataArchive),
+            (status = 202, description = "The archive is large and is being generated in the background; poll the Location URL until download_url is set", body = DataExportResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/me/export")]
+pub async fn export_personal_data_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    let user_id = auth.user_id;
+    let sync_max_events = app_config.data_export_sync_max_events;
+    let expires_at =
+        (Utc::now() + Duration::hours(app_config.data_export_ttl_hours as i64)).naive_utc();
+
+    // * Le esportazioni piccole vengono restituite subito; quelle grandi generate in background
+    let (archive, export) = web::block({
+        let pool = pool.clone();
+        move || -> Result<_, ServiceError> {
+            data_exports_repo::delete_expired(&pool)?;
+            if audit_events_repo::count_for_user(&pool, user_id)? <= sync_max_events {
+                return Ok((Some(data_export::build_archive(&pool, user_id)?), None));
+            }
+            // * Una sola generazione alla volta per utente
+            if let Some(pending) = data_exports_repo::pending_for_user(&pool, user_id)? {
+                return Ok((None, Some((pending, false))));
+            }
+            let export = data_exports_repo::create(
+                &pool,
+                NewDataExport {
+                    user_id,
+                    expires_at,
+                },
+            )?;
+            Ok((None, Some((export, true))))
+        }
+    })
+    .await??;
+
+    let (details, response) = match (archive, export) {
+        (Some(archive), _) => (
+            json!({ "mode": "sync" }),
+            HttpResponse::Ok()
+                .insert_header((header::CONTENT_DISPOSITION, attachment(user_id)))
+                .json(archive),
+        ),
+        (None, Some((export, created))) => {
+            if created {
+                data_export::spawn_generation(pool.get_ref().clone(), export.id, user_id);
+            }
+            (
+                json!({ "mode": "async", "export_id": export.id, "created": created }),
+                HttpResponse::Accepted()
+                    .insert_header((header::LOCATION, format!("/api/me/exports/{}", export.id)))
+                    .json(DataExportResponse::from_export(&export)),
+            )
+        }
+        (None, None) => return Err(ServiceError::InternalServerError),
+    };
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "user.data_export",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(user_id),
+            target_user_id: Some(user_id),
+            client: Some(&client),
+            details,
+        },
+    )
+    .await;
+    Ok(response)
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/me/exports/{id}",
+        params(("id" = Uuid, Path, description = "Export id")),
+        responses(
+            (status = 200, description = "Status of a background export; download_url is set once the archive is ready", body = DataExportResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown or expired export", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/me/exports/{id}")]
+pub async fn get_data_export_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let export = find_export(pool, auth.user_id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(DataExportResponse::from_export(&export)))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/me/exports/{id}/download",
+        params(("id" = Uuid, Path, description = "Export id")),
+        responses(
+            (status = 200, description = "The generated archive", body = PersonalDataArchive),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown or expired export", body = ErrorResponse),
+            (status = 409, description = "Conflict: the archive is still being generated or generation failed", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/me/exports/{id}/download")]
+pub async fn download_data_export_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let export = find_export(pool.clone(), auth.user_id, path.into_inner()).await?;
+    let archive = match (DataExportStatus::parse(&export.status), export.archive) {
+        (DataExportStatus::Ready, Some(archive)) => archive,
+        (DataExportStatus::Pending, _) => {
+            return Err(ServiceError::Conflict(
+                "The export is still being generated".into(),
+            ))
+        }
+        _ => {
+            return Err(ServiceError::Conflict(
+                "The export failed; please request a new one".into(),
+            ))
+        }
+    };
+    audit::record(
+        &pool,
+        AuditEvent {
+            action: "user.data_export.download",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(auth.user_id),
+            target_user_id: Some(auth.user_id),
+            client: Some(&client),
+            details: json!({ "export_id": export.id }),
+        },
+    )
+    .await;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_DISPOSITION, attachment(auth.user_id)))
+        .json(archive))
+}
+
+async fn find_export(
+    pool: web::Data<DbPool>,
+    user_id: Uuid,
+    export_id: Uuid,
+) -> Result<DataExport, ServiceError> {
+    web::block(move || data_exports_repo::find_for_user(&pool, export_id, user_id))
+        .await?
+        .map_err(|e| match e {
+            diesel::result::Error::NotFound => ServiceError::NotFound("Export not found".into()),
+            e => e.into(),
+        })
+}
+
+fn attachment(user_id: Uuid) -> String {
+    format!(
+        "attachment; filename=\"personal-data-{}-{}.json\"",
+        user_id,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    )
+}
@@ -0,0 +1,382 @@
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse};
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    handlers::cookies,
+    models::{
+        audit_event::AuditOutcome,
+        user_identity::{
+            ExternalLoginCallback, IdentityProviderResponse, LinkIdentity, LinkIdentityResponse,
+            UserIdentityResponse,
+        },
+    },
+    repositories::{user_identities_repo, users_repo},
+    services::{
+        audit::{self, AuditEvent},
+        external_login::{self, ExternalCallback, IdentityProviders},
+        jwt::JwtKeys,
+        oauth,
+        password::PasswordService,
+        password_policy::PasswordPolicy,
+        session::{self, TokenSettings},
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/api/auth/providers",
+        responses(
+            (status = 200, description = "Upstream identity providers users can sign in with", body = Vec<IdentityProviderResponse>)
+        ),
+        tag = "authentication"
+    )]
+#[get("/auth/providers")]
+pub async fn list_providers_handler(
+    providers: web::Data<IdentityProviders>,
+    app_config: web::Data<AppConfig>,
+) -> HttpResponse {
+    let providers: Vec<IdentityProviderResponse> = providers
+        .all()
+        .map(|provider| IdentityProviderResponse {
+            id: provider.config.id.clone(),
+            name: provider.config.name.clone(),
+            login_url: app_config
+                .oidc_url(&format!("/api/auth/providers/{}/login", provider.config.id)),
+        })
+        .collect();
+    HttpResponse::Ok().json(providers)
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/auth/providers/{provider}/login",
+        params(("provider" = String, Path, description = "Identity provider id")),
+        responses(
+            (status = 303, description = "Redirect to the provider's login page; the state of the login is kept in a cookie"),
+            (status = 404, description = "Not Found: unknown provider", body = ErrorResponse),
+            (status = 502, description = "Bad Gateway: the provider's discovery document could not be read", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/auth/providers/{provider}/login")]
+pub async fn external_login_handler(
+    pool: web::Data<DbPool>,
+    providers: web::Data<IdentityProviders>,
+    app_config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    let provider_id = path.into_inner();
+    if providers.get(&provider_id).is_none() {
+        return Err(ServiceError::NotFound("Unknown identity provider".into()));
+    }
+    let started = web::block({
+        let app_config = app_config.clone();
+        move || {
+            let provider = providers.get(&provider_id).expect("provider checked above");
+            external_login::start_login(&pool, &providers, provider, None, &app_config)
+        }
+    })
+    .await??;
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, started.authorization_url))
+        .cookie(cookies::external_login_cookie(started.state, &app_config))
+        .finish())
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/auth/providers/{provider}/callback",
+        params(
+            ("provider" = String, Path, description = "Identity provider id"),
+            ExternalLoginCallback
+        ),
+        responses(
+            (status = 303, description = "Redirect to external_login_redirect_url: with the session cookies after a sign-in, with linked={provider} after linking the identity to the account, otherwise with error (access_denied, account_exists, account_inactive, invalid_profile, provider_error or server_error) and error_description"),
+            (status = 404, description = "Not Found: unknown provider", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/auth/providers/{provider}/callback")]
+pub async fn external_callback_handler(
+    pool: web::Data<DbPool>,
+    providers: web::Data<IdentityProviders>,
+    app_config: web::Data<AppConfig>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    client: ClientInfo,
+    req: HttpRequest,
+) -> Result<HttpResponse, ServiceError> {
+    let provider_id = req.match_info().query("provider").to_string();
+    if providers.get(&provider_id).is_none() {
+        return Err(ServiceError::NotFound("Unknown identity provider".into()));
+    }
+    let callback = web::Query::<ExternalLoginCallback>::from_query(req.query_string())
+        .map_err(|e| ServiceError::ValidationError(e.to_string()))?
+        .into_inner();
+    let state_cookie = req
+        .cookie(cookies::EXTERNAL_LOGIN_COOKIE)
+        .map(|cookie| cookie.value().to_string());
+    let redirect_url = app_config
+        .external_login_redirect_url
+        .clone()
+        .unwrap_or_else(|| "/".into());
+    let mut removal = cookies::external_login_cookie(String::new(), &app_config);
+    removal.make_removal();
+
+    let result = web::block({
+        let app_config = app_config.clone();
+        let provider_id = provider_id.clone();
+        move || {
+            let provider = providers.get(&provider_id).expect("provider checked above");
+            external_login::finish_login(
+                &pool,
+                &providers,
+                provider,
+                callback,
+                state_cookie.as_deref(),
+                &client,
+                &app_config,
+            )
+        }
+    })
+    .await?;
+    let login = match result {
+        Ok(ExternalCallback::SignedIn(login)) => login,
+        Ok(ExternalCallback::Linked(identity)) => {
+            info!(
+                "User {} linked identity {} of {}",
+                identity.user_id, identity.id, provider_id
+            );
+            let location = oauth::redirect_with(
+                &absolute_url(&redirect_url, &app_config),
+                &[("linked", &provider_id)],
+            );
+            return Ok(HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, location))
+                .cookie(removal)
+                .finish());
+        }
+        Err(e) => {
+            warn!("External login with {} failed: {}", provider_id, e);
+            let (error, description) = redirect_error(&e);
+            let location = oauth::redirect_with(
+                &absolute_url(&redirect_url, &app_config),
+                &[("error", error), ("error_description", &description)],
+            );
+            return Ok(HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, location))
+                .cookie(removal)
+                .finish());
+        }
+    };
+
+    info!("User {} signed in with {}", login.user.id, provider_id);
+    let claims = session::access_claims(
+        &login.user,
+        login.grants,
+        login.issued.session.id,
+        login.issued.organization.as_ref(),
+        &TokenSettings::global(&app_config),
+        &app_config,
+        &password_policy,
+    );
+    let token = jwt_keys.sign(&claims)?;
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, redirect_url))
+        .cookie(cookies::auth_cookie(token, &app_config))
+        .cookie(cookies::refresh_cookie(
+            login.issued.refresh_token,
+            &app_config,
+        ))
+        .cookie(removal)
+        .finish())
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/me/identities",
+        responses(
+            (status = 200, description = "Upstream identities linked to the current user", body = [UserIdentityResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[get("/me/identities")]
+pub async fn list_identities_handler(
+    pool: web::Data<DbPool>,
+    providers: web::Data<IdentityProviders>,
+    auth: AuthenticatedUser,
+) -> Result<HttpResponse, ServiceError> {
+    let identities =
+        web::block(move || user_identities_repo::list_for_user(&pool, auth.user_id)).await??;
+    let identities: Vec<UserIdentityResponse> = identities
+        .into_iter()
+        .map(|identity| {
+            let name = providers
+                .get(&identity.provider)
+                .map(|provider| provider.config.name.clone());
+            UserIdentityResponse::new(identity, name)
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(identities))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/me/identities",
+        request_body = LinkIdentity,
+        responses(
+            (status = 200, description = "Link started: open authorization_url in the browser; the provider's callback links the identity and redirects to external_login_redirect_url", body = LinkIdentityResponse),
+            (status = 400, description = "Bad Request: invalid input", body = ErrorResponse),
+            (status = 401, description = "Unauthorized: missing token, wrong password or, for accounts without a password, a sign-in that is not recent enough", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown provider", body = ErrorResponse),
+            (status = 502, description = "Bad Gateway: the provider's discovery document could not be read", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[post("/me/identities")]
+pub async fn link_identity_handler(
+    pool: web::Data<DbPool>,
+    providers: web::Data<IdentityProviders>,
+    app_config: web::Data<AppConfig>,
+    password_service: web::Data<PasswordService>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<LinkIdentity>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let body = body.into_inner();
+    if providers.get(&body.provider).is_none() {
+        return Err(ServiceError::NotFound("Unknown identity provider".into()));
+    }
+
+    // * 1. Prima di collegare un provider l'utente conferma la propria identità: con la password
+    // *    se ne ha una, altrimenti con un login recente
+    let (user, failure) = web::block({
+        let (pool, app_config) = (pool.clone(), app_config.clone());
+        let password = body.password.clone();
+        move || -> Result<_, ServiceError> {
+            let user = users_repo::get_own_user(&pool, auth.user_id)
+                .map_err(|_| ServiceError::Unauthorized("User no longer exists".into()))?;
+            let failure = session::reauthentication_failure(
+                &pool,
+                &password_service,
+                &user,
+                password.as_deref(),
+                auth.session_id,
+                &app_config,
+            )?;
+            Ok((user, failure))
+        }
+    })
+    .await??;
+    if let Some(reason) = failure {
+        audit::record(
+            &pool,
+            AuditEvent {
+                action: "identity.link_requested",
+                outcome: AuditOutcome::Failure,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(&client),
+                details: json!({ "provider": body.provider, "reason": reason }),
+            },
+        )
+        .await;
+        return Err(ServiceError::Unauthorized(match reason {
+            "password_missing" => "The current password is required".into(),
+            "invalid_password" => "Password is incorrect".into(),
+            _ => "Sign in again to link a provider".into(),
+        }));
+    }
+
+    // * 2. Il login presso il provider parte come un normale social login, ma lo state ricorda
+    // *    l'utente a cui collegare l'identità
+    let started = web::block({
+        let app_config = app_config.clone();
+        move || {
+            let provider = providers
+                .get(&body.provider)
+                .expect("provider checked above");
+            external_login::start_login(&pool, &providers, provider, Some(user.id), &app_config)
+        }
+    })
+    .await??;
+    Ok(HttpResponse::Ok()
+        .cookie(cookies::external_login_cookie(started.state, &app_config))
+        .json(LinkIdentityResponse {
+            authorization_url: started.authorization_url,
+        }))
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/me/identities/{id}",
+        params(("id" = Uuid, Path, description = "Linked identity id")),
+        responses(
+            (status = 204, description = "Identity unlinked: it can no longer be used to sign in"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: no linked identity with this id", body = ErrorResponse),
+            (status = 409, description = "Conflict: the identity is the account's only login method", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[delete("/me/identities/{id}")]
+pub async fn unlink_identity_handler(
+    pool: web::Data<DbPool>,
+    providers: web::Data<IdentityProviders>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    let identity_id = path.into_inner();
+    let identity = web::block(move || {
+        external_login::unlink_identity(&pool, &providers, auth.user_id, identity_id, &client)
+    })
+    .await??;
+    info!(
+        "User {} unlinked identity {} of {}",
+        identity.user_id, identity.id, identity.provider
+    );
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// * redirect_with richiede un URL assoluto: un path relativo viene risolto sull'issuer
+fn absolute_url(url: &str, app_config: &AppConfig) -> String {
+    if url.starts_with('/') {
+        app_config.oidc_url(url)
+    } else {
+        url.to_string()
+    }
+}
+
+// * Errore per il frontend, che non riceve il JSON dell'errore ma un redirect; i dettagli
+// * degli errori interni restano nei log
+fn redirect_error(error: &ServiceError) -> (&'static str, String) {
+    match error {
+        ServiceError::Unauthorized(message) => ("access_denied", message.clone()),
+        ServiceError::Conflict(message) => ("account_exists", message.clone()),
+        ServiceError::AccountInactive { .. } => ("account_inactive", error.to_string()),
+        ServiceError::Forbidden(message) | ServiceError::ValidationError(message) => {
+            ("invalid_profile", message.clone())
+        }
+        ServiceError::IdentityProvider(message) => ("provider_error", message.clone()),
+        _ => ("server_error", "The login could not be completed".into()),
+    }
+}
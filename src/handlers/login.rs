@@ -1,16 +1,27 @@
-use actix_web::{
-    cookie::{Cookie, SameSite},
-    post, web, HttpResponse,
-};
-use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use actix_web::{post, web, HttpResponse};
 use chrono::{Duration, Utc};
+use serde_json::json;
+use tracing::{error, info};
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
     config::app_config::AppConfig,
     errors::{ErrorResponse, ServiceError},
-    models::{auth_response_model::AuthResponse, claims::Claims, login::LoginUser, user::User},
-    repositories::users_repo,
+    extractors::client_info::ClientInfo,
+    handlers::cookies,
+    models::{
+        audit_event::AuditOutcome, auth_response_model::AuthResponse, login::LoginUser,
+        role::RoleGrants,
+    },
+    repositories::{establish_connection, roles_repo, users_repo},
+    services::{
+        audit::{self, AuditEvent},
+        jwt::JwtKeys,
+        password::PasswordService,
+        password_policy::PasswordPolicy,
+        session::{self, TokenSettings},
+    },
     DbPool,
 };
 
@@ -19,10 +30,12 @@ use crate::{
         path = "/api/login",
         request_body = LoginUser,
         responses(
-            (status = 200, description = "User created", body = User),
+            (status = 200, description = "User logged in; if password_change_required is true the token can only call /api/me/password", body = AuthResponse),
             (status = 400, description = "Bad Request: invalid input", body = ErrorResponse),
             (status = 409, description = "Conflict: user already exists", body = ErrorResponse),
+            (status = 403, description = "Forbidden: account suspended, disabled or pending verification (see error)", body = ErrorResponse, example = json!({"code":403,"error":"Account Suspended","message":"Account suspended until 2026-11-01 00:00 UTC: spam"})),
             (status = 404, description = "Not Found", body = ErrorResponse),
+            (status = 423, description = "Locked: too many failed logins, retry later", body = ErrorResponse),
             (status = 422, description = "Unprocessable Entity: validation error", body = ErrorResponse),
             (status = 500, description = "Internal Server Error", body = ErrorResponse, example = json!({"code":500,"error":"Database Error","message":"Database connection failed"})),
             (status = 500, description = "JWT Key Error", body = ErrorResponse, example = json!({"code":500,"error":"JWT Key Error","message":"Errore lettura chiave privata"})),
@@ -34,66 +47,202 @@ use crate::{
 pub async fn login_handler(
     pool: web::Data<DbPool>,
     app_config: web::Data<AppConfig>,
+    password_service: web::Data<PasswordService>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    client: ClientInfo,
     body: web::Json<LoginUser>,
 ) -> Result<HttpResponse, ServiceError> {
     // * * 1. Validazione dei dati di input ricevuti dal client
-    let _validate = body
-        .validate()
+    body.validate()
         .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
 
     // * * 2. Recupero dell'utente dal database tramite username
-    let user = users_repo::get_user_by_username(&pool, &body.username)
-        .map_err(|_| ServiceError::Unauthorized("Invalid username or password".into()))?;
-
-    // * 3. Parsing dell'hash della password salvata per il confronto
-    let parsed_hash = PasswordHash::new(&user.password)
-        .map_err(|_| ServiceError::JwtGenerationError("Failed to parse password hash".into()))?;
+    let user = match users_repo::get_user_by_username(&pool, &body.username) {
+        Ok(user) => user,
+        Err(_) => {
+            audit_login_failure(&pool, &client, None, "unknown_user", &body.username).await;
+            return Err(ServiceError::Unauthorized(
+                "Invalid username or password".into(),
+            ));
+        }
+    };
 
-    // * 4. Verifica della password fornita rispetto all'hash salvato
-    if Argon2::default()
-        .verify_password(body.password.as_bytes(), &parsed_hash)
+    // * 3. Verifica della password fornita rispetto all'hash salvato (con pepper, se configurato).
+    // *    Dopo troppi tentativi falliti l'account resta bloccato per un periodo configurabile
+    if user.is_locked() {
+        audit_login_failure(&pool, &client, Some(user.id), "locked", &body.username).await;
+        return Err(ServiceError::Locked(
+            "Account temporarily locked after too many failed logins".into(),
+        ));
+    }
+    if password_service
+        .verify_account(&body.password, user.password.as_deref())
         .is_err()
     {
+        audit_login_failure(
+            &pool,
+            &client,
+            Some(user.id),
+            "invalid_password",
+            &body.username,
+        )
+        .await;
+        register_failed_login(&pool, &app_config, &client, user.id).await;
         return Err(ServiceError::Unauthorized(
             "Invalid username or password".into(),
         ));
     }
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        if let Err(e) = users_repo::reset_failed_logins(&pool, user.id) {
+            error!("Failed to reset failed logins for {}: {:?}", user.id, e);
+        }
+    }
+    // * Solo gli account attivi possono accedere; l'errore indica stato, motivo e scadenza
+    if let Err(e) = user.ensure_active() {
+        let reason = format!("account_{}", user.account_status().as_str());
+        audit_login_failure(&pool, &client, Some(user.id), &reason, &body.username).await;
+        return Err(e);
+    }
 
-    // * 5. Creazione dei claims per il JWT (contengono info utente e scadenza token)
-    let now = Utc::now();
-    let claims = Claims {
-        sub: user.id.to_string(),
-        exp: (now + Duration::seconds(app_config.jwt_exp_secs as i64)).timestamp() as usize,
-        iat: now.timestamp() as usize,
-        iss: app_config.jwt_issuer.clone(),
-        aud: app_config.jwt_audience.clone(),
-    };
+    // * 4. Rehash della password se l'hash usa un pepper vecchio o assente
+    if user
+        .password
+        .as_deref()
+        .is_some_and(|hash| password_service.needs_rehash(hash))
+    {
+        match password_service.hash(&body.password) {
+            Ok(new_hash) => match users_repo::update_password_hash(&pool, user.id, &new_hash) {
+                Ok(_) => info!("Upgraded password hash pepper for user {}", user.id),
+                Err(e) => error!(
+                    "Failed to store upgraded password hash for {}: {:?}",
+                    user.id, e
+                ),
+            },
+            Err(e) => error!("Password rehash failed for user {}: {:?}", user.id, e),
+        }
+    }
 
-    let private_key = std::fs::read("/app/private_key.pem")
-        .map_err(|_| ServiceError::JwtGenerationError("Failed to read private key".into()))?;
-    let token = claims
-        .generate_jwt(&private_key)
-        .map_err(|_| ServiceError::JwtGenerationError("Failed to generate JWT".into()))?;
+    // * 5. Creazione della sessione (famiglia di refresh token) per il dispositivo,
+    // *    registrazione del login nel log di audit e lettura dei ruoli dell'utente
+    let (issued, grants) = web::block({
+        let pool = pool.clone();
+        let app_config = app_config.clone();
+        move || -> Result<(session::IssuedSession, RoleGrants), ServiceError> {
+            let mut conn = establish_connection(&pool)?;
+            let issued =
+                session::start_session_with_connection(&mut conn, user.id, &client, &app_config)?;
+            audit::record_with_connection(
+                &mut conn,
+                AuditEvent {
+                    action: "login.success",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(user.id),
+                    target_user_id: Some(user.id),
+                    client: Some(&client),
+                    details: json!({ "session_id": issued.session.id, "device": client.device }),
+                },
+            )?;
+            let grants = roles_repo::grants_for_user_with_connection(&mut conn, user.id)?;
+            Ok((issued, grants))
+        }
+    })
+    .await??;
 
-    // * 7. Creazione di un cookie HTTP-only che contiene il token JWT
-    let cookie = Cookie::build("auth_token", token.clone())
-        .path("/")
-        .http_only(true)
-        .secure(app_config.is_production())
-        .same_site(SameSite::Lax)
-        .max_age(actix_web::cookie::time::Duration::seconds(
-            Duration::days(1).num_seconds(),
-        ))
-        .finish();
+    // * 6. Creazione dei claims per il JWT (contengono info utente, ruoli, sessione e scadenza token).
+    // *    Se la password è scaduta o il cambio è imposto, il token è limitato al cambio password
+    let claims = session::access_claims(
+        &user,
+        grants,
+        issued.session.id,
+        issued.organization.as_ref(),
+        &TokenSettings::global(&app_config),
+        &app_config,
+        &password_policy,
+    );
+    let password_change_required = claims.password_change_required;
+    let roles = claims.roles.clone();
+    if password_change_required {
+        info!("Password change required for user {}", user.id);
+    }
+
+    // * 7. Firma del token con la chiave privata caricata all'avvio
+    let token = jwt_keys.sign(&claims)?;
 
-    // * 8. Restituzione della risposta HTTP con il cookie e i dati dell'utente
+    // * 8. Restituzione della risposta HTTP con i cookie e i dati dell'utente
     let user_res = AuthResponse {
         id: user.id,
         email: user.email,
         username: user.username,
         created_at: user.created_at,
-        token,
+        roles,
+        token: token.clone(),
+        refresh_token: issued.refresh_token.clone(),
+        password_change_required,
     };
 
-    Ok(HttpResponse::Ok().cookie(cookie).json(user_res))
+    Ok(HttpResponse::Ok()
+        .cookie(cookies::auth_cookie(token, &app_config))
+        .cookie(cookies::refresh_cookie(issued.refresh_token, &app_config))
+        .json(user_res))
+}
+
+async fn audit_login_failure(
+    pool: &web::Data<DbPool>,
+    client: &ClientInfo,
+    user_id: Option<Uuid>,
+    reason: &str,
+    username: &str,
+) {
+    audit::record(
+        pool,
+        AuditEvent {
+            action: "login.failure",
+            outcome: AuditOutcome::Failure,
+            actor_id: None,
+            target_user_id: user_id,
+            client: Some(client),
+            details: json!({ "reason": reason, "username": username }),
+        },
+    )
+    .await;
+}
+
+// * Conta il login fallito e, raggiunta la soglia, blocca temporaneamente l'account
+async fn register_failed_login(
+    pool: &web::Data<DbPool>,
+    app_config: &AppConfig,
+    client: &ClientInfo,
+    user_id: Uuid,
+) {
+    if app_config.max_failed_logins == 0 {
+        return;
+    }
+    let attempts = match users_repo::record_failed_login(pool, user_id) {
+        Ok(attempts) => attempts,
+        Err(e) => {
+            error!("Failed to record failed login for {}: {:?}", user_id, e);
+            return;
+        }
+    };
+    if attempts as u32 >= app_config.max_failed_logins {
+        let until = (Utc::now() + Duration::seconds(app_config.lockout_secs as i64)).naive_utc();
+        match users_repo::lock_until(pool, user_id, until) {
+            Ok(_) => {
+                audit::record(
+                    pool,
+                    AuditEvent {
+                        action: "account.locked",
+                        outcome: AuditOutcome::Success,
+                        actor_id: None,
+                        target_user_id: Some(user_id),
+                        client: Some(client),
+                        details: json!({ "failed_attempts": attempts, "locked_until": until }),
+                    },
+                )
+                .await
+            }
+            Err(e) => error!("Failed to lock account {}: {:?}", user_id, e),
+        }
+    }
 }
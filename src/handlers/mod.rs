@@ -1,11 +1,89 @@
 use actix_web::web;
-pub mod register;
+pub mod account;
+pub mod admin_audit;
+pub mod admin_oauth_clients;
+pub mod admin_users;
+pub mod consents;
+pub mod cookies;
+pub mod data_export;
+pub mod external_login;
 pub mod login;
+pub mod oauth;
+pub mod oidc;
+pub mod organizations;
+pub mod password;
+pub mod personal_access_tokens;
+pub mod register;
+pub mod sessions;
 pub fn route_config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             .service(login::login_handler)
-            .service(register::register_handler),
+            .service(register::register_handler)
+            .service(external_login::list_providers_handler)
+            .service(external_login::external_login_handler)
+            .service(external_login::external_callback_handler)
+            .service(external_login::list_identities_handler)
+            .service(external_login::link_identity_handler)
+            .service(external_login::unlink_identity_handler)
+            .service(password::change_password_handler)
+            .service(account::delete_account_handler)
+            .service(data_export::export_personal_data_handler)
+            .service(data_export::get_data_export_handler)
+            .service(data_export::download_data_export_handler)
+            .service(sessions::refresh_handler)
+            .service(sessions::list_sessions_handler)
+            .service(sessions::revoke_other_sessions_handler)
+            .service(sessions::revoke_session_handler)
+            .service(personal_access_tokens::list_tokens_handler)
+            .service(personal_access_tokens::create_token_handler)
+            .service(personal_access_tokens::revoke_token_handler)
+            .service(organizations::create_organization_handler)
+            .service(organizations::list_my_organizations_handler)
+            .service(organizations::switch_organization_handler)
+            .service(organizations::list_members_handler)
+            .service(organizations::invite_member_handler)
+            .service(organizations::accept_invitation_handler)
+            .service(organizations::update_member_handler)
+            .service(organizations::remove_member_handler)
+            .service(admin_users::list_roles_handler)
+            .service(admin_audit::list_audit_events_handler)
+            .service(admin_audit::export_audit_events_handler)
+            .service(consents::list_consents_handler)
+            .service(consents::revoke_consent_handler)
+            .service(
+                web::scope("/admin/oauth/clients")
+                    .service(admin_oauth_clients::list_clients_handler)
+                    .service(admin_oauth_clients::create_client_handler)
+                    .service(admin_oauth_clients::update_token_settings_handler)
+                    .service(admin_oauth_clients::delete_client_handler),
+            )
+            .service(
+                web::scope("/admin/users")
+                    .service(admin_users::list_users_handler)
+                    .service(admin_users::get_user_handler)
+                    .service(admin_users::set_status_handler)
+                    .service(admin_users::disable_user_handler)
+                    .service(admin_users::enable_user_handler)
+                    .service(admin_users::force_password_reset_handler)
+                    .service(admin_users::unlock_user_handler)
+                    .service(admin_users::set_roles_handler)
+                    .service(admin_users::impersonate_user_handler),
+            ),
+    );
+    cfg.service(
+        web::scope("/oauth")
+            .service(oauth::authorize_handler)
+            .service(oauth::consent_handler)
+            .service(oauth::token_handler)
+            .service(oauth::device_authorization_handler)
+            .service(oauth::device_verification_handler)
+            .service(oauth::device_decision_handler)
+            .service(oauth::userinfo_handler),
+    );
+    cfg.service(
+        web::scope("/.well-known")
+            .service(oidc::openid_configuration_handler)
+            .service(oidc::jwks_handler),
     );
 }
-
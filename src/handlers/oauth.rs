@@ -0,0 +1,594 @@
+use actix_web::{get, http::header, post, route, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, OAuthError, OAuthErrorResponse, ServiceError},
+    extractors::{
+        auth::{AuthenticatedUser, OAuthAccessUser},
+        client_info::ClientInfo,
+    },
+    models::{
+        audit_event::AuditOutcome,
+        oauth::{
+            AuthorizeRequest, ConsentChoice, ConsentDecision, ConsentPrompt,
+            DeviceAuthorizationRequest, DeviceAuthorizationResponse, DeviceCodeQuery,
+            DeviceVerification, DeviceVerificationPrompt, OAuthClient, TokenRequest, TokenResponse,
+        },
+        oidc::UserInfo,
+    },
+    repositories::{
+        establish_connection, oauth_clients_repo, oauth_consents_repo, oauth_device_codes_repo,
+        sessions_repo, users_repo,
+    },
+    services::{
+        audit::{self, AuditEvent},
+        device_authorization,
+        jwt::JwtKeys,
+        oauth::{self, AuthorizationContext, AuthorizeError, ClientGrant, Grant, TokenGrant},
+        oidc::{self, IdTokenRequest},
+        password_policy::PasswordPolicy,
+        scope::restrict_to_grants,
+        session::{self, TokenSettings},
+        token_exchange,
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/oauth/authorize",
+        params(AuthorizeRequest),
+        responses(
+            (status = 200, description = "The user must consent: show the prompt and post the decision to /oauth/authorize (only when oauth_consent_url is not configured)", body = ConsentPrompt),
+            (status = 302, description = "Redirect to the client with a code or an error, to the login page or to the consent page"),
+            (status = 400, description = "Bad Request: unknown client or redirect_uri not registered, or access_denied while an admin impersonates the user", body = OAuthErrorResponse),
+            (status = 401, description = "Unauthorized: the user is not logged in and oauth_login_url is not configured", body = OAuthErrorResponse),
+            (status = 500, description = "Internal Server Error", body = OAuthErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[get("/authorize")]
+pub async fn authorize_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    req: HttpRequest,
+    user: Option<AuthenticatedUser>,
+    query: web::Query<AuthorizeRequest>,
+) -> Result<HttpResponse, OAuthError> {
+    ensure_not_impersonated(user.as_ref())?;
+    let context = match validate(&pool, query.into_inner()).await? {
+        Ok(context) => context,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    // * 1. L'utente deve essere autenticato (cookie di sessione o Bearer token)
+    let Some(user) = user else {
+        if context.prompt_none {
+            return Ok(found(context.error_redirect(OAuthError::login_required(
+                "The user is not logged in",
+            ))));
+        }
+        return match &app_config.oauth_login_url {
+            Some(login_url) => {
+                let return_to = format!("/oauth/authorize?{}", req.query_string());
+                Ok(found(oauth::redirect_with(
+                    login_url,
+                    &[("return_to", &return_to)],
+                )))
+            }
+            None => Err(OAuthError::login_required(
+                "Log in at /api/login, then repeat the authorization request",
+            )),
+        };
+    };
+
+    // * 2. I client di terze parti richiedono il consenso dell'utente per gli scope richiesti
+    if !context.client.first_party {
+        let consent = web::block({
+            let pool = pool.clone();
+            let client_id = context.client.id;
+            move || oauth_consents_repo::find(&pool, user.user_id, client_id)
+        })
+        .await??;
+        let covered = consent
+            .is_some_and(|consent| context.scopes.iter().all(|s| consent.scopes.contains(s)));
+        if !covered && context.prompt_none {
+            return Ok(found(context.error_redirect(OAuthError::consent_required(
+                "The user has not consented to the requested scopes",
+            ))));
+        }
+        if !covered {
+            return Ok(match &app_config.oauth_consent_url {
+                Some(consent_url) => found(format!("{}?{}", consent_url, req.query_string())),
+                None => HttpResponse::Ok().json(ConsentPrompt {
+                    client_id: context.client.id,
+                    client_name: context.client.name.clone(),
+                    scopes: context.scopes.clone(),
+                    redirect_uri: context.redirect_uri.clone(),
+                    state: context.state.clone(),
+                    nonce: context.nonce.clone(),
+                }),
+            });
+        }
+    }
+
+    // * 3. Codice di autorizzazione e redirect verso il client
+    let redirect = issue_code(pool, app_config, context, user).await?;
+    Ok(found(redirect))
+}
+
+#[utoipa::path(
+        post,
+        path = "/oauth/authorize",
+        request_body(content = ConsentDecision, content_type = "application/x-www-form-urlencoded"),
+        responses(
+            (status = 303, description = "Redirect to the client with a code (approve) or error=access_denied (deny)"),
+            (status = 400, description = "Bad Request: unknown client or redirect_uri not registered, or access_denied while an admin impersonates the user", body = OAuthErrorResponse),
+            (status = 401, description = "Unauthorized: the user is not logged in", body = OAuthErrorResponse),
+            (status = 500, description = "Internal Server Error", body = OAuthErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[post("/authorize")]
+pub async fn consent_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    user: Option<AuthenticatedUser>,
+    client: ClientInfo,
+    form: web::Form<ConsentDecision>,
+) -> Result<HttpResponse, OAuthError> {
+    let user = user.ok_or_else(|| OAuthError::login_required("Log in to answer the request"))?;
+    ensure_not_impersonated(Some(&user))?;
+    let decision = form.decision;
+    let context = match validate(&pool, form.request()).await? {
+        Ok(context) => context,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    let (action, outcome) = match decision {
+        ConsentChoice::Approve => ("oauth.consent.granted", AuditOutcome::Success),
+        ConsentChoice::Deny => ("oauth.consent.denied", AuditOutcome::Failure),
+    };
+    audit::record(
+        &pool,
+        AuditEvent {
+            action,
+            outcome,
+            actor_id: Some(user.user_id),
+            target_user_id: Some(user.user_id),
+            client: Some(&client),
+            details: json!({ "client_id": context.client.id, "scope": context.scope() }),
+        },
+    )
+    .await;
+
+    if decision == ConsentChoice::Deny {
+        let mut params = vec![
+            ("error", "access_denied"),
+            ("error_description", "The user denied the request"),
+        ];
+        if let Some(state) = context.state.as_deref() {
+            params.push(("state", state));
+        }
+        return Ok(see_other(oauth::redirect_with(
+            &context.redirect_uri,
+            &params,
+        )));
+    }
+
+    web::block({
+        let pool = pool.clone();
+        let (user_id, client_id, requested) =
+            (user.user_id, context.client.id, context.scopes.clone());
+        move || -> Result<_, diesel::result::Error> {
+            let mut conn = establish_connection(&pool)?;
+            oauth::grant_consent_with_connection(&mut conn, user_id, client_id, &requested)
+        }
+    })
+    .await??;
+
+    let redirect = issue_code(pool, app_config, context, user).await?;
+    Ok(see_other(redirect))
+}
+
+#[utoipa::path(
+        post,
+        path = "/oauth/token",
+        request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+        responses(
+            (status = 200, description = "Access and refresh token (grant_type authorization_code, refresh_token or urn:ietf:params:oauth:grant-type:device_code), plus an ID token when the openid scope was granted; only an access token for grant_type client_credentials and urn:ietf:params:oauth:grant-type:token-exchange", body = TokenResponse),
+            (status = 400, description = "Bad Request: invalid request, grant or target audience; authorization_pending, slow_down, access_denied or expired_token while a device polls", body = OAuthErrorResponse),
+            (status = 401, description = "Unauthorized: client authentication failed", body = OAuthErrorResponse),
+            (status = 500, description = "Internal Server Error", body = OAuthErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[post("/token")]
+pub async fn token_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    req: HttpRequest,
+    client_info: ClientInfo,
+    form: web::Form<TokenRequest>,
+) -> Result<HttpResponse, OAuthError> {
+    let form = form.into_inner();
+    let authorization = oauth::authorization_header(&req);
+    let (client, grant) = web::block({
+        let pool = pool.clone();
+        let app_config = app_config.clone();
+        let jwt_keys = jwt_keys.clone();
+        move || -> Result<_, OAuthError> {
+            let client = oauth::authenticate_client(
+                &pool,
+                authorization.as_deref(),
+                form.client_id.as_deref(),
+                form.client_secret.as_deref(),
+            )?;
+            let grant = match form.grant_type.as_deref() {
+                Some("authorization_code") => Grant::User(Box::new(oauth::exchange_code(
+                    &pool,
+                    &client,
+                    &form,
+                    &client_info,
+                    &app_config,
+                )?)),
+                Some("refresh_token") => Grant::User(Box::new(oauth::refresh(
+                    &pool,
+                    &client,
+                    &form,
+                    &client_info,
+                    &app_config,
+                )?)),
+                Some(device_authorization::GRANT_TYPE) => {
+                    Grant::User(Box::new(device_authorization::exchange_device_code(
+                        &pool,
+                        &client,
+                        form.device_code.as_deref(),
+                        &client_info,
+                        &app_config,
+                    )?))
+                }
+                Some("client_credentials") => Grant::Client(oauth::client_credentials(
+                    &pool,
+                    &client,
+                    &form,
+                    &client_info,
+                )?),
+                Some(token_exchange::GRANT_TYPE) => {
+                    Grant::Exchange(Box::new(token_exchange::exchange(
+                        &pool,
+                        &client,
+                        &form,
+                        &client_info,
+                        &app_config,
+                        &jwt_keys,
+                    )?))
+                }
+                Some(other) => {
+                    return Err(OAuthError::unsupported_grant_type(format!(
+                        "Unsupported grant_type {}",
+                        other
+                    )))
+                }
+                None => return Err(OAuthError::invalid_request("Missing grant_type")),
+            };
+            Ok((client, grant))
+        }
+    })
+    .await??;
+
+    let response = match grant {
+        Grant::User(grant) => {
+            user_token_response(&client, *grant, &app_config, &password_policy, &jwt_keys)?
+        }
+        Grant::Client(ClientGrant { scope }) => {
+            let claims = oauth::client_claims(&client, &scope, &app_config);
+            TokenResponse {
+                access_token: jwt_keys.sign(&claims)?,
+                token_type: "Bearer",
+                expires_in: claims.exp as i64 - claims.iat as i64,
+                refresh_token: None,
+                scope,
+                id_token: None,
+                issued_token_type: None,
+            }
+        }
+        // * RFC 8693: nessun refresh token, il servizio ne chiede uno nuovo con il token dell'utente
+        Grant::Exchange(claims) => TokenResponse {
+            access_token: jwt_keys.sign(&claims)?,
+            token_type: "Bearer",
+            expires_in: claims.exp as i64 - claims.iat as i64,
+            refresh_token: None,
+            scope: claims.scope.clone().unwrap_or_default(),
+            id_token: None,
+            issued_token_type: Some(token_exchange::ACCESS_TOKEN_TYPE),
+        },
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .insert_header((header::PRAGMA, "no-cache"))
+        .json(response))
+}
+
+// * Access token legato alla sessione dell'utente, più l'ID token se è stato concesso openid
+fn user_token_response(
+    client: &OAuthClient,
+    grant: TokenGrant,
+    app_config: &AppConfig,
+    password_policy: &PasswordPolicy,
+    jwt_keys: &JwtKeys,
+) -> Result<TokenResponse, OAuthError> {
+    let TokenGrant {
+        issued,
+        user,
+        grants,
+        scope,
+        nonce,
+        auth_time,
+    } = grant;
+    // * Il client riceve gli scope concessi, limitati a quelli che i ruoli dell'utente consentono
+    let scopes = restrict_to_grants(&grants, scope.split_whitespace());
+    let mut claims = session::access_claims(
+        &user,
+        grants,
+        issued.session.id,
+        issued.organization.as_ref(),
+        &TokenSettings::for_client(client, app_config),
+        app_config,
+        password_policy,
+    );
+    claims.client_id = Some(client.id.to_string());
+    claims.set_scopes(&scopes);
+    let access_token = jwt_keys.sign(&claims)?;
+    let id_token = if oidc::is_openid(&scope) {
+        let id_claims = oidc::id_token_claims(
+            IdTokenRequest {
+                user: &user,
+                client_id: client.id,
+                session_id: issued.session.id,
+                scope: &scope,
+                nonce,
+                auth_time,
+                access_token: &access_token,
+            },
+            app_config,
+        );
+        Some(jwt_keys.sign(&id_claims)?)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: claims.exp as i64 - claims.iat as i64,
+        refresh_token: Some(issued.refresh_token),
+        scope: scopes.join(" "),
+        id_token,
+        issued_token_type: None,
+    })
+}
+
+#[utoipa::path(
+        method(get, post),
+        path = "/oauth/userinfo",
+        responses(
+            (status = 200, description = "Claims about the user allowed by the scopes of the access token", body = UserInfo),
+            (status = 401, description = "Unauthorized: missing, invalid or revoked access token", body = OAuthErrorResponse),
+            (status = 403, description = "Forbidden: the access token was not issued with the openid scope", body = OAuthErrorResponse),
+            (status = 500, description = "Internal Server Error", body = OAuthErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[route("/userinfo", method = "GET", method = "POST")]
+pub async fn userinfo_handler(
+    pool: web::Data<DbPool>,
+    user: Option<OAuthAccessUser>,
+) -> Result<HttpResponse, OAuthError> {
+    let OAuthAccessUser(user) =
+        user.ok_or_else(|| OAuthError::invalid_token("Missing or invalid access token"))?;
+    let session_id = user
+        .session_id
+        .ok_or_else(|| OAuthError::insufficient_scope("The openid scope is required"))?;
+
+    // * Solo le sessioni OAuth con scope openid; i claim dipendono dagli scope concessi
+    let (account, scope) = web::block(move || -> Result<_, diesel::result::Error> {
+        let session = sessions_repo::get_session(&pool, session_id)?;
+        let account = users_repo::get_own_user(&pool, user.user_id)?;
+        Ok((account, session.scope))
+    })
+    .await??;
+    let scope = scope
+        .filter(|scope| oidc::is_openid(scope))
+        .ok_or_else(|| OAuthError::insufficient_scope("The openid scope is required"))?;
+    let scopes: Vec<&str> = scope.split_whitespace().collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(UserInfo::for_scopes(&account, &scopes)))
+}
+
+#[utoipa::path(
+        post,
+        path = "/oauth/device_authorization",
+        request_body(content = DeviceAuthorizationRequest, content_type = "application/x-www-form-urlencoded"),
+        responses(
+            (status = 200, description = "Device and user codes; the device polls /oauth/token while the user confirms the code", body = DeviceAuthorizationResponse),
+            (status = 400, description = "Bad Request: invalid scope", body = OAuthErrorResponse),
+            (status = 401, description = "Unauthorized: client authentication failed", body = OAuthErrorResponse),
+            (status = 500, description = "Internal Server Error", body = OAuthErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[post("/device_authorization")]
+pub async fn device_authorization_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    req: HttpRequest,
+    form: web::Form<DeviceAuthorizationRequest>,
+) -> Result<HttpResponse, OAuthError> {
+    let form = form.into_inner();
+    let authorization = oauth::authorization_header(&req);
+    let started = web::block({
+        let app_config = app_config.clone();
+        move || -> Result<_, OAuthError> {
+            let client = oauth::authenticate_client(
+                &pool,
+                authorization.as_deref(),
+                form.client_id.as_deref(),
+                form.client_secret.as_deref(),
+            )?;
+            device_authorization::start(&pool, &client, form.scope.as_deref(), &app_config)
+        }
+    })
+    .await??;
+
+    let user_code = device_authorization::format_user_code(&started.authorization.user_code);
+    let verification_uri = app_config
+        .oauth_device_url
+        .clone()
+        .unwrap_or_else(|| app_config.oidc_url("/oauth/device"));
+    let verification_uri_complete =
+        oauth::redirect_with(&verification_uri, &[("user_code", &user_code)]);
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(DeviceAuthorizationResponse {
+            device_code: started.device_code,
+            user_code,
+            verification_uri,
+            verification_uri_complete,
+            expires_in: app_config.device_code_ttl_secs as i64,
+            interval: started.authorization.interval_secs,
+        }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/oauth/device",
+        params(DeviceCodeQuery),
+        responses(
+            (status = 200, description = "The device waiting for the user's confirmation", body = DeviceVerificationPrompt),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown, expired or already answered user code", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[get("/device")]
+pub async fn device_verification_handler(
+    pool: web::Data<DbPool>,
+    _user: AuthenticatedUser,
+    query: web::Query<DeviceCodeQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_code = device_authorization::normalize_user_code(&query.user_code);
+    let prompt = web::block(move || -> Result<_, ServiceError> {
+        let authorization = oauth_device_codes_repo::find_pending_by_user_code(&pool, &user_code)?
+            .ok_or_else(|| ServiceError::NotFound("Unknown or expired code".into()))?;
+        let client = oauth_clients_repo::get_client(&pool, authorization.client_id)?;
+        Ok(DeviceVerificationPrompt {
+            client_id: client.id,
+            client_name: client.name,
+            scopes: authorization
+                .scope
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            user_code: device_authorization::format_user_code(&authorization.user_code),
+            expires_at: authorization.expires_at,
+        })
+    })
+    .await??;
+    Ok(HttpResponse::Ok().json(prompt))
+}
+
+#[utoipa::path(
+        post,
+        path = "/oauth/device",
+        request_body = DeviceVerification,
+        responses(
+            (status = 204, description = "Decision recorded; the device receives its tokens (approve) or access_denied (deny) at the next poll"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown, expired or already answered user code", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "oauth"
+    )]
+#[post("/device")]
+pub async fn device_decision_handler(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<DeviceVerification>,
+) -> Result<HttpResponse, ServiceError> {
+    // * Approvare un dispositivo crea una sessione rinnovabile senza claim act
+    user.ensure_not_impersonated()?;
+    let body = body.into_inner();
+    web::block(move || -> Result<_, ServiceError> {
+        let auth_time = oauth::authenticated_at(&pool, &user)?;
+        device_authorization::decide(
+            &pool,
+            &body.user_code,
+            body.decision == ConsentChoice::Approve,
+            user.user_id,
+            auth_time,
+            &client,
+        )?
+        .ok_or_else(|| ServiceError::NotFound("Unknown or expired code".into()))
+    })
+    .await??;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// * Un admin che impersona l'utente non può autorizzare client: il codice emesso porterebbe a
+// * una sessione normale, rinnovabile e senza claim act
+fn ensure_not_impersonated(user: Option<&AuthenticatedUser>) -> Result<(), OAuthError> {
+    match user.map(AuthenticatedUser::ensure_not_impersonated) {
+        Some(Err(_)) => Err(OAuthError::access_denied(
+            "Not allowed while impersonating a user",
+        )),
+        _ => Ok(()),
+    }
+}
+
+// * Valida la richiesta; gli errori da rimandare al client diventano un redirect
+async fn validate(
+    pool: &web::Data<DbPool>,
+    request: AuthorizeRequest,
+) -> Result<Result<AuthorizationContext, HttpResponse>, OAuthError> {
+    let pool = pool.clone();
+    match web::block(move || oauth::validate_authorize_request(&pool, &request)).await? {
+        Ok(context) => Ok(Ok(context)),
+        Err(AuthorizeError::Redirect(location)) => Ok(Err(found(location))),
+        Err(AuthorizeError::Invalid(e)) => Err(e),
+    }
+}
+
+async fn issue_code(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    context: AuthorizationContext,
+    user: AuthenticatedUser,
+) -> Result<String, OAuthError> {
+    Ok(web::block(move || {
+        let auth_time = oauth::authenticated_at(&pool, &user)?;
+        oauth::issue_code(&pool, &context, user.user_id, auth_time, &app_config)
+    })
+    .await??)
+}
+
+fn found(location: String) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+fn see_other(location: String) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
@@ -0,0 +1,39 @@
+use actix_web::{get, http::header, web, HttpResponse};
+
+use crate::{
+    config::app_config::AppConfig,
+    models::oidc::{JwkSet, OpenIdConfiguration},
+    services::{jwt::JwtKeys, oidc},
+};
+
+#[utoipa::path(
+        get,
+        path = "/.well-known/openid-configuration",
+        responses(
+            (status = 200, description = "OpenID Connect discovery document", body = OpenIdConfiguration)
+        ),
+        tag = "oauth"
+    )]
+#[get("/openid-configuration")]
+pub async fn openid_configuration_handler(app_config: web::Data<AppConfig>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .json(oidc::discovery_document(&app_config))
+}
+
+#[utoipa::path(
+        get,
+        path = "/.well-known/jwks.json",
+        responses(
+            (status = 200, description = "Public keys that verify ID tokens and access tokens", body = JwkSet)
+        ),
+        tag = "oauth"
+    )]
+#[get("/jwks.json")]
+pub async fn jwks_handler(jwt_keys: web::Data<JwtKeys>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .json(JwkSet {
+            keys: vec![jwt_keys.jwk().clone()],
+        })
+}
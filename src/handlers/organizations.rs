@@ -0,0 +1,353 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    handlers::cookies,
+    models::organization::{
+        CreateOrganization, InviteMember, MemberResponse, OrganizationResponse, SwitchOrganization,
+        SwitchOrganizationResponse, UpdateMember,
+    },
+    repositories::{establish_connection, organizations_repo, roles_repo, users_repo},
+    services::{
+        jwt::JwtKeys,
+        organization,
+        password_policy::PasswordPolicy,
+        session::{self, TokenSettings},
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        post,
+        path = "/api/organizations",
+        request_body = CreateOrganization,
+        responses(
+            (status = 201, description = "Organization created; the current user is its owner", body = OrganizationResponse),
+            (status = 400, description = "Bad Request: invalid name or slug", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 409, description = "Conflict: the slug is already taken", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[post("/organizations")]
+pub async fn create_organization_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<CreateOrganization>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let user_id = auth.user_id;
+    let (created, membership) =
+        web::block(move || organization::create(&pool, user_id, body.into_inner(), &client))
+            .await??;
+    info!("User {} created organization {}", user_id, created.id);
+    Ok(
+        HttpResponse::Created().json(OrganizationResponse::from_membership(
+            created, membership, None,
+        )),
+    )
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/me/organizations",
+        responses(
+            (status = 200, description = "Organizations the current user is a member of, with the active one marked, and pending invitations", body = [OrganizationResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[get("/me/organizations")]
+pub async fn list_my_organizations_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+) -> Result<HttpResponse, ServiceError> {
+    let active_organization_id = auth
+        .claims
+        .org_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok());
+    let organizations =
+        web::block(move || organizations_repo::list_for_user(&pool, auth.user_id)).await??;
+    let organizations: Vec<OrganizationResponse> = organizations
+        .into_iter()
+        .map(|(organization, membership)| {
+            OrganizationResponse::from_membership(organization, membership, active_organization_id)
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(organizations))
+}
+
+#[utoipa::path(
+        put,
+        path = "/api/me/organization",
+        request_body = SwitchOrganization,
+        responses(
+            (status = 200, description = "Active organization of the current session changed; the new access token carries org_id and org_roles, and refreshed tokens keep them", body = SwitchOrganizationResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: only tokens of a direct login session can switch organization, not those issued to OAuth clients, exchanged or used to impersonate", body = ErrorResponse),
+            (status = 404, description = "Not Found: the user is not a member of the organization", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[put("/me/organization")]
+pub async fn switch_organization_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<SwitchOrganization>,
+) -> Result<HttpResponse, ServiceError> {
+    // * Il token emesso è quello di un login diretto: i personal access token non hanno una
+    // * sessione da aggiornare, mentre i token dei client OAuth, quelli scambiati e quelli di
+    // * impersonazione perderebbero client, scope ristretti e claim act
+    let delegated = auth.claims.client_id.is_some() || auth.claims.act.is_some();
+    let session_id = match (auth.session_id, auth.personal_access_token_id) {
+        (Some(session_id), None) if !delegated => session_id,
+        _ => {
+            return Err(ServiceError::Forbidden(
+                "Only tokens of a direct login session can switch organization".into(),
+            ))
+        }
+    };
+    let user_id = auth.user_id;
+    let organization_id = body.into_inner().organization_id;
+    let (session, membership, user, grants) = web::block(move || -> Result<_, ServiceError> {
+        let (session, membership) =
+            organization::switch(&pool, user_id, session_id, organization_id, &client)?;
+        let mut conn = establish_connection(&pool)?;
+        let user = users_repo::get_user_by_id_with_connection(&mut conn, user_id)?;
+        let grants = roles_repo::grants_for_user_with_connection(&mut conn, user_id)?;
+        Ok((session, membership, user, grants))
+    })
+    .await??;
+
+    let claims = session::access_claims(
+        &user,
+        grants,
+        session.id,
+        membership.as_ref(),
+        &TokenSettings::global(&app_config),
+        &app_config,
+        &password_policy,
+    );
+    let token = jwt_keys.sign(&claims)?;
+    info!(
+        "User {} switched session {} to organization {:?}",
+        user_id, session.id, session.organization_id
+    );
+    Ok(HttpResponse::Ok()
+        .cookie(cookies::auth_cookie(token.clone(), &app_config))
+        .json(SwitchOrganizationResponse {
+            access_token: token,
+            token_type: "Bearer",
+            expires_in: claims.exp as i64 - claims.iat as i64,
+            organization_id: session.organization_id,
+            org_roles: claims.org_roles,
+        }))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/organizations/{id}/members",
+        params(("id" = Uuid, Path, description = "Organization id")),
+        responses(
+            (status = 200, description = "Members of the organization, oldest first", body = [MemberResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: only owners and teachers can list members", body = ErrorResponse),
+            (status = 404, description = "Not Found: the user is not a member of the organization", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[get("/organizations/{id}/members")]
+pub async fn list_members_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = path.into_inner();
+    let members =
+        web::block(move || organization::list_members(&pool, organization_id, auth.user_id))
+            .await??;
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/organizations/{id}/members",
+        params(("id" = Uuid, Path, description = "Organization id")),
+        request_body = InviteMember,
+        responses(
+            (status = 202, description = "Invitation recorded; the user becomes a member after accepting it. The response is the same whether or not the account exists or is already a member"),
+            (status = 400, description = "Bad Request: missing user or invalid roles", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: only owners can invite members", body = ErrorResponse),
+            (status = 404, description = "Not Found: the user is not a member of the organization", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[post("/organizations/{id}/members")]
+pub async fn invite_member_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+    body: web::Json<InviteMember>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let organization_id = path.into_inner();
+    let actor_id = auth.user_id;
+    web::block(move || {
+        organization::invite_member(&pool, organization_id, actor_id, body.into_inner(), &client)
+    })
+    .await??;
+    info!(
+        "User {} sent an invitation to organization {}",
+        actor_id, organization_id
+    );
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/me/organizations/{id}/accept",
+        params(("id" = Uuid, Path, description = "Organization id")),
+        responses(
+            (status = 200, description = "Invitation accepted; the current user is now a member with the offered roles", body = OrganizationResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while impersonating the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: no pending invitation to the organization", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[post("/me/organizations/{id}/accept")]
+pub async fn accept_invitation_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    // * Entrare in un'organizzazione è una scelta dell'utente, non di chi lo impersona
+    auth.ensure_not_impersonated()?;
+    let organization_id = path.into_inner();
+    let user_id = auth.user_id;
+    let (joined, membership) = web::block(move || {
+        organization::accept_invitation(&pool, organization_id, user_id, &client)
+    })
+    .await??;
+    info!("User {} joined organization {}", user_id, organization_id);
+    Ok(
+        HttpResponse::Ok().json(OrganizationResponse::from_membership(
+            joined, membership, None,
+        )),
+    )
+}
+
+#[utoipa::path(
+        put,
+        path = "/api/organizations/{id}/members/{user_id}",
+        params(
+            ("id" = Uuid, Path, description = "Organization id"),
+            ("user_id" = Uuid, Path, description = "Member user id")
+        ),
+        request_body = UpdateMember,
+        responses(
+            (status = 200, description = "Roles of the member replaced", body = MemberResponse),
+            (status = 400, description = "Bad Request: invalid roles", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: only owners can change roles", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown organization or member", body = ErrorResponse),
+            (status = 409, description = "Conflict: the organization would be left without an owner", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[put("/organizations/{id}/members/{user_id}")]
+pub async fn update_member_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<UpdateMember>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    let (organization_id, member_id) = path.into_inner();
+    let actor_id = auth.user_id;
+    let (membership, user) = web::block(move || -> Result<_, ServiceError> {
+        let membership = organization::update_member(
+            &pool,
+            organization_id,
+            actor_id,
+            member_id,
+            &body.roles,
+            &client,
+        )?;
+        let user = users_repo::get_user_by_id(&pool, member_id)?;
+        Ok((membership, user))
+    })
+    .await??;
+    info!(
+        "User {} set the roles of {} in organization {} to {:?}",
+        actor_id, member_id, organization_id, membership.roles
+    );
+    Ok(HttpResponse::Ok().json(MemberResponse {
+        user_id: user.id,
+        username: user.username,
+        joined_at: membership.joined_at(),
+        roles: membership.roles,
+    }))
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/organizations/{id}/members/{user_id}",
+        params(
+            ("id" = Uuid, Path, description = "Organization id"),
+            ("user_id" = Uuid, Path, description = "Member user id; members can remove themselves to leave, and invited users to decline")
+        ),
+        responses(
+            (status = 204, description = "Member removed or invitation declined; refreshed tokens no longer carry the organization"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: only owners can remove other members", body = ErrorResponse),
+            (status = 404, description = "Not Found: unknown organization or member", body = ErrorResponse),
+            (status = 409, description = "Conflict: the last owner cannot be removed", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "organizations"
+    )]
+#[delete("/organizations/{id}/members/{user_id}")]
+pub async fn remove_member_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ServiceError> {
+    let (organization_id, member_id) = path.into_inner();
+    let actor_id = auth.user_id;
+    web::block(move || {
+        organization::remove_member(&pool, organization_id, actor_id, member_id, &client)
+    })
+    .await??;
+    info!(
+        "User {} removed {} from organization {}",
+        actor_id, member_id, organization_id
+    );
+    Ok(HttpResponse::NoContent().finish())
+}
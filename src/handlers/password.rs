@@ -0,0 +1,152 @@
+use actix_web::{post, web, HttpResponse};
+use serde_json::json;
+use tracing::{error, info};
+use validator::Validate;
+
+use crate::{
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::PasswordChangeUser, client_info::ClientInfo},
+    models::{audit_event::AuditOutcome, change_password::ChangePassword, user::User},
+    repositories::{as_user, password_history_repo, sessions_repo, users_repo},
+    services::{
+        audit::{self, AuditEvent},
+        password::PasswordService,
+        password_policy::{PasswordContext, PasswordPolicy},
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        post,
+        path = "/api/me/password",
+        request_body = ChangePassword,
+        responses(
+            (status = 204, description = "Password changed; all other sessions are revoked"),
+            (status = 400, description = "Bad Request: invalid input or password policy violation", body = ErrorResponse),
+            (status = 401, description = "Unauthorized: missing token or wrong current password", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "authentication"
+    )]
+#[post("/me/password")]
+pub async fn change_password_handler(
+    pool: web::Data<DbPool>,
+    password_service: web::Data<PasswordService>,
+    password_policy: web::Data<PasswordPolicy>,
+    PasswordChangeUser(auth): PasswordChangeUser,
+    client: ClientInfo,
+    body: web::Json<ChangePassword>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+
+    // * 1-2. Lettura dell'utente e della cronologia, verifica della password attuale, policy e
+    // *      hashing: query e Argon2 sono bloccanti e girano fuori dal worker async
+    let user_id = auth.user_id;
+    let body = body.into_inner();
+    let history_depth = password_policy.config().history_depth;
+    let checked = web::block({
+        let pool = pool.clone();
+        move || -> Result<Option<(User, String)>, ServiceError> {
+            let user = users_repo::get_own_user(&pool, user_id)
+                .map_err(|_| ServiceError::Unauthorized("User no longer exists".into()))?;
+            if password_service
+                .verify_account(&body.current_password, user.password.as_deref())
+                .is_err()
+            {
+                return Ok(None);
+            }
+
+            // * Controllo della policy, inclusa la cronologia delle password precedenti
+            let mut previous_hashes: Vec<String> = user.password.iter().cloned().collect();
+            if history_depth > 1 {
+                previous_hashes.extend(as_user(&pool, user.id, |conn| {
+                    password_history_repo::recent_hashes_with_connection(
+                        conn,
+                        user.id,
+                        history_depth - 1,
+                    )
+                })?);
+            }
+            let context = PasswordContext {
+                username: &user.username,
+                email: &user.email,
+                previous_hashes: &previous_hashes,
+            };
+            password_policy
+                .check(&body.new_password, &context, &password_service)
+                .map_err(ServiceError::PasswordPolicy)?;
+
+            let new_hash = password_service.hash(&body.new_password).map_err(|e| {
+                error!("Password hashing failed for user {}: {:?}", user.id, e);
+                ServiceError::InternalServerError
+            })?;
+            Ok(Some((user, new_hash)))
+        }
+    })
+    .await??;
+    let Some((user, new_hash)) = checked else {
+        audit::record(
+            &pool,
+            AuditEvent {
+                action: "password.change",
+                outcome: AuditOutcome::Failure,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(&client),
+                details: json!({ "reason": "invalid_current_password" }),
+            },
+        )
+        .await;
+        return Err(ServiceError::Unauthorized(
+            "Current password is incorrect".into(),
+        ));
+    };
+
+    // * 3. Aggiornamento della password: quella attuale passa nella cronologia,
+    // *    che viene poi ridotta alla profondità configurata
+    let current_session_id = auth.session_id;
+    web::block(move || -> Result<(), ServiceError> {
+        as_user(&pool, user.id, |conn| {
+            if let Some(current_hash) = user.password.clone().filter(|_| history_depth > 1) {
+                password_history_repo::add_entry_with_connection(
+                    conn,
+                    password_history_repo::NewPasswordHistoryEntry {
+                        user_id: user.id,
+                        password_hash: current_hash,
+                    },
+                )?;
+            }
+            password_history_repo::prune_with_connection(
+                conn,
+                user.id,
+                history_depth.saturating_sub(1),
+            )?;
+            users_repo::change_password_with_connection(conn, user.id, &new_hash)?;
+            // * Le altre sessioni vengono chiuse: chi conosceva la vecchia password perde l'accesso
+            let revoked = sessions_repo::revoke_all_except_with_connection(
+                conn,
+                user.id,
+                current_session_id,
+            )?;
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "password.change",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(user.id),
+                    target_user_id: Some(user.id),
+                    client: Some(&client),
+                    details: json!({ "revoked_sessions": revoked }),
+                },
+            )
+        })?;
+        Ok(())
+    })
+    .await??;
+
+    info!("Password changed for user {}", auth.user_id);
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,116 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    models::personal_access_token::{CreatePersonalAccessToken, PersonalAccessTokenResponse},
+    repositories::personal_access_tokens_repo,
+    services::personal_access_token,
+    DbPool,
+};
+
+#[utoipa::path(
+        get,
+        path = "/api/me/tokens",
+        responses(
+            (status = 200, description = "Personal access tokens of the current user that are not revoked, newest first", body = [PersonalAccessTokenResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "personal access tokens"
+    )]
+#[get("/me/tokens")]
+pub async fn list_tokens_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+) -> Result<HttpResponse, ServiceError> {
+    let tokens =
+        web::block(move || personal_access_tokens_repo::list_for_user(&pool, auth.user_id))
+            .await??;
+    let tokens: Vec<PersonalAccessTokenResponse> = tokens
+        .into_iter()
+        .map(|token| PersonalAccessTokenResponse::from_token(token, None))
+        .collect();
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[utoipa::path(
+        post,
+        path = "/api/me/tokens",
+        request_body = CreatePersonalAccessToken,
+        responses(
+            (status = 201, description = "Token created; the kapp_pat_ key is shown only in this response", body = PersonalAccessTokenResponse),
+            (status = 400, description = "Bad Request: invalid input or a scope that cannot be granted", body = ErrorResponse),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: a personal access token cannot create other tokens, nor can an admin impersonating the user", body = ErrorResponse),
+            (status = 409, description = "Conflict: too many active tokens", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "personal access tokens"
+    )]
+#[post("/me/tokens")]
+pub async fn create_token_handler(
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    body: web::Json<CreatePersonalAccessToken>,
+) -> Result<HttpResponse, ServiceError> {
+    body.validate()
+        .map_err(|e| ServiceError::ValidationError(format!("{:?}", e)))?;
+    // * Un token rubato non deve poter generare altri token che sopravvivano alla sua revoca
+    if auth.personal_access_token_id.is_some() {
+        return Err(ServiceError::Forbidden(
+            "Personal access tokens cannot create other tokens".into(),
+        ));
+    }
+    auth.ensure_not_impersonated()?;
+    let user_id = auth.user_id;
+    let (created, secret) = web::block(move || {
+        personal_access_token::create(&pool, user_id, body.into_inner(), &client, &app_config)
+    })
+    .await??;
+    info!(
+        "User {} created personal access token {}",
+        user_id, created.id
+    );
+    Ok(
+        HttpResponse::Created().json(PersonalAccessTokenResponse::from_token(
+            created,
+            Some(secret),
+        )),
+    )
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/me/tokens/{id}",
+        params(("id" = Uuid, Path, description = "Personal access token id")),
+        responses(
+            (status = 204, description = "Token revoked: requests using it are rejected from now on"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 404, description = "Not Found: no active token with this id", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "personal access tokens"
+    )]
+#[delete("/me/tokens/{id}")]
+pub async fn revoke_token_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let token_id = path.into_inner();
+    let user_id = auth.user_id;
+    web::block(move || personal_access_token::revoke(&pool, user_id, token_id, &client)).await??;
+    info!(
+        "User {} revoked personal access token {}",
+        user_id, token_id
+    );
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,20 +1,28 @@
 use crate::{
     config::app_config::AppConfig,
     errors::{ErrorResponse, ServiceError},
+    extractors::client_info::ClientInfo,
+    handlers::cookies,
     models::{
-        auth_response_model::AuthResponse, claims::Claims, register::RegisterUser, user::User,
+        audit_event::AuditOutcome, auth_response_model::AuthResponse, register::RegisterUser,
+        role::STUDENT_ROLE, user::User,
+    },
+    repositories::{establish_connection, roles_repo, users_repo},
+    services::{
+        audit::{self, AuditEvent},
+        jwt::JwtKeys,
+        password::PasswordService,
+        password_policy::{PasswordContext, PasswordPolicy},
+        session::{self, TokenSettings},
     },
-    repositories::{establish_connection, users_repo},
     DbPool,
 };
 use actix_web::{
     cookie::{Cookie, SameSite},
     post, web, HttpResponse,
 };
-use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
-use chrono::{Duration, Utc};
 use diesel::prelude::*;
-use rand::rngs::OsRng;
+use serde_json::json;
 use tracing::error;
 use validator::Validate;
 
@@ -24,7 +32,7 @@ use validator::Validate;
         request_body = RegisterUser,
         responses(
             (status = 200, description = "User created", body = AuthResponse),
-            (status = 400, description = "Bad Request: invalid input", body = ErrorResponse),
+            (status = 400, description = "Bad Request: invalid input or password policy violation", body = ErrorResponse),
             (status = 409, description = "Conflict: user already exists", body = ErrorResponse),
             (status = 404, description = "Not Found", body = ErrorResponse),
             (status = 422, description = "Unprocessable Entity: validation error", body = ErrorResponse),
@@ -39,6 +47,10 @@ use validator::Validate;
 pub async fn register_handler(
     pool: web::Data<DbPool>,
     app_config: web::Data<AppConfig>,
+    password_service: web::Data<PasswordService>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    client: ClientInfo,
     body: web::Json<RegisterUser>,
 ) -> Result<HttpResponse, ServiceError> {
     // Validazione input
@@ -53,6 +65,19 @@ pub async fn register_handler(
         )));
     }
 
+    let context = PasswordContext {
+        username: &body.username,
+        email: &body.email,
+        ..Default::default()
+    };
+    if let Err(violations) = password_policy.check(&body.password, &context, &password_service) {
+        error!(
+            "Password policy violation for new user {}: {:?}",
+            body.username, violations
+        );
+        return Err(ServiceError::PasswordPolicy(violations));
+    }
+
     if users_repo::user_exists_by_username_or_email(&pool, &body.username, &body.email).map_err(
         |err| {
             error!(
@@ -69,37 +94,27 @@ pub async fn register_handler(
         return Err(ServiceError::Conflict("Username o email già in uso".into()));
     }
 
-    let private_key = std::fs::read("./private_key.pem").map_err(|err| {
-        error!("JWT Key Error: Errore lettura chiave privata: {:?}", err);
-        ServiceError::JwtKeyError(format!("Errore lettura chiave privata: {:?}", err))
+    let password_hash = password_service.hash(&body.password).map_err(|e| {
+        error!(
+            "Password hashing failed for user {}: {:?}",
+            body.username, e
+        );
+        ServiceError::ValidationError("Hashing della password fallito".into())
     })?;
 
-    let mut rng = OsRng;
-    let salt = SaltString::generate(&mut rng);
-    let password_hash = Argon2::default()
-        .hash_password(body.password.as_bytes(), &salt)
-        .map_err(|e| {
-            error!(
-                "Password hashing failed for user {}: {:?}",
-                body.username, e
-            );
-            ServiceError::ValidationError("Hashing della password fallito".into())
-        })?
-        .to_string();
-
     let new_user = users_repo::NewUser {
         username: body.username.clone(),
         email: body.email.clone(),
-        password: password_hash,
+        password: Some(password_hash),
     };
 
-    let (user, token) = web::block({
+    let (user, roles, token, refresh_token) = web::block({
         let pool = pool.clone();
-        let private_key = private_key.clone();
+        let jwt_keys = jwt_keys.clone();
         let app_config = app_config.clone();
-        let new_user = new_user;
+        let password_policy = password_policy.clone();
 
-        move || -> Result<(User, String), ServiceError> {
+        move || -> Result<(User, Vec<String>, String, String), ServiceError> {
             let mut conn = establish_connection(&pool).map_err(|e| {
                 error!(
                     "Database connection error in blocking thread for new user {}: {:?}",
@@ -108,29 +123,51 @@ pub async fn register_handler(
                 ServiceError::DatabaseError(e)
             })?;
 
-            conn.transaction::<(User, String), diesel::result::Error, _>(|conn| {
-                let user = users_repo::create_user_with_connection(conn, new_user.clone())?;
+            conn.transaction::<(User, Vec<String>, String, String), diesel::result::Error, _>(
+                |conn| {
+                    let user = users_repo::create_user_with_connection(conn, new_user.clone())?;
+                    // * Ogni nuovo utente parte con il ruolo di studente
+                    roles_repo::assign_role_with_connection(conn, user.id, STUDENT_ROLE)?;
+                    let grants = roles_repo::grants_for_user_with_connection(conn, user.id)?;
+                    let issued = session::start_session_with_connection(
+                        conn,
+                        user.id,
+                        &client,
+                        &app_config,
+                    )?;
+                    audit::record_with_connection(
+                        conn,
+                        AuditEvent {
+                            action: "user.registered",
+                            outcome: AuditOutcome::Success,
+                            actor_id: Some(user.id),
+                            target_user_id: Some(user.id),
+                            client: Some(&client),
+                            details: json!({ "session_id": issued.session.id }),
+                        },
+                    )?;
 
-                let now = Utc::now();
-                let claims = Claims {
-                    sub: user.id.to_string(),
-                    exp: (now + Duration::seconds(app_config.jwt_exp_secs as i64)).timestamp()
-                        as usize,
-                    iat: now.timestamp() as usize,
-                    iss: app_config.jwt_issuer.clone(),
-                    aud: app_config.jwt_audience.clone(),
-                };
-
-                let token = claims.generate_jwt(&private_key).map_err(|e| {
-                    error!(
-                        "JWT generation failed within transaction for user {}: {:?}",
-                        user.username, e
+                    let claims = session::access_claims(
+                        &user,
+                        grants.clone(),
+                        issued.session.id,
+                        issued.organization.as_ref(),
+                        &TokenSettings::global(&app_config),
+                        &app_config,
+                        &password_policy,
                     );
-                    diesel::result::Error::RollbackTransaction
-                })?;
 
-                Ok((user, token))
-            })
+                    let token = jwt_keys.sign(&claims).map_err(|e| {
+                        error!(
+                            "JWT generation failed within transaction for user {}: {:?}",
+                            user.username, e
+                        );
+                        diesel::result::Error::RollbackTransaction
+                    })?;
+
+                    Ok((user, grants.roles, token, issued.refresh_token))
+                },
+            )
             .map_err(|e| {
                 error!(
                     "Database transaction failed for user {}: {:?}",
@@ -161,8 +198,14 @@ pub async fn register_handler(
         username: user.username,
         email: user.email,
         created_at: user.created_at,
+        roles,
         token,
+        refresh_token: refresh_token.clone(),
+        password_change_required: false,
     };
 
-    Ok(HttpResponse::Ok().cookie(cookie).json(auth_response))
+    Ok(HttpResponse::Ok()
+        .cookie(cookie)
+        .cookie(cookies::refresh_cookie(refresh_token, &app_config))
+        .json(auth_response))
 }
@@ -0,0 +1,227 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{ErrorResponse, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    handlers::cookies,
+    models::{
+        audit_event::AuditOutcome,
+        auth_response_model::AuthResponse,
+        session::{RefreshTokenRequest, SessionResponse},
+    },
+    repositories::{as_user, establish_connection, roles_repo, sessions_repo, users_repo},
+    services::{
+        audit::{self, AuditEvent},
+        jwt::JwtKeys,
+        password_policy::PasswordPolicy,
+        session::{self, TokenSettings},
+    },
+    DbPool,
+};
+
+#[utoipa::path(
+        post,
+        path = "/api/token/refresh",
+        request_body(content = RefreshTokenRequest, description = "Optional when the refresh_token cookie is sent"),
+        responses(
+            (status = 200, description = "New access and refresh token for the same session", body = AuthResponse),
+            (status = 401, description = "Unauthorized: invalid, expired, revoked or reused refresh token", body = ErrorResponse),
+            (status = 403, description = "Forbidden: account suspended, disabled or pending verification (see error)", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "sessions"
+    )]
+#[post("/token/refresh")]
+pub async fn refresh_handler(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    app_config: web::Data<AppConfig>,
+    password_policy: web::Data<PasswordPolicy>,
+    jwt_keys: web::Data<JwtKeys>,
+    client: ClientInfo,
+    body: Option<web::Json<RefreshTokenRequest>>,
+) -> Result<HttpResponse, ServiceError> {
+    // * 1. Il refresh token arriva nel body (client mobile) o nel cookie (client web)
+    let refresh_token = body
+        .and_then(|body| body.into_inner().refresh_token)
+        .or_else(|| {
+            req.cookie(cookies::REFRESH_COOKIE)
+                .map(|c| c.value().to_string())
+        })
+        .ok_or_else(|| ServiceError::Unauthorized("Missing refresh token".into()))?;
+
+    // * 2. Rotazione del refresh token (solo per un account attivo) e caricamento dell'utente
+    // *    della sessione e dei suoi ruoli
+    let (issued, user, grants) = web::block({
+        let pool = pool.clone();
+        let app_config = app_config.clone();
+        move || -> Result<_, ServiceError> {
+            let mut conn = establish_connection(&pool)?;
+            let issued = session::refresh_with_connection(
+                &mut conn,
+                &refresh_token,
+                &client,
+                None,
+                &app_config,
+            )?;
+            let user =
+                users_repo::get_user_by_id_with_connection(&mut conn, issued.session.user_id)?;
+            let grants = roles_repo::grants_for_user_with_connection(&mut conn, user.id)?;
+            Ok((issued, user, grants))
+        }
+    })
+    .await??;
+
+    // * 3. Nuovo token di accesso, con le stesse regole del login
+    let claims = session::access_claims(
+        &user,
+        grants,
+        issued.session.id,
+        issued.organization.as_ref(),
+        &TokenSettings::global(&app_config),
+        &app_config,
+        &password_policy,
+    );
+    let password_change_required = claims.password_change_required;
+    let roles = claims.roles.clone();
+    let token = jwt_keys.sign(&claims)?;
+
+    let auth_response = AuthResponse {
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        created_at: user.created_at,
+        roles,
+        token: token.clone(),
+        refresh_token: issued.refresh_token.clone(),
+        password_change_required,
+    };
+
+    Ok(HttpResponse::Ok()
+        .cookie(cookies::auth_cookie(token, &app_config))
+        .cookie(cookies::refresh_cookie(issued.refresh_token, &app_config))
+        .json(auth_response))
+}
+
+#[utoipa::path(
+        get,
+        path = "/api/me/sessions",
+        responses(
+            (status = 200, description = "Active sessions of the current user", body = [SessionResponse]),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "sessions"
+    )]
+#[get("/me/sessions")]
+pub async fn list_sessions_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+) -> Result<HttpResponse, ServiceError> {
+    let current_session_id = auth.session_id;
+    let sessions =
+        web::block(move || sessions_repo::list_active_for_user(&pool, auth.user_id)).await??;
+    let sessions: Vec<SessionResponse> = sessions
+        .into_iter()
+        .map(|s| SessionResponse::from_session(s, current_session_id))
+        .collect();
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/me/sessions/{id}",
+        params(("id" = Uuid, Path, description = "Session id")),
+        responses(
+            (status = 204, description = "Session revoked"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 404, description = "Not Found: no active session with this id", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "sessions"
+    )]
+#[delete("/me/sessions/{id}")]
+pub async fn revoke_session_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    let session_id = path.into_inner();
+    let revoked = web::block(move || -> Result<usize, ServiceError> {
+        Ok(as_user(&pool, auth.user_id, |conn| {
+            let revoked = sessions_repo::revoke_with_connection(conn, auth.user_id, session_id)?;
+            if revoked > 0 {
+                audit::record_with_connection(
+                    conn,
+                    AuditEvent {
+                        action: "session.revoked",
+                        outcome: AuditOutcome::Success,
+                        actor_id: Some(auth.user_id),
+                        target_user_id: Some(auth.user_id),
+                        client: Some(&client),
+                        details: json!({ "session_id": session_id, "reason": "owner_revoked" }),
+                    },
+                )?;
+            }
+            Ok(revoked)
+        })?)
+    })
+    .await??;
+    if revoked == 0 {
+        return Err(ServiceError::NotFound("Session not found".into()));
+    }
+    info!("Session {} revoked by its owner", session_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+        delete,
+        path = "/api/me/sessions",
+        responses(
+            (status = 204, description = "All other sessions revoked; the current one stays active"),
+            (status = 401, description = "Unauthorized", body = ErrorResponse),
+            (status = 403, description = "Forbidden: not allowed while an admin impersonates the user", body = ErrorResponse),
+            (status = 500, description = "Internal Server Error", body = ErrorResponse)
+        ),
+        tag = "sessions"
+    )]
+#[delete("/me/sessions")]
+pub async fn revoke_other_sessions_handler(
+    pool: web::Data<DbPool>,
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+) -> Result<HttpResponse, ServiceError> {
+    auth.ensure_not_impersonated()?;
+    let user_id = auth.user_id;
+    let revoked = web::block(move || -> Result<usize, ServiceError> {
+        Ok(as_user(&pool, auth.user_id, |conn| {
+            let revoked = sessions_repo::revoke_all_except_with_connection(
+                conn,
+                auth.user_id,
+                auth.session_id,
+            )?;
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "session.revoked_others",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(auth.user_id),
+                    target_user_id: Some(auth.user_id),
+                    client: Some(&client),
+                    details: json!({ "kept_session_id": auth.session_id, "revoked_sessions": revoked }),
+                },
+            )?;
+            Ok(revoked)
+        })?)
+    })
+    .await??;
+    info!("User {} logged out {} other sessions", user_id, revoked);
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,9 +1,11 @@
 pub mod config;
 pub mod errors;
+pub mod extractors;
 pub mod handlers;
 pub mod models;
 pub mod repositories;
 pub mod schema;
+pub mod services;
 
 pub use diesel::r2d2::{ConnectionManager, Pool};
 pub use diesel::PgConnection;
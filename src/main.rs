@@ -1,22 +1,36 @@
 use actix_cors::Cors;
-use actix_web::middleware::Logger;
+use actix_web::middleware::{from_fn, Logger};
 use actix_web::{web, App, HttpServer};
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::PgConnection;
 use ketchapp_auth_api::config::app_config::AppConfig;
 use ketchapp_auth_api::config::open_api::ApiDoc;
+use ketchapp_auth_api::extractors::request_id::assign_request_id;
 use ketchapp_auth_api::handlers::route_config;
+use ketchapp_auth_api::repositories::roles_repo;
+use ketchapp_auth_api::services::account_deletion;
+use ketchapp_auth_api::services::breached_passwords::{BreachedPasswordFilter, BreachedPasswords};
+use ketchapp_auth_api::services::data_export;
+use ketchapp_auth_api::services::external_login::IdentityProviders;
+use ketchapp_auth_api::services::jwt::JwtKeys;
+use ketchapp_auth_api::services::outbox;
+use ketchapp_auth_api::services::password::PasswordService;
+use ketchapp_auth_api::services::password_policy::PasswordPolicy;
+use ketchapp_auth_api::services::scope;
 use std::env;
-use tracing::info;
+use tracing::{info, warn};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-type DbPool = Pool<ConnectionManager<PgConnection>>;
-
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
     dotenvy::dotenv().ok();
     let app_config = AppConfig::from_files().expect("Failed to load AppConfig");
+    let password_service =
+        PasswordService::from_config(&app_config).expect("Failed to load password peppers");
+    let jwt_keys = JwtKeys::from_config(&app_config).expect("Failed to load JWT keys");
+    let identity_providers =
+        IdentityProviders::from_config(&app_config).expect("Failed to load the identity providers");
 
     // Safely set the environment variable without using unsafe
     let rust_log = app_config
@@ -29,6 +43,18 @@ async fn main() -> Result<(), std::io::Error> {
 
     info!("Starting server...");
 
+    let breached_passwords = match &app_config.breached_password_filter {
+        Some(path) => {
+            let filter = BreachedPasswordFilter::load(path)
+                .expect("Failed to load breached password filter");
+            info!("Loaded breached password filter from {}", path);
+            BreachedPasswords::new(filter)
+        }
+        None => BreachedPasswords::default(),
+    };
+    let password_policy =
+        PasswordPolicy::new(app_config.password_policy.clone(), breached_passwords);
+
     let host = app_config.host.clone();
     let port = app_config.port;
     let database_url = app_config.database_url.clone();
@@ -37,6 +63,30 @@ async fn main() -> Result<(), std::io::Error> {
         .build(manager)
         .expect("Failed to create pool");
 
+    let mut openapi = ApiDoc::openapi();
+    password_policy.document(
+        &mut openapi,
+        &[
+            ("RegisterUser", "password"),
+            ("ChangePassword", "new_password"),
+        ],
+    );
+    // * Il catalogo degli scope è la tabella permissions, letta una volta per la documentazione
+    let scope_catalogue =
+        roles_repo::scope_catalogue(&pool).expect("Failed to load the scope catalogue");
+    scope::document(&mut openapi, &scope_catalogue, &app_config);
+
+    account_deletion::spawn_purge_job(pool.clone(), app_config.account_purge_interval_secs);
+    data_export::resume_pending(pool.clone());
+    match &app_config.outbox_webhook_url {
+        Some(url) => outbox::spawn_relay_job(
+            pool.clone(),
+            url.clone(),
+            app_config.outbox_relay_interval_secs,
+        ),
+        None => warn!("outbox_webhook_url is not set: outbox events are not published"),
+    }
+
     let server_address = format!("{}:{}", host, port);
 
     info!("Starting HTTP server at {}", server_address);
@@ -45,6 +95,11 @@ async fn main() -> Result<(), std::io::Error> {
         App::new()
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(app_config.clone()))
+            .app_data(web::Data::new(password_service.clone()))
+            .app_data(web::Data::new(password_policy.clone()))
+            .app_data(web::Data::new(jwt_keys.clone()))
+            .app_data(web::Data::new(identity_providers.clone()))
+            .wrap(from_fn(assign_request_id))
             .wrap(Logger::default())
             .wrap(
                 Cors::default()
@@ -55,8 +110,7 @@ async fn main() -> Result<(), std::io::Error> {
             )
             .configure(route_config)
             .service(
-                SwaggerUi::new("/swagger-ui/{_:.*}")
-                    .url("/api-doc/openapi.json", ApiDoc::openapi()),
+                SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-doc/openapi.json", openapi.clone()),
             )
     })
     .bind((host, port))?
@@ -0,0 +1,133 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::models::user::{AccountStatus, User};
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Admin User",
+    description = "A user account as seen by support staff"
+)]
+pub struct AdminUserResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    // * Stato effettivo: uno stato scaduto è mostrato come attivo
+    pub status: AccountStatus,
+    pub status_reason: Option<String>,
+    pub status_expires_at: Option<NaiveDateTime>,
+    // * Per gli account cancellati: quando verranno eliminati definitivamente
+    pub purge_after: Option<NaiveDateTime>,
+    pub roles: Vec<String>,
+    pub must_change_password: bool,
+    pub password_changed_at: NaiveDateTime,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl AdminUserResponse {
+    pub fn from_user(user: User, roles: Vec<String>) -> Self {
+        // * Un blocco già scaduto non viene mostrato
+        let locked_until = user.locked_until.filter(|_| user.is_locked());
+        Self {
+            status: user.account_status(),
+            status_reason: user.status_reason,
+            status_expires_at: user.status_expires_at,
+            purge_after: user.purge_after,
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            roles,
+            must_change_password: user.must_change_password,
+            password_changed_at: user.password_changed_at,
+            failed_login_attempts: user.failed_login_attempts,
+            locked_until,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Admin User Page",
+    description = "One page of user search results"
+)]
+pub struct AdminUserPage {
+    pub items: Vec<AdminUserResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Deserialize, IntoParams, Validate, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct UserSearchQuery {
+    // * Sottostringa cercata in username ed email
+    pub q: Option<String>,
+    // * Pagina richiesta, a partire da 1
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 100))]
+    pub per_page: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Assign Roles",
+    description = "The complete set of roles the user should have",
+    example = json!({"roles": ["student", "teacher"]})
+)]
+pub struct AssignRoles {
+    #[validate(length(max = 16))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Account Status Change",
+    description = "New account status; reason and expiry are ignored when the status is active",
+    example = json!({"status": "suspended", "reason": "Spam in group chat", "expires_at": "2026-11-01T00:00:00"})
+)]
+pub struct AccountStatusChange {
+    pub status: AccountStatus,
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+    // * Dopo questa data l'account torna attivo (UTC)
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Default)]
+#[schema(title = "Status Reason", example = json!({"reason": "Fraudulent activity"}))]
+pub struct StatusReason {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Impersonation Request",
+    description = "Why support staff need to act as the user; recorded in the audit log",
+    example = json!({"reason": "Ticket #4812: timer does not start"})
+)]
+pub struct ImpersonationRequest {
+    #[validate(length(min = 1, max = 500))]
+    pub reason: String,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Impersonation Token",
+    description = "Short-lived access token acting as the user on behalf of the admin (act claim). It has no refresh token"
+)]
+pub struct ImpersonationResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub user_id: Uuid,
+    pub username: String,
+}
@@ -0,0 +1,108 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = crate::schema::audit_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[schema(
+    title = "Audit Event",
+    description = "One entry of the security audit log"
+)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub occurred_at: NaiveDateTime,
+    pub action: String,
+    pub outcome: String,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    #[schema(value_type = Object)]
+    pub details: Value,
+    // * Admin che ha compiuto l'azione impersonando l'utente
+    pub impersonator_id: Option<Uuid>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::audit_events)]
+pub struct NewAuditLogEntry {
+    pub action: String,
+    pub outcome: String,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub details: Value,
+    pub impersonator_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, IntoParams, Validate, Debug, Default)]
+#[into_params(parameter_in = Query)]
+pub struct AuditEventQuery {
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    // * Azione esatta, oppure prefisso se termina con "*" (es. "login.*")
+    pub action: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub ip_address: Option<String>,
+    pub request_id: Option<String>,
+    pub impersonator_id: Option<Uuid>,
+    // * Solo gli eventi compiuti (true) o non compiuti (false) durante un'impersonificazione
+    pub impersonated: Option<bool>,
+    // * Intervallo temporale (UTC), estremi inclusi
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    #[validate(range(min = 1))]
+    pub page: Option<i64>,
+    #[validate(range(min = 1, max = 500))]
+    pub per_page: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Audit Event Page",
+    description = "One page of audit events, newest first"
+)]
+pub struct AuditEventPage {
+    pub items: Vec<AuditLogEntry>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct ExportFormatQuery {
+    pub format: Option<ExportFormat>,
+}
@@ -9,5 +9,11 @@ pub struct AuthResponse {
     pub username: String,
     pub email: String,
     pub created_at: NaiveDateTime,
+    // * Ruoli dell'utente, gli stessi del claim "roles" del token
+    pub roles: Vec<String>,
     pub token: String,
-}
\ No newline at end of file
+    // * Refresh token della sessione, da scambiare su /api/token/refresh
+    pub refresh_token: String,
+    // * Se true il token permette solo di chiamare /api/me/password
+    pub password_change_required: bool,
+}
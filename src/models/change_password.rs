@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Validate, Serialize, Deserialize, ToSchema)]
+#[schema(
+    title = "Change Password",
+    description = "Change the password of the authenticated user",
+    example = json!({"current_password": "Secret123!", "new_password": "Another long passphrase 42!"})
+)]
+pub struct ChangePassword {
+    #[validate(length(min = 1, max = 1024))]
+    #[schema(min_length = 1, max_length = 1024)]
+    pub current_password: String,
+
+    // * Validata dalla PasswordPolicy nel handler
+    #[schema(min_length = 8)]
+    pub new_password: String,
+}
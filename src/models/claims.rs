@@ -1,22 +1,141 @@
-use jsonwebtoken::{Algorithm, Header};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::config::app_config::AppConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // User ID
     pub exp: usize,  // Expiration timestamp
     pub iat: usize,  // Issued at timestamp
     pub iss: String, // Issuer
     pub aud: String, // Audience
+    // Token limitato al solo cambio password (password scaduta o cambio imposto)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub password_change_required: bool,
+    // Id della sessione (famiglia di refresh token) che ha emesso il token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    // Nomi dei ruoli dell'utente al momento dell'emissione del token
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    // Client OAuth a cui è stato emesso il token (assente per i login diretti)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    // Scope concessi al token, separati da spazi
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Chi agisce per conto del soggetto (RFC 8693, sezione 4.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    // Organizzazione attiva nella sessione e ruoli dell'utente in essa
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub org_roles: Vec<String>,
+}
+
+/// Party acting on behalf of the subject of a token. A delegated token can
+/// be delegated again, so the previous actor is nested in `act`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+    // * L'attore è un admin che impersona l'utente (sub è l'id dell'admin)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub impersonation: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
 }
 
 impl Claims {
-    pub fn generate_jwt(
-        &self,
-        private_key: &[u8],
-    ) -> Result<String, jsonwebtoken::errors::Error> {
+    // * Claims standard per un utente, con scadenza presa dalla configurazione
+    pub fn new(sub: String, app_config: &AppConfig) -> Self {
+        Self::with_ttl(sub, app_config, app_config.jwt_exp_secs as i64)
+    }
+
+    pub fn with_ttl(sub: String, app_config: &AppConfig, ttl_secs: i64) -> Self {
+        let now = Utc::now();
+        Self {
+            sub,
+            exp: (now + Duration::seconds(ttl_secs)).timestamp() as usize,
+            iat: now.timestamp() as usize,
+            iss: app_config.jwt_issuer.clone(),
+            aud: app_config.jwt_audience.clone(),
+            password_change_required: false,
+            sid: None,
+            roles: Vec::new(),
+            client_id: None,
+            scope: None,
+            act: None,
+            org_id: None,
+            org_roles: Vec::new(),
+        }
+    }
+
+    // * Token del grant client_credentials: il soggetto è il client stesso, non un utente
+    pub fn is_client_token(&self) -> bool {
+        self.client_id.as_deref() == Some(self.sub.as_str())
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    // * Admin che impersona l'utente, anche se il token è stato poi scambiato da un servizio
+    pub fn impersonator_id(&self) -> Option<Uuid> {
+        std::iter::successors(self.act.as_ref(), |actor| actor.act.as_deref())
+            .find(|actor| actor.impersonation)
+            .and_then(|actor| Uuid::parse_str(&actor.sub).ok())
+    }
+
+    pub fn has_org_role(&self, role: &str) -> bool {
+        self.org_roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|granted| granted.split_whitespace().any(|s| s == scope))
+    }
+
+    // * Nessuno scope concesso equivale a un claim assente
+    pub fn set_scopes(&mut self, scopes: &[String]) {
+        self.scope = (!scopes.is_empty()).then(|| scopes.join(" "));
+    }
+
+    pub fn generate_jwt(&self, private_key: &[u8]) -> Result<String, jsonwebtoken::errors::Error> {
+        let encoding_key = EncodingKey::from_rsa_pem(private_key)?;
+        self.sign(&encoding_key)
+    }
+
+    pub fn sign(&self, encoding_key: &EncodingKey) -> Result<String, jsonwebtoken::errors::Error> {
         let header = Header::new(Algorithm::RS256);
-        let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key)?;
-        jsonwebtoken::encode(&header, self, &encoding_key)
+        jsonwebtoken::encode(&header, self, encoding_key)
+    }
+
+    pub fn decode(
+        token: &str,
+        decoding_key: &DecodingKey,
+        issuer: &str,
+        audiences: &[&str],
+    ) -> Result<Self, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(audiences);
+        jsonwebtoken::decode::<Claims>(token, decoding_key, &validation).map(|data| data.claims)
+    }
+
+    // * Come decode, senza controllare l'audience
+    pub fn decode_any_audience(
+        token: &str,
+        decoding_key: &DecodingKey,
+        issuer: &str,
+    ) -> Result<Self, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[issuer]);
+        validation.validate_aud = false;
+        jsonwebtoken::decode::<Claims>(token, decoding_key, &validation).map(|data| data.claims)
     }
 }
@@ -0,0 +1,292 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{
+    audit_event::AuditLogEntry,
+    oauth::ConsentResponse,
+    organization::{Membership, Organization},
+    personal_access_token::PersonalAccessToken,
+    session::Session,
+    user::User,
+    user_identity::UserIdentity,
+};
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl DataExportStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataExportStatus::Pending => "pending",
+            DataExportStatus::Ready => "ready",
+            DataExportStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "pending" => DataExportStatus::Pending,
+            "ready" => DataExportStatus::Ready,
+            _ => DataExportStatus::Failed,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::data_exports)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DataExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub status: String,
+    pub archive: Option<Value>,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::data_exports)]
+pub struct NewDataExport {
+    pub user_id: Uuid,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Data Export",
+    description = "An archive of the user's personal data generated in the background"
+)]
+pub struct DataExportResponse {
+    pub id: Uuid,
+    pub status: DataExportStatus,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+    // * Presente quando l'archivio è pronto
+    pub download_url: Option<String>,
+}
+
+impl DataExportResponse {
+    pub fn from_export(export: &DataExport) -> Self {
+        let status = DataExportStatus::parse(&export.status);
+        Self {
+            id: export.id,
+            status,
+            created_at: export.created_at,
+            completed_at: export.completed_at,
+            expires_at: export.expires_at,
+            download_url: (status == DataExportStatus::Ready)
+                .then(|| format!("/api/me/exports/{}/download", export.id)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+#[schema(
+    title = "Personal Data Archive",
+    description = "Everything the authentication service holds about the user; password hashes and token hashes are never included"
+)]
+pub struct PersonalDataArchive {
+    pub generated_at: NaiveDateTime,
+    pub profile: ExportedProfile,
+    pub roles: Vec<String>,
+    // * Organizzazioni di cui l'utente è membro o a cui è invitato, con i ruoli che vi ricopre
+    #[serde(default)]
+    pub memberships: Vec<ExportedMembership>,
+    pub sessions: Vec<ExportedSession>,
+    // * Account presso identity provider esterni collegati all'utente
+    #[serde(default)]
+    pub identities: Vec<ExportedIdentity>,
+    // * API key create dall'utente (senza gli hash), comprese quelle revocate
+    #[serde(default)]
+    pub personal_access_tokens: Vec<ExportedPersonalAccessToken>,
+    // * Date dei cambi password registrati nella cronologia (senza gli hash)
+    pub password_changes: Vec<NaiveDateTime>,
+    // * Applicazioni OAuth autorizzate dall'utente
+    pub consents: Vec<ConsentResponse>,
+    pub audit_events: Vec<ExportedAuditEvent>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedProfile {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub password_changed_at: NaiveDateTime,
+    pub must_change_password: bool,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl From<User> for ExportedProfile {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            status: user.status,
+            status_reason: user.status_reason,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            password_changed_at: user.password_changed_at,
+            must_change_password: user.must_change_password,
+            failed_login_attempts: user.failed_login_attempts,
+            locked_until: user.locked_until,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedMembership {
+    pub organization_id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub roles: Vec<String>,
+    pub joined_at: NaiveDateTime,
+    // * Invito non ancora accettato; joined_at è allora la data dell'invito
+    #[serde(default)]
+    pub invitation_pending: bool,
+}
+
+impl From<(Organization, Membership)> for ExportedMembership {
+    fn from((organization, membership): (Organization, Membership)) -> Self {
+        Self {
+            organization_id: organization.id,
+            name: organization.name,
+            slug: organization.slug,
+            joined_at: membership.joined_at(),
+            invitation_pending: membership.is_pending(),
+            roles: membership.roles,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedSession {
+    pub id: Uuid,
+    pub device: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub oauth_client_id: Option<Uuid>,
+    pub scope: Option<String>,
+}
+
+impl From<Session> for ExportedSession {
+    fn from(session: Session) -> Self {
+        Self {
+            id: session.id,
+            device: session.device,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+            revoked_at: session.revoked_at,
+            oauth_client_id: session.oauth_client_id,
+            scope: session.scope,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedIdentity {
+    pub id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+impl From<UserIdentity> for ExportedIdentity {
+    fn from(identity: UserIdentity) -> Self {
+        Self {
+            id: identity.id,
+            provider: identity.provider,
+            subject: identity.subject,
+            email: identity.email,
+            created_at: identity.created_at,
+            last_login_at: identity.last_login_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedPersonalAccessToken {
+    pub id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<PersonalAccessToken> for ExportedPersonalAccessToken {
+    fn from(token: PersonalAccessToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            revoked_at: token.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct ExportedAuditEvent {
+    pub occurred_at: NaiveDateTime,
+    pub action: String,
+    pub outcome: String,
+    // * true se l'azione è stata compiuta dall'utente stesso
+    pub by_user: bool,
+    // * true se l'azione è stata compiuta dal supporto impersonando l'utente
+    pub impersonated: bool,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[schema(value_type = Object)]
+    pub details: Value,
+}
+
+impl ExportedAuditEvent {
+    // * Le azioni di altri (es. un admin) sull'utente non ne rivelano l'identità né l'indirizzo
+    pub fn from_entry(entry: AuditLogEntry, user_id: Uuid) -> Self {
+        let impersonated = entry.impersonator_id.is_some();
+        let by_user = !impersonated
+            && (entry.actor_id == Some(user_id)
+                || (entry.actor_id.is_none() && entry.target_user_id == Some(user_id)));
+        Self {
+            occurred_at: entry.occurred_at,
+            action: entry.action,
+            outcome: entry.outcome,
+            by_user,
+            impersonated,
+            ip_address: entry.ip_address.filter(|_| by_user),
+            user_agent: entry.user_agent.filter(|_| by_user),
+            details: if by_user { entry.details } else { Value::Null },
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Validate, Serialize, Deserialize, ToSchema)]
+#[schema(
+    title = "Delete Account",
+    description = "Delete the account of the authenticated user",
+    example = json!({"password": "Secret123!"})
+)]
+pub struct DeleteAccount {
+    // * La password attuale, richiesta di nuovo per confermare la cancellazione; gli account
+    // * senza password devono invece aver fatto il login da poco
+    #[validate(length(min = 1, max = 1024))]
+    #[schema(min_length = 1, max_length = 1024)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Account Deletion",
+    description = "The account is deleted and will be permanently erased at purge_after"
+)]
+pub struct AccountDeletionResponse {
+    pub purge_after: NaiveDateTime,
+}
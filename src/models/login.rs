@@ -13,12 +13,9 @@ pub struct LoginUser {
     #[schema(min_length = 6, max_length = 16, pattern = "^[a-zA-Z]{6,16}$")]
     pub username: String,
 
-    #[validate(custom(function = "validate_password"))]
-    #[schema(
-        min_length = 8,
-        max_length = 32,
-        pattern = "^[A-Za-z\\d@$!%*?&]{8,32}$"
-    )]
+    // * Al login non si applica la password policy: le regole possono cambiare nel tempo
+    #[validate(length(min = 1, max = 1024))]
+    #[schema(min_length = 1, max_length = 1024)]
     pub password: String,
 }
 
@@ -32,25 +29,6 @@ pub fn validate_username_logic(username: &str) -> Result<(), String> {
     Ok(())
 }
 
-pub fn validate_password_logic(password: &str) -> Result<(), String> {
-    if password.len() < 8 || password.len() > 32 {
-        return Err("Password must be between 8 and 32 characters".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        return Err("Password must contain at least one lowercase letter".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        return Err("Password must contain at least one uppercase letter".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err("Password must contain at least one digit".into());
-    }
-    if !password.chars().any(|c| "@$!%*?&".contains(c)) {
-        return Err("Password must contain at least one special character (@$!%*?&)".into());
-    }
-    Ok(())
-}
-
 pub fn validate_username(username: &str) -> Result<(), ValidationError> {
     validate_username_logic(username).map_err(|e| {
         let mut err = ValidationError::new("invalid_username");
@@ -58,12 +36,3 @@ pub fn validate_username(username: &str) -> Result<(), ValidationError> {
         err
     })
 }
-
-pub fn validate_password(password: &str) -> Result<(), ValidationError> {
-    if let Err(e) = validate_password_logic(password) {
-        let mut err = ValidationError::new("invalid_password");
-        err.message = Some(e.to_string().into());
-        return Err(err);
-    }
-    Ok(())
-}
@@ -1,5 +1,19 @@
+pub mod admin_user;
+pub mod audit_event;
+pub mod auth_response_model;
+pub mod change_password;
 pub mod claims;
+pub mod data_export;
+pub mod delete_account;
 pub mod login;
+pub mod oauth;
+pub mod oidc;
+pub mod organization;
+pub mod outbox_event;
+pub mod password_history;
+pub mod personal_access_token;
 pub mod register;
+pub mod role;
+pub mod session;
 pub mod user;
-pub mod auth_response_model;
\ No newline at end of file
+pub mod user_identity;
@@ -0,0 +1,466 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_clients)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthClient {
+    pub id: Uuid,
+    pub name: String,
+    pub client_secret_hash: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+    pub first_party: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub audience: Option<String>,
+    pub access_token_ttl_secs: Option<i32>,
+    pub refresh_token_ttl_secs: Option<i32>,
+    pub idle_timeout_secs: Option<i32>,
+    pub token_exchange_audiences: Vec<String>,
+}
+
+impl OAuthClient {
+    // * I client confidenziali (con un backend) si autenticano con il client secret
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+
+    pub fn token_settings(&self) -> ClientTokenSettings {
+        ClientTokenSettings {
+            audience: self.audience.clone(),
+            access_token_ttl_secs: self.access_token_ttl_secs,
+            refresh_token_ttl_secs: self.refresh_token_ttl_secs,
+            idle_timeout_secs: self.idle_timeout_secs,
+            token_exchange_audiences: self.token_exchange_audiences.clone(),
+        }
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_clients)]
+pub struct NewOAuthClient {
+    pub name: String,
+    pub client_secret_hash: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+    pub first_party: bool,
+    pub created_by: Option<Uuid>,
+    pub audience: Option<String>,
+    pub access_token_ttl_secs: Option<i32>,
+    pub refresh_token_ttl_secs: Option<i32>,
+    pub idle_timeout_secs: Option<i32>,
+    pub token_exchange_audiences: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug, Clone, Default)]
+#[schema(
+    title = "Client Token Settings",
+    description = "Audience and lifetimes of the tokens issued to a client, where null falls back to the global configuration, and the audiences it may request through token exchange",
+    example = json!({"audience": "ketchapp-gateway", "access_token_ttl_secs": 300, "refresh_token_ttl_secs": 604800, "idle_timeout_secs": 86400, "token_exchange_audiences": ["ketchapp-analytics"]})
+)]
+pub struct ClientTokenSettings {
+    #[validate(length(min = 1, max = 255))]
+    #[schema(min_length = 1, max_length = 255)]
+    pub audience: Option<String>,
+    // * Al massimo un giorno: i token di accesso non si possono revocare presso le altre API
+    #[validate(range(min = 60, max = 86400))]
+    #[schema(minimum = 60, maximum = 86400)]
+    pub access_token_ttl_secs: Option<i32>,
+    #[validate(range(min = 300, max = 31536000))]
+    #[schema(minimum = 300, maximum = 31536000)]
+    pub refresh_token_ttl_secs: Option<i32>,
+    #[validate(range(min = 300, max = 31536000))]
+    #[schema(minimum = 300, maximum = 31536000)]
+    pub idle_timeout_secs: Option<i32>,
+    // * Servizi a valle per cui il client può scambiare il token di un utente (RFC 8693)
+    #[serde(default)]
+    #[validate(length(max = 16))]
+    pub token_exchange_audiences: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Register OAuth Client",
+    description = "A new application allowed to log users in through OAuth 2.0",
+    example = json!({"name": "KetchApp Web", "redirect_uris": ["https://app.ketchapp.example/callback"], "scopes": ["profile"], "confidential": false, "first_party": true})
+)]
+pub struct RegisterOAuthClient {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    // * Vuoto per i servizi che usano solo il grant client_credentials
+    #[serde(default)]
+    #[validate(length(max = 10))]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    #[validate(length(max = 32))]
+    pub scopes: Vec<String>,
+    // * false per SPA e app mobile, che non possono custodire un secret
+    #[serde(default = "default_confidential")]
+    pub confidential: bool,
+    #[serde(default)]
+    pub first_party: bool,
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub token_settings: ClientTokenSettings,
+}
+
+fn default_confidential() -> bool {
+    true
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "OAuth Client",
+    description = "A registered OAuth client; client_secret is only returned when the client is created"
+)]
+pub struct OAuthClientResponse {
+    pub client_id: Uuid,
+    pub name: String,
+    pub confidential: bool,
+    pub first_party: bool,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    #[serde(flatten)]
+    pub token_settings: ClientTokenSettings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+impl OAuthClientResponse {
+    pub fn from_client(client: OAuthClient, client_secret: Option<String>) -> Self {
+        Self {
+            confidential: client.is_confidential(),
+            token_settings: client.token_settings(),
+            client_id: client.id,
+            name: client.name,
+            first_party: client.first_party,
+            redirect_uris: client.redirect_uris,
+            allowed_scopes: client.allowed_scopes,
+            created_at: client.created_at,
+            client_secret,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_authorization_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuthorizationCode {
+    pub code_hash: String,
+    pub client_id: Uuid,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub consumed_at: Option<NaiveDateTime>,
+    pub session_id: Option<Uuid>,
+    pub nonce: Option<String>,
+    pub auth_time: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_authorization_codes)]
+pub struct NewAuthorizationCode {
+    pub code_hash: String,
+    pub client_id: Uuid,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub expires_at: NaiveDateTime,
+    pub nonce: Option<String>,
+    pub auth_time: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_consents)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthConsent {
+    pub user_id: Uuid,
+    pub client_id: Uuid,
+    pub scopes: Vec<String>,
+    pub granted_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_consents)]
+pub struct NewOAuthConsent {
+    pub user_id: Uuid,
+    pub client_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+#[schema(
+    title = "OAuth Consent",
+    description = "An application the user allowed to act on their behalf"
+)]
+pub struct ConsentResponse {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub granted_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl ConsentResponse {
+    pub fn new(consent: OAuthConsent, client_name: String) -> Self {
+        Self {
+            client_id: consent.client_id,
+            client_name,
+            scopes: consent.scopes,
+            granted_at: consent.granted_at,
+            updated_at: consent.updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, IntoParams, Debug, Clone, Default)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizeRequest {
+    // * Deve valere "code"
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    // * Obbligatorio se il client ha più di un redirect URI registrato
+    pub redirect_uri: Option<String>,
+    // * Scope separati da spazi; se assente valgono tutti quelli consentiti al client
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    // * BASE64URL(SHA256(code_verifier)), obbligatorio
+    pub code_challenge: Option<String>,
+    // * Solo "S256" è supportato
+    pub code_challenge_method: Option<String>,
+    // * OpenID Connect: valore riportato nell'ID token, per legarlo alla richiesta
+    pub nonce: Option<String>,
+    // * OpenID Connect: "none" per un'autorizzazione silenziosa, senza login né consenso
+    pub prompt: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsentChoice {
+    Approve,
+    Deny,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[schema(
+    title = "Consent Decision",
+    description = "The parameters of the authorization request, repeated by the consent page, and the user's decision"
+)]
+pub struct ConsentDecision {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub nonce: Option<String>,
+    pub decision: ConsentChoice,
+}
+
+impl ConsentDecision {
+    pub fn request(&self) -> AuthorizeRequest {
+        AuthorizeRequest {
+            response_type: self.response_type.clone(),
+            client_id: self.client_id.clone(),
+            redirect_uri: self.redirect_uri.clone(),
+            scope: self.scope.clone(),
+            state: self.state.clone(),
+            code_challenge: self.code_challenge.clone(),
+            code_challenge_method: self.code_challenge_method.clone(),
+            nonce: self.nonce.clone(),
+            prompt: None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Consent Prompt",
+    description = "The application is asking for access: show this to the user and post the decision to /oauth/authorize"
+)]
+pub struct ConsentPrompt {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub redirect_uri: String,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Default)]
+#[schema(
+    title = "Token Request",
+    description = "OAuth 2.0 token request (application/x-www-form-urlencoded) for the authorization_code, refresh_token, client_credentials, device_code and token-exchange grants. Confidential clients authenticate with HTTP Basic or client_secret"
+)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    // * Grant urn:ietf:params:oauth:grant-type:device_code
+    pub device_code: Option<String>,
+    // * Grant urn:ietf:params:oauth:grant-type:token-exchange (RFC 8693)
+    pub subject_token: Option<String>,
+    pub subject_token_type: Option<String>,
+    pub actor_token: Option<String>,
+    pub requested_token_type: Option<String>,
+    pub audience: Option<String>,
+    // * client_credentials: scope richiesti, se assenti valgono tutti quelli del client
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Token Response",
+    description = "OAuth 2.0 access token response"
+)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    // * Assente per il grant client_credentials
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+    // * ID token OpenID Connect, solo se è stato concesso lo scope openid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    // * Solo per il token exchange
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<&'static str>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceCodeStatus {
+    Pending,
+    Approved,
+    Denied,
+    Consumed,
+}
+
+impl DeviceCodeStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceCodeStatus::Pending => "pending",
+            DeviceCodeStatus::Approved => "approved",
+            DeviceCodeStatus::Denied => "denied",
+            DeviceCodeStatus::Consumed => "consumed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "pending" => DeviceCodeStatus::Pending,
+            "approved" => DeviceCodeStatus::Approved,
+            "denied" => DeviceCodeStatus::Denied,
+            _ => DeviceCodeStatus::Consumed,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_device_codes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeviceCode {
+    pub device_code_hash: String,
+    pub user_code: String,
+    pub client_id: Uuid,
+    pub scope: String,
+    pub status: String,
+    pub user_id: Option<Uuid>,
+    pub auth_time: Option<NaiveDateTime>,
+    pub interval_secs: i32,
+    pub last_polled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl DeviceCode {
+    pub fn status(&self) -> DeviceCodeStatus {
+        DeviceCodeStatus::parse(&self.status)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::oauth_device_codes)]
+pub struct NewDeviceCode {
+    pub device_code_hash: String,
+    pub user_code: String,
+    pub client_id: Uuid,
+    pub scope: String,
+    pub interval_secs: i32,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Default)]
+#[schema(
+    title = "Device Authorization Request",
+    description = "Starts the device authorization grant (RFC 8628), application/x-www-form-urlencoded. Confidential clients authenticate with HTTP Basic or client_secret"
+)]
+pub struct DeviceAuthorizationRequest {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    // * Scope separati da spazi; se assente valgono tutti quelli consentiti al client
+    pub scope: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Device Authorization Response",
+    description = "Show user_code and verification_uri to the user, then poll /oauth/token with device_code every interval seconds"
+)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: i64,
+    pub interval: i32,
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct DeviceCodeQuery {
+    // * Codice mostrato dal dispositivo; maiuscole, minuscole e trattini sono indifferenti
+    pub user_code: String,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Device Verification Prompt",
+    description = "The device asking for access: show this to the user and post the decision to /oauth/device"
+)]
+pub struct DeviceVerificationPrompt {
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    pub user_code: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[schema(
+    title = "Device Verification",
+    description = "The user's decision on the device identified by user_code",
+    example = json!({"user_code": "WDJB-MJHT", "decision": "approve"})
+)]
+pub struct DeviceVerification {
+    pub user_code: String,
+    pub decision: ConsentChoice,
+}
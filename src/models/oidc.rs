@@ -0,0 +1,104 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::user::User;
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "OpenID Provider Metadata",
+    description = "OpenID Connect discovery document (OpenID Connect Discovery 1.0, section 3)"
+)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub device_authorization_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<&'static str>,
+    pub response_types_supported: Vec<&'static str>,
+    pub response_modes_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<&'static str>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+    pub prompt_values_supported: Vec<&'static str>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+#[schema(
+    title = "JSON Web Key",
+    description = "Public RSA key used to verify the tokens signed by the service"
+)]
+pub struct Jwk {
+    pub kty: &'static str,
+    #[serde(rename = "use")]
+    pub key_use: &'static str,
+    pub alg: &'static str,
+    pub kid: String,
+    // * Modulo ed esponente della chiave in base64url, senza padding
+    pub n: String,
+    pub e: String,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(title = "JSON Web Key Set")]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+#[schema(
+    title = "UserInfo",
+    description = "Claims about the user, limited to the scopes granted to the client"
+)]
+pub struct UserInfo {
+    pub sub: String,
+    // * Scope profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+    // * Scope email
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
+
+impl UserInfo {
+    pub fn for_scopes(user: &User, scopes: &[&str]) -> Self {
+        let profile = scopes.contains(&"profile");
+        let email = scopes.contains(&"email");
+        Self {
+            sub: user.id.to_string(),
+            preferred_username: profile.then(|| user.username.clone()),
+            updated_at: profile.then(|| user.updated_at.and_utc().timestamp()),
+            email: email.then(|| user.email.clone()),
+            // * Non esiste ancora una verifica dell'indirizzo email
+            email_verified: email.then_some(false),
+        }
+    }
+}
+
+/// Claims of an OpenID Connect ID token. The audience is the client the
+/// token was issued to, not the API audience of access tokens.
+#[derive(Serialize, Debug, Clone)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    // * Momento del login dell'utente, in secondi dall'epoch
+    pub auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub azp: String,
+    pub sid: String,
+    // * Metà sinistra dello SHA-256 dell'access token, in base64url
+    pub at_hash: String,
+    #[serde(flatten)]
+    pub user: UserInfo,
+}
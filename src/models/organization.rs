@@ -0,0 +1,213 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+// * Ruoli che un utente può avere all'interno di un'organizzazione (vincolo di memberships)
+pub const ORG_OWNER_ROLE: &str = "owner";
+pub const ORG_TEACHER_ROLE: &str = "teacher";
+pub const ORG_STUDENT_ROLE: &str = "student";
+pub const ORG_ROLES: [&str; 3] = [ORG_OWNER_ROLE, ORG_TEACHER_ROLE, ORG_STUDENT_ROLE];
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::organizations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::organizations)]
+pub struct NewOrganization {
+    pub name: String,
+    pub slug: String,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::memberships)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub roles: Vec<String>,
+    pub created_at: NaiveDateTime,
+    // * None finché l'utente non accetta l'invito: fino ad allora non è membro
+    pub accepted_at: Option<NaiveDateTime>,
+}
+
+impl Membership {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.accepted_at.is_none()
+    }
+
+    // * Per gli inviti in attesa, la data dell'invito
+    pub fn joined_at(&self) -> NaiveDateTime {
+        self.accepted_at.unwrap_or(self.created_at)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::memberships)]
+pub struct NewMembership {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub roles: Vec<String>,
+    pub accepted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Create Organization",
+    description = "A school or other organization; the user creating it becomes its owner",
+    example = json!({"name": "Liceo Galilei", "slug": "liceo-galilei"})
+)]
+pub struct CreateOrganization {
+    #[validate(length(min = 1, max = 255))]
+    #[schema(min_length = 1, max_length = 255)]
+    pub name: String,
+    #[validate(custom(function = "validate_slug"))]
+    #[schema(min_length = 3, max_length = 64, pattern = "^[a-z0-9]+(-[a-z0-9]+)*$")]
+    pub slug: String,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Switch Organization",
+    description = "Organization the tokens of the current session are issued for; null for none",
+    example = json!({"organization_id": "2b1f6c0e-8f4e-4a57-9d55-0c7f0d7a8c11"})
+)]
+pub struct SwitchOrganization {
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Invite Member",
+    description = "Invite a user, by username or email, to join the organization with the given roles",
+    example = json!({"user": "johndoe", "roles": ["teacher"]})
+)]
+pub struct InviteMember {
+    #[validate(length(min = 1, max = 254))]
+    pub user: String,
+    #[validate(custom(function = "validate_org_roles"))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Update Member",
+    description = "Replace the roles of a member of the organization",
+    example = json!({"roles": ["teacher", "student"]})
+)]
+pub struct UpdateMember {
+    #[validate(custom(function = "validate_org_roles"))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Organization",
+    description = "An organization the current user is a member of or is invited to"
+)]
+pub struct OrganizationResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub created_at: NaiveDateTime,
+    // * Ruoli dell'utente corrente nell'organizzazione
+    pub roles: Vec<String>,
+    // * true per l'organizzazione attiva nella sessione della richiesta corrente
+    pub active: bool,
+    // * true per un invito non ancora accettato: i ruoli sono quelli offerti
+    pub invitation_pending: bool,
+}
+
+impl OrganizationResponse {
+    pub fn from_membership(
+        organization: Organization,
+        membership: Membership,
+        active_organization_id: Option<Uuid>,
+    ) -> Self {
+        Self {
+            active: Some(organization.id) == active_organization_id,
+            invitation_pending: membership.is_pending(),
+            id: organization.id,
+            name: organization.name,
+            slug: organization.slug,
+            created_at: organization.created_at,
+            roles: membership.roles,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(title = "Member", description = "A member of an organization")]
+pub struct MemberResponse {
+    pub user_id: Uuid,
+    pub username: String,
+    pub roles: Vec<String>,
+    pub joined_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Organization Switched",
+    description = "A new access token for the current session, issued for the selected organization"
+)]
+pub struct SwitchOrganizationResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: i64,
+    pub organization_id: Option<Uuid>,
+    pub org_roles: Vec<String>,
+}
+
+pub fn validate_slug_logic(slug: &str) -> Result<(), String> {
+    if slug.len() < 3 || slug.len() > 64 {
+        return Err("Slug must be 3-64 characters".into());
+    }
+    let valid_chars = slug
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid_chars || slug.starts_with('-') || slug.ends_with('-') || slug.contains("--") {
+        return Err(
+            "Slug must contain lowercase letters and digits separated by single hyphens".into(),
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_slug(slug: &str) -> Result<(), ValidationError> {
+    if let Err(e) = validate_slug_logic(slug) {
+        let mut err = ValidationError::new("invalid_slug");
+        err.message = Some(e.into());
+        return Err(err);
+    }
+    Ok(())
+}
+
+pub fn validate_org_roles(roles: &[String]) -> Result<(), ValidationError> {
+    if roles.is_empty() || roles.iter().any(|role| !ORG_ROLES.contains(&role.as_str())) {
+        let mut err = ValidationError::new("invalid_org_roles");
+        err.message = Some(
+            format!(
+                "Roles must be a non-empty subset of {}",
+                ORG_ROLES.join(", ")
+            )
+            .into(),
+        );
+        return Err(err);
+    }
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde_json::Value;
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::outbox_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub event_type: String,
+    pub aggregate_id: Uuid,
+    pub payload: Value,
+    pub created_at: NaiveDateTime,
+    pub published_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::outbox_events)]
+pub struct NewOutboxEvent {
+    pub event_type: String,
+    pub aggregate_id: Uuid,
+    pub payload: Value,
+}
@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::password_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PasswordHistoryEntry {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub password_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::password_history)]
+pub struct NewPasswordHistoryEntry {
+    pub user_id: Uuid,
+    pub password_hash: String,
+}
@@ -0,0 +1,84 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::personal_access_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::personal_access_tokens)]
+pub struct NewPersonalAccessToken {
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Validate, Debug)]
+#[schema(
+    title = "Create Personal Access Token",
+    description = "A long-lived API key for scripts, acting as the current user with the given scopes",
+    example = json!({"name": "Nightly sync", "scopes": ["study.read"], "expires_in_days": 90})
+)]
+pub struct CreatePersonalAccessToken {
+    #[validate(length(min = 1, max = 100))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+    #[validate(length(min = 1, max = 32))]
+    pub scopes: Vec<String>,
+    // * Assente per un token senza scadenza
+    #[validate(range(min = 1, max = 3650))]
+    #[schema(minimum = 1, maximum = 3650)]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Personal Access Token",
+    description = "An API key of the current user; the key itself is only returned when the token is created"
+)]
+pub struct PersonalAccessTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    // * Primi caratteri della chiave, per riconoscerla
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl PersonalAccessTokenResponse {
+    pub fn from_token(token: PersonalAccessToken, secret: Option<String>) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token.scopes,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            token: secret,
+        }
+    }
+}
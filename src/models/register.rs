@@ -1,4 +1,3 @@
-use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::{Validate, ValidationError};
@@ -18,16 +17,16 @@ pub struct RegisterUser {
     pub username: String,
 
     #[validate(custom(function = "validate_email"))]
-    #[schema(format = "email", pattern = "^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\\.[a-zA-Z]{2,}$")]
+    #[schema(
+        format = "email",
+        pattern = "^[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+\\.[a-zA-Z]{2,}$"
+    )]
     #[validate(email)]
     pub email: String,
 
-    #[validate(custom(function = "validate_password"))]
-    #[schema(
-        min_length = 8,
-        max_length = 32,
-        pattern = "^[A-Za-z\\d@$!%*?&]{8,32}$"
-    )]
+    // * Validata dalla PasswordPolicy nel handler; i vincoli nello schema OpenAPI
+    // * vengono impostati all'avvio a partire dalla configurazione
+    #[schema(min_length = 8)]
     pub password: String,
 }
 
@@ -36,7 +35,7 @@ impl From<RegisterUser> for NewUser {
         NewUser {
             username: reg.username,
             email: reg.email,
-            password: reg.password,
+            password: Some(reg.password),
         }
     }
 }
@@ -61,25 +60,6 @@ pub fn validate_email_logic(email: &str) -> Result<(), String> {
     Ok(())
 }
 
-pub fn validate_password_logic(password: &str) -> Result<(), String> {
-    if password.len() < 8 || password.len() > 32 {
-        return Err("Password must be between 8 and 32 characters".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        return Err("Password must contain at least one lowercase letter".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        return Err("Password must contain at least one uppercase letter".into());
-    }
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err("Password must contain at least one digit".into());
-    }
-    if !password.chars().any(|c| "@$!%*?&".contains(c)) {
-        return Err("Password must contain at least one special character (@$!%*?&)".into());
-    }
-    Ok(())
-}
-
 pub fn validate_username(username: &str) -> Result<(), ValidationError> {
     if let Err(e) = validate_username_logic(username) {
         let mut err = ValidationError::new("invalid_username");
@@ -97,12 +77,3 @@ pub fn validate_email(email: &str) -> Result<(), ValidationError> {
     }
     Ok(())
 }
-
-pub fn validate_password(password: &str) -> Result<(), ValidationError> {
-    if let Err(e) = validate_password_logic(password) {
-        let mut err = ValidationError::new("invalid_password");
-        err.message = Some(e.to_string().into());
-        return Err(err);
-    }
-    Ok(())
-}
@@ -0,0 +1,49 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// * Nomi dei ruoli creati dalla migrazione iniziale
+pub const STUDENT_ROLE: &str = "student";
+pub const TEACHER_ROLE: &str = "teacher";
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Queryable, Selectable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = crate::schema::roles)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[schema(title = "Role", description = "A role that can be assigned to users")]
+pub struct Role {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::user_roles)]
+pub struct NewUserRole {
+    pub user_id: Uuid,
+    pub role_id: Uuid,
+}
+
+// * Un permesso è uno scope dei token di accesso; role_permissions decide quali ruoli lo concedono
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::permissions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Permission {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+}
+
+/// Roles of a user together with the scopes of the catalogue (the `permissions`
+/// table) that those roles grant through `role_permissions`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoleGrants {
+    pub roles: Vec<String>,
+    // * Scope concessi dai ruoli, in ordine alfabetico
+    pub scopes: Vec<String>,
+    // * Scope del catalogo che i ruoli non concedono
+    pub withheld_scopes: Vec<String>,
+}
@@ -0,0 +1,91 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::sessions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub previous_refresh_token_hash: Option<String>,
+    pub device: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub oauth_client_id: Option<Uuid>,
+    pub scope: Option<String>,
+    pub auth_time: Option<NaiveDateTime>,
+    pub organization_id: Option<Uuid>,
+}
+
+impl Session {
+    // * Momento del login dell'utente: per le sessioni OAuth quello che ha autorizzato il client
+    pub fn authenticated_at(&self) -> NaiveDateTime {
+        self.auth_time.unwrap_or(self.created_at)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct NewSession {
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub device: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub oauth_client_id: Option<Uuid>,
+    pub scope: Option<String>,
+    pub auth_time: Option<NaiveDateTime>,
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Session",
+    description = "A logged-in device of the current user"
+)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub device: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    // * Applicazione OAuth a cui è stata concessa la sessione, se non è un login diretto
+    pub oauth_client_id: Option<Uuid>,
+    // * true per la sessione usata dalla richiesta corrente
+    pub current: bool,
+}
+
+impl SessionResponse {
+    pub fn from_session(session: Session, current_session_id: Option<Uuid>) -> Self {
+        Self {
+            current: Some(session.id) == current_session_id,
+            id: session.id,
+            device: session.device,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            oauth_client_id: session.oauth_client_id,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Default)]
+#[schema(
+    title = "Refresh Token",
+    description = "Refresh token to exchange; if omitted the refresh_token cookie is used",
+    example = json!({"refresh_token": "9f0c..."})
+)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: Option<String>,
+}
@@ -1,9 +1,11 @@
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
-use serde::{Serialize};
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::errors::ServiceError;
+
 #[derive(Queryable, Selectable, Serialize, ToSchema, Debug, Clone)]
 #[diesel(table_name = crate::schema::users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -11,9 +13,98 @@ pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    pub password: String,
+    // * None per gli account creati da un identity provider esterno, finché l'utente non
+    // * imposta una password
+    pub password: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub password_changed_at: NaiveDateTime,
+    #[serde(skip_serializing)]
+    pub must_change_password: bool,
+    pub status: String,
+    #[serde(skip_serializing)]
+    pub failed_login_attempts: i32,
+    #[serde(skip_serializing)]
+    pub locked_until: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub status_reason: Option<String>,
+    #[serde(skip_serializing)]
+    pub status_expires_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub purge_after: Option<NaiveDateTime>,
+}
+
+impl User {
+    // * Stato effettivo dell'account: uno stato con scadenza passata torna attivo
+    pub fn account_status(&self) -> AccountStatus {
+        let expired = self
+            .status_expires_at
+            .is_some_and(|until| until <= chrono::Utc::now().naive_utc());
+        if expired {
+            AccountStatus::Active
+        } else {
+            AccountStatus::from_db(&self.status)
+        }
+    }
+
+    // * Errore se l'account non può autenticarsi (sospeso, disabilitato o da verificare)
+    pub fn ensure_active(&self) -> Result<(), ServiceError> {
+        match self.account_status() {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Deleted => Err(ServiceError::AccountInactive {
+                status: AccountStatus::Deleted,
+                reason: None,
+                until: self.purge_after,
+            }),
+            status => Err(ServiceError::AccountInactive {
+                status,
+                reason: self.status_reason.clone(),
+                until: self.status_expires_at,
+            }),
+        }
+    }
+
+    // * Indica se l'account è bloccato per troppi tentativi di login falliti
+    pub fn is_locked(&self) -> bool {
+        self.locked_until
+            .is_some_and(|until| until > chrono::Utc::now().naive_utc())
+    }
+}
+
+/// Account status stored in `users.status`.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    Active,
+    Suspended,
+    Disabled,
+    PendingVerification,
+    // * Cancellato dal proprietario, in attesa della cancellazione definitiva
+    Deleted,
+}
+
+impl AccountStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Disabled => "disabled",
+            AccountStatus::PendingVerification => "pending_verification",
+            AccountStatus::Deleted => "deleted",
+        }
+    }
+
+    // * Valori sconosciuti sono trattati come disabilitati, per sicurezza
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "active" => AccountStatus::Active,
+            "suspended" => AccountStatus::Suspended,
+            "pending_verification" => AccountStatus::PendingVerification,
+            "deleted" => AccountStatus::Deleted,
+            _ => AccountStatus::Disabled,
+        }
+    }
 }
 
 #[derive(Insertable, Debug, Clone)]
@@ -21,5 +112,5 @@ pub struct User {
 pub struct NewUser {
     pub username: String,
     pub email: String,
-    pub password: String,
-}
\ No newline at end of file
+    pub password: Option<String>,
+}
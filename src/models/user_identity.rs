@@ -0,0 +1,131 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::user_identities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::user_identities)]
+pub struct NewUserIdentity {
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::external_login_states)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ExternalLoginState {
+    pub state_hash: String,
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    // * Utente a cui collegare l'identità, se il login è stato avviato dalle impostazioni
+    pub link_user_id: Option<Uuid>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schema::external_login_states)]
+pub struct NewExternalLoginState {
+    pub state_hash: String,
+    pub provider: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: NaiveDateTime,
+    pub link_user_id: Option<Uuid>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Identity Provider",
+    description = "An upstream provider users can sign in with"
+)]
+pub struct IdentityProviderResponse {
+    pub id: String,
+    pub name: String,
+    // * Endpoint da aprire nel browser per iniziare il login
+    pub login_url: String,
+}
+
+/// Parameters the upstream provider appends to the callback URL
+/// (RFC 6749, sections 4.1.2 and 4.1.2.1).
+#[derive(Deserialize, IntoParams, Debug)]
+pub struct ExternalLoginCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Linked Identity",
+    description = "An account at an upstream identity provider linked to the current user"
+)]
+pub struct UserIdentityResponse {
+    pub id: Uuid,
+    pub provider: String,
+    // * Nome del provider, assente se non è più configurato (l'identità non permette di accedere)
+    pub provider_name: Option<String>,
+    pub email: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: Option<NaiveDateTime>,
+}
+
+impl UserIdentityResponse {
+    pub fn new(identity: UserIdentity, provider_name: Option<String>) -> Self {
+        Self {
+            id: identity.id,
+            provider: identity.provider,
+            provider_name,
+            email: identity.email,
+            created_at: identity.created_at,
+            last_login_at: identity.last_login_at,
+        }
+    }
+}
+
+#[derive(Validate, Serialize, Deserialize, ToSchema)]
+#[schema(
+    title = "Link Identity",
+    description = "Link a provider to the current user, confirming the user's identity first",
+    example = json!({"provider": "google", "password": "Secret123!"})
+)]
+pub struct LinkIdentity {
+    // * Id del provider, come in GET /api/auth/providers
+    #[validate(length(min = 1, max = 64))]
+    #[schema(min_length = 1, max_length = 64)]
+    pub provider: String,
+    // * La password attuale; gli account senza password devono invece aver fatto il login
+    // * da poco
+    #[validate(length(min = 1, max = 1024))]
+    #[schema(min_length = 1, max_length = 1024)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+#[schema(
+    title = "Identity Link Started",
+    description = "Open authorization_url in the browser to sign in at the provider; the callback links the identity"
+)]
+pub struct LinkIdentityResponse {
+    pub authorization_url: String,
+}
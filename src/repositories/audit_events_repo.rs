@@ -0,0 +1,143 @@
+pub use crate::models::audit_event::{AuditEventQuery, AuditLogEntry, NewAuditLogEntry};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::audit_events;
+use crate::schema::audit_events::dsl::*;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+// * Aggiunge un evento al log di audit (la tabella accetta solo inserimenti)
+pub fn insert_with_connection(
+    conn: &mut PgConnection,
+    entry: NewAuditLogEntry,
+) -> Result<AuditLogEntry, diesel::result::Error> {
+    diesel::insert_into(audit_events::table)
+        .values(&entry)
+        .get_result(conn)
+}
+
+// * Query filtrata secondo i parametri di ricerca dell'endpoint di amministrazione
+fn filtered(query: &AuditEventQuery) -> audit_events::BoxedQuery<'static, Pg> {
+    let mut statement = audit_events.into_boxed();
+    if let Some(actor) = query.actor_id {
+        statement = statement.filter(actor_id.eq(actor));
+    }
+    if let Some(target) = query.target_user_id {
+        statement = statement.filter(target_user_id.eq(target));
+    }
+    if let Some(wanted) = query.action.as_deref().filter(|a| !a.is_empty()) {
+        statement = match wanted.strip_suffix('*') {
+            Some(prefix) => statement.filter(action.like(format!(
+                "{}%",
+                prefix
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            ))),
+            None => statement.filter(action.eq(wanted.to_string())),
+        };
+    }
+    if let Some(wanted) = query.outcome {
+        statement = statement.filter(outcome.eq(wanted.as_str()));
+    }
+    if let Some(ip) = query.ip_address.as_deref().filter(|ip| !ip.is_empty()) {
+        statement = statement.filter(ip_address.eq(ip.to_string()));
+    }
+    if let Some(request) = query.request_id.as_deref().filter(|r| !r.is_empty()) {
+        statement = statement.filter(request_id.eq(request.to_string()));
+    }
+    if let Some(impersonator) = query.impersonator_id {
+        statement = statement.filter(impersonator_id.eq(impersonator));
+    }
+    if let Some(wanted) = query.impersonated {
+        statement = if wanted {
+            statement.filter(impersonator_id.is_not_null())
+        } else {
+            statement.filter(impersonator_id.is_null())
+        };
+    }
+    if let Some(from) = query.from {
+        statement = statement.filter(occurred_at.ge(from));
+    }
+    if let Some(to) = query.to {
+        statement = statement.filter(occurred_at.le(to));
+    }
+    statement
+}
+
+// * Cerca gli eventi, dal più recente, e restituisce la pagina richiesta con il totale
+pub fn search(
+    pool: &PgPool,
+    query: &AuditEventQuery,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<AuditLogEntry>, i64), diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    let total = filtered(query).count().get_result::<i64>(&mut conn)?;
+    let page = filtered(query)
+        .order((occurred_at.desc(), id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<AuditLogEntry>(&mut conn)?;
+    Ok((page, total))
+}
+
+// * Eventi in cui l'utente è l'autore o il destinatario dell'azione, o l'admin che lo ha
+// * compiuto impersonando un altro utente
+fn involving(user_id: uuid::Uuid) -> audit_events::BoxedQuery<'static, Pg> {
+    audit_events
+        .filter(
+            actor_id
+                .eq(user_id)
+                .or(target_user_id.eq(user_id))
+                .or(impersonator_id.eq(user_id)),
+        )
+        .into_boxed()
+}
+
+// * Numero di eventi che riguardano l'utente
+pub fn count_for_user(pool: &PgPool, user_id: uuid::Uuid) -> Result<i64, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    involving(user_id).count().get_result(&mut conn)
+}
+
+// * Tutti gli eventi che riguardano l'utente, dal più vecchio
+pub fn for_user_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> Result<Vec<AuditLogEntry>, diesel::result::Error> {
+    involving(user_id)
+        .order((occurred_at.asc(), id.asc()))
+        .load::<AuditLogEntry>(conn)
+}
+
+// * Anonimizza gli eventi che riguardano un utente eliminato: gli id, l'indirizzo IP e lo
+// * user agent vengono rimossi e i dettagli sostituiti. È l'unica modifica consentita dal
+// * trigger append-only, abilitata solo per la transazione corrente
+pub fn anonymize_user_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+    username: &str,
+) -> Result<usize, diesel::result::Error> {
+    diesel::sql_query("SET LOCAL app.audit_maintenance = 'on'").execute(conn)?;
+    let updated = diesel::sql_query(
+        "UPDATE audit_events SET \
+             ip_address = CASE WHEN actor_id = $1 OR impersonator_id = $1 \
+                 OR (actor_id IS NULL AND target_user_id = $1) THEN NULL ELSE ip_address END, \
+             user_agent = CASE WHEN actor_id = $1 OR impersonator_id = $1 \
+                 OR (actor_id IS NULL AND target_user_id = $1) THEN NULL ELSE user_agent END, \
+             actor_id = NULLIF(actor_id, $1), \
+             target_user_id = NULLIF(target_user_id, $1), \
+             impersonator_id = NULLIF(impersonator_id, $1), \
+             details = jsonb_build_object('anonymized', true) \
+         WHERE actor_id = $1 \
+            OR target_user_id = $1 \
+            OR impersonator_id = $1 \
+            OR details ->> 'username' = $2 \
+            OR strpos(details::text, $1::text) > 0",
+    )
+    .bind::<diesel::sql_types::Uuid, _>(user_id)
+    .bind::<diesel::sql_types::Text, _>(username)
+    .execute(conn)?;
+    diesel::sql_query("SET LOCAL app.audit_maintenance = 'off'").execute(conn)?;
+    Ok(updated)
+}
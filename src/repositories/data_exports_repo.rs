@@ -0,0 +1,87 @@
+pub use crate::models::data_export::{DataExport, DataExportStatus, NewDataExport};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::data_exports;
+use crate::schema::data_exports::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use serde_json::Value;
+use uuid::Uuid;
+
+// * Registra una nuova richiesta di esportazione, in attesa di essere generata
+pub fn create(pool: &PgPool, export: NewDataExport) -> Result<DataExport, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::insert_into(data_exports::table)
+        .values(&export)
+        .get_result(&mut conn)
+}
+
+// * Recupera un'esportazione non scaduta dell'utente
+pub fn find_for_user(
+    pool: &PgPool,
+    export_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<DataExport, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    data_exports
+        .find(export_id)
+        .filter(user_id.eq(other_user_id))
+        .filter(expires_at.gt(now))
+        .first::<DataExport>(&mut conn)
+}
+
+// * Esportazione ancora in corso per l'utente, se presente
+pub fn pending_for_user(
+    pool: &PgPool,
+    other_user_id: Uuid,
+) -> Result<Option<DataExport>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    data_exports
+        .filter(user_id.eq(other_user_id))
+        .filter(status.eq(DataExportStatus::Pending.as_str()))
+        .filter(expires_at.gt(now))
+        .first::<DataExport>(&mut conn)
+        .optional()
+}
+
+// * Esportazioni non scadute ancora da generare, dalla meno recente
+pub fn list_pending(pool: &PgPool) -> Result<Vec<DataExport>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    data_exports
+        .filter(status.eq(DataExportStatus::Pending.as_str()))
+        .filter(expires_at.gt(now))
+        .order(created_at.asc())
+        .load::<DataExport>(&mut conn)
+}
+
+// * Salva l'archivio generato e segna l'esportazione come pronta
+pub fn complete(
+    pool: &PgPool,
+    export_id: Uuid,
+    generated: Value,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(data_exports.find(export_id))
+        .set((
+            status.eq(DataExportStatus::Ready.as_str()),
+            archive.eq(generated),
+            completed_at.eq(now),
+        ))
+        .execute(&mut conn)
+}
+
+// * Segna l'esportazione come fallita
+pub fn fail(pool: &PgPool, export_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(data_exports.find(export_id))
+        .set((
+            status.eq(DataExportStatus::Failed.as_str()),
+            completed_at.eq(now),
+        ))
+        .execute(&mut conn)
+}
+
+// * Elimina le esportazioni scadute, archivi compresi
+pub fn delete_expired(pool: &PgPool) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(data_exports.filter(expires_at.le(now))).execute(&mut conn)
+}
@@ -0,0 +1,33 @@
+pub use crate::models::user_identity::{ExternalLoginState, NewExternalLoginState};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::external_login_states;
+use crate::schema::external_login_states::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+// * Salva un login appena avviato verso un provider ed elimina quelli scaduti
+pub fn create(
+    pool: &PgPool,
+    state: NewExternalLoginState,
+) -> Result<ExternalLoginState, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(external_login_states.filter(expires_at.lt(now))).execute(&mut conn)?;
+    diesel::insert_into(external_login_states::table)
+        .values(&state)
+        .get_result(&mut conn)
+}
+
+// * Elimina e restituisce lo state ancora valido: ogni state può completare un solo login
+pub fn consume(
+    pool: &PgPool,
+    other_state_hash: &str,
+) -> Result<Option<ExternalLoginState>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(
+        external_login_states
+            .filter(state_hash.eq(other_state_hash))
+            .filter(expires_at.gt(now)),
+    )
+    .get_result(&mut conn)
+    .optional()
+}
@@ -0,0 +1,126 @@
+use crate::models::organization::ORG_OWNER_ROLE;
+pub use crate::models::organization::{Membership, NewMembership};
+use crate::schema::memberships::dsl::*;
+use crate::schema::{memberships, users};
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+pub fn create_with_connection(
+    conn: &mut PgConnection,
+    membership: NewMembership,
+) -> Result<Membership, diesel::result::Error> {
+    diesel::insert_into(memberships::table)
+        .values(&membership)
+        .get_result(conn)
+}
+
+// * Appartenenza accettata: un invito in attesa non rende membri
+pub fn find_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<Option<Membership>, diesel::result::Error> {
+    memberships
+        .find((other_organization_id, other_user_id))
+        .filter(accepted_at.is_not_null())
+        .first::<Membership>(conn)
+        .optional()
+}
+
+// * Appartenenza o invito in attesa, qualunque sia lo stato
+pub fn find_any_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<Option<Membership>, diesel::result::Error> {
+    memberships
+        .find((other_organization_id, other_user_id))
+        .first::<Membership>(conn)
+        .optional()
+}
+
+pub fn find_invitation_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<Option<Membership>, diesel::result::Error> {
+    memberships
+        .find((other_organization_id, other_user_id))
+        .filter(accepted_at.is_null())
+        .first::<Membership>(conn)
+        .optional()
+}
+
+pub fn accept_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<Membership, diesel::result::Error> {
+    diesel::update(
+        memberships
+            .find((other_organization_id, other_user_id))
+            .filter(accepted_at.is_null()),
+    )
+    .set(accepted_at.eq(now))
+    .get_result(conn)
+}
+
+// * L'unica appartenenza accettata dell'utente, None se non ne ha o ne ha più di una
+pub fn only_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Option<Membership>, diesel::result::Error> {
+    let mut found = memberships
+        .filter(user_id.eq(other_user_id))
+        .filter(accepted_at.is_not_null())
+        .limit(2)
+        .load::<Membership>(conn)?;
+    Ok(if found.len() == 1 { found.pop() } else { None })
+}
+
+// * Membri dell'organizzazione con il loro username, dal primo entrato. Gli inviti in attesa
+// * non compaiono: l'elenco non rivela quali account esistono
+pub fn list_members_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+) -> Result<Vec<(Membership, String)>, diesel::result::Error> {
+    memberships
+        .inner_join(users::table)
+        .filter(organization_id.eq(other_organization_id))
+        .filter(accepted_at.is_not_null())
+        .order(accepted_at.asc())
+        .select((Membership::as_select(), users::username))
+        .load(conn)
+}
+
+pub fn update_roles_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+    new_roles: &[String],
+) -> Result<Membership, diesel::result::Error> {
+    diesel::update(memberships.find((other_organization_id, other_user_id)))
+        .set(roles.eq(new_roles))
+        .get_result(conn)
+}
+
+pub fn delete_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(memberships.find((other_organization_id, other_user_id))).execute(conn)
+}
+
+pub fn count_owners_with_connection(
+    conn: &mut PgConnection,
+    other_organization_id: Uuid,
+) -> Result<i64, diesel::result::Error> {
+    memberships
+        .filter(organization_id.eq(other_organization_id))
+        .filter(accepted_at.is_not_null())
+        .filter(roles.contains(vec![ORG_OWNER_ROLE]))
+        .count()
+        .get_result(conn)
+}
@@ -1,10 +1,15 @@
+use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
 use diesel::result::Error;
-use diesel::PgConnection;
+use diesel::sql_types::Text;
 use r2d2::{Pool, PooledConnection};
+use uuid::Uuid;
 
 pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 
+// * Ruolo senza privilegi con cui vengono eseguite le query per conto di un utente autenticato
+pub const APP_USER_ROLE: &str = "ketchapp_app_user";
+
 pub fn establish_connection(
     pool: &PgPool,
 ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
@@ -15,4 +20,36 @@ pub fn establish_connection(
         )
     })
 }
+pub mod audit_events_repo;
+pub mod data_exports_repo;
+pub mod external_login_states_repo;
+pub mod memberships_repo;
+pub mod oauth_clients_repo;
+pub mod oauth_codes_repo;
+pub mod oauth_consents_repo;
+pub mod oauth_device_codes_repo;
+pub mod organizations_repo;
+pub mod outbox_repo;
+pub mod password_history_repo;
+pub mod personal_access_tokens_repo;
+pub mod roles_repo;
+pub mod sessions_repo;
+pub mod user_identities_repo;
 pub mod users_repo;
+
+// * Esegue `f` in una transazione per conto dell'utente: `app.current_user_id` identifica
+// * l'utente e il ruolo passa a APP_USER_ROLE, così le policy di row-level security si applicano.
+// * Entrambe le impostazioni valgono solo per la transazione e non restano sulla connessione
+pub fn as_user<T, F>(pool: &PgPool, user_id: Uuid, f: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, Error>,
+{
+    let mut conn = establish_connection(pool)?;
+    conn.transaction(|conn| {
+        diesel::sql_query("SELECT set_config('app.current_user_id', $1, true)")
+            .bind::<Text, _>(user_id.to_string())
+            .execute(conn)?;
+        diesel::sql_query(format!("SET LOCAL ROLE {}", APP_USER_ROLE)).execute(conn)?;
+        f(conn)
+    })
+}
@@ -0,0 +1,66 @@
+pub use crate::models::oauth::{ClientTokenSettings, NewOAuthClient, OAuthClient};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::oauth_clients;
+use crate::schema::oauth_clients::dsl::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Registra un nuovo client OAuth
+pub fn create(pool: &PgPool, client: NewOAuthClient) -> Result<OAuthClient, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::insert_into(oauth_clients::table)
+        .values(&client)
+        .get_result(&mut conn)
+}
+
+// * Recupera un client tramite il suo client_id
+pub fn get_client(pool: &PgPool, client_id: Uuid) -> Result<OAuthClient, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    oauth_clients
+        .find(client_id)
+        .first::<OAuthClient>(&mut conn)
+}
+
+// * Elenca i client registrati, dal più recente
+pub fn list_clients(pool: &PgPool) -> Result<Vec<OAuthClient>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    oauth_clients
+        .order(created_at.desc())
+        .load::<OAuthClient>(&mut conn)
+}
+
+// * Nomi dei client indicati, per mostrarli all'utente
+pub fn names_with_connection(
+    conn: &mut PgConnection,
+    client_ids: &[Uuid],
+) -> Result<Vec<(Uuid, String)>, diesel::result::Error> {
+    oauth_clients
+        .filter(id.eq_any(client_ids))
+        .select((id, name))
+        .load(conn)
+}
+
+// * Sostituisce audience, durate dei token e audience del token exchange del client; None se il client non esiste
+pub fn update_token_settings(
+    pool: &PgPool,
+    client_id: Uuid,
+    settings: ClientTokenSettings,
+) -> Result<Option<OAuthClient>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(oauth_clients.find(client_id))
+        .set((
+            audience.eq(settings.audience),
+            access_token_ttl_secs.eq(settings.access_token_ttl_secs),
+            refresh_token_ttl_secs.eq(settings.refresh_token_ttl_secs),
+            idle_timeout_secs.eq(settings.idle_timeout_secs),
+            token_exchange_audiences.eq(settings.token_exchange_audiences),
+        ))
+        .get_result(&mut conn)
+        .optional()
+}
+
+// * Elimina un client; codici, consensi e sessioni del client seguono in cascata
+pub fn delete_client(pool: &PgPool, client_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(oauth_clients.find(client_id)).execute(&mut conn)
+}
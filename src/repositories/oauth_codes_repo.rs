@@ -0,0 +1,42 @@
+pub use crate::models::oauth::{AuthorizationCode, NewAuthorizationCode};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::oauth_authorization_codes;
+use crate::schema::oauth_authorization_codes::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Salva un codice di autorizzazione appena emesso ed elimina quelli scaduti
+pub fn create(
+    pool: &PgPool,
+    code: NewAuthorizationCode,
+) -> Result<AuthorizationCode, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(oauth_authorization_codes.filter(expires_at.lt(now))).execute(&mut conn)?;
+    diesel::insert_into(oauth_authorization_codes::table)
+        .values(&code)
+        .get_result(&mut conn)
+}
+
+// * Blocca il codice per lo scambio, così che due richieste concorrenti non lo usino entrambe
+pub fn lock_with_connection(
+    conn: &mut PgConnection,
+    other_code_hash: &str,
+) -> Result<Option<AuthorizationCode>, diesel::result::Error> {
+    oauth_authorization_codes
+        .find(other_code_hash)
+        .for_update()
+        .first::<AuthorizationCode>(conn)
+        .optional()
+}
+
+// * Segna il codice come usato e ricorda la sessione creata con lo scambio
+pub fn consume_with_connection(
+    conn: &mut PgConnection,
+    other_code_hash: &str,
+    issued_session_id: Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(oauth_authorization_codes.find(other_code_hash))
+        .set((consumed_at.eq(now), session_id.eq(issued_session_id)))
+        .execute(conn)
+}
@@ -0,0 +1,61 @@
+pub use crate::models::oauth::{NewOAuthConsent, OAuthConsent};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::oauth_consents;
+use crate::schema::oauth_consents::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Consenso dato dall'utente al client, se presente
+pub fn find(
+    pool: &PgPool,
+    other_user_id: Uuid,
+    other_client_id: Uuid,
+) -> Result<Option<OAuthConsent>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    find_with_connection(&mut conn, other_user_id, other_client_id)
+}
+
+pub fn find_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    other_client_id: Uuid,
+) -> Result<Option<OAuthConsent>, diesel::result::Error> {
+    oauth_consents
+        .find((other_user_id, other_client_id))
+        .first::<OAuthConsent>(conn)
+        .optional()
+}
+
+// * Salva il consenso, sostituendo gli scope di un consenso precedente
+pub fn upsert_with_connection(
+    conn: &mut PgConnection,
+    consent: NewOAuthConsent,
+) -> Result<OAuthConsent, diesel::result::Error> {
+    diesel::insert_into(oauth_consents::table)
+        .values(&consent)
+        .on_conflict((user_id, client_id))
+        .do_update()
+        .set((scopes.eq(&consent.scopes), updated_at.eq(now)))
+        .get_result(conn)
+}
+
+// * Elenca i consensi dell'utente, dal più recente
+pub fn list_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Vec<OAuthConsent>, diesel::result::Error> {
+    oauth_consents
+        .filter(user_id.eq(other_user_id))
+        .order(updated_at.desc())
+        .load::<OAuthConsent>(conn)
+}
+
+// * Revoca il consenso; restituisce il numero di consensi eliminati (0 o 1)
+pub fn delete_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    other_client_id: Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(oauth_consents.find((other_user_id, other_client_id))).execute(conn)
+}
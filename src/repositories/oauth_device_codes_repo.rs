@@ -0,0 +1,91 @@
+pub use crate::models::oauth::{DeviceCode, DeviceCodeStatus, NewDeviceCode};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::oauth_device_codes;
+use crate::schema::oauth_device_codes::dsl::*;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Salva una nuova richiesta di autorizzazione del dispositivo ed elimina quelle scadute da
+// * più di un giorno (prima il polling riceve ancora expired_token)
+pub fn create(pool: &PgPool, code: NewDeviceCode) -> Result<DeviceCode, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::delete(
+        oauth_device_codes.filter(expires_at.lt(Utc::now().naive_utc() - Duration::days(1))),
+    )
+    .execute(&mut conn)?;
+    diesel::insert_into(oauth_device_codes::table)
+        .values(&code)
+        .get_result(&mut conn)
+}
+
+// * Richiesta ancora in attesa della decisione dell'utente, cercata per user code
+pub fn find_pending_by_user_code(
+    pool: &PgPool,
+    other_user_code: &str,
+) -> Result<Option<DeviceCode>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    oauth_device_codes
+        .filter(user_code.eq(other_user_code))
+        .filter(status.eq(DeviceCodeStatus::Pending.as_str()))
+        .filter(expires_at.gt(now))
+        .first::<DeviceCode>(&mut conn)
+        .optional()
+}
+
+// * Registra la decisione dell'utente, solo se la richiesta è ancora in attesa
+pub fn decide_with_connection(
+    conn: &mut PgConnection,
+    other_user_code: &str,
+    decision: DeviceCodeStatus,
+    decided_by: Uuid,
+    decided_auth_time: NaiveDateTime,
+) -> Result<Option<DeviceCode>, diesel::result::Error> {
+    diesel::update(
+        oauth_device_codes
+            .filter(user_code.eq(other_user_code))
+            .filter(status.eq(DeviceCodeStatus::Pending.as_str()))
+            .filter(expires_at.gt(now)),
+    )
+    .set((
+        status.eq(decision.as_str()),
+        user_id.eq(decided_by),
+        auth_time.eq(decided_auth_time),
+    ))
+    .get_result(conn)
+    .optional()
+}
+
+// * Blocca la richiesta per il polling, così che due richieste concorrenti non la usino entrambe
+pub fn lock_with_connection(
+    conn: &mut PgConnection,
+    other_device_code_hash: &str,
+) -> Result<Option<DeviceCode>, diesel::result::Error> {
+    oauth_device_codes
+        .find(other_device_code_hash)
+        .for_update()
+        .first::<DeviceCode>(conn)
+        .optional()
+}
+
+// * Ricorda l'ultimo polling e l'intervallo richiesto da qui in avanti
+pub fn record_poll_with_connection(
+    conn: &mut PgConnection,
+    other_device_code_hash: &str,
+    new_interval_secs: i32,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(oauth_device_codes.find(other_device_code_hash))
+        .set((last_polled_at.eq(now), interval_secs.eq(new_interval_secs)))
+        .execute(conn)
+}
+
+// * Segna la richiesta come usata: il device code non può ottenere altri token
+pub fn consume_with_connection(
+    conn: &mut PgConnection,
+    other_device_code_hash: &str,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(oauth_device_codes.find(other_device_code_hash))
+        .set(status.eq(DeviceCodeStatus::Consumed.as_str()))
+        .execute(conn)
+}
@@ -0,0 +1,58 @@
+pub use crate::models::organization::{Membership, NewOrganization, Organization};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::{memberships, organizations};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+pub fn create_with_connection(
+    conn: &mut PgConnection,
+    organization: NewOrganization,
+) -> Result<Organization, diesel::result::Error> {
+    diesel::insert_into(organizations::table)
+        .values(&organization)
+        .get_result(conn)
+}
+
+pub fn slug_exists_with_connection(
+    conn: &mut PgConnection,
+    other_slug: &str,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        organizations::table.filter(organizations::slug.eq(other_slug)),
+    ))
+    .get_result(conn)
+}
+
+// * Blocca la riga dell'organizzazione fino alla fine della transazione, così le modifiche
+// * concorrenti ai membri non possono lasciarla senza proprietari
+pub fn lock_with_connection(
+    conn: &mut PgConnection,
+    organization_id: Uuid,
+) -> Result<Organization, diesel::result::Error> {
+    organizations::table
+        .find(organization_id)
+        .for_update()
+        .first::<Organization>(conn)
+}
+
+// * Organizzazioni di cui l'utente è membro o a cui è invitato, con i suoi ruoli, in ordine di
+// * nome
+pub fn list_for_user_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+) -> Result<Vec<(Organization, Membership)>, diesel::result::Error> {
+    organizations::table
+        .inner_join(memberships::table)
+        .filter(memberships::user_id.eq(user_id))
+        .order(organizations::name.asc())
+        .select((Organization::as_select(), Membership::as_select()))
+        .load(conn)
+}
+
+pub fn list_for_user(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<(Organization, Membership)>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    list_for_user_with_connection(&mut conn, user_id)
+}
@@ -0,0 +1,39 @@
+pub use crate::models::outbox_event::{NewOutboxEvent, OutboxEvent};
+use crate::schema::outbox_events;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Accoda un evento per gli altri servizi, nella stessa transazione della modifica che descrive
+pub fn add_with_connection(
+    conn: &mut PgConnection,
+    event: NewOutboxEvent,
+) -> Result<OutboxEvent, diesel::result::Error> {
+    diesel::insert_into(outbox_events::table)
+        .values(&event)
+        .get_result(conn)
+}
+
+// * Eventi ancora da pubblicare, dal più vecchio. Le righe restano bloccate fino alla fine della
+// * transazione e le altre istanze del relay le saltano
+pub fn lock_unpublished_with_connection(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<OutboxEvent>, diesel::result::Error> {
+    outbox_events::table
+        .filter(outbox_events::published_at.is_null())
+        .order(outbox_events::created_at.asc())
+        .limit(limit)
+        .for_update()
+        .skip_locked()
+        .select(OutboxEvent::as_select())
+        .load(conn)
+}
+
+pub fn mark_published_with_connection(
+    conn: &mut PgConnection,
+    ids: &[Uuid],
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(outbox_events::table.filter(outbox_events::id.eq_any(ids)))
+        .set(outbox_events::published_at.eq(diesel::dsl::now))
+        .execute(conn)
+}
@@ -0,0 +1,61 @@
+pub use crate::models::password_history::{NewPasswordHistoryEntry, PasswordHistoryEntry};
+use crate::schema::password_history;
+use crate::schema::password_history::dsl::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Recupera gli hash delle password precedenti di un utente, dal più recente
+pub fn recent_hashes_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    limit: usize,
+) -> Result<Vec<String>, diesel::result::Error> {
+    password_history
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.desc())
+        .limit(limit as i64)
+        .select(password_hash)
+        .load(conn)
+}
+
+// * Date in cui le password precedenti sono state sostituite, dalla più vecchia
+pub fn change_dates_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Vec<chrono::NaiveDateTime>, diesel::result::Error> {
+    password_history
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.asc())
+        .select(created_at)
+        .load(conn)
+}
+
+// * Salva l'hash di una password che non è più quella attuale
+pub fn add_entry_with_connection(
+    conn: &mut PgConnection,
+    entry: NewPasswordHistoryEntry,
+) -> Result<PasswordHistoryEntry, diesel::result::Error> {
+    diesel::insert_into(password_history::table)
+        .values(&entry)
+        .get_result(conn)
+}
+
+// * Mantiene solo le `keep` voci più recenti di un utente e restituisce quante ne ha eliminate
+pub fn prune_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    keep: usize,
+) -> Result<usize, diesel::result::Error> {
+    let kept_ids = password_history
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.desc())
+        .limit(keep as i64)
+        .select(id)
+        .load::<Uuid>(conn)?;
+    diesel::delete(
+        password_history
+            .filter(user_id.eq(other_user_id))
+            .filter(id.ne_all(kept_ids)),
+    )
+    .execute(conn)
+}
@@ -0,0 +1,93 @@
+pub use crate::models::personal_access_token::{NewPersonalAccessToken, PersonalAccessToken};
+use crate::repositories::{as_user, establish_connection, PgPool};
+use crate::schema::personal_access_tokens;
+use crate::schema::personal_access_tokens::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+pub fn create_with_connection(
+    conn: &mut PgConnection,
+    token: NewPersonalAccessToken,
+) -> Result<PersonalAccessToken, diesel::result::Error> {
+    diesel::insert_into(personal_access_tokens::table)
+        .values(&token)
+        .get_result(conn)
+}
+
+// * Conta i token non revocati di un utente
+pub fn count_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<i64, diesel::result::Error> {
+    personal_access_tokens
+        .filter(user_id.eq(other_user_id))
+        .filter(revoked_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+// * Elenca i token non revocati di un utente, dal più recente, con i privilegi del ruolo
+// * applicativo
+pub fn list_for_user(
+    pool: &PgPool,
+    other_user_id: Uuid,
+) -> Result<Vec<PersonalAccessToken>, diesel::result::Error> {
+    as_user(pool, other_user_id, |conn| {
+        personal_access_tokens
+            .filter(user_id.eq(other_user_id))
+            .filter(revoked_at.is_null())
+            .order(created_at.desc())
+            .load::<PersonalAccessToken>(conn)
+    })
+}
+
+// * Tutti i token dell'utente, compresi quelli revocati (per l'export dei dati)
+pub fn list_all_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Vec<PersonalAccessToken>, diesel::result::Error> {
+    personal_access_tokens
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.desc())
+        .load::<PersonalAccessToken>(conn)
+}
+
+// * Recupera il token valido (non revocato e non scaduto) con questo hash
+pub fn find_active_by_hash(
+    pool: &PgPool,
+    other_token_hash: &str,
+) -> Result<Option<PersonalAccessToken>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    personal_access_tokens
+        .filter(token_hash.eq(other_token_hash))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.is_null().or(expires_at.gt(now)))
+        .first::<PersonalAccessToken>(&mut conn)
+        .optional()
+}
+
+// * Aggiorna l'ultimo utilizzo del token
+pub fn touch(pool: &PgPool, token_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(personal_access_tokens.find(token_id))
+        .set(last_used_at.eq(now))
+        .execute(&mut conn)
+}
+
+// * Revoca un token dell'utente e lo restituisce, se era ancora valido
+pub fn revoke_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    token_id: Uuid,
+) -> Result<Option<PersonalAccessToken>, diesel::result::Error> {
+    diesel::update(
+        personal_access_tokens
+            .filter(id.eq(token_id))
+            .filter(user_id.eq(other_user_id))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(now))
+    .get_result(conn)
+    .optional()
+}
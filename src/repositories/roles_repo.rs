@@ -0,0 +1,148 @@
+pub use crate::models::role::{NewUserRole, Permission, Role, RoleGrants};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::{permissions, role_permissions, roles, user_roles};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Recupera tutti i ruoli disponibili
+pub fn list_roles(pool: &PgPool) -> Result<Vec<Role>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    roles::table
+        .order(roles::name.asc())
+        .load::<Role>(&mut conn)
+}
+
+// * Recupera i nomi dei ruoli di un utente, in ordine alfabetico
+pub fn role_names_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Vec<String>, diesel::result::Error> {
+    user_roles::table
+        .inner_join(roles::table)
+        .filter(user_roles::user_id.eq(other_user_id))
+        .order(roles::name.asc())
+        .select(roles::name)
+        .load(conn)
+}
+
+pub fn role_names_for_user(
+    pool: &PgPool,
+    other_user_id: Uuid,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    role_names_for_user_with_connection(&mut conn, other_user_id)
+}
+
+// * Ruoli di un utente con gli scope che concedono tramite role_permissions
+pub fn grants_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<RoleGrants, diesel::result::Error> {
+    let roles = role_names_for_user_with_connection(conn, other_user_id)?;
+    let granted = user_roles::table
+        .inner_join(role_permissions::table.on(role_permissions::role_id.eq(user_roles::role_id)))
+        .filter(user_roles::user_id.eq(other_user_id))
+        .select(role_permissions::permission_id);
+    let (scopes, withheld_scopes) = permissions::table
+        .order(permissions::name.asc())
+        .select((permissions::name, permissions::id.eq_any(granted)))
+        .load::<(String, bool)>(conn)?
+        .into_iter()
+        .partition::<Vec<_>, _>(|(_, granted)| *granted);
+    Ok(RoleGrants {
+        roles,
+        scopes: scopes.into_iter().map(|(name, _)| name).collect(),
+        withheld_scopes: withheld_scopes.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+pub fn grants_for_user(
+    pool: &PgPool,
+    other_user_id: Uuid,
+) -> Result<RoleGrants, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    grants_for_user_with_connection(&mut conn, other_user_id)
+}
+
+// * Catalogo degli scope: ogni permesso con i nomi dei ruoli che lo concedono
+pub fn scope_catalogue(
+    pool: &PgPool,
+) -> Result<Vec<(Permission, Vec<String>)>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    let catalogue = permissions::table
+        .order(permissions::name.asc())
+        .select(Permission::as_select())
+        .load::<Permission>(&mut conn)?;
+    let granted_by = role_permissions::table
+        .inner_join(roles::table)
+        .order(roles::name.asc())
+        .select((role_permissions::permission_id, roles::name))
+        .load::<(Uuid, String)>(&mut conn)?;
+    Ok(catalogue
+        .into_iter()
+        .map(|permission| {
+            let roles = granted_by
+                .iter()
+                .filter(|(permission_id, _)| *permission_id == permission.id)
+                .map(|(_, role)| role.clone())
+                .collect();
+            (permission, roles)
+        })
+        .collect())
+}
+
+// * Assegna un ruolo (per nome) a un utente; restituisce false se il ruolo non esiste.
+// * Assegnare un ruolo già presente non è un errore.
+pub fn assign_role_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    role_name: &str,
+) -> Result<bool, diesel::result::Error> {
+    let Some(role_id) = roles::table
+        .filter(roles::name.eq(role_name))
+        .select(roles::id)
+        .first::<Uuid>(conn)
+        .optional()?
+    else {
+        return Ok(false);
+    };
+    diesel::insert_into(user_roles::table)
+        .values(NewUserRole {
+            user_id: other_user_id,
+            role_id,
+        })
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(true)
+}
+
+// * Rimuove un ruolo (per nome) da un utente e restituisce quante righe ha eliminato
+pub fn remove_role_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    role_name: &str,
+) -> Result<usize, diesel::result::Error> {
+    let role_ids = roles::table
+        .filter(roles::name.eq(role_name))
+        .select(roles::id);
+    diesel::delete(
+        user_roles::table
+            .filter(user_roles::user_id.eq(other_user_id))
+            .filter(user_roles::role_id.eq_any(role_ids)),
+    )
+    .execute(conn)
+}
+
+// * Recupera le coppie (utente, ruolo) per un insieme di utenti, per evitare una query per utente
+pub fn role_names_for_users(
+    pool: &PgPool,
+    user_ids: &[Uuid],
+) -> Result<Vec<(Uuid, String)>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    user_roles::table
+        .inner_join(roles::table)
+        .filter(user_roles::user_id.eq_any(user_ids))
+        .order(roles::name.asc())
+        .select((user_roles::user_id, roles::name))
+        .load(&mut conn)
+}
@@ -0,0 +1,165 @@
+pub use crate::models::session::{NewSession, Session};
+use crate::repositories::{as_user, establish_connection, PgPool};
+use crate::schema::sessions;
+use crate::schema::sessions::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+// * Crea una nuova sessione usando una connessione esistente (per transazioni)
+pub fn create_session_with_connection(
+    conn: &mut PgConnection,
+    new_session: NewSession,
+) -> Result<Session, diesel::result::Error> {
+    diesel::insert_into(sessions::table)
+        .values(&new_session)
+        .get_result(conn)
+}
+
+// * Recupera una sessione tramite id
+pub fn get_session(pool: &PgPool, session_id: Uuid) -> Result<Session, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    get_session_with_connection(&mut conn, session_id)
+}
+
+pub fn get_session_with_connection(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+) -> Result<Session, diesel::result::Error> {
+    sessions.find(session_id).first::<Session>(conn)
+}
+
+// * Recupera la sessione attiva (non revocata e non scaduta) che ha questo refresh token
+pub fn find_active_by_refresh_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<Option<Session>, diesel::result::Error> {
+    sessions
+        .filter(refresh_token_hash.eq(token_hash))
+        .filter(revoked_at.is_null())
+        .filter(expires_at.gt(now))
+        .first::<Session>(conn)
+        .optional()
+}
+
+// * Recupera la sessione in cui questo refresh token è già stato ruotato (possibile furto)
+pub fn find_by_previous_refresh_hash(
+    conn: &mut PgConnection,
+    token_hash: &str,
+) -> Result<Option<Session>, diesel::result::Error> {
+    sessions
+        .filter(previous_refresh_token_hash.eq(token_hash))
+        .first::<Session>(conn)
+        .optional()
+}
+
+// * Sostituisce il refresh token della sessione, ricordando quello precedente
+pub fn rotate_refresh_token(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    old_hash: &str,
+    new_hash: &str,
+) -> Result<Session, diesel::result::Error> {
+    diesel::update(sessions.find(session_id))
+        .set((
+            previous_refresh_token_hash.eq(old_hash),
+            refresh_token_hash.eq(new_hash),
+            last_seen_at.eq(now),
+        ))
+        .get_result(conn)
+}
+
+// * Cambia l'organizzazione per cui vengono emessi i token della sessione
+pub fn set_organization_with_connection(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    other_organization_id: Option<Uuid>,
+) -> Result<Session, diesel::result::Error> {
+    diesel::update(sessions.find(session_id))
+        .set(organization_id.eq(other_organization_id))
+        .get_result(conn)
+}
+
+// * Aggiorna l'ultimo accesso della sessione
+pub fn touch(pool: &PgPool, session_id: Uuid) -> Result<usize, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(sessions.find(session_id))
+        .set(last_seen_at.eq(now))
+        .execute(&mut conn)
+}
+
+// * Elenca le sessioni attive di un utente, dalla più recente, con i privilegi del ruolo
+// * applicativo
+pub fn list_active_for_user(
+    pool: &PgPool,
+    other_user_id: Uuid,
+) -> Result<Vec<Session>, diesel::result::Error> {
+    as_user(pool, other_user_id, |conn| {
+        sessions
+            .filter(user_id.eq(other_user_id))
+            .filter(revoked_at.is_null())
+            .filter(expires_at.gt(now))
+            .order(last_seen_at.desc())
+            .load::<Session>(conn)
+    })
+}
+
+// * Revoca tutte le sessioni concesse dall'utente al client OAuth
+pub fn revoke_for_client_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    client_id: Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(
+        sessions
+            .filter(user_id.eq(other_user_id))
+            .filter(oauth_client_id.eq(client_id))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(now))
+    .execute(conn)
+}
+
+// * Tutte le sessioni di un utente, anche revocate o scadute, dalla più vecchia
+pub fn list_all_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+) -> Result<Vec<Session>, diesel::result::Error> {
+    sessions
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.asc())
+        .load::<Session>(conn)
+}
+
+// * Revoca una sessione dell'utente; restituisce il numero di sessioni revocate (0 o 1)
+pub fn revoke_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    session_id: Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(
+        sessions
+            .filter(id.eq(session_id))
+            .filter(user_id.eq(other_user_id))
+            .filter(revoked_at.is_null()),
+    )
+    .set(revoked_at.eq(now))
+    .execute(conn)
+}
+
+// * Revoca tutte le sessioni dell'utente tranne quella indicata (se presente)
+pub fn revoke_all_except_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: Uuid,
+    keep_session_id: Option<Uuid>,
+) -> Result<usize, diesel::result::Error> {
+    let active = sessions
+        .filter(user_id.eq(other_user_id))
+        .filter(revoked_at.is_null());
+    match keep_session_id {
+        Some(keep) => diesel::update(active.filter(id.ne(keep)))
+            .set(revoked_at.eq(now))
+            .execute(conn),
+        None => diesel::update(active).set(revoked_at.eq(now)).execute(conn),
+    }
+}
@@ -0,0 +1,73 @@
+pub use crate::models::user_identity::{NewUserIdentity, UserIdentity};
+use crate::repositories::{establish_connection, PgPool};
+use crate::schema::user_identities;
+use crate::schema::user_identities::dsl::*;
+use diesel::dsl::now;
+use diesel::prelude::*;
+
+// * Identità esterna già collegata, cercata per provider e identificativo presso il provider
+pub fn find_with_connection(
+    conn: &mut PgConnection,
+    other_provider: &str,
+    other_subject: &str,
+) -> Result<Option<UserIdentity>, diesel::result::Error> {
+    user_identities
+        .filter(provider.eq(other_provider))
+        .filter(subject.eq(other_subject))
+        .first::<UserIdentity>(conn)
+        .optional()
+}
+
+pub fn create_with_connection(
+    conn: &mut PgConnection,
+    identity: NewUserIdentity,
+) -> Result<UserIdentity, diesel::result::Error> {
+    diesel::insert_into(user_identities::table)
+        .values(&identity)
+        .get_result(conn)
+}
+
+// * Registra un login tramite l'identità, aggiornando l'email comunicata dal provider
+pub fn record_login_with_connection(
+    conn: &mut PgConnection,
+    identity_id: uuid::Uuid,
+    new_email: Option<&str>,
+) -> Result<usize, diesel::result::Error> {
+    diesel::update(user_identities.find(identity_id))
+        .set((last_login_at.eq(now), email.eq(new_email)))
+        .execute(conn)
+}
+
+// * Elenca le identità collegate a un utente, dalla più vecchia
+pub fn list_for_user(
+    pool: &PgPool,
+    other_user_id: uuid::Uuid,
+) -> Result<Vec<UserIdentity>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    list_for_user_with_connection(&mut conn, other_user_id)
+}
+
+pub fn list_for_user_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: uuid::Uuid,
+) -> Result<Vec<UserIdentity>, diesel::result::Error> {
+    user_identities
+        .filter(user_id.eq(other_user_id))
+        .order(created_at.asc())
+        .load::<UserIdentity>(conn)
+}
+
+// * Elimina un'identità dell'utente e la restituisce, se esiste
+pub fn delete_with_connection(
+    conn: &mut PgConnection,
+    other_user_id: uuid::Uuid,
+    identity_id: uuid::Uuid,
+) -> Result<Option<UserIdentity>, diesel::result::Error> {
+    diesel::delete(
+        user_identities
+            .filter(id.eq(identity_id))
+            .filter(user_id.eq(other_user_id)),
+    )
+    .get_result(conn)
+    .optional()
+}
@@ -1,5 +1,5 @@
-pub use crate::models::user::{NewUser, User};
-use crate::repositories::{establish_connection, PgPool};
+pub use crate::models::user::{AccountStatus, NewUser, User};
+use crate::repositories::{as_user, establish_connection, PgPool};
 use crate::schema::users;
 use crate::schema::users::dsl::*;
 use diesel::prelude::*;
@@ -45,4 +45,246 @@ pub fn get_user_by_username(
     users
         .filter(username.eq(other_username))
         .first::<User>(&mut conn)
-}
\ No newline at end of file
+}
+// * Recupera un utente tramite id
+pub fn get_user_by_id(pool: &PgPool, user_id: uuid::Uuid) -> Result<User, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    users.find(user_id).first::<User>(&mut conn)
+}
+
+// * Recupera l'utente autenticato con i privilegi del ruolo applicativo: la row-level
+// * security gli permette di vedere solo la propria riga
+pub fn get_own_user(pool: &PgPool, user_id: uuid::Uuid) -> Result<User, diesel::result::Error> {
+    as_user(pool, user_id, |conn| {
+        users.find(user_id).first::<User>(conn)
+    })
+}
+
+// * Recupera un utente tramite email (confronto senza maiuscole), per collegare i login esterni
+pub fn find_by_email_with_connection(
+    conn: &mut PgConnection,
+    other_email: &str,
+) -> Result<Option<User>, diesel::result::Error> {
+    users
+        .filter(email.ilike(escape_like(other_email)))
+        .first::<User>(conn)
+        .optional()
+}
+
+pub fn find_by_username_with_connection(
+    conn: &mut PgConnection,
+    other_username: &str,
+) -> Result<Option<User>, diesel::result::Error> {
+    users
+        .filter(username.eq(other_username))
+        .first::<User>(conn)
+        .optional()
+}
+
+// * Verifica se lo username è già in uso, per generarne uno libero
+pub fn username_exists_with_connection(
+    conn: &mut PgConnection,
+    other_username: &str,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        users.filter(username.eq(other_username)),
+    ))
+    .get_result(conn)
+}
+
+// * Recupera un utente tramite id usando una connessione esistente (per transazioni)
+pub fn get_user_by_id_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> Result<User, diesel::result::Error> {
+    users.find(user_id).first::<User>(conn)
+}
+
+// * Recupera l'utente bloccandone la riga fino al termine della transazione
+pub fn lock_user_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> Result<User, diesel::result::Error> {
+    users.find(user_id).for_update().first::<User>(conn)
+}
+
+// * Aggiorna l'hash della password di un utente (es. rehash con un nuovo pepper)
+pub fn update_password_hash(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+    new_password_hash: &str,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(users.find(user_id))
+        .set(password.eq(new_password_hash))
+        .get_result(&mut conn)
+}
+
+// * Imposta una nuova password scelta dall'utente: azzera la scadenza e l'obbligo di cambio
+pub fn change_password_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+    new_password_hash: &str,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.find(user_id))
+        .set((
+            password.eq(new_password_hash),
+            password_changed_at.eq(diesel::dsl::now),
+            must_change_password.eq(false),
+        ))
+        .get_result(conn)
+}
+
+// * Cerca gli utenti per username o email (sottostringa, senza distinzione tra maiuscole e
+// * minuscole) e restituisce la pagina richiesta insieme al numero totale di risultati
+pub fn search_users(
+    pool: &PgPool,
+    query: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<User>, i64), diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    let filtered = || {
+        let mut statement = users.into_boxed();
+        if let Some(query) = query.filter(|q| !q.is_empty()) {
+            let pattern = format!("%{}%", escape_like(query));
+            statement = statement.filter(username.ilike(pattern.clone()).or(email.ilike(pattern)));
+        }
+        statement
+    };
+    let total = filtered().count().get_result::<i64>(&mut conn)?;
+    let page = filtered()
+        .order((created_at.desc(), id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .load::<User>(&mut conn)?;
+    Ok((page, total))
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// * Aggiorna lo stato dell'account, con il motivo e l'eventuale scadenza;
+// * un account cancellato che torna in un altro stato non viene più eliminato
+pub fn set_status_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+    new_status: AccountStatus,
+    reason: Option<&str>,
+    expires_at: Option<chrono::NaiveDateTime>,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.find(user_id))
+        .set((
+            status.eq(new_status.as_str()),
+            status_reason.eq(reason),
+            status_expires_at.eq(expires_at),
+            purge_after.eq(None::<chrono::NaiveDateTime>),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+// * Segna l'account come cancellato; verrà eliminato definitivamente dopo `erase_at`
+pub fn schedule_deletion_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+    reason: &str,
+    erase_at: chrono::NaiveDateTime,
+) -> Result<User, diesel::result::Error> {
+    diesel::update(users.find(user_id))
+        .set((
+            status.eq(AccountStatus::Deleted.as_str()),
+            status_reason.eq(reason),
+            status_expires_at.eq(None::<chrono::NaiveDateTime>),
+            purge_after.eq(erase_at),
+            updated_at.eq(diesel::dsl::now),
+        ))
+        .get_result(conn)
+}
+
+// * Id degli account cancellati il cui periodo di ripristino è terminato
+pub fn due_for_purge(pool: &PgPool, limit: i64) -> Result<Vec<uuid::Uuid>, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    users
+        .filter(status.eq(AccountStatus::Deleted.as_str()))
+        .filter(purge_after.le(diesel::dsl::now))
+        .order(purge_after.asc())
+        .limit(limit)
+        .select(id)
+        .load(&mut conn)
+}
+
+// * Blocca l'utente se deve ancora essere eliminato (potrebbe essere stato ripristinato nel frattempo)
+pub fn lock_due_for_purge_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> Result<Option<User>, diesel::result::Error> {
+    users
+        .find(user_id)
+        .filter(status.eq(AccountStatus::Deleted.as_str()))
+        .filter(purge_after.le(diesel::dsl::now))
+        .for_update()
+        .first::<User>(conn)
+        .optional()
+}
+
+// * Elimina definitivamente l'utente; sessioni, ruoli e cronologia password seguono in cascata
+pub fn delete_user_with_connection(
+    conn: &mut PgConnection,
+    user_id: uuid::Uuid,
+) -> Result<usize, diesel::result::Error> {
+    diesel::delete(users.find(user_id)).execute(conn)
+}
+
+// * Obbliga l'utente a cambiare password al prossimo login
+pub fn force_password_change(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(users.find(user_id))
+        .set(must_change_password.eq(true))
+        .get_result(&mut conn)
+}
+
+// * Conta un login fallito e restituisce il numero di tentativi falliti consecutivi
+pub fn record_failed_login(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+) -> Result<i32, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(users.find(user_id))
+        .set(failed_login_attempts.eq(failed_login_attempts + 1))
+        .returning(failed_login_attempts)
+        .get_result(&mut conn)
+}
+
+// * Blocca i login fino a `until` e azzera il contatore dei tentativi
+pub fn lock_until(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+    until: chrono::NaiveDateTime,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(users.find(user_id))
+        .set((locked_until.eq(until), failed_login_attempts.eq(0)))
+        .get_result(&mut conn)
+}
+
+// * Azzera i tentativi falliti e rimuove l'eventuale blocco (login riuscito o sblocco manuale)
+pub fn reset_failed_logins(
+    pool: &PgPool,
+    user_id: uuid::Uuid,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    diesel::update(users.find(user_id))
+        .set((
+            failed_login_attempts.eq(0),
+            locked_until.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .get_result(&mut conn)
+}
@@ -1,5 +1,259 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_events (id) {
+        id -> Uuid,
+        occurred_at -> Timestamptz,
+        #[max_length = 64]
+        action -> Varchar,
+        #[max_length = 16]
+        outcome -> Varchar,
+        actor_id -> Nullable<Uuid>,
+        target_user_id -> Nullable<Uuid>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        user_agent -> Nullable<Text>,
+        #[max_length = 64]
+        request_id -> Nullable<Varchar>,
+        details -> Jsonb,
+        impersonator_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    data_exports (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 16]
+        status -> Varchar,
+        archive -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    external_login_states (state_hash) {
+        #[max_length = 64]
+        state_hash -> Varchar,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 128]
+        code_verifier -> Varchar,
+        #[max_length = 64]
+        nonce -> Varchar,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        link_user_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    memberships (organization_id, user_id) {
+        organization_id -> Uuid,
+        user_id -> Uuid,
+        roles -> Array<Text>,
+        created_at -> Timestamptz,
+        accepted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    oauth_authorization_codes (code_hash) {
+        #[max_length = 64]
+        code_hash -> Varchar,
+        client_id -> Uuid,
+        user_id -> Uuid,
+        redirect_uri -> Text,
+        scope -> Text,
+        #[max_length = 128]
+        code_challenge -> Varchar,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        consumed_at -> Nullable<Timestamptz>,
+        session_id -> Nullable<Uuid>,
+        #[max_length = 255]
+        nonce -> Nullable<Varchar>,
+        auth_time -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    oauth_clients (id) {
+        id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 64]
+        client_secret_hash -> Nullable<Varchar>,
+        redirect_uris -> Array<Text>,
+        allowed_scopes -> Array<Text>,
+        first_party -> Bool,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        #[max_length = 255]
+        audience -> Nullable<Varchar>,
+        access_token_ttl_secs -> Nullable<Int4>,
+        refresh_token_ttl_secs -> Nullable<Int4>,
+        idle_timeout_secs -> Nullable<Int4>,
+        token_exchange_audiences -> Array<Text>,
+    }
+}
+
+diesel::table! {
+    oauth_consents (user_id, client_id) {
+        user_id -> Uuid,
+        client_id -> Uuid,
+        scopes -> Array<Text>,
+        granted_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    oauth_device_codes (device_code_hash) {
+        #[max_length = 64]
+        device_code_hash -> Varchar,
+        #[max_length = 16]
+        user_code -> Varchar,
+        client_id -> Uuid,
+        scope -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        user_id -> Nullable<Uuid>,
+        auth_time -> Nullable<Timestamptz>,
+        interval_secs -> Int4,
+        last_polled_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    organizations (id) {
+        id -> Uuid,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 64]
+        slug -> Varchar,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    outbox_events (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        event_type -> Varchar,
+        aggregate_id -> Uuid,
+        payload -> Jsonb,
+        created_at -> Timestamptz,
+        published_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    password_history (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 255]
+        password_hash -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    permissions (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        name -> Varchar,
+        description -> Text,
+    }
+}
+
+diesel::table! {
+    personal_access_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 32]
+        token_prefix -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        scopes -> Array<Text>,
+        created_at -> Timestamptz,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    role_permissions (role_id, permission_id) {
+        role_id -> Uuid,
+        permission_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Uuid,
+        #[max_length = 32]
+        name -> Varchar,
+        description -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        refresh_token_hash -> Varchar,
+        #[max_length = 64]
+        previous_refresh_token_hash -> Nullable<Varchar>,
+        #[max_length = 255]
+        device -> Nullable<Varchar>,
+        user_agent -> Nullable<Text>,
+        #[max_length = 45]
+        ip_address -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        oauth_client_id -> Nullable<Uuid>,
+        scope -> Nullable<Text>,
+        auth_time -> Nullable<Timestamptz>,
+        organization_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    user_identities (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        last_login_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    user_roles (user_id, role_id) {
+        user_id -> Uuid,
+        role_id -> Uuid,
+        assigned_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Uuid,
@@ -8,8 +262,62 @@ diesel::table! {
         #[max_length = 255]
         email -> Varchar,
         #[max_length = 255]
-        password -> Varchar,
+        password -> Nullable<Varchar>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        password_changed_at -> Timestamptz,
+        must_change_password -> Bool,
+        #[max_length = 32]
+        status -> Varchar,
+        failed_login_attempts -> Int4,
+        locked_until -> Nullable<Timestamptz>,
+        status_reason -> Nullable<Text>,
+        status_expires_at -> Nullable<Timestamptz>,
+        purge_after -> Nullable<Timestamptz>,
     }
 }
+
+diesel::joinable!(data_exports -> users (user_id));
+diesel::joinable!(external_login_states -> users (link_user_id));
+diesel::joinable!(memberships -> organizations (organization_id));
+diesel::joinable!(memberships -> users (user_id));
+diesel::joinable!(oauth_authorization_codes -> oauth_clients (client_id));
+diesel::joinable!(oauth_authorization_codes -> users (user_id));
+diesel::joinable!(oauth_clients -> users (created_by));
+diesel::joinable!(oauth_consents -> oauth_clients (client_id));
+diesel::joinable!(oauth_consents -> users (user_id));
+diesel::joinable!(oauth_device_codes -> oauth_clients (client_id));
+diesel::joinable!(oauth_device_codes -> users (user_id));
+diesel::joinable!(organizations -> users (created_by));
+diesel::joinable!(password_history -> users (user_id));
+diesel::joinable!(personal_access_tokens -> users (user_id));
+diesel::joinable!(role_permissions -> permissions (permission_id));
+diesel::joinable!(role_permissions -> roles (role_id));
+diesel::joinable!(sessions -> oauth_clients (oauth_client_id));
+diesel::joinable!(sessions -> organizations (organization_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(user_identities -> users (user_id));
+diesel::joinable!(user_roles -> roles (role_id));
+diesel::joinable!(user_roles -> users (user_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    audit_events,
+    data_exports,
+    external_login_states,
+    memberships,
+    oauth_authorization_codes,
+    oauth_clients,
+    oauth_consents,
+    oauth_device_codes,
+    organizations,
+    outbox_events,
+    password_history,
+    permissions,
+    personal_access_tokens,
+    role_permissions,
+    roles,
+    sessions,
+    user_identities,
+    user_roles,
+    users,
+);
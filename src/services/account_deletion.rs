@@ -0,0 +1,145 @@
+use std::time::Duration as StdDuration;
+
+use actix_web::{rt, web};
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use serde_json::json;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    extractors::client_info::ClientInfo,
+    models::{audit_event::AuditOutcome, user::User},
+    repositories::{
+        audit_events_repo, establish_connection,
+        outbox_repo::{self, NewOutboxEvent},
+        sessions_repo, users_repo, PgPool,
+    },
+    services::audit::{self, AuditEvent},
+    DbPool,
+};
+
+// * Account eliminati definitivamente a ogni passaggio del job
+const PURGE_BATCH_SIZE: i64 = 100;
+
+pub const DELETION_REASON: &str = "Deleted at the user's request";
+
+// * Segna l'account come cancellato e chiude tutte le sessioni. I dati restano fino alla
+// * fine del periodo di ripristino; gli altri servizi ricevono "user.deletion_scheduled"
+pub fn schedule_with_connection(
+    conn: &mut PgConnection,
+    user: &User,
+    client: &ClientInfo,
+    grace_days: u32,
+) -> Result<User, diesel::result::Error> {
+    let purge_after = (Utc::now() + Duration::days(grace_days as i64)).naive_utc();
+    let deleted =
+        users_repo::schedule_deletion_with_connection(conn, user.id, DELETION_REASON, purge_after)?;
+    let revoked = sessions_repo::revoke_all_except_with_connection(conn, user.id, None)?;
+    audit::record_with_connection(
+        conn,
+        AuditEvent {
+            action: "user.deletion_requested",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(user.id),
+            target_user_id: Some(user.id),
+            client: Some(client),
+            details: json!({ "purge_after": purge_after, "revoked_sessions": revoked }),
+        },
+    )?;
+    outbox_repo::add_with_connection(
+        conn,
+        NewOutboxEvent {
+            event_type: "user.deletion_scheduled".into(),
+            aggregate_id: user.id,
+            payload: json!({ "user_id": user.id, "purge_after": purge_after }),
+        },
+    )?;
+    Ok(deleted)
+}
+
+// * Annulla la cancellazione di un account ancora nel periodo di ripristino
+pub fn restored_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+) -> Result<(), diesel::result::Error> {
+    outbox_repo::add_with_connection(
+        conn,
+        NewOutboxEvent {
+            event_type: "user.restored".into(),
+            aggregate_id: user_id,
+            payload: json!({ "user_id": user_id }),
+        },
+    )
+    .map(|_| ())
+}
+
+// * Elimina definitivamente un account il cui periodo di ripristino è terminato: il log di
+// * audit viene anonimizzato, l'utente cancellato e gli altri servizi ricevono "user.deleted"
+fn purge_user(pool: &PgPool, user_id: Uuid) -> Result<bool, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    conn.transaction(|conn| {
+        let Some(user) = users_repo::lock_due_for_purge_with_connection(conn, user_id)? else {
+            return Ok(false);
+        };
+        let anonymized =
+            audit_events_repo::anonymize_user_with_connection(conn, user.id, &user.username)?;
+        users_repo::delete_user_with_connection(conn, user.id)?;
+        outbox_repo::add_with_connection(
+            conn,
+            NewOutboxEvent {
+                event_type: "user.deleted".into(),
+                aggregate_id: user.id,
+                payload: json!({ "user_id": user.id }),
+            },
+        )?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "user.purged",
+                outcome: AuditOutcome::Success,
+                actor_id: None,
+                target_user_id: None,
+                client: None,
+                details: json!({ "anonymized_events": anonymized }),
+            },
+        )?;
+        Ok(true)
+    })
+}
+
+// * Elimina tutti gli account scaduti e restituisce quanti ne sono stati cancellati
+pub fn purge_due_accounts(pool: &PgPool) -> Result<usize, diesel::result::Error> {
+    let mut purged = 0;
+    loop {
+        let due = users_repo::due_for_purge(pool, PURGE_BATCH_SIZE)?;
+        let batch = due.len() as i64;
+        let mut progressed = false;
+        for user_id in due {
+            if purge_user(pool, user_id)? {
+                purged += 1;
+                progressed = true;
+            }
+        }
+        if batch < PURGE_BATCH_SIZE || !progressed {
+            return Ok(purged);
+        }
+    }
+}
+
+// * Avvia il job periodico che elimina gli account al termine del periodo di ripristino
+pub fn spawn_purge_job(pool: DbPool, interval_secs: u64) {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(StdDuration::from_secs(interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            match web::block(move || purge_due_accounts(&pool)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(purged)) => info!("Purged {} deleted accounts", purged),
+                Ok(Err(e)) => error!("Account purge failed: {:?}", e),
+                Err(e) => error!("Account purge failed: {:?}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,149 @@
+use actix_web::web;
+use diesel::PgConnection;
+use serde_json::Value;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    extractors::client_info::ClientInfo,
+    models::audit_event::{AuditLogEntry, AuditOutcome, NewAuditLogEntry},
+    repositories::{audit_events_repo, establish_connection},
+    DbPool,
+};
+
+/// An action worth keeping a trace of: who did what to whom, from where.
+pub struct AuditEvent<'a> {
+    pub action: &'a str,
+    pub outcome: AuditOutcome,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub client: Option<&'a ClientInfo>,
+    pub details: Value,
+}
+
+impl AuditEvent<'_> {
+    fn into_entry(self) -> NewAuditLogEntry {
+        NewAuditLogEntry {
+            action: self.action.to_string(),
+            outcome: self.outcome.as_str().to_string(),
+            actor_id: self.actor_id,
+            target_user_id: self.target_user_id,
+            ip_address: self
+                .client
+                .and_then(|c| c.ip_address)
+                .map(|ip| ip.to_string()),
+            user_agent: self.client.and_then(|c| c.user_agent.clone()),
+            request_id: self.client.and_then(|c| c.request_id.clone()),
+            details: self.details,
+            impersonator_id: self.client.and_then(|c| c.impersonator_id),
+        }
+    }
+}
+
+// * Salva l'evento usando una connessione esistente, così resta nella stessa transazione
+// * dell'operazione che descrive
+pub fn record_with_connection(
+    conn: &mut PgConnection,
+    event: AuditEvent<'_>,
+) -> Result<(), diesel::result::Error> {
+    let entry = event.into_entry();
+    trace(&entry);
+    audit_events_repo::insert_with_connection(conn, entry).map(|_| ())
+}
+
+// * Salva l'evento fuori da transazioni; un errore viene registrato nei log ma non
+// * fa fallire la richiesta
+pub async fn record(pool: &web::Data<DbPool>, event: AuditEvent<'_>) {
+    let entry = event.into_entry();
+    trace(&entry);
+    let pool = pool.clone();
+    let action = entry.action.clone();
+    let result = web::block(move || -> Result<(), diesel::result::Error> {
+        let mut conn = establish_connection(&pool)?;
+        audit_events_repo::insert_with_connection(&mut conn, entry).map(|_| ())
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Failed to store audit event {}: {:?}", action, e),
+        Err(e) => error!("Failed to store audit event {}: {:?}", action, e),
+    }
+}
+
+// * Copia dell'evento nei log applicativi (target tracing "audit")
+fn trace(entry: &NewAuditLogEntry) {
+    info!(
+        target: "audit",
+        action = %entry.action,
+        outcome = %entry.outcome,
+        actor_id = ?entry.actor_id,
+        target_user_id = ?entry.target_user_id,
+        ip_address = ?entry.ip_address,
+        request_id = ?entry.request_id,
+        impersonator_id = ?entry.impersonator_id,
+        details = %entry.details,
+        "audit event"
+    );
+}
+
+const CSV_COLUMNS: [&str; 11] = [
+    "id",
+    "occurred_at",
+    "action",
+    "outcome",
+    "actor_id",
+    "target_user_id",
+    "ip_address",
+    "user_agent",
+    "request_id",
+    "details",
+    "impersonator_id",
+];
+
+// * Esporta gli eventi in CSV (RFC 4180). I valori che un foglio di calcolo
+// * interpreterebbe come formule vengono preceduti da un apice
+pub fn to_csv(entries: &[AuditLogEntry]) -> String {
+    let mut csv = CSV_COLUMNS.join(",");
+    csv.push_str("\r\n");
+    for entry in entries {
+        let fields = [
+            entry.id.to_string(),
+            entry
+                .occurred_at
+                .format("%Y-%m-%dT%H:%M:%S%.6f")
+                .to_string(),
+            entry.action.clone(),
+            entry.outcome.clone(),
+            entry.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            entry
+                .target_user_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            entry.ip_address.clone().unwrap_or_default(),
+            entry.user_agent.clone().unwrap_or_default(),
+            entry.request_id.clone().unwrap_or_default(),
+            entry.details.to_string(),
+            entry
+                .impersonator_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use sha1::{Digest, Sha1};
+
+const MAGIC: &[u8; 8] = b"KAPPBF01";
+// * Intestazione: magic, numero di bit (u64) e numero di hash (u32), little endian
+const HEADER_LEN: u64 = 8 + 8 + 4;
+
+/// Bloom filter over the SHA-1 digests of breached passwords, built offline
+/// from a HIBP-format dump (`SHA1HEX:COUNT` per line).
+///
+/// False positives are possible (a safe password may be rejected), false
+/// negatives are not.
+pub struct BreachedPasswordFilter {
+    bits: Vec<u8>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BreachedPasswordFilter {
+    // * Dimensiona il filtro per il numero di elementi atteso e il tasso di falsi positivi
+    pub fn with_capacity(expected_items: u64, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(n * false_positive_rate.ln()) / (ln2 * ln2))
+            .ceil()
+            .max(8.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(8) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    pub fn insert_digest(&mut self, digest: &[u8; 20]) {
+        for index in bit_indexes(digest, self.num_bits, self.num_hashes) {
+            self.bits[(index / 8) as usize] |= 1 << (index % 8);
+        }
+    }
+
+    pub fn contains_digest(&self, digest: &[u8; 20]) -> bool {
+        bit_indexes(digest, self.num_bits, self.num_hashes)
+            .all(|index| self.bits[(index / 8) as usize] & (1 << (index % 8)) != 0)
+    }
+
+    pub fn contains_password(&self, password: &str) -> bool {
+        self.contains_digest(&Sha1::digest(password.as_bytes()).into())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a breached password filter file",
+            ));
+        }
+        let mut num_bits = [0u8; 8];
+        reader.read_exact(&mut num_bits)?;
+        let mut num_hashes = [0u8; 4];
+        reader.read_exact(&mut num_hashes)?;
+        let num_bits = u64::from_le_bytes(num_bits);
+        let num_hashes = u32::from_le_bytes(num_hashes);
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "empty breached password filter",
+            ));
+        }
+        // * La dimensione dichiarata nell'intestazione deve corrispondere al file, prima di allocare
+        let num_bytes = num_bits.div_ceil(8);
+        if file_len.checked_sub(HEADER_LEN) != Some(num_bytes) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "breached password filter size does not match its header",
+            ));
+        }
+        let num_bytes = usize::try_from(num_bytes).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "breached password filter too large for this platform",
+            )
+        })?;
+        let mut bits = vec![0u8; num_bytes];
+        reader.read_exact(&mut bits)?;
+        Ok(Self {
+            bits,
+            num_bits,
+            num_hashes,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.num_bits.to_le_bytes())?;
+        writer.write_all(&self.num_hashes.to_le_bytes())?;
+        writer.write_all(&self.bits)?;
+        writer.flush()
+    }
+}
+
+// * Doppio hashing (Kirsch-Mitzenmacher) a partire dal digest SHA-1
+fn bit_indexes(digest: &[u8; 20], num_bits: u64, num_hashes: u32) -> impl Iterator<Item = u64> {
+    let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+    (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+}
+
+// * Decodifica una riga HIBP (`SHA1HEX:COUNT` oppure solo `SHA1HEX`) nel digest binario
+pub fn parse_hibp_line(line: &str) -> Option<[u8; 20]> {
+    let hex = line.split(':').next()?.trim();
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut digest = [0u8; 20];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+/// Shared handle to the optional filter; checks pass when no filter is loaded.
+#[derive(Clone, Default)]
+pub struct BreachedPasswords {
+    filter: Option<Arc<BreachedPasswordFilter>>,
+}
+
+impl BreachedPasswords {
+    pub fn new(filter: BreachedPasswordFilter) -> Self {
+        Self {
+            filter: Some(Arc::new(filter)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    pub fn is_breached(&self, password: &str) -> bool {
+        self.filter
+            .as_ref()
+            .is_some_and(|filter| filter.contains_password(password))
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use actix_web::{rt, web};
+use chrono::Utc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        data_export::{ExportedAuditEvent, PersonalDataArchive},
+        oauth::ConsentResponse,
+    },
+    repositories::{
+        audit_events_repo, data_exports_repo, establish_connection, oauth_clients_repo,
+        oauth_consents_repo, organizations_repo, password_history_repo,
+        personal_access_tokens_repo, roles_repo, sessions_repo, user_identities_repo, users_repo,
+        PgPool,
+    },
+    DbPool,
+};
+
+// * Raccoglie tutti i dati dell'utente in un'unica transazione di sola lettura, così che
+// * l'archivio sia coerente anche se nel frattempo l'utente continua a usare il servizio
+pub fn build_archive(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<PersonalDataArchive, diesel::result::Error> {
+    let mut conn = establish_connection(pool)?;
+    conn.build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run(|conn| {
+            let user = users_repo::get_user_by_id_with_connection(conn, user_id)?;
+            let roles = roles_repo::role_names_for_user_with_connection(conn, user_id)?;
+            let memberships = organizations_repo::list_for_user_with_connection(conn, user_id)?;
+            let sessions = sessions_repo::list_all_for_user_with_connection(conn, user_id)?;
+            let identities = user_identities_repo::list_for_user_with_connection(conn, user_id)?;
+            let tokens =
+                personal_access_tokens_repo::list_all_for_user_with_connection(conn, user_id)?;
+            let password_changes =
+                password_history_repo::change_dates_with_connection(conn, user_id)?;
+            let consents = oauth_consents_repo::list_for_user_with_connection(conn, user_id)?;
+            let client_ids: Vec<Uuid> = consents.iter().map(|c| c.client_id).collect();
+            let client_names: HashMap<Uuid, String> =
+                oauth_clients_repo::names_with_connection(conn, &client_ids)?
+                    .into_iter()
+                    .collect();
+            let audit_events = audit_events_repo::for_user_with_connection(conn, user_id)?;
+            Ok(PersonalDataArchive {
+                generated_at: Utc::now().naive_utc(),
+                profile: user.into(),
+                roles,
+                memberships: memberships.into_iter().map(Into::into).collect(),
+                sessions: sessions.into_iter().map(Into::into).collect(),
+                identities: identities.into_iter().map(Into::into).collect(),
+                personal_access_tokens: tokens.into_iter().map(Into::into).collect(),
+                password_changes,
+                consents: consents
+                    .into_iter()
+                    .map(|consent| {
+                        let name = client_names
+                            .get(&consent.client_id)
+                            .cloned()
+                            .unwrap_or_default();
+                        ConsentResponse::new(consent, name)
+                    })
+                    .collect(),
+                audit_events: audit_events
+                    .into_iter()
+                    .map(|entry| ExportedAuditEvent::from_entry(entry, user_id))
+                    .collect(),
+            })
+        })
+}
+
+// * Genera l'archivio in background e lo salva nell'esportazione indicata
+pub fn spawn_generation(pool: DbPool, export_id: Uuid, user_id: Uuid) {
+    rt::spawn(async move {
+        let result = web::block(move || -> Result<(), diesel::result::Error> {
+            let generated = build_archive(&pool, user_id).and_then(|archive| {
+                serde_json::to_value(archive)
+                    .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+            });
+            match generated {
+                Ok(archive) => data_exports_repo::complete(&pool, export_id, archive).map(|_| ()),
+                Err(e) => {
+                    error!("Data export {} failed: {:?}", export_id, e);
+                    data_exports_repo::fail(&pool, export_id).map(|_| ())
+                }
+            }
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => info!("Data export {} generated", export_id),
+            Ok(Err(e)) => error!("Failed to store data export {}: {:?}", export_id, e),
+            Err(e) => error!("Failed to store data export {}: {:?}", export_id, e),
+        }
+    });
+}
+
+// * Riprende le esportazioni rimaste in attesa: la generazione avviata prima di un riavvio
+// * non arriva mai a completarle
+pub fn resume_pending(pool: DbPool) {
+    rt::spawn(async move {
+        let pending = web::block({
+            let pool = pool.clone();
+            move || data_exports_repo::list_pending(&pool)
+        })
+        .await;
+        match pending {
+            Ok(Ok(exports)) => {
+                for export in exports {
+                    info!("Resuming data export {}", export.id);
+                    spawn_generation(pool.clone(), export.id, export.user_id);
+                }
+            }
+            Ok(Err(e)) => error!("Failed to load pending data exports: {:?}", e),
+            Err(e) => error!("Failed to load pending data exports: {:?}", e),
+        }
+    });
+}
@@ -0,0 +1,271 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::{rngs::OsRng, Rng};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::OAuthError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome,
+        oauth::{DeviceCode, DeviceCodeStatus, NewDeviceCode, OAuthClient},
+    },
+    repositories::{establish_connection, oauth_device_codes_repo, roles_repo, users_repo, PgPool},
+    services::{
+        audit::{self, AuditEvent},
+        oauth::{self, TokenGrant},
+        session::{self, SessionGrant},
+    },
+};
+
+pub const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// * Alfabeto dei codici utente: solo consonanti maiuscole, niente vocali (parole) né
+// * caratteri che si confondono (RFC 8628, sezione 6.1). 20^8 combinazioni
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+// * Incremento dell'intervallo di polling a ogni slow_down (RFC 8628, sezione 3.5)
+const SLOW_DOWN_STEP_SECS: i32 = 5;
+
+pub fn generate_user_code() -> String {
+    (0..USER_CODE_LENGTH)
+        .map(|_| USER_CODE_ALPHABET[OsRng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// * Il codice digitato dall'utente può contenere minuscole, spazi e trattini
+pub fn normalize_user_code(input: &str) -> String {
+    input
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// * Codice mostrato all'utente, diviso in due gruppi per leggibilità: WDJB-MJHT
+pub fn format_user_code(code: &str) -> String {
+    if code.len() == USER_CODE_LENGTH {
+        format!("{}-{}", &code[..4], &code[4..])
+    } else {
+        code.to_string()
+    }
+}
+
+/// A started device authorization, with the device code that is only
+/// available right after it is created.
+pub struct StartedDeviceAuthorization {
+    pub device_code: String,
+    pub authorization: DeviceCode,
+}
+
+// * Avvia il flusso per il client: device code segreto per il polling e user code da digitare
+pub fn start(
+    pool: &PgPool,
+    client: &OAuthClient,
+    scope: Option<&str>,
+    app_config: &AppConfig,
+) -> Result<StartedDeviceAuthorization, OAuthError> {
+    let scope = oauth::requested_scopes(scope, client)
+        .map_err(OAuthError::invalid_scope)?
+        .join(" ");
+    let device_code = oauth::generate_token();
+    // * Un user code già in uso è improbabile ma possibile: si riprova con uno nuovo
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = oauth_device_codes_repo::create(
+            pool,
+            NewDeviceCode {
+                device_code_hash: oauth::hash_token(&device_code),
+                user_code: generate_user_code(),
+                client_id: client.id,
+                scope: scope.clone(),
+                interval_secs: app_config.device_code_interval_secs as i32,
+                expires_at: (Utc::now()
+                    + Duration::seconds(app_config.device_code_ttl_secs as i64))
+                .naive_utc(),
+            },
+        );
+        match result {
+            Ok(authorization) => {
+                return Ok(StartedDeviceAuthorization {
+                    device_code,
+                    authorization,
+                })
+            }
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) if attempts < 3 => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// * L'utente approva o rifiuta il dispositivo. Approvare vale come consenso agli scope richiesti
+pub fn decide(
+    pool: &PgPool,
+    user_code: &str,
+    approve: bool,
+    user_id: Uuid,
+    auth_time: NaiveDateTime,
+    client_info: &ClientInfo,
+) -> Result<Option<DeviceCode>, diesel::result::Error> {
+    let decision = if approve {
+        DeviceCodeStatus::Approved
+    } else {
+        DeviceCodeStatus::Denied
+    };
+    let mut conn = establish_connection(pool)?;
+    conn.transaction(|conn| {
+        let Some(authorization) = oauth_device_codes_repo::decide_with_connection(
+            conn,
+            &normalize_user_code(user_code),
+            decision,
+            user_id,
+            auth_time,
+        )?
+        else {
+            return Ok(None);
+        };
+        if approve {
+            let scopes: Vec<String> = authorization
+                .scope
+                .split_whitespace()
+                .map(str::to_string)
+                .collect();
+            oauth::grant_consent_with_connection(conn, user_id, authorization.client_id, &scopes)?;
+        }
+        let (action, outcome) = if approve {
+            ("oauth.device.approved", AuditOutcome::Success)
+        } else {
+            ("oauth.device.denied", AuditOutcome::Failure)
+        };
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action,
+                outcome,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(client_info),
+                details: json!({ "client_id": authorization.client_id, "scope": authorization.scope }),
+            },
+        )?;
+        Ok(Some(authorization))
+    })
+}
+
+// * Polling del dispositivo all'endpoint /oauth/token. Ogni risposta aggiorna l'istante
+// * dell'ultimo polling; chi interroga prima dell'intervallo riceve slow_down
+pub fn exchange_device_code(
+    pool: &PgPool,
+    client: &OAuthClient,
+    device_code: Option<&str>,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<TokenGrant, OAuthError> {
+    let device_code =
+        device_code.ok_or_else(|| OAuthError::invalid_request("Missing device_code"))?;
+    let device_code_hash = oauth::hash_token(device_code);
+
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let Some(authorization) =
+            oauth_device_codes_repo::lock_with_connection(conn, &device_code_hash)?
+                .filter(|authorization| authorization.client_id == client.id)
+        else {
+            return Ok(Err(OAuthError::invalid_grant("Invalid device_code")));
+        };
+        let now = Utc::now().naive_utc();
+        if authorization.expires_at <= now {
+            return Ok(Err(OAuthError::expired_token(
+                "The device code has expired, start the flow again",
+            )));
+        }
+
+        let too_early = authorization
+            .last_polled_at
+            .is_some_and(|last| last + Duration::seconds(authorization.interval_secs as i64) > now);
+        let interval = if too_early {
+            authorization.interval_secs + SLOW_DOWN_STEP_SECS
+        } else {
+            authorization.interval_secs
+        };
+        oauth_device_codes_repo::record_poll_with_connection(conn, &device_code_hash, interval)?;
+        if too_early {
+            return Ok(Err(OAuthError::slow_down(format!(
+                "Polling too fast, wait {} seconds between requests",
+                interval
+            ))));
+        }
+
+        match authorization.status() {
+            DeviceCodeStatus::Pending => {
+                return Ok(Err(OAuthError::authorization_pending(
+                    "The user has not yet confirmed the code",
+                )))
+            }
+            DeviceCodeStatus::Denied => {
+                return Ok(Err(OAuthError::access_denied(
+                    "The user denied the request",
+                )))
+            }
+            DeviceCodeStatus::Consumed => {
+                return Ok(Err(OAuthError::invalid_grant(
+                    "The device code was already used",
+                )))
+            }
+            DeviceCodeStatus::Approved => {}
+        }
+        let (Some(user_id), Some(auth_time)) = (authorization.user_id, authorization.auth_time)
+        else {
+            return Ok(Err(OAuthError::invalid_grant("Invalid device_code")));
+        };
+        let user = users_repo::get_user_by_id_with_connection(conn, user_id)?;
+        if user.ensure_active().is_err() {
+            return Ok(Err(OAuthError::invalid_grant("The account is not active")));
+        }
+
+        let issued = session::start_client_session_with_connection(
+            conn,
+            user.id,
+            client_info,
+            app_config,
+            Some(SessionGrant {
+                client,
+                scope: &authorization.scope,
+                auth_time,
+            }),
+        )?;
+        oauth_device_codes_repo::consume_with_connection(conn, &device_code_hash)?;
+        let grants = roles_repo::grants_for_user_with_connection(conn, user.id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "oauth.token.issued",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "client_id": client.id,
+                    "grant_type": GRANT_TYPE,
+                    "scope": authorization.scope,
+                    "session_id": issued.session.id,
+                }),
+            },
+        )?;
+        Ok(Ok(TokenGrant {
+            issued,
+            user,
+            grants,
+            scope: authorization.scope,
+            nonce: None,
+            auth_time,
+        }))
+    })?
+}
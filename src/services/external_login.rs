@@ -0,0 +1,853 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, Utc};
+use config::ConfigError;
+use diesel::prelude::*;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::{rngs::OsRng, Rng};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::{AppConfig, IdentityProviderConfig},
+    errors::ServiceError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome,
+        register::validate_email_logic,
+        role::{RoleGrants, STUDENT_ROLE},
+        user::{NewUser, User},
+        user_identity::{ExternalLoginCallback, ExternalLoginState, NewExternalLoginState},
+    },
+    repositories::{
+        establish_connection, external_login_states_repo, roles_repo,
+        user_identities_repo::{self, NewUserIdentity, UserIdentity},
+        users_repo, PgPool,
+    },
+    services::{
+        audit::{self, AuditEvent},
+        http_client::{HttpClient, HttpError},
+        oauth,
+        session::{self, IssuedSession},
+    },
+};
+
+/// Endpoints of an upstream provider, taken from the configuration or from
+/// its OpenID Connect discovery document.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: Option<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+}
+
+/// An upstream identity provider with its client secret and the documents
+/// fetched from it (discovery and signing keys), cached after first use.
+pub struct IdentityProvider {
+    pub config: IdentityProviderConfig,
+    client_secret: Option<String>,
+    metadata: Mutex<Option<Arc<ProviderMetadata>>>,
+    jwks: Mutex<Option<Arc<JwkSet>>>,
+}
+
+/// The configured identity providers, shared by all workers.
+#[derive(Clone, Default)]
+pub struct IdentityProviders {
+    providers: Arc<Vec<IdentityProvider>>,
+    http: HttpClient,
+}
+
+impl IdentityProviders {
+    pub fn from_config(config: &AppConfig) -> Result<Self, ConfigError> {
+        let mut ids = HashSet::new();
+        let mut providers = Vec::new();
+        for provider in &config.identity_providers {
+            validate_provider(provider)?;
+            if !ids.insert(provider.id.clone()) {
+                return Err(ConfigError::Message(format!(
+                    "Duplicate identity provider {}",
+                    provider.id
+                )));
+            }
+            providers.push(IdentityProvider {
+                client_secret: load_client_secret(provider)?,
+                config: provider.clone(),
+                metadata: Mutex::new(None),
+                jwks: Mutex::new(None),
+            });
+        }
+        let http = HttpClient::new(config.identity_provider_proxy.as_deref())
+            .map_err(|e| ConfigError::Message(e.to_string()))?;
+        Ok(Self {
+            providers: Arc::new(providers),
+            http,
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IdentityProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.config.id == id)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &IdentityProvider> {
+        self.providers.iter()
+    }
+
+    pub fn http(&self) -> &HttpClient {
+        &self.http
+    }
+}
+
+// * L'id compare negli URL e nel database: solo lettere minuscole, cifre, '-' e '_'
+fn validate_provider(provider: &IdentityProviderConfig) -> Result<(), ConfigError> {
+    let valid_id = !provider.id.is_empty()
+        && provider.id.len() <= 64
+        && provider
+            .id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'-' | b'_'));
+    if !valid_id {
+        return Err(ConfigError::Message(format!(
+            "Identity provider id {:?} must be 1-64 lowercase letters, digits, '-' or '_'",
+            provider.id
+        )));
+    }
+    // * Senza issuer (OAuth 2.0 puro) gli endpoint vanno indicati e il profilo arriva da userinfo
+    if provider.issuer.is_none()
+        && (provider.authorization_endpoint.is_none()
+            || provider.token_endpoint.is_none()
+            || provider.userinfo_endpoint.is_none())
+    {
+        return Err(ConfigError::Message(format!(
+            "Identity provider {} needs either `issuer` or the authorization, token and userinfo endpoints",
+            provider.id
+        )));
+    }
+    Ok(())
+}
+
+fn load_client_secret(provider: &IdentityProviderConfig) -> Result<Option<String>, ConfigError> {
+    let secret = match (&provider.client_secret_file, &provider.client_secret_env) {
+        (Some(path), _) => std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::Message(format!(
+                "Failed to read the client secret of {} from {}: {}",
+                provider.id, path, e
+            ))
+        })?,
+        (None, Some(var)) => std::env::var(var).map_err(|_| {
+            ConfigError::Message(format!(
+                "Client secret env var {} of {} is not set",
+                var, provider.id
+            ))
+        })?,
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(secret.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn provider_error(
+    provider: &IdentityProviderConfig,
+    message: impl std::fmt::Display,
+) -> ServiceError {
+    ServiceError::IdentityProvider(format!("{}: {}", provider.name, message))
+}
+
+/// Tokens returned by the provider's token endpoint.
+#[derive(Deserialize, Debug)]
+struct ProviderTokens {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+impl IdentityProvider {
+    fn is_openid(&self) -> bool {
+        self.config.scopes.iter().any(|scope| scope == "openid")
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        http: &HttpClient,
+        url: &str,
+        bearer_token: Option<&str>,
+    ) -> Result<T, ServiceError> {
+        let fetch = || -> Result<T, HttpError> {
+            let response = http.get(url, bearer_token)?;
+            if !response.is_success() {
+                warn!(
+                    "{} answered {} to GET {}",
+                    self.config.id, response.status, url
+                );
+                return Err(HttpError::Status(response.status));
+            }
+            response.json()
+        };
+        fetch().map_err(|e| provider_error(&self.config, format!("{} failed: {}", url, e)))
+    }
+
+    // * Endpoint del provider: quelli configurati hanno la precedenza sul discovery, che viene
+    // * letto una sola volta
+    pub fn metadata(&self, http: &HttpClient) -> Result<Arc<ProviderMetadata>, ServiceError> {
+        if let Some(metadata) = self.metadata.lock().unwrap().clone() {
+            return Ok(metadata);
+        }
+        let config = &self.config;
+        let discovered = match &config.issuer {
+            Some(issuer)
+                if config.authorization_endpoint.is_none()
+                    || config.token_endpoint.is_none()
+                    || config.jwks_uri.is_none() =>
+            {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    issuer.trim_end_matches('/')
+                );
+                let document: ProviderMetadata = self.get_json(http, &url, None)?;
+                if document.issuer.as_deref() != Some(issuer.as_str()) {
+                    return Err(provider_error(
+                        config,
+                        "the discovery document is for another issuer",
+                    ));
+                }
+                Some(document)
+            }
+            _ => None,
+        };
+        let pick = |configured: &Option<String>, discovered: Option<&String>| {
+            configured.clone().or_else(|| discovered.cloned())
+        };
+        let missing = || provider_error(config, "missing endpoint in the configuration");
+        let metadata = Arc::new(ProviderMetadata {
+            issuer: config.issuer.clone(),
+            authorization_endpoint: pick(
+                &config.authorization_endpoint,
+                discovered.as_ref().map(|d| &d.authorization_endpoint),
+            )
+            .ok_or_else(missing)?,
+            token_endpoint: pick(
+                &config.token_endpoint,
+                discovered.as_ref().map(|d| &d.token_endpoint),
+            )
+            .ok_or_else(missing)?,
+            userinfo_endpoint: pick(
+                &config.userinfo_endpoint,
+                discovered
+                    .as_ref()
+                    .and_then(|d| d.userinfo_endpoint.as_ref()),
+            ),
+            jwks_uri: pick(
+                &config.jwks_uri,
+                discovered.as_ref().and_then(|d| d.jwks_uri.as_ref()),
+            ),
+        });
+        *self.metadata.lock().unwrap() = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    // * Chiavi di firma del provider; vengono riscaricate quando un token usa una chiave nuova
+    fn jwks(
+        &self,
+        http: &HttpClient,
+        metadata: &ProviderMetadata,
+        refresh: bool,
+    ) -> Result<Arc<JwkSet>, ServiceError> {
+        if !refresh {
+            if let Some(jwks) = self.jwks.lock().unwrap().clone() {
+                return Ok(jwks);
+            }
+        }
+        let url = metadata
+            .jwks_uri
+            .as_deref()
+            .ok_or_else(|| provider_error(&self.config, "no jwks_uri to verify ID tokens"))?;
+        let jwks: Arc<JwkSet> = Arc::new(self.get_json(http, url, None)?);
+        *self.jwks.lock().unwrap() = Some(jwks.clone());
+        Ok(jwks)
+    }
+
+    // * Verifica firma, issuer, audience, scadenza e nonce dell'ID token e ne restituisce i claims
+    pub fn verify_id_token(
+        &self,
+        http: &HttpClient,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<Map<String, Value>, ServiceError> {
+        let invalid =
+            |reason: &str| provider_error(&self.config, format!("invalid ID token: {}", reason));
+        let metadata = self.metadata(http)?;
+        let header = decode_header(id_token).map_err(|_| invalid("malformed"))?;
+        // * Gli algoritmi simmetrici userebbero il client secret come chiave: non sono accettati
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(invalid("unsupported algorithm"));
+        }
+        let find_key = |jwks: &JwkSet| match &header.kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+            None => None,
+        };
+        let jwk = match find_key(self.jwks(http, &metadata, false)?.as_ref()) {
+            Some(jwk) => jwk,
+            None => find_key(self.jwks(http, &metadata, true)?.as_ref())
+                .ok_or_else(|| invalid("unknown signing key"))?,
+        };
+        let key = DecodingKey::from_jwk(&jwk).map_err(|_| invalid("unusable signing key"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.config.client_id]);
+        match &metadata.issuer {
+            Some(issuer) => {
+                validation.set_issuer(&[issuer]);
+                validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+            }
+            None => validation.set_required_spec_claims(&["exp", "aud", "sub"]),
+        }
+        let claims = decode::<Map<String, Value>>(id_token, &key, &validation)
+            .map_err(|e| invalid(&e.to_string()))?
+            .claims;
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err(invalid("nonce mismatch"));
+        }
+        Ok(claims)
+    }
+
+    // * Scambia il codice con i token del provider e ricava il profilo dell'utente
+    pub fn fetch_profile(
+        &self,
+        http: &HttpClient,
+        code: &str,
+        login_state: &ExternalLoginState,
+        app_config: &AppConfig,
+    ) -> Result<ExternalProfile, ServiceError> {
+        let metadata = self.metadata(http)?;
+        let redirect_uri = callback_url(app_config, &self.config.id);
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login_state.code_verifier.as_str()),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let response = http
+            .post_form(&metadata.token_endpoint, &form)
+            .map_err(|e| provider_error(&self.config, e))?;
+        if !response.is_success() {
+            warn!(
+                "{} token endpoint answered {}: {}",
+                self.config.id,
+                response.status,
+                String::from_utf8_lossy(&response.body)
+            );
+            return Err(provider_error(
+                &self.config,
+                "the authorization code was rejected",
+            ));
+        }
+        let tokens: ProviderTokens = response
+            .json()
+            .map_err(|e| provider_error(&self.config, e))?;
+
+        let mut claims = match &tokens.id_token {
+            Some(id_token) => self.verify_id_token(http, id_token, &login_state.nonce)?,
+            None if self.is_openid() && metadata.userinfo_endpoint.is_none() => {
+                return Err(provider_error(&self.config, "no ID token in the response"))
+            }
+            None => Map::new(),
+        };
+        // * Senza ID token (OAuth 2.0) o senza email nell'ID token il profilo viene da userinfo
+        if let Some(userinfo_endpoint) = metadata
+            .userinfo_endpoint
+            .as_deref()
+            .filter(|_| tokens.id_token.is_none() || !claims.contains_key("email"))
+        {
+            let userinfo: Map<String, Value> =
+                self.get_json(http, userinfo_endpoint, Some(&tokens.access_token))?;
+            let subject_claim = &self.config.subject_claim;
+            if let Some(subject) = claims.get(subject_claim) {
+                if userinfo.get(subject_claim) != Some(subject) {
+                    return Err(provider_error(
+                        &self.config,
+                        "userinfo is about another account",
+                    ));
+                }
+            }
+            for (name, value) in userinfo {
+                claims.entry(name).or_insert(value);
+            }
+        }
+        ExternalProfile::from_claims(&claims, &self.config.subject_claim)
+            .ok_or_else(|| provider_error(&self.config, "the account has no identifier"))
+    }
+}
+
+/// The account at the upstream provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalProfile {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub username_hint: Option<String>,
+}
+
+impl ExternalProfile {
+    pub fn from_claims(claims: &Map<String, Value>, subject_claim: &str) -> Option<Self> {
+        // * Alcuni provider OAuth 2.0 usano identificativi numerici
+        let text = |name: &str| match claims.get(name) {
+            Some(Value::String(value)) if !value.is_empty() => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        };
+        let email = text("email");
+        let email_verified = match claims.get("email_verified") {
+            Some(Value::Bool(verified)) => *verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        let username_hint = text("preferred_username")
+            .or_else(|| text("login"))
+            .or_else(|| text("given_name"))
+            .or_else(|| text("name"))
+            .or_else(|| email.clone());
+        Some(Self {
+            subject: text(subject_claim)?,
+            email,
+            email_verified,
+            username_hint,
+        })
+    }
+}
+
+// * URL di ritorno registrato presso il provider
+pub fn callback_url(app_config: &AppConfig, provider_id: &str) -> String {
+    app_config.oidc_url(&format!("/api/auth/providers/{}/callback", provider_id))
+}
+
+// * Username locale ricavato dal profilo: 6-16 lettere, come richiesto dalla tabella users
+pub fn username_base(hint: Option<&str>) -> String {
+    let hint = hint.unwrap_or_default();
+    let local_part = hint.split('@').next().unwrap_or_default();
+    let mut base: String = local_part
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .take(12)
+        .collect();
+    while base.len() < 6 {
+        base.push_str("user");
+    }
+    base.truncate(12);
+    base
+}
+
+fn random_letters(count: usize) -> String {
+    (0..count)
+        .map(|_| (b'a' + OsRng.gen_range(0..26)) as char)
+        .collect()
+}
+
+/// A login started towards a provider: the browser is sent to
+/// `authorization_url` and `state` is kept in a cookie for the callback.
+pub struct StartedLogin {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+// * Avvia il login: state, nonce e PKCE vengono salvati per la verifica nel callback.
+// * Con `link_user_id` il callback collega l'identità a quell'utente invece di autenticare
+pub fn start_login(
+    pool: &PgPool,
+    providers: &IdentityProviders,
+    provider: &IdentityProvider,
+    link_user_id: Option<Uuid>,
+    app_config: &AppConfig,
+) -> Result<StartedLogin, ServiceError> {
+    let metadata = provider.metadata(providers.http())?;
+    let state = oauth::generate_token();
+    let code_verifier = oauth::generate_token();
+    let nonce = oauth::generate_token();
+    external_login_states_repo::create(
+        pool,
+        NewExternalLoginState {
+            state_hash: oauth::hash_token(&state),
+            provider: provider.config.id.clone(),
+            code_verifier: code_verifier.clone(),
+            nonce: nonce.clone(),
+            expires_at: (Utc::now() + Duration::seconds(app_config.external_login_ttl_secs as i64))
+                .naive_utc(),
+            link_user_id,
+        },
+    )?;
+
+    let redirect_uri = callback_url(app_config, &provider.config.id);
+    let scope = provider.config.scopes.join(" ");
+    let challenge = oauth::code_challenge(&code_verifier);
+    let mut params = vec![
+        ("response_type", "code"),
+        ("client_id", provider.config.client_id.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("scope", scope.as_str()),
+        ("state", state.as_str()),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    if provider.is_openid() {
+        params.push(("nonce", nonce.as_str()));
+    }
+    Ok(StartedLogin {
+        authorization_url: oauth::redirect_with(&metadata.authorization_endpoint, &params),
+        state,
+    })
+}
+
+/// A completed external login, with the new session of the user.
+pub struct ExternalLogin {
+    pub user: User,
+    pub issued: IssuedSession,
+    pub grants: RoleGrants,
+}
+
+/// Result of the provider's callback: a sign-in, or an identity linked from
+/// the account settings.
+pub enum ExternalCallback {
+    SignedIn(Box<ExternalLogin>),
+    Linked(UserIdentity),
+}
+
+// * Completa il login dal callback del provider: verifica lo state, ottiene il profilo e
+// * trova, collega o crea l'account locale
+pub fn finish_login(
+    pool: &PgPool,
+    providers: &IdentityProviders,
+    provider: &IdentityProvider,
+    callback: ExternalLoginCallback,
+    state_cookie: Option<&str>,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<ExternalCallback, ServiceError> {
+    if let Some(error) = callback.error {
+        return Err(ServiceError::Unauthorized(format!(
+            "{} did not complete the login: {}",
+            provider.config.name,
+            callback.error_description.unwrap_or(error)
+        )));
+    }
+    let (Some(code), Some(state)) = (callback.code, callback.state) else {
+        return Err(ServiceError::ValidationError(
+            "Missing code or state".into(),
+        ));
+    };
+    // * Lo state deve coincidere con il cookie: il login lo completa il browser che l'ha avviato
+    if state_cookie != Some(state.as_str()) {
+        return Err(ServiceError::Unauthorized(
+            "The login was started from another browser, start it again".into(),
+        ));
+    }
+    let login_state = external_login_states_repo::consume(pool, &oauth::hash_token(&state))?
+        .filter(|login_state| login_state.provider == provider.config.id)
+        .ok_or_else(|| {
+            ServiceError::Unauthorized("Unknown or expired login, start it again".into())
+        })?;
+    let profile = provider.fetch_profile(providers.http(), &code, &login_state, app_config)?;
+    if let Some(user_id) = login_state.link_user_id {
+        return link_to_user(pool, provider, &profile, user_id, client_info)
+            .map(ExternalCallback::Linked);
+    }
+
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = match resolve_user(conn, provider, &profile, client_info)? {
+            Ok(user) => user,
+            Err(e) => return Ok(Err(e)),
+        };
+        if let Err(e) = user.ensure_active() {
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "login.failure",
+                    outcome: AuditOutcome::Failure,
+                    actor_id: None,
+                    target_user_id: Some(user.id),
+                    client: Some(client_info),
+                    details: json!({
+                        "reason": format!("account_{}", user.account_status().as_str()),
+                        "provider": provider.config.id,
+                    }),
+                },
+            )?;
+            return Ok(Err(e));
+        }
+        let issued =
+            session::start_session_with_connection(conn, user.id, client_info, app_config)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "login.success",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "session_id": issued.session.id,
+                    "device": client_info.device,
+                    "provider": provider.config.id,
+                }),
+            },
+        )?;
+        let grants = roles_repo::grants_for_user_with_connection(conn, user.id)?;
+        Ok(Ok(ExternalCallback::SignedIn(Box::new(ExternalLogin {
+            user,
+            issued,
+            grants,
+        }))))
+    })?
+}
+
+// * Collega l'identità all'utente che ha avviato il collegamento; un'identità già collegata
+// * a un altro account non viene spostata
+fn link_to_user(
+    pool: &PgPool,
+    provider: &IdentityProvider,
+    profile: &ExternalProfile,
+    user_id: Uuid,
+    client_info: &ClientInfo,
+) -> Result<UserIdentity, ServiceError> {
+    let provider_id = provider.config.id.as_str();
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let user = users_repo::lock_user_with_connection(conn, user_id)?;
+        if let Err(e) = user.ensure_active() {
+            return Ok(Err(e));
+        }
+        if let Some(identity) =
+            user_identities_repo::find_with_connection(conn, provider_id, &profile.subject)?
+        {
+            if identity.user_id == user.id {
+                return Ok(Ok(identity));
+            }
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "identity.linked",
+                    outcome: AuditOutcome::Failure,
+                    actor_id: Some(user.id),
+                    target_user_id: Some(user.id),
+                    client: Some(client_info),
+                    details: json!({
+                        "provider": provider_id,
+                        "reason": "linked_to_another_user",
+                    }),
+                },
+            )?;
+            return Ok(Err(ServiceError::Conflict(format!(
+                "This {} account is already linked to another user",
+                provider.config.name
+            ))));
+        }
+        let identity = link_identity(conn, user.id, provider_id, profile)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "identity.linked",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "identity_id": identity.id,
+                    "provider": provider_id,
+                    "subject": profile.subject,
+                    "reason": "user_request",
+                }),
+            },
+        )?;
+        Ok(Ok(identity))
+    })?
+}
+
+// * Scollega un'identità dell'utente. Non deve restare un account senza modi per accedere:
+// * serve una password o un'altra identità di un provider ancora configurato
+pub fn unlink_identity(
+    pool: &PgPool,
+    providers: &IdentityProviders,
+    user_id: Uuid,
+    identity_id: Uuid,
+    client_info: &ClientInfo,
+) -> Result<UserIdentity, ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // * Il lock sull'utente serializza le rimozioni concorrenti delle sue identità
+        let user = users_repo::lock_user_with_connection(conn, user_id)?;
+        let identities = user_identities_repo::list_for_user_with_connection(conn, user.id)?;
+        let Some(identity) = identities.iter().find(|i| i.id == identity_id).cloned() else {
+            return Ok(Err(ServiceError::NotFound("Identity not found".into())));
+        };
+        let other_logins = identities
+            .iter()
+            .filter(|other| other.id != identity.id && providers.get(&other.provider).is_some())
+            .count();
+        if user.password.is_none() && other_logins == 0 {
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "identity.unlinked",
+                    outcome: AuditOutcome::Failure,
+                    actor_id: Some(user.id),
+                    target_user_id: Some(user.id),
+                    client: Some(client_info),
+                    details: json!({
+                        "identity_id": identity.id,
+                        "provider": identity.provider,
+                        "reason": "last_login_method",
+                    }),
+                },
+            )?;
+            return Ok(Err(ServiceError::Conflict(
+                "This is the only way to sign in to the account: set a password or link another provider first"
+                    .into(),
+            )));
+        }
+        user_identities_repo::delete_with_connection(conn, user.id, identity.id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "identity.unlinked",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "identity_id": identity.id,
+                    "provider": identity.provider,
+                    "subject": identity.subject,
+                }),
+            },
+        )?;
+        Ok(Ok(identity))
+    })?
+}
+
+// * Utente locale dell'identità: quello già collegato, altrimenti l'account con la stessa
+// * email verificata (se il provider lo consente), altrimenti un nuovo account
+fn resolve_user(
+    conn: &mut PgConnection,
+    provider: &IdentityProvider,
+    profile: &ExternalProfile,
+    client_info: &ClientInfo,
+) -> Result<Result<User, ServiceError>, diesel::result::Error> {
+    let provider_id = provider.config.id.as_str();
+    if let Some(identity) =
+        user_identities_repo::find_with_connection(conn, provider_id, &profile.subject)?
+    {
+        user_identities_repo::record_login_with_connection(
+            conn,
+            identity.id,
+            profile.email.as_deref(),
+        )?;
+        return Ok(Ok(users_repo::get_user_by_id_with_connection(
+            conn,
+            identity.user_id,
+        )?));
+    }
+
+    let Some(email) = profile.email.as_deref() else {
+        return Ok(Err(ServiceError::Forbidden(format!(
+            "{} did not share an email address, which is needed to create the account",
+            provider.config.name
+        ))));
+    };
+    if let Some(existing) = users_repo::find_by_email_with_connection(conn, email)? {
+        if !(provider.config.link_verified_email && profile.email_verified) {
+            return Ok(Err(ServiceError::Conflict(
+                "An account with this email already exists: sign in with it to link the provider"
+                    .into(),
+            )));
+        }
+        link_identity(conn, existing.id, provider_id, profile)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "identity.linked",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(existing.id),
+                target_user_id: Some(existing.id),
+                client: Some(client_info),
+                details: json!({
+                    "provider": provider_id,
+                    "subject": profile.subject,
+                    "reason": "verified_email",
+                }),
+            },
+        )?;
+        return Ok(Ok(existing));
+    }
+    if validate_email_logic(email).is_err() {
+        return Ok(Err(ServiceError::ValidationError(format!(
+            "The email address shared by {} is not valid",
+            provider.config.name
+        ))));
+    }
+
+    let base = username_base(profile.username_hint.as_deref());
+    let mut username = base.clone();
+    let mut attempts = 0;
+    while users_repo::username_exists_with_connection(conn, &username)? {
+        attempts += 1;
+        if attempts > 5 {
+            return Ok(Err(ServiceError::Conflict(
+                "Could not choose a free username, please register instead".into(),
+            )));
+        }
+        username = format!("{}{}", base, random_letters(4));
+    }
+    let user = users_repo::create_user_with_connection(
+        conn,
+        NewUser {
+            username,
+            email: email.to_string(),
+            password: None,
+        },
+    )?;
+    // * Come nella registrazione, ogni nuovo utente parte con il ruolo di studente
+    roles_repo::assign_role_with_connection(conn, user.id, STUDENT_ROLE)?;
+    link_identity(conn, user.id, provider_id, profile)?;
+    audit::record_with_connection(
+        conn,
+        AuditEvent {
+            action: "user.registered",
+            outcome: AuditOutcome::Success,
+            actor_id: Some(user.id),
+            target_user_id: Some(user.id),
+            client: Some(client_info),
+            details: json!({ "provider": provider_id }),
+        },
+    )?;
+    Ok(Ok(user))
+}
+
+fn link_identity(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    provider_id: &str,
+    profile: &ExternalProfile,
+) -> Result<UserIdentity, diesel::result::Error> {
+    user_identities_repo::create_with_connection(
+        conn,
+        NewUserIdentity {
+            user_id,
+            provider: provider_id.to_string(),
+            subject: profile.subject.clone(),
+            email: profile.email.clone(),
+            last_login_at: Some(Utc::now().naive_utc()),
+        },
+    )
+}
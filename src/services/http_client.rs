@@ -0,0 +1,137 @@
+use std::io::Read;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::Proxy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+// * Limiti delle chiamate verso i provider: un provider lento o una risposta enorme
+// * non devono bloccare un thread del pool a lungo
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_BYTES: u64 = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error("Invalid proxy URL {0}")]
+    InvalidProxy(String),
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unexpected HTTP status {0}")]
+    Status(u16),
+    #[error("Response body larger than {MAX_RESPONSE_BYTES} bytes")]
+    TooLarge,
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, HttpError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// Blocking HTTP client for the back-channel calls to upstream identity
+/// providers (discovery, JWKS, token and userinfo endpoints) and for the
+/// outbox relay.
+///
+/// https URLs are verified against the bundled webpki roots. With `proxy` set,
+/// every request goes through that egress proxy, tunnelled with CONNECT for
+/// https, so the TLS session still ends at the provider.
+#[derive(Clone, Debug, Default)]
+pub struct HttpClient {
+    proxy: Option<Proxy>,
+    // * Il client bloccante avvia un proprio runtime e non si può creare dentro quello di
+    // * actix: nasce alla prima chiamata, che avviene sempre in web::block
+    client: Arc<OnceLock<Client>>,
+}
+
+impl HttpClient {
+    pub fn new(proxy: Option<&str>) -> Result<Self, HttpError> {
+        let proxy = proxy
+            .map(|proxy| Proxy::all(proxy).map_err(|_| HttpError::InvalidProxy(proxy.into())))
+            .transpose()?;
+        Ok(Self {
+            proxy,
+            client: Arc::default(),
+        })
+    }
+
+    fn client(&self) -> Result<&Client, HttpError> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let builder = Client::builder()
+            .timeout(TIMEOUT)
+            .connect_timeout(TIMEOUT)
+            .user_agent("ketchapp-auth-api");
+        // * Senza proxy configurato si va diretti, senza leggere HTTP(S)_PROXY dall'ambiente
+        let builder = match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder.no_proxy(),
+        };
+        let _ = self.client.set(builder.build()?);
+        Ok(self.client.get().expect("client initialized above"))
+    }
+
+    pub fn get(&self, url: &str, bearer_token: Option<&str>) -> Result<HttpResponse, HttpError> {
+        let mut request = self.client()?.get(url).header(ACCEPT, "application/json");
+        if let Some(token) = bearer_token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        send(request)
+    }
+
+    pub fn post_form(&self, url: &str, form: &[(&str, &str)]) -> Result<HttpResponse, HttpError> {
+        send(
+            self.client()?
+                .post(url)
+                .header(ACCEPT, "application/json")
+                .form(form),
+        )
+    }
+
+    pub fn post_json<T: Serialize>(&self, url: &str, body: &T) -> Result<HttpResponse, HttpError> {
+        send(
+            self.client()?
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(body)?),
+        )
+    }
+}
+
+// * Il corpo si legge al massimo fino al limite, qualunque cosa dichiari il server
+fn send(request: RequestBuilder) -> Result<HttpResponse, HttpError> {
+    let response = request.send()?;
+    let status = response.status().as_u16();
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_RESPONSE_BYTES)
+    {
+        return Err(HttpError::TooLarge);
+    }
+    let mut body = Vec::new();
+    response
+        .take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err(HttpError::TooLarge);
+    }
+    Ok(HttpResponse { status, body })
+}
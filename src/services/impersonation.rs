@@ -0,0 +1,101 @@
+use chrono::{Duration, Utc};
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    extractors::client_info::ClientInfo,
+    models::{
+        claims::{Actor, Claims},
+        role::{RoleGrants, ADMIN_ROLE},
+        session::{NewSession, Session},
+        user::User,
+    },
+    repositories::sessions_repo,
+    services::session,
+};
+
+// * Si impersonano solo account attivi e senza ruolo admin: il supporto vede l'app come
+// * l'utente ma non ottiene privilegi che non ha già
+pub fn ensure_can_impersonate(
+    admin_id: Uuid,
+    target: &User,
+    roles: &[String],
+) -> Result<(), ServiceError> {
+    if target.id == admin_id {
+        return Err(ServiceError::Conflict(
+            "You cannot impersonate yourself".into(),
+        ));
+    }
+    if roles.iter().any(|role| role == ADMIN_ROLE) {
+        return Err(ServiceError::Conflict(
+            "Administrators cannot be impersonated".into(),
+        ));
+    }
+    target
+        .ensure_active()
+        .map_err(|_| ServiceError::Conflict("Only active accounts can be impersonated".into()))
+}
+
+// * Sessione dell'utente che esiste solo perché la revoca raggiunga il token di impersonazione:
+// * il refresh token generato non viene restituito, quindi non si può rinnovare
+pub fn start_session_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    client: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<Session, diesel::result::Error> {
+    sessions_repo::create_session_with_connection(
+        conn,
+        NewSession {
+            user_id,
+            refresh_token_hash: session::hash_refresh_token(&session::generate_refresh_token()),
+            device: client.device.clone(),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.map(|ip| ip.to_string()),
+            expires_at: (Utc::now()
+                + Duration::seconds(app_config.impersonation_token_ttl_secs as i64))
+            .naive_utc(),
+            oauth_client_id: None,
+            scope: None,
+            auth_time: None,
+            organization_id: None,
+        },
+    )
+}
+
+// * Token breve dell'utente con l'admin nel claim act, legato alla sessione di impersonazione;
+// * gli scope sono quelli dei ruoli dell'utente
+pub fn impersonation_claims(
+    user_id: Uuid,
+    grants: RoleGrants,
+    admin_id: Uuid,
+    session_id: Uuid,
+    app_config: &AppConfig,
+) -> Claims {
+    let mut claims = Claims::with_ttl(
+        user_id.to_string(),
+        app_config,
+        app_config.impersonation_token_ttl_secs as i64,
+    );
+    claims.sid = Some(session_id.to_string());
+    claims.set_scopes(&grants.scopes);
+    claims.roles = grants.roles;
+    claims.act = Some(Actor {
+        sub: admin_id.to_string(),
+        impersonation: true,
+        act: None,
+    });
+    claims
+}
+
+// * Chi impersona deve restare un admin attivo per tutta la durata del token
+pub fn ensure_impersonator_allowed(admin: &User, roles: &[String]) -> Result<(), ServiceError> {
+    if admin.ensure_active().is_err() || !roles.iter().any(|role| role == ADMIN_ROLE) {
+        return Err(ServiceError::Unauthorized(
+            "The impersonating administrator is no longer allowed to impersonate".into(),
+        ));
+    }
+    Ok(())
+}
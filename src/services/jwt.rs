@@ -0,0 +1,170 @@
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use rsa::{
+    pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding},
+    RsaPrivateKey,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    models::{claims::Claims, oidc::Jwk},
+};
+
+// * Chiavi RSA per firmare e verificare i JWT, caricate una sola volta all'avvio
+#[derive(Clone)]
+pub struct JwtKeys {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl JwtKeys {
+    pub fn new(private_key_pem: &[u8], public_key_pem: &[u8]) -> Result<Self, ServiceError> {
+        let (n, e) = rsa_public_components(public_key_pem)
+            .map_err(|e| ServiceError::JwtKeyError(format!("Invalid public key: {}", e)))?;
+        Ok(Self {
+            jwk: jwk_for(&n, &e),
+            encoding_key: EncodingKey::from_rsa_pem(private_key_pem)
+                .map_err(|e| ServiceError::JwtKeyError(format!("Invalid private key: {}", e)))?,
+            decoding_key: DecodingKey::from_rsa_pem(public_key_pem)
+                .map_err(|e| ServiceError::JwtKeyError(format!("Invalid public key: {}", e)))?,
+        })
+    }
+
+    // * La chiave pubblica si ricava da quella privata: nel repository c'è un solo file di chiave
+    pub fn from_private_key(private_key_pem: &[u8]) -> Result<Self, ServiceError> {
+        let public_key_pem = std::str::from_utf8(private_key_pem)
+            .ok()
+            .and_then(|pem| RsaPrivateKey::from_pkcs8_pem(pem).ok())
+            .and_then(|key| key.to_public_key().to_public_key_pem(LineEnding::LF).ok())
+            .ok_or_else(|| ServiceError::JwtKeyError("Invalid private key".into()))?;
+        Self::new(private_key_pem, public_key_pem.as_bytes())
+    }
+
+    pub fn from_config(config: &AppConfig) -> Result<Self, ServiceError> {
+        let private_key = std::fs::read(&config.jwt_private_key_path).map_err(|e| {
+            ServiceError::JwtKeyError(format!("Errore lettura chiave privata: {:?}", e))
+        })?;
+        Self::from_private_key(&private_key)
+    }
+
+    // * Firma RS256 con il kid della chiave, così che i client possano sceglierla dal JWKS
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, ServiceError> {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.jwk.kid.clone());
+        jsonwebtoken::encode(&header, claims, &self.encoding_key)
+            .map_err(|e| ServiceError::JwtGenerationError(format!("Errore generazione JWT: {}", e)))
+    }
+
+    // * Chiave pubblica in formato JWK, pubblicata su /.well-known/jwks.json
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+
+    pub fn verify(&self, token: &str, config: &AppConfig) -> Result<Claims, ServiceError> {
+        Claims::decode(
+            token,
+            &self.decoding_key,
+            &config.jwt_issuer,
+            &config.accepted_audiences(),
+        )
+        .map_err(|_| ServiceError::Unauthorized("Invalid or expired token".into()))
+    }
+
+    // * I token dei client OAuth possono avere l'audience del client: vanno accettati solo dove
+    // * l'audience non conta, come /oauth/userinfo
+    pub fn verify_any_audience(
+        &self,
+        token: &str,
+        config: &AppConfig,
+    ) -> Result<Claims, ServiceError> {
+        Claims::decode_any_audience(token, &self.decoding_key, &config.jwt_issuer)
+            .map_err(|_| ServiceError::Unauthorized("Invalid or expired token".into()))
+    }
+}
+
+// * Il kid è il thumbprint JWK della chiave (RFC 7638): cambia solo se cambia la chiave
+fn jwk_for(n: &[u8], e: &[u8]) -> Jwk {
+    let n = URL_SAFE_NO_PAD.encode(n);
+    let e = URL_SAFE_NO_PAD.encode(e);
+    let thumbprint = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+    Jwk {
+        kty: "RSA",
+        key_use: "sig",
+        alg: "RS256",
+        kid: URL_SAFE_NO_PAD.encode(Sha256::digest(thumbprint.as_bytes())),
+        n,
+        e,
+    }
+}
+
+// * Modulo ed esponente di una chiave pubblica RSA in PEM, sia SubjectPublicKeyInfo
+// * ("PUBLIC KEY") sia PKCS#1 ("RSA PUBLIC KEY")
+fn rsa_public_components(pem: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let pem = std::str::from_utf8(pem).map_err(|_| "not a PEM file".to_string())?;
+    let body: String = pem
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("-----END"))
+        .collect();
+    let der = STANDARD
+        .decode(body)
+        .map_err(|e| format!("invalid base64: {}", e))?;
+
+    let (key, _) = der_read(&der, DER_SEQUENCE)?;
+    let rsa_key = match der_read(key, DER_SEQUENCE) {
+        // * SubjectPublicKeyInfo: algoritmo seguito dalla chiave PKCS#1 in una BIT STRING
+        Ok((_algorithm, rest)) => {
+            let (bits, _) = der_read(rest, DER_BIT_STRING)?;
+            let (rsa_key, _) = der_read(bits.get(1..).unwrap_or_default(), DER_SEQUENCE)?;
+            rsa_key
+        }
+        Err(_) => key,
+    };
+    let (n, rest) = der_read(rsa_key, DER_INTEGER)?;
+    let (e, _) = der_read(rest, DER_INTEGER)?;
+    Ok((strip_leading_zeros(n), strip_leading_zeros(e)))
+}
+
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_SEQUENCE: u8 = 0x30;
+
+// * Legge un elemento DER con il tag atteso: (contenuto, byte successivi)
+fn der_read(input: &[u8], expected_tag: u8) -> Result<(&[u8], &[u8]), String> {
+    let (&tag, rest) = input.split_first().ok_or("truncated DER")?;
+    if tag != expected_tag {
+        return Err(format!("unexpected DER tag {:#04x}", tag));
+    }
+    let (&first, mut rest) = rest.split_first().ok_or("truncated DER")?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err("invalid DER length".into());
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | b as usize);
+        rest = &rest[count..];
+        len
+    };
+    if rest.len() < len {
+        return Err("truncated DER".into());
+    }
+    Ok((&rest[..len], &rest[len..]))
+}
+
+fn strip_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    bytes[start..].to_vec()
+}
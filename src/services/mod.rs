@@ -0,0 +1,19 @@
+pub mod account_deletion;
+pub mod audit;
+pub mod breached_passwords;
+pub mod data_export;
+pub mod device_authorization;
+pub mod external_login;
+pub mod http_client;
+pub mod impersonation;
+pub mod jwt;
+pub mod oauth;
+pub mod oidc;
+pub mod organization;
+pub mod outbox;
+pub mod password;
+pub mod password_policy;
+pub mod personal_access_token;
+pub mod scope;
+pub mod session;
+pub mod token_exchange;
@@ -0,0 +1,665 @@
+use std::collections::BTreeSet;
+
+use actix_web::{http::header, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::{rngs::OsRng, RngCore};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::{OAuthError, ServiceError},
+    extractors::{auth::AuthenticatedUser, client_info::ClientInfo},
+    models::{
+        audit_event::AuditOutcome,
+        claims::Claims,
+        oauth::{
+            AuthorizeRequest, NewAuthorizationCode, NewOAuthConsent, OAuthClient, TokenRequest,
+        },
+        role::RoleGrants,
+        user::User,
+    },
+    repositories::{
+        establish_connection, oauth_clients_repo, oauth_codes_repo, oauth_consents_repo,
+        roles_repo, sessions_repo, users_repo, PgPool,
+    },
+    services::{
+        audit::{self, AuditEvent},
+        oidc,
+        session::{self, IssuedSession, SessionGrant},
+    },
+};
+
+pub const CODE_CHALLENGE_METHOD: &str = "S256";
+
+/// A validated authorization request, ready to be shown for consent or
+/// answered with a code.
+#[derive(Debug, Clone)]
+pub struct AuthorizationContext {
+    pub client: OAuthClient,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+    // * prompt=none: niente pagine di login o consenso, solo un codice o un errore
+    pub prompt_none: bool,
+}
+
+impl AuthorizationContext {
+    pub fn scope(&self) -> String {
+        self.scopes.join(" ")
+    }
+
+    // * Redirect al client con un errore, per le richieste già validate
+    pub fn error_redirect(&self, error: OAuthError) -> String {
+        error_location(&self.redirect_uri, self.state.as_deref(), error)
+    }
+}
+
+/// Why an authorization request was refused. Until the client and its
+/// redirect URI are known the error is shown to the user; afterwards it is
+/// sent back to the client through the redirect URI.
+#[derive(Debug)]
+pub enum AuthorizeError {
+    Invalid(OAuthError),
+    Redirect(String),
+}
+
+impl From<OAuthError> for AuthorizeError {
+    fn from(e: OAuthError) -> Self {
+        AuthorizeError::Invalid(e)
+    }
+}
+
+/// What the token endpoint grants: access on behalf of a user, access for
+/// the client itself (`client_credentials`), or a user's token exchanged for
+/// another audience with the client as actor (token exchange).
+pub enum Grant {
+    User(Box<TokenGrant>),
+    Client(ClientGrant),
+    Exchange(Box<Claims>),
+}
+
+/// Access granted to a client for itself, with the scopes it obtained.
+pub struct ClientGrant {
+    pub scope: String,
+}
+
+/// Tokens issued at the token endpoint, before the access token is signed.
+pub struct TokenGrant {
+    pub issued: IssuedSession,
+    pub user: User,
+    pub grants: RoleGrants,
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub auth_time: NaiveDateTime,
+}
+
+// * Token casuale (codici di autorizzazione e client secret), 256 bit in esadecimale
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    session::to_hex(&bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    session::to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+// * Il code verifier PKCE è lungo 43-128 caratteri "unreserved" (RFC 7636, sezione 4.1)
+fn is_valid_code_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+// * Con S256 la challenge è lo SHA-256 del verifier in base64url senza padding: 43 caratteri
+pub fn is_valid_code_challenge(challenge: &str) -> bool {
+    challenge.len() == 43
+        && challenge
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'))
+}
+
+// * Challenge PKCE S256: BASE64URL(SHA256(code_verifier))
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+// * Verifica PKCE S256: la challenge del verifier deve coincidere con quella ricevuta
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    is_valid_code_verifier(code_verifier) && self::code_challenge(code_verifier) == code_challenge
+}
+
+// * Redirect URI accettati alla registrazione: https, http solo verso loopback (app native,
+// * RFC 8252) oppure uno schema privato con un punto (es. com.ketchapp.mobile:/callback)
+pub fn validate_redirect_uri(uri: &str) -> Result<(), String> {
+    let url = Url::parse(uri).map_err(|e| format!("{} is not a valid URI: {}", uri, e))?;
+    if url.fragment().is_some() {
+        return Err(format!("{} must not contain a fragment", uri));
+    }
+    let allowed = match url.scheme() {
+        "https" => url.host().is_some(),
+        "http" => matches!(
+            url.host_str(),
+            Some("localhost") | Some("127.0.0.1") | Some("[::1]")
+        ),
+        scheme => scheme.contains('.'),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} must use https, http on a loopback address or a private-use scheme",
+            uri
+        ))
+    }
+}
+
+// * Uno scope è una sequenza di caratteri stampabili senza spazi, virgolette e backslash
+pub fn is_valid_scope_token(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope
+            .bytes()
+            .all(|b| b == 0x21 || (0x23..=0x5B).contains(&b) || (0x5D..=0x7E).contains(&b))
+}
+
+// * Scope richiesti, senza duplicati; devono essere tutti consentiti al client.
+// * Se la richiesta non ne indica, valgono tutti quelli del client
+pub fn resolve_scopes(requested: Option<&str>, allowed: &[String]) -> Result<Vec<String>, String> {
+    let requested: Vec<&str> = requested.unwrap_or_default().split_whitespace().collect();
+    if requested.is_empty() {
+        return Ok(allowed.to_vec());
+    }
+    let mut seen = BTreeSet::new();
+    let mut scopes = Vec::new();
+    for scope in requested {
+        if !allowed.iter().any(|a| a == scope) {
+            return Err(format!("The client may not request the scope {}", scope));
+        }
+        if seen.insert(scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    Ok(scopes)
+}
+
+// * Scope che un utente può concedere al client: i suoi più quelli di OpenID Connect,
+// * richiedibili anche se non registrati. Senza richiesta valgono quelli del client
+pub fn requested_scopes(
+    requested: Option<&str>,
+    client: &OAuthClient,
+) -> Result<Vec<String>, String> {
+    if requested.is_none() {
+        return Ok(client.allowed_scopes.clone());
+    }
+    let mut permitted = client.allowed_scopes.clone();
+    permitted.extend(
+        oidc::SCOPES
+            .iter()
+            .filter(|s| !client.allowed_scopes.iter().any(|a| a == *s))
+            .map(|s| s.to_string()),
+    );
+    resolve_scopes(requested, &permitted)
+}
+
+// * Salva il consenso dell'utente, che copre anche gli scope concessi in precedenza
+pub fn grant_consent_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    client_id: Uuid,
+    requested: &[String],
+) -> Result<(), diesel::result::Error> {
+    let mut scopes = oauth_consents_repo::find_with_connection(conn, user_id, client_id)?
+        .map(|previous| previous.scopes)
+        .unwrap_or_default();
+    for scope in requested {
+        if !scopes.contains(scope) {
+            scopes.push(scope.clone());
+        }
+    }
+    oauth_consents_repo::upsert_with_connection(
+        conn,
+        NewOAuthConsent {
+            user_id,
+            client_id,
+            scopes,
+        },
+    )
+    .map(|_| ())
+}
+
+// * Aggiunge i parametri alla query del redirect URI, mantenendo quelli già presenti
+pub fn redirect_with(redirect_uri: &str, params: &[(&str, &str)]) -> String {
+    match Url::parse(redirect_uri) {
+        Ok(mut url) => {
+            url.query_pairs_mut().extend_pairs(params);
+            url.to_string()
+        }
+        Err(_) => redirect_uri.to_string(),
+    }
+}
+
+// * Redirect al client con un errore, riportando lo state della richiesta
+fn error_redirect(redirect_uri: &str, state: Option<&str>, error: OAuthError) -> AuthorizeError {
+    AuthorizeError::Redirect(error_location(redirect_uri, state, error))
+}
+
+fn error_location(redirect_uri: &str, state: Option<&str>, error: OAuthError) -> String {
+    let mut params = vec![
+        ("error", error.error),
+        ("error_description", error.description.as_str()),
+    ];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    redirect_with(redirect_uri, &params)
+}
+
+// * Controlla una richiesta di autorizzazione: client, redirect URI (confronto esatto),
+// * response_type, PKCE obbligatorio con S256 e scope
+pub fn validate_authorize_request(
+    pool: &PgPool,
+    request: &AuthorizeRequest,
+) -> Result<AuthorizationContext, AuthorizeError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| OAuthError::invalid_request("Missing or malformed client_id"))?;
+    let client = oauth_clients_repo::get_client(pool, client_id).map_err(|e| match e {
+        diesel::result::Error::NotFound => OAuthError::invalid_request("Unknown client_id"),
+        e => e.into(),
+    })?;
+    let redirect_uri = match (&request.redirect_uri, client.redirect_uris.as_slice()) {
+        (Some(uri), registered) if registered.contains(uri) => uri.clone(),
+        (None, [only]) => only.clone(),
+        (None, _) => return Err(OAuthError::invalid_request("Missing redirect_uri").into()),
+        (Some(_), _) => {
+            return Err(OAuthError::invalid_request(
+                "redirect_uri is not registered for this client",
+            )
+            .into())
+        }
+    };
+
+    let state = request.state.as_deref();
+    if request.response_type.as_deref() != Some("code") {
+        return Err(error_redirect(
+            &redirect_uri,
+            state,
+            OAuthError::unsupported_response_type("Only response_type=code is supported"),
+        ));
+    }
+    let code_challenge = match request.code_challenge.as_deref() {
+        Some(challenge) if is_valid_code_challenge(challenge) => challenge.to_string(),
+        Some(_) => {
+            return Err(error_redirect(
+                &redirect_uri,
+                state,
+                OAuthError::invalid_request("Malformed code_challenge"),
+            ))
+        }
+        None => {
+            return Err(error_redirect(
+                &redirect_uri,
+                state,
+                OAuthError::invalid_request("PKCE is required: missing code_challenge"),
+            ))
+        }
+    };
+    if request.code_challenge_method.as_deref() != Some(CODE_CHALLENGE_METHOD) {
+        return Err(error_redirect(
+            &redirect_uri,
+            state,
+            OAuthError::invalid_request("Only code_challenge_method=S256 is supported"),
+        ));
+    }
+    let scopes = requested_scopes(request.scope.as_deref(), &client)
+        .map_err(|e| error_redirect(&redirect_uri, state, OAuthError::invalid_scope(e)))?;
+    if request
+        .nonce
+        .as_ref()
+        .is_some_and(|nonce| nonce.len() > 255)
+    {
+        return Err(error_redirect(
+            &redirect_uri,
+            state,
+            OAuthError::invalid_request("nonce is too long"),
+        ));
+    }
+    let prompts: Vec<&str> = request
+        .prompt
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    let prompt_none = prompts.contains(&"none");
+    if prompt_none && prompts.len() > 1 {
+        return Err(error_redirect(
+            &redirect_uri,
+            state,
+            OAuthError::invalid_request("prompt=none cannot be combined with other values"),
+        ));
+    }
+
+    Ok(AuthorizationContext {
+        client,
+        redirect_uri,
+        scopes,
+        state: request.state.clone(),
+        code_challenge,
+        nonce: request.nonce.clone(),
+        prompt_none,
+    })
+}
+
+// * Emette un codice di autorizzazione monouso e restituisce il redirect verso il client
+pub fn issue_code(
+    pool: &PgPool,
+    context: &AuthorizationContext,
+    user_id: Uuid,
+    auth_time: NaiveDateTime,
+    app_config: &AppConfig,
+) -> Result<String, diesel::result::Error> {
+    let code = generate_token();
+    oauth_codes_repo::create(
+        pool,
+        NewAuthorizationCode {
+            code_hash: hash_token(&code),
+            client_id: context.client.id,
+            user_id,
+            redirect_uri: context.redirect_uri.clone(),
+            scope: context.scope(),
+            code_challenge: context.code_challenge.clone(),
+            expires_at: (Utc::now() + Duration::seconds(app_config.oauth_code_ttl_secs as i64))
+                .naive_utc(),
+            nonce: context.nonce.clone(),
+            auth_time,
+        },
+    )?;
+    let mut params = vec![("code", code.as_str())];
+    if let Some(state) = context.state.as_deref() {
+        params.push(("state", state));
+    }
+    Ok(redirect_with(&context.redirect_uri, &params))
+}
+
+// * Momento in cui l'utente ha fatto login: l'inizio della sessione del token o, per i
+// * token senza sessione, la loro emissione
+pub fn authenticated_at(
+    pool: &PgPool,
+    user: &AuthenticatedUser,
+) -> Result<NaiveDateTime, diesel::result::Error> {
+    match user.session_id {
+        Some(session_id) => Ok(sessions_repo::get_session(pool, session_id)?.authenticated_at()),
+        None => Ok(DateTime::from_timestamp(user.claims.iat as i64, 0)
+            .unwrap_or_else(Utc::now)
+            .naive_utc()),
+    }
+}
+
+// * Header Authorization della richiesta, che può contenere le credenziali HTTP Basic del client
+pub fn authorization_header(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+// * Credenziali del client: HTTP Basic oppure client_id/client_secret nel corpo
+fn presented_credentials(
+    authorization: Option<&str>,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> (Option<String>, Option<String>) {
+    let basic = authorization
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| {
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded.trim())
+                .ok()
+        })
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            decoded
+                .split_once(':')
+                .map(|(id, secret)| (id.to_string(), secret.to_string()))
+        });
+    match basic {
+        Some((id, secret)) => (Some(id), Some(secret)),
+        None => (
+            client_id.map(str::to_string),
+            client_secret.map(str::to_string),
+        ),
+    }
+}
+
+// * Autentica il client: quelli confidenziali devono presentare il secret, quelli pubblici
+// * sono identificati dal solo client_id e il possesso del codice è provato da PKCE
+pub fn authenticate_client(
+    pool: &PgPool,
+    authorization: Option<&str>,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, secret) = presented_credentials(authorization, client_id, client_secret);
+    let client_id = client_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| OAuthError::invalid_client("Missing or malformed client_id"))?;
+    let client = oauth_clients_repo::get_client(pool, client_id).map_err(|e| match e {
+        diesel::result::Error::NotFound => OAuthError::invalid_client("Unknown client"),
+        e => e.into(),
+    })?;
+    match (&client.client_secret_hash, secret.filter(|s| !s.is_empty())) {
+        (Some(expected), Some(secret)) if *expected == hash_token(&secret) => Ok(client),
+        (None, None) => Ok(client),
+        (None, Some(_)) => Err(OAuthError::invalid_client(
+            "Public clients must not send a client secret",
+        )),
+        _ => Err(OAuthError::invalid_client("Invalid client credentials")),
+    }
+}
+
+// * Scambia un codice di autorizzazione con una sessione. Un codice già usato viene
+// * trattato come rubato: la sessione creata con il primo scambio viene revocata
+pub fn exchange_code(
+    pool: &PgPool,
+    client: &OAuthClient,
+    form: &TokenRequest,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<TokenGrant, OAuthError> {
+    let code = form
+        .code
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing code"))?;
+    let code_verifier = form
+        .code_verifier
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("PKCE is required: missing code_verifier"))?;
+    let code_hash = hash_token(code);
+
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let Some(authorization) = oauth_codes_repo::lock_with_connection(conn, &code_hash)? else {
+            return Ok(Err(OAuthError::invalid_grant("Invalid authorization code")));
+        };
+        if authorization.client_id != client.id {
+            return Ok(Err(OAuthError::invalid_grant("Invalid authorization code")));
+        }
+        if authorization.consumed_at.is_some() {
+            if let Some(session_id) = authorization.session_id {
+                sessions_repo::revoke_with_connection(conn, authorization.user_id, session_id)?;
+            }
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "oauth.code_reuse",
+                    outcome: AuditOutcome::Failure,
+                    actor_id: None,
+                    target_user_id: Some(authorization.user_id),
+                    client: Some(client_info),
+                    details: json!({ "client_id": client.id, "session_id": authorization.session_id }),
+                },
+            )?;
+            return Ok(Err(OAuthError::invalid_grant(
+                "Authorization code already used",
+            )));
+        }
+        if authorization.expires_at <= Utc::now().naive_utc() {
+            return Ok(Err(OAuthError::invalid_grant("Authorization code expired")));
+        }
+        if form.redirect_uri.as_deref() != Some(authorization.redirect_uri.as_str()) {
+            return Ok(Err(OAuthError::invalid_grant(
+                "redirect_uri does not match the authorization request",
+            )));
+        }
+        if !verify_pkce(code_verifier, &authorization.code_challenge) {
+            return Ok(Err(OAuthError::invalid_grant("Invalid code_verifier")));
+        }
+        let user = users_repo::get_user_by_id_with_connection(conn, authorization.user_id)?;
+        if user.ensure_active().is_err() {
+            return Ok(Err(OAuthError::invalid_grant("The account is not active")));
+        }
+
+        let issued = session::start_client_session_with_connection(
+            conn,
+            user.id,
+            client_info,
+            app_config,
+            Some(SessionGrant {
+                client,
+                scope: &authorization.scope,
+                auth_time: authorization.auth_time,
+            }),
+        )?;
+        oauth_codes_repo::consume_with_connection(conn, &code_hash, issued.session.id)?;
+        let grants = roles_repo::grants_for_user_with_connection(conn, user.id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "oauth.token.issued",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user.id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "client_id": client.id,
+                    "grant_type": "authorization_code",
+                    "scope": authorization.scope,
+                    "session_id": issued.session.id,
+                }),
+            },
+        )?;
+        Ok(Ok(TokenGrant {
+            issued,
+            user,
+            grants,
+            scope: authorization.scope,
+            nonce: authorization.nonce,
+            auth_time: authorization.auth_time,
+        }))
+    })?
+}
+
+// * Ruota il refresh token di una sessione concessa al client
+pub fn refresh(
+    pool: &PgPool,
+    client: &OAuthClient,
+    form: &TokenRequest,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<TokenGrant, OAuthError> {
+    let refresh_token = form
+        .refresh_token
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing refresh_token"))?;
+    let mut conn = establish_connection(pool)?;
+    let issued = session::refresh_with_connection(
+        &mut conn,
+        refresh_token,
+        client_info,
+        Some(client),
+        app_config,
+    )
+    .map_err(|e| match e {
+        ServiceError::AccountInactive { .. } => {
+            OAuthError::invalid_grant("The account is not active")
+        }
+        _ => OAuthError::invalid_grant("Invalid or expired refresh token"),
+    })?;
+    let user = users_repo::get_user_by_id_with_connection(&mut conn, issued.session.user_id)?;
+    let grants = roles_repo::grants_for_user_with_connection(&mut conn, user.id)?;
+    let scope = issued.session.scope.clone().unwrap_or_default();
+    let auth_time = issued.session.authenticated_at();
+    Ok(TokenGrant {
+        issued,
+        user,
+        grants,
+        scope,
+        nonce: None,
+        auth_time,
+    })
+}
+
+// * Grant client_credentials: un servizio ottiene un token per sé stesso. Solo i client
+// * confidenziali, che hanno provato il secret, e solo con i propri scope
+pub fn client_credentials(
+    pool: &PgPool,
+    client: &OAuthClient,
+    form: &TokenRequest,
+    client_info: &ClientInfo,
+) -> Result<ClientGrant, OAuthError> {
+    if !client.is_confidential() {
+        return Err(OAuthError::unauthorized_client(
+            "Only confidential clients may use the client_credentials grant",
+        ));
+    }
+    let scope = resolve_scopes(form.scope.as_deref(), &client.allowed_scopes)
+        .map_err(OAuthError::invalid_scope)?
+        .join(" ");
+    let mut conn = establish_connection(pool)?;
+    audit::record_with_connection(
+        &mut conn,
+        AuditEvent {
+            action: "oauth.token.issued",
+            outcome: AuditOutcome::Success,
+            actor_id: None,
+            target_user_id: None,
+            client: Some(client_info),
+            details: json!({
+                "client_id": client.id,
+                "grant_type": "client_credentials",
+                "scope": scope,
+            }),
+        },
+    )?;
+    Ok(ClientGrant { scope })
+}
+
+// * Claims del token di un servizio: sub è il client_id, senza sessione né ruoli. Audience e
+// * durata sono quelle del client, se le ha
+pub fn client_claims(client: &OAuthClient, scope: &str, app_config: &AppConfig) -> Claims {
+    let mut claims = Claims::with_ttl(
+        client.id.to_string(),
+        app_config,
+        client
+            .access_token_ttl_secs
+            .map_or(app_config.jwt_exp_secs as i64, i64::from),
+    );
+    claims.aud = client
+        .audience
+        .clone()
+        .unwrap_or_else(|| app_config.client_credentials_audience().to_string());
+    claims.client_id = Some(client.id.to_string());
+    claims.scope = Some(scope.to_string());
+    claims
+}
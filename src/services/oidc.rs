@@ -0,0 +1,101 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    models::{
+        oidc::{IdTokenClaims, OpenIdConfiguration, UserInfo},
+        user::User,
+    },
+    services::{device_authorization, token_exchange},
+};
+
+// * Scope standard di OpenID Connect, che ogni client può richiedere
+pub const SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+pub fn is_openid(scope: &str) -> bool {
+    scope.split_whitespace().any(|s| s == "openid")
+}
+
+pub fn discovery_document(app_config: &AppConfig) -> OpenIdConfiguration {
+    OpenIdConfiguration {
+        issuer: app_config.oidc_issuer.clone(),
+        authorization_endpoint: app_config.oidc_url("/oauth/authorize"),
+        token_endpoint: app_config.oidc_url("/oauth/token"),
+        device_authorization_endpoint: app_config.oidc_url("/oauth/device_authorization"),
+        userinfo_endpoint: app_config.oidc_url("/oauth/userinfo"),
+        jwks_uri: app_config.oidc_url("/.well-known/jwks.json"),
+        scopes_supported: SCOPES.to_vec(),
+        response_types_supported: vec!["code"],
+        response_modes_supported: vec!["query"],
+        grant_types_supported: vec![
+            "authorization_code",
+            "refresh_token",
+            "client_credentials",
+            device_authorization::GRANT_TYPE,
+            token_exchange::GRANT_TYPE,
+        ],
+        subject_types_supported: vec!["public"],
+        id_token_signing_alg_values_supported: vec!["RS256"],
+        token_endpoint_auth_methods_supported: vec![
+            "client_secret_basic",
+            "client_secret_post",
+            "none",
+        ],
+        code_challenge_methods_supported: vec!["S256"],
+        claims_supported: vec![
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "azp",
+            "sid",
+            "at_hash",
+            "preferred_username",
+            "updated_at",
+            "email",
+            "email_verified",
+        ],
+        prompt_values_supported: vec!["none"],
+    }
+}
+
+// * at_hash: metà sinistra dello SHA-256 dell'access token in base64url (OIDC Core 3.1.3.6)
+pub fn at_hash(access_token: &str) -> String {
+    let digest = Sha256::digest(access_token.as_bytes());
+    URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+}
+
+/// Login details carried into an ID token.
+pub struct IdTokenRequest<'a> {
+    pub user: &'a User,
+    pub client_id: Uuid,
+    pub session_id: Uuid,
+    pub scope: &'a str,
+    pub nonce: Option<String>,
+    pub auth_time: NaiveDateTime,
+    pub access_token: &'a str,
+}
+
+// * Claims dell'ID token: l'audience è il client, l'issuer l'URL pubblico del servizio
+pub fn id_token_claims(request: IdTokenRequest<'_>, app_config: &AppConfig) -> IdTokenClaims {
+    let now = Utc::now();
+    let scopes: Vec<&str> = request.scope.split_whitespace().collect();
+    IdTokenClaims {
+        iss: app_config.oidc_issuer.clone(),
+        aud: request.client_id.to_string(),
+        exp: (now + Duration::seconds(app_config.jwt_exp_secs as i64)).timestamp() as usize,
+        iat: now.timestamp() as usize,
+        auth_time: request.auth_time.and_utc().timestamp(),
+        nonce: request.nonce,
+        azp: request.client_id.to_string(),
+        sid: request.session_id.to_string(),
+        at_hash: at_hash(request.access_token),
+        user: UserInfo::for_scopes(request.user, &scopes),
+    }
+}
@@ -0,0 +1,428 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    errors::ServiceError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome,
+        organization::{
+            CreateOrganization, InviteMember, MemberResponse, Membership, NewMembership,
+            NewOrganization, Organization, ORG_OWNER_ROLE, ORG_ROLES, ORG_TEACHER_ROLE,
+        },
+        session::Session,
+    },
+    repositories::{
+        establish_connection, memberships_repo, organizations_repo, sessions_repo, users_repo,
+        PgPool,
+    },
+    services::audit::{self, AuditEvent},
+};
+
+// * Ruoli senza duplicati, nell'ordine di ORG_ROLES
+pub fn normalize_roles(roles: &[String]) -> Vec<String> {
+    ORG_ROLES
+        .iter()
+        .filter(|role| roles.iter().any(|r| r == *role))
+        .map(|role| role.to_string())
+        .collect()
+}
+
+// * Appartenenza di chi agisce, con uno dei ruoli richiesti. A chi non è membro l'organizzazione
+// * risulta inesistente
+fn require_member_with_connection(
+    conn: &mut PgConnection,
+    organization_id: Uuid,
+    user_id: Uuid,
+    allowed_roles: &[&str],
+) -> Result<Result<Membership, ServiceError>, diesel::result::Error> {
+    Ok(
+        match memberships_repo::find_with_connection(conn, organization_id, user_id)? {
+            None => Err(ServiceError::NotFound("Organization not found".into())),
+            Some(membership) if allowed_roles.iter().any(|role| membership.has_role(role)) => {
+                Ok(membership)
+            }
+            Some(_) => Err(ServiceError::Forbidden(format!(
+                "This requires one of the organization roles: {}",
+                allowed_roles.join(", ")
+            ))),
+        },
+    )
+}
+
+// * Un'organizzazione deve sempre avere almeno un proprietario
+fn ensure_owner_remains_with_connection(
+    conn: &mut PgConnection,
+    member: &Membership,
+    remaining_roles: &[String],
+) -> Result<Result<(), ServiceError>, diesel::result::Error> {
+    let loses_ownership =
+        member.has_role(ORG_OWNER_ROLE) && !remaining_roles.iter().any(|r| r == ORG_OWNER_ROLE);
+    if loses_ownership
+        && memberships_repo::count_owners_with_connection(conn, member.organization_id)? <= 1
+    {
+        return Ok(Err(ServiceError::Conflict(
+            "An organization must keep at least one owner".into(),
+        )));
+    }
+    Ok(Ok(()))
+}
+
+// * Crea l'organizzazione; chi la crea ne diventa proprietario
+pub fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    request: CreateOrganization,
+    client_info: &ClientInfo,
+) -> Result<(Organization, Membership), ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    let created = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if organizations_repo::slug_exists_with_connection(conn, &request.slug)? {
+            return Ok(Err(ServiceError::Conflict(format!(
+                "The slug {} is already taken",
+                request.slug
+            ))));
+        }
+        let organization = organizations_repo::create_with_connection(
+            conn,
+            NewOrganization {
+                name: request.name.trim().to_string(),
+                slug: request.slug,
+                created_by: Some(user_id),
+            },
+        )?;
+        let membership = memberships_repo::create_with_connection(
+            conn,
+            NewMembership {
+                organization_id: organization.id,
+                user_id,
+                roles: vec![ORG_OWNER_ROLE.to_string()],
+                accepted_at: Some(Utc::now().naive_utc()),
+            },
+        )?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.created",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(client_info),
+                details: json!({
+                    "organization_id": organization.id,
+                    "name": organization.name,
+                    "slug": organization.slug,
+                }),
+            },
+        )?;
+        Ok(Ok((organization, membership)))
+    })??;
+    Ok(created)
+}
+
+// * Cambia l'organizzazione attiva della sessione (None per nessuna). Solo le sessioni dei
+// * login diretti: quelle dei client OAuth emettono token con le regole del client
+pub fn switch(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+    organization_id: Option<Uuid>,
+    client_info: &ClientInfo,
+) -> Result<(Session, Option<Membership>), ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    let switched = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let Some(session) = sessions_repo::get_session_with_connection(conn, session_id)
+            .optional()?
+            .filter(|session| session.user_id == user_id && session.revoked_at.is_none())
+        else {
+            return Ok(Err(ServiceError::Unauthorized(
+                "The session has ended".into(),
+            )));
+        };
+        if session.oauth_client_id.is_some() {
+            return Ok(Err(ServiceError::Forbidden(
+                "Only sessions of a direct login can switch organization".into(),
+            )));
+        }
+        let membership = match organization_id {
+            Some(organization_id) => {
+                match memberships_repo::find_with_connection(conn, organization_id, user_id)? {
+                    Some(membership) => Some(membership),
+                    None => {
+                        return Ok(Err(ServiceError::NotFound("Organization not found".into())))
+                    }
+                }
+            }
+            None => None,
+        };
+        let session =
+            sessions_repo::set_organization_with_connection(conn, session_id, organization_id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.switched",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(client_info),
+                details: json!({
+                    "session_id": session_id,
+                    "organization_id": organization_id,
+                }),
+            },
+        )?;
+        Ok(Ok((session, membership)))
+    })??;
+    Ok(switched)
+}
+
+// * Membri dell'organizzazione, visibili a proprietari e insegnanti
+pub fn list_members(
+    pool: &PgPool,
+    organization_id: Uuid,
+    user_id: Uuid,
+) -> Result<Vec<MemberResponse>, ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    require_member_with_connection(
+        &mut conn,
+        organization_id,
+        user_id,
+        &[ORG_OWNER_ROLE, ORG_TEACHER_ROLE],
+    )??;
+    let members = memberships_repo::list_members_with_connection(&mut conn, organization_id)?;
+    Ok(members
+        .into_iter()
+        .map(|(membership, username)| MemberResponse {
+            user_id: membership.user_id,
+            username,
+            joined_at: membership.joined_at(),
+            roles: membership.roles,
+        })
+        .collect())
+}
+
+// * Invita un utente, cercato per username o email, con i ruoli indicati. Solo i proprietari.
+// * L'utente diventa membro solo accettando l'invito; chi invita non scopre se l'account esiste
+// * o è già membro: in quei casi non succede nulla. Un nuovo invito a chi non ha ancora risposto
+// * sostituisce i ruoli offerti
+pub fn invite_member(
+    pool: &PgPool,
+    organization_id: Uuid,
+    actor_id: Uuid,
+    request: InviteMember,
+    client_info: &ClientInfo,
+) -> Result<(), ServiceError> {
+    let roles = normalize_roles(&request.roles);
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if let Err(e) =
+            require_member_with_connection(conn, organization_id, actor_id, &[ORG_OWNER_ROLE])?
+        {
+            return Ok(Err(e));
+        }
+        organizations_repo::lock_with_connection(conn, organization_id)?;
+        let identifier = request.user.trim();
+        let user = match users_repo::find_by_email_with_connection(conn, identifier)? {
+            Some(user) => Some(user),
+            None => users_repo::find_by_username_with_connection(conn, identifier)?,
+        };
+        let Some(user) = user else {
+            return Ok(Ok(()));
+        };
+        let invitation =
+            match memberships_repo::find_any_with_connection(conn, organization_id, user.id)? {
+                Some(membership) if !membership.is_pending() => return Ok(Ok(())),
+                Some(_) => memberships_repo::update_roles_with_connection(
+                    conn,
+                    organization_id,
+                    user.id,
+                    &roles,
+                )?,
+                None => memberships_repo::create_with_connection(
+                    conn,
+                    NewMembership {
+                        organization_id,
+                        user_id: user.id,
+                        roles,
+                        accepted_at: None,
+                    },
+                )?,
+            };
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.members.invite",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(actor_id),
+                target_user_id: Some(user.id),
+                client: Some(client_info),
+                details: json!({
+                    "organization_id": organization_id,
+                    "roles": invitation.roles,
+                }),
+            },
+        )?;
+        Ok(Ok(()))
+    })?
+}
+
+// * L'utente invitato accetta e diventa membro con i ruoli offerti
+pub fn accept_invitation(
+    pool: &PgPool,
+    organization_id: Uuid,
+    user_id: Uuid,
+    client_info: &ClientInfo,
+) -> Result<(Organization, Membership), ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    let accepted = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if memberships_repo::find_invitation_with_connection(conn, organization_id, user_id)?
+            .is_none()
+        {
+            return Ok(Err(ServiceError::NotFound("Invitation not found".into())));
+        }
+        let organization = organizations_repo::lock_with_connection(conn, organization_id)?;
+        let membership = memberships_repo::accept_with_connection(conn, organization_id, user_id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.invitation.accepted",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(client_info),
+                details: json!({
+                    "organization_id": organization_id,
+                    "roles": membership.roles,
+                }),
+            },
+        )?;
+        Ok(Ok((organization, membership)))
+    })??;
+    Ok(accepted)
+}
+
+// * Sostituisce i ruoli di un membro. Solo i proprietari
+pub fn update_member(
+    pool: &PgPool,
+    organization_id: Uuid,
+    actor_id: Uuid,
+    member_id: Uuid,
+    roles: &[String],
+    client_info: &ClientInfo,
+) -> Result<Membership, ServiceError> {
+    let roles = normalize_roles(roles);
+    let mut conn = establish_connection(pool)?;
+    let updated = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if let Err(e) =
+            require_member_with_connection(conn, organization_id, actor_id, &[ORG_OWNER_ROLE])?
+        {
+            return Ok(Err(e));
+        }
+        organizations_repo::lock_with_connection(conn, organization_id)?;
+        let Some(member) =
+            memberships_repo::find_with_connection(conn, organization_id, member_id)?
+        else {
+            return Ok(Err(ServiceError::NotFound("Member not found".into())));
+        };
+        if let Err(e) = ensure_owner_remains_with_connection(conn, &member, &roles)? {
+            return Ok(Err(e));
+        }
+        let updated = memberships_repo::update_roles_with_connection(
+            conn,
+            organization_id,
+            member_id,
+            &roles,
+        )?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.members.update",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(actor_id),
+                target_user_id: Some(member_id),
+                client: Some(client_info),
+                details: json!({
+                    "organization_id": organization_id,
+                    "previous_roles": member.roles,
+                    "roles": updated.roles,
+                }),
+            },
+        )?;
+        Ok(Ok(updated))
+    })??;
+    Ok(updated)
+}
+
+// * Rimuove un membro: lo fa un proprietario, oppure il membro stesso per uscire. I token già
+// * emessi restano validi fino alla scadenza, ma al rinnovo non riportano più l'organizzazione.
+// * Un utente invitato rifiuta l'invito rimuovendo se stesso
+pub fn remove_member(
+    pool: &PgPool,
+    organization_id: Uuid,
+    actor_id: Uuid,
+    member_id: Uuid,
+    client_info: &ClientInfo,
+) -> Result<(), ServiceError> {
+    let mut conn = establish_connection(pool)?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        if actor_id == member_id {
+            if let Some(invitation) =
+                memberships_repo::find_invitation_with_connection(conn, organization_id, actor_id)?
+            {
+                memberships_repo::delete_with_connection(conn, organization_id, actor_id)?;
+                audit::record_with_connection(
+                    conn,
+                    AuditEvent {
+                        action: "organization.invitation.declined",
+                        outcome: AuditOutcome::Success,
+                        actor_id: Some(actor_id),
+                        target_user_id: Some(actor_id),
+                        client: Some(client_info),
+                        details: json!({
+                            "organization_id": organization_id,
+                            "roles": invitation.roles,
+                        }),
+                    },
+                )?;
+                return Ok(Ok(()));
+            }
+        }
+        let allowed_roles: &[&str] = if actor_id == member_id {
+            &ORG_ROLES
+        } else {
+            &[ORG_OWNER_ROLE]
+        };
+        if let Err(e) =
+            require_member_with_connection(conn, organization_id, actor_id, allowed_roles)?
+        {
+            return Ok(Err(e));
+        }
+        organizations_repo::lock_with_connection(conn, organization_id)?;
+        let Some(member) =
+            memberships_repo::find_with_connection(conn, organization_id, member_id)?
+        else {
+            return Ok(Err(ServiceError::NotFound("Member not found".into())));
+        };
+        if let Err(e) = ensure_owner_remains_with_connection(conn, &member, &[])? {
+            return Ok(Err(e));
+        }
+        memberships_repo::delete_with_connection(conn, organization_id, member_id)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "organization.members.remove",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(actor_id),
+                target_user_id: Some(member_id),
+                client: Some(client_info),
+                details: json!({
+                    "organization_id": organization_id,
+                    "roles": member.roles,
+                }),
+            },
+        )?;
+        Ok(Ok(()))
+    })?
+}
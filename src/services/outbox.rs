@@ -0,0 +1,119 @@
+use std::time::Duration as StdDuration;
+
+use actix_web::{rt, web};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    repositories::{
+        establish_connection,
+        outbox_repo::{self, OutboxEvent},
+        PgPool,
+    },
+    services::http_client::{HttpClient, HttpError},
+    DbPool,
+};
+
+// * Eventi pubblicati in una transazione: restano bloccati finché il lotto non è consegnato
+const RELAY_BATCH_SIZE: i64 = 50;
+
+#[derive(Error, Debug)]
+pub enum RelayError {
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+    #[error("Delivery of event {0} failed: {1}")]
+    Delivery(Uuid, HttpError),
+}
+
+/// Body of the POST sent to `outbox_webhook_url` for every event. Delivery is
+/// at least once: receivers deduplicate on `id`.
+#[derive(Serialize, Debug)]
+pub struct PublishedEvent<'a> {
+    pub id: Uuid,
+    pub event_type: &'a str,
+    pub aggregate_id: Uuid,
+    pub payload: &'a Value,
+    pub created_at: NaiveDateTime,
+}
+
+fn deliver(http: &HttpClient, url: &str, event: &OutboxEvent) -> Result<(), HttpError> {
+    let response = http.post_json(
+        url,
+        &PublishedEvent {
+            id: event.id,
+            event_type: &event.event_type,
+            aggregate_id: event.aggregate_id,
+            payload: &event.payload,
+            created_at: event.created_at,
+        },
+    )?;
+    if !response.is_success() {
+        return Err(HttpError::Status(response.status));
+    }
+    Ok(())
+}
+
+// * Consegna un lotto nell'ordine di creazione. Al primo errore si ferma: gli eventi già
+// * consegnati vengono segnati come pubblicati, gli altri restano per il passaggio successivo
+fn relay_batch(pool: &PgPool, http: &HttpClient, url: &str) -> Result<(usize, bool), RelayError> {
+    let mut conn = establish_connection(pool)?;
+    let (published, failure, full) = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let events = outbox_repo::lock_unpublished_with_connection(conn, RELAY_BATCH_SIZE)?;
+        let mut delivered = Vec::new();
+        let mut failure = None;
+        for event in &events {
+            match deliver(http, url, event) {
+                Ok(()) => delivered.push(event.id),
+                Err(e) => {
+                    failure = Some(RelayError::Delivery(event.id, e));
+                    break;
+                }
+            }
+        }
+        outbox_repo::mark_published_with_connection(conn, &delivered)?;
+        Ok((
+            delivered.len(),
+            failure,
+            events.len() as i64 == RELAY_BATCH_SIZE,
+        ))
+    })?;
+    match failure {
+        Some(e) => Err(e),
+        None => Ok((published, full)),
+    }
+}
+
+// * Pubblica tutti gli eventi in coda e restituisce quanti ne sono stati consegnati
+pub fn relay_pending(pool: &PgPool, http: &HttpClient, url: &str) -> Result<usize, RelayError> {
+    let mut published = 0;
+    loop {
+        let (batch, full) = relay_batch(pool, http, url)?;
+        published += batch;
+        if !full {
+            return Ok(published);
+        }
+    }
+}
+
+// * Avvia il job periodico che pubblica gli eventi dell'outbox verso `url`
+pub fn spawn_relay_job(pool: DbPool, url: String, interval_secs: u64) {
+    let http = HttpClient::default();
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(StdDuration::from_secs(interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            let (pool, http, url) = (pool.clone(), http.clone(), url.clone());
+            match web::block(move || relay_pending(&pool, &http, &url)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(published)) => info!("Published {} outbox events", published),
+                Ok(Err(e)) => error!("Outbox relay failed: {}", e),
+                Err(e) => error!("Outbox relay failed: {:?}", e),
+            }
+        }
+    });
+}
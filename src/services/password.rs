@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use argon2::{
+    password_hash::{Error as HashError, PasswordHash, SaltString},
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHasher, PasswordVerifier, Version,
+};
+use config::ConfigError;
+use rand::rngs::OsRng;
+use tracing::warn;
+
+use crate::config::app_config::{AppConfig, PepperConfig};
+
+/// Hashes and verifies passwords with Argon2id, mixing in an optional
+/// server-side pepper through Argon2's secret parameter.
+///
+/// The pepper version is stored in the PHC string as the `keyid` parameter,
+/// so old peppers can stay configured for verification while new hashes use
+/// the highest configured version.
+#[derive(Clone, Default)]
+pub struct PasswordService {
+    peppers: BTreeMap<u32, Vec<u8>>,
+}
+
+impl PasswordService {
+    pub fn new(peppers: BTreeMap<u32, Vec<u8>>) -> Self {
+        Self { peppers }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Result<Self, ConfigError> {
+        let mut peppers = BTreeMap::new();
+        for pepper in &config.password_peppers {
+            let secret = load_pepper(pepper)?;
+            if peppers.insert(pepper.version, secret).is_some() {
+                return Err(ConfigError::Message(format!(
+                    "Duplicate password pepper version {}",
+                    pepper.version
+                )));
+            }
+        }
+        Ok(Self::new(peppers))
+    }
+
+    // * Versione del pepper usata per i nuovi hash (None se il pepper non è configurato)
+    pub fn current_version(&self) -> Option<u32> {
+        self.peppers.keys().next_back().copied()
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, HashError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = match self.current_version() {
+            Some(version) => {
+                let params = ParamsBuilder::new()
+                    .keyid(KeyId::new(version.to_string().as_bytes())?)
+                    .build()?;
+                self.argon2(Some(version), params)?
+                    .hash_password(password.as_bytes(), &salt)?
+            }
+            None => Argon2::default().hash_password(password.as_bytes(), &salt)?,
+        };
+        Ok(hash.to_string())
+    }
+
+    // * Verifica la password di un account: senza password locale (solo login esterni) fallisce
+    pub fn verify_account(
+        &self,
+        password: &str,
+        stored_hash: Option<&str>,
+    ) -> Result<(), HashError> {
+        match stored_hash {
+            Some(stored_hash) => self.verify(password, stored_hash),
+            None => Err(HashError::Password),
+        }
+    }
+
+    pub fn verify(&self, password: &str, stored_hash: &str) -> Result<(), HashError> {
+        let parsed_hash = PasswordHash::new(stored_hash)?;
+        let version = pepper_version(&parsed_hash)?;
+        if let Some(version) = version {
+            if !self.peppers.contains_key(&version) {
+                warn!("Password hash uses unknown pepper version {}", version);
+                return Err(HashError::Password);
+            }
+        }
+        let params = Params::try_from(&parsed_hash)?;
+        self.argon2(version, params)?
+            .verify_password(password.as_bytes(), &parsed_hash)
+    }
+
+    // * Indica se l'hash salvato va rigenerato con il pepper corrente
+    pub fn needs_rehash(&self, stored_hash: &str) -> bool {
+        match PasswordHash::new(stored_hash) {
+            Ok(parsed_hash) => match pepper_version(&parsed_hash) {
+                Ok(version) => version != self.current_version(),
+                Err(_) => true,
+            },
+            Err(_) => true,
+        }
+    }
+
+    fn argon2(&self, version: Option<u32>, params: Params) -> Result<Argon2<'_>, HashError> {
+        match version.and_then(|v| self.peppers.get(&v)) {
+            Some(secret) => Ok(Argon2::new_with_secret(
+                secret,
+                Algorithm::Argon2id,
+                Version::V0x13,
+                params,
+            )?),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+}
+
+fn pepper_version(parsed_hash: &PasswordHash<'_>) -> Result<Option<u32>, HashError> {
+    let params = Params::try_from(parsed_hash)?;
+    if params.keyid().is_empty() {
+        return Ok(None);
+    }
+    std::str::from_utf8(params.keyid())
+        .ok()
+        .and_then(|keyid| keyid.parse().ok())
+        .map(Some)
+        .ok_or(HashError::ParamValueInvalid(
+            argon2::password_hash::errors::InvalidValue::Malformed,
+        ))
+}
+
+fn load_pepper(pepper: &PepperConfig) -> Result<Vec<u8>, ConfigError> {
+    let secret = match (&pepper.file, &pepper.env) {
+        (Some(path), _) => std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::Message(format!(
+                "Failed to read password pepper v{} from {}: {}",
+                pepper.version, path, e
+            ))
+        })?,
+        (None, Some(var)) => std::env::var(var).map_err(|_| {
+            ConfigError::Message(format!(
+                "Password pepper v{} env var {} is not set",
+                pepper.version, var
+            ))
+        })?,
+        (None, None) => {
+            return Err(ConfigError::Message(format!(
+                "Password pepper v{} needs either `file` or `env`",
+                pepper.version
+            )))
+        }
+    };
+    let secret = secret.trim_end_matches(['\r', '\n']).as_bytes().to_vec();
+    if secret.is_empty() {
+        return Err(ConfigError::Message(format!(
+            "Password pepper v{} is empty",
+            pepper.version
+        )));
+    }
+    Ok(secret)
+}
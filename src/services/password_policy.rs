@@ -0,0 +1,243 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+use utoipa::openapi::{schema::Schema, OpenApi, RefOr};
+use utoipa::ToSchema;
+
+use crate::config::app_config::PasswordPolicyConfig;
+use crate::services::breached_passwords::BreachedPasswords;
+use crate::services::password::PasswordService;
+
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    MinLength,
+    MaxLength,
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+    Strength,
+    ContainsUserInfo,
+    Breached,
+    Reused,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub rule: PolicyRule,
+    pub message: String,
+}
+
+// * Dati dell'utente usati per i controlli che dipendono dal contesto
+#[derive(Default)]
+pub struct PasswordContext<'a> {
+    pub username: &'a str,
+    pub email: &'a str,
+    // * Hash delle password precedenti (inclusa quella attuale), dal più recente
+    pub previous_hashes: &'a [String],
+}
+
+/// Single place where password rules live: length, character classes,
+/// estimated strength, user info, breach corpus and reuse history, all
+/// driven by `[password_policy]` in the config.
+#[derive(Clone, Default)]
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+    breached_passwords: BreachedPasswords,
+}
+
+impl PasswordPolicy {
+    pub fn new(config: PasswordPolicyConfig, breached_passwords: BreachedPasswords) -> Self {
+        Self {
+            config,
+            breached_passwords,
+        }
+    }
+
+    pub fn config(&self) -> &PasswordPolicyConfig {
+        &self.config
+    }
+
+    pub fn check(
+        &self,
+        password: &str,
+        context: &PasswordContext<'_>,
+        password_service: &PasswordService,
+    ) -> Result<(), Vec<PolicyViolation>> {
+        let config = &self.config;
+        let mut violations = Vec::new();
+        let mut violation =
+            |rule, message: String| violations.push(PolicyViolation { rule, message });
+
+        let length = password.chars().count();
+        if length < config.min_length {
+            violation(
+                PolicyRule::MinLength,
+                format!("Password must be at least {} characters", config.min_length),
+            );
+        }
+        if length > config.max_length {
+            violation(
+                PolicyRule::MaxLength,
+                format!("Password must be at most {} characters", config.max_length),
+            );
+        }
+        if config.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violation(
+                PolicyRule::Lowercase,
+                "Password must contain at least one lowercase letter".into(),
+            );
+        }
+        if config.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violation(
+                PolicyRule::Uppercase,
+                "Password must contain at least one uppercase letter".into(),
+            );
+        }
+        if config.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violation(
+                PolicyRule::Digit,
+                "Password must contain at least one digit".into(),
+            );
+        }
+        if config.require_symbol && !password.chars().any(|c| !c.is_alphanumeric()) {
+            violation(
+                PolicyRule::Symbol,
+                "Password must contain at least one symbol or space".into(),
+            );
+        }
+
+        let user_inputs = user_inputs(context);
+        if config.forbid_user_info {
+            let lowered = password.to_lowercase();
+            if user_inputs
+                .iter()
+                .any(|input| lowered.contains(input.as_str()))
+            {
+                violation(
+                    PolicyRule::ContainsUserInfo,
+                    "Password must not contain your username or email".into(),
+                );
+            }
+        }
+
+        let score = strength_score(password, &user_inputs);
+        if score < config.min_strength_score {
+            violation(
+                PolicyRule::Strength,
+                format!(
+                    "Password is too easy to guess (strength {} of 4, at least {} required)",
+                    score, config.min_strength_score
+                ),
+            );
+        }
+
+        if self.breached_passwords.is_breached(password) {
+            violation(
+                PolicyRule::Breached,
+                "This password has appeared in a known data breach, please choose another one"
+                    .into(),
+            );
+        }
+
+        if config.history_depth > 0
+            && context
+                .previous_hashes
+                .iter()
+                .take(config.history_depth)
+                .any(|hash| password_service.verify(password, hash).is_ok())
+        {
+            violation(
+                PolicyRule::Reused,
+                format!(
+                    "Password must differ from your last {} passwords",
+                    config.history_depth
+                ),
+            );
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    // * Indica se la password ha superato l'età massima configurata
+    pub fn is_expired(&self, password_changed_at: NaiveDateTime) -> bool {
+        self.config.max_age_days.is_some_and(|days| {
+            password_changed_at + Duration::days(days as i64) < Utc::now().naive_utc()
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let config = &self.config;
+        let mut classes = Vec::new();
+        if config.require_lowercase {
+            classes.push("a lowercase letter");
+        }
+        if config.require_uppercase {
+            classes.push("an uppercase letter");
+        }
+        if config.require_digit {
+            classes.push("a digit");
+        }
+        if config.require_symbol {
+            classes.push("a symbol or space");
+        }
+        let mut description = format!("{}-{} characters", config.min_length, config.max_length);
+        if !classes.is_empty() {
+            description.push_str(&format!(", containing {}", classes.join(", ")));
+        }
+        description.push_str(&format!(
+            ". Minimum strength score {} of 4",
+            config.min_strength_score
+        ));
+        if config.forbid_user_info {
+            description.push_str(", must not contain the username or email");
+        }
+        if config.history_depth > 0 {
+            description.push_str(&format!(
+                ", must differ from the last {} passwords",
+                config.history_depth
+            ));
+        }
+        description.push('.');
+        description
+    }
+
+    // * Riporta le regole configurate negli schemi OpenAPI che contengono una password
+    pub fn document(&self, openapi: &mut OpenApi, schemas: &[(&str, &str)]) {
+        let Some(components) = openapi.components.as_mut() else {
+            return;
+        };
+        for (schema_name, property) in schemas {
+            let Some(RefOr::T(Schema::Object(object))) = components.schemas.get_mut(*schema_name)
+            else {
+                continue;
+            };
+            if let Some(RefOr::T(Schema::Object(field))) = object.properties.get_mut(*property) {
+                field.min_length = Some(self.config.min_length);
+                field.max_length = Some(self.config.max_length);
+                field.pattern = None;
+                field.description = Some(self.describe());
+            }
+        }
+    }
+}
+
+fn user_inputs(context: &PasswordContext<'_>) -> Vec<String> {
+    let local_part = context.email.split('@').next().unwrap_or_default();
+    [context.username, local_part]
+        .iter()
+        .map(|input| input.to_lowercase())
+        .filter(|input| input.chars().count() >= 3)
+        .collect()
+}
+
+/// Strength estimate from [`zxcvbn`], on its 0-4 scale. The user inputs
+/// (username and email local part) count as dictionary words.
+pub fn strength_score(password: &str, user_inputs: &[String]) -> u8 {
+    let user_inputs: Vec<&str> = user_inputs.iter().map(String::as_str).collect();
+    zxcvbn::zxcvbn(password, &user_inputs).score().into()
+}
@@ -0,0 +1,160 @@
+use chrono::{Duration, Utc};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome, personal_access_token::CreatePersonalAccessToken, user::User,
+    },
+    repositories::{
+        as_user,
+        personal_access_tokens_repo::{self, NewPersonalAccessToken, PersonalAccessToken},
+        users_repo, PgPool,
+    },
+    services::{
+        audit::{self, AuditEvent},
+        oauth,
+    },
+};
+
+/// Prefix of every personal access token, so that secret scanners can
+/// recognise leaked keys.
+pub const TOKEN_PREFIX: &str = "kapp_pat_";
+
+// * Caratteri casuali mostrati dopo il prefisso negli elenchi
+const DISPLAY_PREFIX_CHARS: usize = 8;
+
+// * Token non revocati che un utente può avere contemporaneamente
+const MAX_TOKENS_PER_USER: i64 = 50;
+
+pub fn generate_token() -> String {
+    format!("{}{}", TOKEN_PREFIX, oauth::generate_token())
+}
+
+pub fn is_personal_access_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+// * Prefisso e primi caratteri casuali: bastano a riconoscere la chiave senza rivelarla
+pub fn display_prefix(token: &str) -> String {
+    token
+        .chars()
+        .take(TOKEN_PREFIX.len() + DISPLAY_PREFIX_CHARS)
+        .collect()
+}
+
+// * Crea un token per l'utente; la chiave in chiaro viene restituita solo qui
+pub fn create(
+    pool: &PgPool,
+    user_id: Uuid,
+    request: CreatePersonalAccessToken,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<(PersonalAccessToken, String), ServiceError> {
+    if let Some(scope) = request.scopes.iter().find(|scope| {
+        !app_config
+            .personal_access_token_scopes
+            .iter()
+            .any(|allowed| allowed == *scope)
+    }) {
+        return Err(ServiceError::ValidationError(format!(
+            "The scope {} cannot be granted to personal access tokens",
+            scope
+        )));
+    }
+    let mut scopes = request.scopes;
+    scopes.sort();
+    scopes.dedup();
+
+    let secret = generate_token();
+    let new_token = NewPersonalAccessToken {
+        user_id,
+        name: request.name.trim().to_string(),
+        token_prefix: display_prefix(&secret),
+        token_hash: oauth::hash_token(&secret),
+        scopes,
+        expires_at: request
+            .expires_in_days
+            .map(|days| (Utc::now() + Duration::days(days as i64)).naive_utc()),
+    };
+    let created = as_user(pool, user_id, |conn| {
+        // * Il lock sull'utente serializza le creazioni concorrenti, così il limite regge
+        users_repo::lock_user_with_connection(conn, user_id)?;
+        if personal_access_tokens_repo::count_for_user_with_connection(conn, user_id)?
+            >= MAX_TOKENS_PER_USER
+        {
+            return Ok(Err(ServiceError::Conflict(format!(
+                "At most {} personal access tokens can be active, revoke one first",
+                MAX_TOKENS_PER_USER
+            ))));
+        }
+        let created = personal_access_tokens_repo::create_with_connection(conn, new_token)?;
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "personal_access_token.created",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(user_id),
+                target_user_id: Some(user_id),
+                client: Some(client_info),
+                details: json!({
+                    "token_id": created.id,
+                    "name": created.name,
+                    "token_prefix": created.token_prefix,
+                    "scopes": created.scopes,
+                    "expires_at": created.expires_at,
+                }),
+            },
+        )?;
+        Ok(Ok(created))
+    })??;
+    Ok((created, secret))
+}
+
+pub fn revoke(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_id: Uuid,
+    client_info: &ClientInfo,
+) -> Result<PersonalAccessToken, ServiceError> {
+    let revoked = as_user(pool, user_id, |conn| {
+        let revoked = personal_access_tokens_repo::revoke_with_connection(conn, user_id, token_id)?;
+        if let Some(token) = &revoked {
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "personal_access_token.revoked",
+                    outcome: AuditOutcome::Success,
+                    actor_id: Some(user_id),
+                    target_user_id: Some(user_id),
+                    client: Some(client_info),
+                    details: json!({
+                        "token_id": token.id,
+                        "name": token.name,
+                        "token_prefix": token.token_prefix,
+                    }),
+                },
+            )?;
+        }
+        Ok(revoked)
+    })?;
+    revoked.ok_or_else(|| ServiceError::NotFound("Personal access token not found".into()))
+}
+
+// * Risolve la chiave presentata come Bearer: il token deve essere valido e l'account attivo
+pub fn authenticate(
+    pool: &PgPool,
+    secret: &str,
+) -> Result<(PersonalAccessToken, User), ServiceError> {
+    let token = personal_access_tokens_repo::find_active_by_hash(pool, &oauth::hash_token(secret))?
+        .ok_or_else(|| {
+            ServiceError::Unauthorized("Invalid, expired or revoked personal access token".into())
+        })?;
+    let user = users_repo::get_own_user(pool, token.user_id)
+        .map_err(|_| ServiceError::Unauthorized("User no longer exists".into()))?;
+    user.ensure_active()?;
+    Ok((token, user))
+}
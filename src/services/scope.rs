@@ -0,0 +1,71 @@
+use utoipa::openapi::{
+    security::{AuthorizationCode, ClientCredentials, Flow, OAuth2, Scopes, SecurityScheme},
+    OpenApi,
+};
+
+use crate::{
+    config::app_config::AppConfig,
+    models::role::{Permission, RoleGrants},
+    services::oidc,
+};
+
+/// Name of the OAuth2 security scheme in the OpenAPI document.
+pub const SECURITY_SCHEME: &str = "oauth2";
+
+// * Scope delle API di amministrazione, concesso al ruolo admin da role_permissions
+pub const ADMIN_SCOPE: &str = "admin";
+
+// * Scope delegati a un client o a un personal access token: quelli del catalogo restano solo
+// * se i ruoli dell'utente li concedono, gli altri (es. openid) passano invariati
+pub fn restrict_to_grants<'a>(
+    grants: &RoleGrants,
+    scopes: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    scopes
+        .into_iter()
+        .filter(|scope| !grants.withheld_scopes.iter().any(|w| w == scope))
+        .map(str::to_string)
+        .collect()
+}
+
+// * Pubblica il catalogo (permesso e ruoli che lo concedono) come security scheme OAuth2,
+// * con gli scope OpenID Connect
+pub fn document(
+    openapi: &mut OpenApi,
+    catalogue: &[(Permission, Vec<String>)],
+    app_config: &AppConfig,
+) {
+    let mut entries: Vec<(String, String)> = catalogue
+        .iter()
+        .map(|(permission, roles)| {
+            let description = if roles.is_empty() {
+                permission.description.clone()
+            } else {
+                format!("{} (roles: {})", permission.description, roles.join(", "))
+            };
+            (permission.name.clone(), description)
+        })
+        .collect();
+    entries.extend(
+        oidc::SCOPES
+            .iter()
+            .map(|scope| (scope.to_string(), format!("OpenID Connect {} scope", scope))),
+    );
+    let scopes = || entries.iter().cloned().collect::<Scopes>();
+    let token_url = app_config.oidc_url("/oauth/token");
+    let mut scheme = OAuth2::new([
+        Flow::AuthorizationCode(AuthorizationCode::new(
+            app_config.oidc_url("/oauth/authorize"),
+            token_url.clone(),
+            scopes(),
+        )),
+        Flow::ClientCredentials(ClientCredentials::new(token_url, scopes())),
+    ]);
+    scheme.description = Some(
+        "Bearer access token. Direct logins carry the scopes of the user's roles, OAuth clients the scopes they were granted".into(),
+    );
+    openapi
+        .components
+        .get_or_insert_with(Default::default)
+        .add_security_scheme(SECURITY_SCHEME, SecurityScheme::OAuth2(scheme));
+}
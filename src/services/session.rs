@@ -0,0 +1,314 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::{rngs::OsRng, RngCore};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::ServiceError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome, claims::Claims, oauth::OAuthClient, organization::Membership,
+        role::RoleGrants, user::User,
+    },
+    repositories::{
+        memberships_repo,
+        sessions_repo::{self, NewSession, Session},
+        users_repo, PgPool,
+    },
+    services::{
+        audit::{self, AuditEvent},
+        password::PasswordService,
+        password_policy::PasswordPolicy,
+    },
+};
+
+// * Durata del token limitato al cambio password
+const RESTRICTED_TOKEN_EXP_SECS: i64 = 15 * 60;
+
+/// A session together with the plaintext refresh token, which is only
+/// available right after the session is created or rotated, and the
+/// membership of the user in the active organization of the session.
+pub struct IssuedSession {
+    pub session: Session,
+    pub refresh_token: String,
+    pub organization: Option<Membership>,
+}
+
+/// Audience and lifetimes of the tokens of a session: those set on the OAuth
+/// client, falling back to the global configuration.
+#[derive(Debug, Clone)]
+pub struct TokenSettings {
+    pub audience: String,
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_secs: i64,
+    pub idle_timeout_secs: Option<i64>,
+}
+
+impl TokenSettings {
+    // * Login diretti dalle app di KetchApp, senza client OAuth
+    pub fn global(app_config: &AppConfig) -> Self {
+        Self {
+            audience: app_config.jwt_audience.clone(),
+            access_token_ttl_secs: app_config.jwt_exp_secs as i64,
+            refresh_token_ttl_secs: app_config.refresh_token_exp_secs as i64,
+            idle_timeout_secs: app_config.session_idle_timeout_secs.map(|secs| secs as i64),
+        }
+    }
+
+    pub fn for_client(client: &OAuthClient, app_config: &AppConfig) -> Self {
+        let global = Self::global(app_config);
+        Self {
+            audience: client.audience.clone().unwrap_or(global.audience),
+            access_token_ttl_secs: client
+                .access_token_ttl_secs
+                .map_or(global.access_token_ttl_secs, i64::from),
+            refresh_token_ttl_secs: client
+                .refresh_token_ttl_secs
+                .map_or(global.refresh_token_ttl_secs, i64::from),
+            idle_timeout_secs: client
+                .idle_timeout_secs
+                .map(i64::from)
+                .or(global.idle_timeout_secs),
+        }
+    }
+}
+
+pub fn generate_refresh_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+pub fn hash_refresh_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// * Il cambio password è imposto o la password è scaduta. Un account senza password locale
+// * non ha una password che possa scadere
+pub fn password_change_required(user: &User, password_policy: &PasswordPolicy) -> bool {
+    user.must_change_password
+        || (user.password.is_some() && password_policy.is_expired(user.password_changed_at))
+}
+
+// * Claims del token di accesso legato alla sessione, con i ruoli dell'utente e gli scope che
+// * concedono, l'organizzazione attiva con i ruoli in essa, audience e durata di settings. Se la
+// * password è scaduta o il cambio è imposto, il token è limitato al cambio password e ha vita breve.
+pub fn access_claims(
+    user: &User,
+    grants: RoleGrants,
+    session_id: Uuid,
+    organization: Option<&Membership>,
+    settings: &TokenSettings,
+    app_config: &AppConfig,
+    password_policy: &PasswordPolicy,
+) -> Claims {
+    let password_change_required = password_change_required(user, password_policy);
+    let ttl_secs = if password_change_required {
+        RESTRICTED_TOKEN_EXP_SECS.min(settings.access_token_ttl_secs)
+    } else {
+        settings.access_token_ttl_secs
+    };
+    let mut claims = Claims::with_ttl(user.id.to_string(), app_config, ttl_secs);
+    claims.aud = settings.audience.clone();
+    claims.password_change_required = password_change_required;
+    claims.sid = Some(session_id.to_string());
+    claims.set_scopes(&grants.scopes);
+    claims.roles = grants.roles;
+    if let Some(membership) = organization {
+        claims.org_id = Some(membership.organization_id.to_string());
+        claims.org_roles = membership.roles.clone();
+    }
+    claims
+}
+
+// * Crea una nuova sessione (famiglia di refresh token) per l'utente
+pub fn start_session_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    client: &ClientInfo,
+    app_config: &AppConfig,
+) -> Result<IssuedSession, diesel::result::Error> {
+    start_client_session_with_connection(conn, user_id, client, app_config, None)
+}
+
+/// OAuth client a session is issued to, with the scopes the user granted
+/// and the time of the login that authorized it.
+pub struct SessionGrant<'a> {
+    pub client: &'a OAuthClient,
+    pub scope: &'a str,
+    pub auth_time: NaiveDateTime,
+}
+
+// * Come start_session_with_connection; con un grant la sessione appartiene al client OAuth,
+// * dura quanto stabilito per il client e il suo refresh token vale solo all'endpoint /oauth/token.
+// * Se l'utente appartiene a una sola organizzazione, la sessione parte con quella attiva
+pub fn start_client_session_with_connection(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    client: &ClientInfo,
+    app_config: &AppConfig,
+    grant: Option<SessionGrant<'_>>,
+) -> Result<IssuedSession, diesel::result::Error> {
+    let refresh_token = generate_refresh_token();
+    let organization = memberships_repo::only_for_user_with_connection(conn, user_id)?;
+    let settings = match &grant {
+        Some(grant) => TokenSettings::for_client(grant.client, app_config),
+        None => TokenSettings::global(app_config),
+    };
+    let session = sessions_repo::create_session_with_connection(
+        conn,
+        NewSession {
+            user_id,
+            refresh_token_hash: hash_refresh_token(&refresh_token),
+            device: client.device.clone(),
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.map(|ip| ip.to_string()),
+            expires_at: (Utc::now() + Duration::seconds(settings.refresh_token_ttl_secs))
+                .naive_utc(),
+            oauth_client_id: grant.as_ref().map(|g| g.client.id),
+            auth_time: grant.as_ref().map(|g| g.auth_time),
+            scope: grant.map(|g| g.scope.to_string()),
+            organization_id: organization.as_ref().map(|m| m.organization_id),
+        },
+    )?;
+    Ok(IssuedSession {
+        session,
+        refresh_token,
+        organization,
+    })
+}
+
+// * Ruota il refresh token. Se viene presentato un token già ruotato la sessione
+// * è considerata compromessa e viene revocata. Il token deve appartenere al client
+// * OAuth indicato (None per i login diretti) e la sessione non deve aver superato il
+// * timeout di inattività previsto per quel client.
+pub fn refresh_with_connection(
+    conn: &mut PgConnection,
+    refresh_token: &str,
+    client: &ClientInfo,
+    oauth_client: Option<&OAuthClient>,
+    app_config: &AppConfig,
+) -> Result<IssuedSession, ServiceError> {
+    let token_hash = hash_refresh_token(refresh_token);
+    let idle_timeout_secs = match oauth_client {
+        Some(oauth_client) => TokenSettings::for_client(oauth_client, app_config),
+        None => TokenSettings::global(app_config),
+    }
+    .idle_timeout_secs;
+    let invalid = || ServiceError::Unauthorized("Invalid or expired refresh token".into());
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let Some(session) = sessions_repo::find_active_by_refresh_hash(conn, &token_hash)?
+            .filter(|session| session.oauth_client_id == oauth_client.map(|c| c.id))
+        else {
+            if let Some(reused) = sessions_repo::find_by_previous_refresh_hash(conn, &token_hash)? {
+                warn!(
+                    "Refresh token reuse detected for session {}, revoking it",
+                    reused.id
+                );
+                sessions_repo::revoke_with_connection(conn, reused.user_id, reused.id)?;
+                audit::record_with_connection(
+                    conn,
+                    AuditEvent {
+                        action: "session.revoked",
+                        outcome: AuditOutcome::Success,
+                        actor_id: None,
+                        target_user_id: Some(reused.user_id),
+                        client: Some(client),
+                        details: json!({ "session_id": reused.id, "reason": "refresh_token_reuse" }),
+                    },
+                )?;
+            }
+            return Ok(Err(invalid()));
+        };
+        // * Una sessione inutilizzata oltre il timeout di inattività non si può più rinnovare
+        if idle_timeout_secs.is_some_and(|idle| {
+            session.last_seen_at + Duration::seconds(idle) < Utc::now().naive_utc()
+        }) {
+            sessions_repo::revoke_with_connection(conn, session.user_id, session.id)?;
+            audit::record_with_connection(
+                conn,
+                AuditEvent {
+                    action: "session.revoked",
+                    outcome: AuditOutcome::Success,
+                    actor_id: None,
+                    target_user_id: Some(session.user_id),
+                    client: Some(client),
+                    details: json!({ "session_id": session.id, "reason": "idle_timeout" }),
+                },
+            )?;
+            return Ok(Err(invalid()));
+        }
+        // * Un account non più attivo non ottiene nuovi token: il refresh token presentato non
+        // * viene ruotato
+        let user = users_repo::get_user_by_id_with_connection(conn, session.user_id)?;
+        if let Err(e) = user.ensure_active() {
+            return Ok(Err(e));
+        }
+        let new_token = generate_refresh_token();
+        let session = sessions_repo::rotate_refresh_token(
+            conn,
+            session.id,
+            &token_hash,
+            &hash_refresh_token(&new_token),
+        )?;
+        // * Se nel frattempo l'utente è uscito dall'organizzazione, i token non la riportano più
+        let organization = active_membership_with_connection(conn, &session)?;
+        Ok(Ok(IssuedSession {
+            session,
+            refresh_token: new_token,
+            organization,
+        }))
+    })?
+}
+
+// * Appartenenza dell'utente della sessione alla sua organizzazione attiva, se è ancora membro
+pub fn active_membership_with_connection(
+    conn: &mut PgConnection,
+    session: &Session,
+) -> Result<Option<Membership>, diesel::result::Error> {
+    match session.organization_id {
+        Some(organization_id) => {
+            memberships_repo::find_with_connection(conn, organization_id, session.user_id)
+        }
+        None => Ok(None),
+    }
+}
+
+// * Conferma dell'identità per le operazioni sensibili: la password attuale se l'account ne ha
+// * una, altrimenti un login della sessione avvenuto entro reauthentication_max_age_secs.
+// * Restituisce il motivo del rifiuto, da registrare nel log di audit
+pub fn reauthentication_failure(
+    pool: &PgPool,
+    password_service: &PasswordService,
+    user: &User,
+    password: Option<&str>,
+    session_id: Option<Uuid>,
+    app_config: &AppConfig,
+) -> Result<Option<&'static str>, diesel::result::Error> {
+    Ok(match (&user.password, password) {
+        (Some(_), None) => Some("password_missing"),
+        (Some(_), Some(password)) => password_service
+            .verify_account(password, user.password.as_deref())
+            .err()
+            .map(|_| "invalid_password"),
+        (None, _) => {
+            let authenticated_at = match session_id {
+                Some(session_id) => sessions_repo::get_session(pool, session_id)
+                    .optional()?
+                    .map(|session| session.authenticated_at()),
+                None => None,
+            };
+            let max_age = Duration::seconds(app_config.reauthentication_max_age_secs as i64);
+            let recent = authenticated_at.is_some_and(|at| at + max_age >= Utc::now().naive_utc());
+            (!recent).then_some("reauthentication_required")
+        }
+    })
+}
@@ -0,0 +1,168 @@
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    config::app_config::AppConfig,
+    errors::OAuthError,
+    extractors::client_info::ClientInfo,
+    models::{
+        audit_event::AuditOutcome,
+        claims::{Actor, Claims},
+        oauth::{OAuthClient, TokenRequest},
+    },
+    repositories::{establish_connection, roles_repo, sessions_repo, users_repo, PgPool},
+    services::{
+        audit::{self, AuditEvent},
+        jwt::JwtKeys,
+        oauth, scope,
+        session::TokenSettings,
+    },
+};
+
+pub const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+// * Tipi di token di RFC 8693, sezione 3: si scambiano e si emettono solo access token JWT
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+
+// * Scambia il token di un utente con uno più ristretto per un servizio a valle. Il client che
+// * chiama è l'attore: deve essere confidenziale e autorizzato per l'audience richiesta. Il
+// * nuovo token non ha refresh token e non dura più di quello presentato
+pub fn exchange(
+    pool: &PgPool,
+    client: &OAuthClient,
+    form: &TokenRequest,
+    client_info: &ClientInfo,
+    app_config: &AppConfig,
+    jwt_keys: &JwtKeys,
+) -> Result<Claims, OAuthError> {
+    if !client.is_confidential() {
+        return Err(OAuthError::unauthorized_client(
+            "Only confidential clients may exchange tokens",
+        ));
+    }
+    let subject_token = form
+        .subject_token
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing subject_token"))?;
+    match form.subject_token_type.as_deref() {
+        Some(ACCESS_TOKEN_TYPE | JWT_TOKEN_TYPE) => {}
+        Some(other) => {
+            return Err(OAuthError::invalid_request(format!(
+                "Unsupported subject_token_type {}",
+                other
+            )))
+        }
+        None => return Err(OAuthError::invalid_request("Missing subject_token_type")),
+    }
+    if form.actor_token.is_some() {
+        return Err(OAuthError::invalid_request(
+            "actor_token is not supported: the authenticated client is the actor",
+        ));
+    }
+    if form
+        .requested_token_type
+        .as_deref()
+        .is_some_and(|requested| requested != ACCESS_TOKEN_TYPE)
+    {
+        return Err(OAuthError::invalid_request(
+            "Only access tokens can be requested",
+        ));
+    }
+    let audience = form
+        .audience
+        .as_deref()
+        .ok_or_else(|| OAuthError::invalid_request("Missing audience"))?;
+    if !client
+        .token_exchange_audiences
+        .iter()
+        .any(|allowed| allowed == audience)
+    {
+        return Err(OAuthError::invalid_target(format!(
+            "The client may not request tokens for the audience {}",
+            audience
+        )));
+    }
+
+    // * Il token presentato può avere qualsiasi audience, ma deve essere di un utente
+    let subject = jwt_keys
+        .verify_any_audience(subject_token, app_config)
+        .map_err(|_| OAuthError::invalid_grant("Invalid or expired subject_token"))?;
+    if subject.is_client_token() || subject.password_change_required {
+        return Err(OAuthError::invalid_grant(
+            "The subject_token does not represent a user",
+        ));
+    }
+    let user_id = Uuid::parse_str(&subject.sub)
+        .map_err(|_| OAuthError::invalid_grant("Invalid subject_token subject"))?;
+    let available: Vec<String> = subject
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    let scopes = oauth::resolve_scopes(form.scope.as_deref(), &available)
+        .map_err(OAuthError::invalid_scope)?;
+
+    let mut conn = establish_connection(pool)?;
+    let user = users_repo::get_user_by_id_with_connection(&mut conn, user_id)
+        .map_err(|_| OAuthError::invalid_grant("The subject_token user no longer exists"))?;
+    if user.ensure_active().is_err() {
+        return Err(OAuthError::invalid_grant("The account is not active"));
+    }
+    // * Un token legato a una sessione revocata non si può più scambiare
+    if let Some(sid) = &subject.sid {
+        let session_id = Uuid::parse_str(sid)
+            .map_err(|_| OAuthError::invalid_grant("Invalid subject_token session"))?;
+        let session = sessions_repo::get_session_with_connection(&mut conn, session_id)
+            .map_err(|_| OAuthError::invalid_grant("The subject_token session has ended"))?;
+        if session.user_id != user_id || session.revoked_at.is_some() {
+            return Err(OAuthError::invalid_grant(
+                "The subject_token session has ended",
+            ));
+        }
+    }
+    let grants = roles_repo::grants_for_user_with_connection(&mut conn, user_id)?;
+    // * I ruoli potrebbero essere cambiati dopo l'emissione del token presentato
+    let scopes = scope::restrict_to_grants(&grants, scopes.iter().map(String::as_str));
+
+    let mut claims = Claims::with_ttl(
+        subject.sub.clone(),
+        app_config,
+        TokenSettings::for_client(client, app_config).access_token_ttl_secs,
+    );
+    claims.exp = claims.exp.min(subject.exp);
+    claims.aud = audience.to_string();
+    claims.sid = subject.sid.clone();
+    claims.roles = grants.roles;
+    claims.client_id = Some(client.id.to_string());
+    claims.set_scopes(&scopes);
+    claims.org_id = subject.org_id.clone();
+    claims.org_roles = subject.org_roles.clone();
+    claims.act = Some(Actor {
+        sub: client.id.to_string(),
+        impersonation: false,
+        act: subject.act.map(Box::new),
+    });
+
+    audit::record_with_connection(
+        &mut conn,
+        AuditEvent {
+            action: "oauth.token.exchanged",
+            outcome: AuditOutcome::Success,
+            actor_id: None,
+            target_user_id: Some(user_id),
+            client: Some(client_info),
+            details: json!({
+                "client_id": client.id,
+                "audience": audience,
+                "scope": claims.scope,
+                "subject_client_id": subject.client_id,
+                "session_id": subject.sid,
+                "act": claims.act,
+            }),
+        },
+    )?;
+    Ok(claims)
+}
@@ -0,0 +1,45 @@
+use chrono::NaiveDate;
+use ketchapp_auth_api::models::audit_event::AuditLogEntry;
+use ketchapp_auth_api::services::audit::to_csv;
+use serde_json::json;
+
+fn entry(user_agent: &str) -> AuditLogEntry {
+    AuditLogEntry {
+        id: uuid::Uuid::nil(),
+        occurred_at: NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap(),
+        action: "login.failure".into(),
+        outcome: "failure".into(),
+        actor_id: None,
+        target_user_id: None,
+        ip_address: Some("10.0.0.1".into()),
+        user_agent: Some(user_agent.into()),
+        request_id: Some("req-1".into()),
+        details: json!({ "reason": "invalid_password" }),
+        impersonator_id: None,
+    }
+}
+
+#[test]
+fn test_csv_export_has_header_and_quotes_fields() {
+    let csv = to_csv(&[entry("Mozilla/5.0 (X11, \"Linux\")")]);
+    let lines: Vec<&str> = csv.split("\r\n").collect();
+    assert_eq!(
+        lines[0],
+        "id,occurred_at,action,outcome,actor_id,target_user_id,ip_address,user_agent,request_id,details,impersonator_id"
+    );
+    assert_eq!(
+        lines[1],
+        "00000000-0000-0000-0000-000000000000,2026-10-19T09:30:00.000000,login.failure,failure,,,\
+         10.0.0.1,\"Mozilla/5.0 (X11, \"\"Linux\"\")\",req-1,\"{\"\"reason\"\":\"\"invalid_password\"\"}\","
+    );
+    assert_eq!(lines[2], "");
+}
+
+#[test]
+fn test_csv_export_neutralizes_formulas() {
+    let csv = to_csv(&[entry("=HYPERLINK(\"http://evil\")")]);
+    assert!(csv.contains(",\"'=HYPERLINK(\"\"http://evil\"\")\","));
+}
@@ -0,0 +1,89 @@
+use ketchapp_auth_api::services::breached_passwords::{
+    parse_hibp_line, BreachedPasswordFilter, BreachedPasswords,
+};
+
+// Righe di riempimento nel formato del dump HIBP, più una riga malformata
+const HIBP_SAMPLE: &str = "\
+2B4A3E3F7E5A0E4A3C7F9B41A4A3D8F9A6B2F0C1:12
+F4D8F3A1C02D91C0B5A93BDB1FCC5A4A50A8C3E0:3
+not-a-hash-line
+";
+
+fn sha1_hex(password: &str) -> String {
+    use sha1::{Digest, Sha1};
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+#[test]
+fn test_parse_hibp_line() {
+    assert!(parse_hibp_line("2B4A3E3F7E5A0E4A3C7F9B41A4A3D8F9A6B2F0C1:12").is_some());
+    assert!(parse_hibp_line("2b4a3e3f7e5a0e4a3c7f9b41a4a3d8f9a6b2f0c1").is_some());
+    assert!(parse_hibp_line("not-a-hash-line").is_none());
+    assert!(parse_hibp_line("").is_none());
+}
+
+#[test]
+fn test_filter_detects_breached_passwords() {
+    let mut filter = BreachedPasswordFilter::with_capacity(1_000, 0.001);
+    let dump = format!(
+        "{}:42\n{}:7\n{}",
+        sha1_hex("Password1!"),
+        sha1_hex("P@ssw0rd"),
+        HIBP_SAMPLE
+    );
+    for line in dump.lines() {
+        if let Some(digest) = parse_hibp_line(line) {
+            filter.insert_digest(&digest);
+        }
+    }
+    assert!(filter.contains_password("Password1!"));
+    assert!(filter.contains_password("P@ssw0rd"));
+    assert!(!filter.contains_password("correct horse battery staple"));
+}
+
+#[test]
+fn test_filter_round_trips_through_file() {
+    let mut filter = BreachedPasswordFilter::with_capacity(10, 0.01);
+    filter.insert_digest(&parse_hibp_line(&sha1_hex("Password1!")).unwrap());
+
+    let path = std::env::temp_dir().join(format!("breach-filter-{}.bin", uuid::Uuid::new_v4()));
+    filter.save(&path).unwrap();
+    let loaded = BreachedPasswordFilter::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let breached = BreachedPasswords::new(loaded);
+    assert!(breached.is_enabled());
+    assert!(breached.is_breached("Password1!"));
+    assert!(!BreachedPasswords::default().is_breached("Password1!"));
+}
+
+#[test]
+fn test_filter_header_must_match_the_file_size() {
+    let path = std::env::temp_dir().join(format!("breach-filter-{}.bin", uuid::Uuid::new_v4()));
+    // * Un'intestazione che dichiara 2^63 bit con un corpo di pochi byte non deve allocare nulla
+    let mut forged = b"KAPPBF01".to_vec();
+    forged.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+    forged.extend_from_slice(&7u32.to_le_bytes());
+    forged.extend_from_slice(&[0u8; 16]);
+    std::fs::write(&path, &forged).unwrap();
+    let oversized = BreachedPasswordFilter::load(&path);
+
+    // * Un file troncato è rifiutato allo stesso modo
+    let mut filter = BreachedPasswordFilter::with_capacity(10, 0.01);
+    filter.insert_digest(&parse_hibp_line(&sha1_hex("Password1!")).unwrap());
+    filter.save(&path).unwrap();
+    let saved = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &saved[..saved.len() - 1]).unwrap();
+    let truncated = BreachedPasswordFilter::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    for result in [oversized, truncated] {
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(std::io::ErrorKind::InvalidData)
+        );
+    }
+}
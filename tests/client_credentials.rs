@@ -0,0 +1,86 @@
+use actix_web::{get, http::StatusCode, test, web, App, HttpResponse};
+use chrono::Utc;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::extractors::auth::AuthenticatedUser;
+use ketchapp_auth_api::models::oauth::OAuthClient;
+use ketchapp_auth_api::services::oauth::client_claims;
+use ketchapp_auth_api::DbPool;
+
+mod common;
+use common::{app_config_with, jwt_keys};
+
+fn app_config() -> AppConfig {
+    app_config_with(serde_json::json!({
+        "client_credentials_audience": "internal-services",
+    }))
+}
+
+#[get("/me")]
+async fn me(user: AuthenticatedUser) -> HttpResponse {
+    HttpResponse::Ok().body(user.user_id.to_string())
+}
+
+fn service_client() -> OAuthClient {
+    OAuthClient {
+        id: uuid::Uuid::new_v4(),
+        name: "Analytics".into(),
+        client_secret_hash: Some("0".repeat(64)),
+        redirect_uris: Vec::new(),
+        allowed_scopes: vec!["stats.read".into()],
+        first_party: true,
+        created_by: None,
+        created_at: Utc::now().naive_utc(),
+        audience: None,
+        access_token_ttl_secs: None,
+        refresh_token_ttl_secs: None,
+        idle_timeout_secs: None,
+        token_exchange_audiences: Vec::new(),
+    }
+}
+
+#[actix_web::test]
+async fn test_client_token_names_the_client() {
+    let config = app_config();
+    let client = service_client();
+    let claims = client_claims(&client, "stats.read", &config);
+    assert_eq!(claims.sub, client.id.to_string());
+    assert_eq!(claims.aud, "internal-services");
+    assert_eq!(claims.scope.as_deref(), Some("stats.read"));
+    assert!(claims.is_client_token());
+    assert!(claims.sid.is_none() && claims.roles.is_empty());
+
+    // * Il token del servizio passa la stessa verifica dei token degli utenti
+    let keys = jwt_keys();
+    let token = keys.sign(&claims).unwrap();
+    let verified = keys.verify(&token, &config).unwrap();
+    assert_eq!(verified.sub, claims.sub);
+}
+
+#[actix_web::test]
+async fn test_client_token_is_not_a_user() {
+    let config = app_config();
+    let keys = jwt_keys();
+    let token = keys
+        .sign(&client_claims(&service_client(), "", &config))
+        .unwrap();
+
+    let pool: DbPool = r2d2::Pool::builder()
+        .build_unchecked(ConnectionManager::<PgConnection>::new("postgres://unused"));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool))
+            .service(me),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/me")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
@@ -0,0 +1,80 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{get, web, App, HttpResponse};
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::extractors::client_info::{client_ip, ClientInfo};
+use serde_json::json;
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn test_forwarded_for_is_only_trusted_from_configured_proxies() {
+    let proxy = ip("10.0.0.2");
+    let client = ip("203.0.113.7");
+
+    assert_eq!(
+        client_ip(Some(client), Some("1.2.3.4"), &[proxy]),
+        Some(client)
+    );
+    assert_eq!(
+        client_ip(Some(proxy), Some("198.51.100.1, 203.0.113.7"), &[proxy]),
+        Some(client)
+    );
+    // * Più proxy fidati in fila: si salta ognuno di loro
+    assert_eq!(
+        client_ip(
+            Some(proxy),
+            Some("198.51.100.1, 203.0.113.7, 10.0.0.3"),
+            &[proxy, ip("10.0.0.3")]
+        ),
+        Some(client)
+    );
+    assert_eq!(
+        client_ip(Some(proxy), Some("2001:db8::1"), &[proxy]),
+        Some(ip("2001:db8::1"))
+    );
+    // * Una voce non valida non viene mai registrata
+    let forged = format!("{}, ", "x".repeat(100));
+    assert_eq!(client_ip(Some(proxy), Some(&forged), &[proxy]), Some(proxy));
+    assert_eq!(client_ip(Some(proxy), None, &[proxy]), Some(proxy));
+    assert_eq!(client_ip(None, Some("1.2.3.4"), &[proxy]), None);
+}
+
+#[get("/ip")]
+async fn show_ip(client: ClientInfo) -> HttpResponse {
+    HttpResponse::Ok().body(
+        client
+            .ip_address
+            .map(|ip| ip.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+#[actix_web::test]
+async fn test_client_info_ignores_forwarded_headers_from_untrusted_peers() {
+    let config: AppConfig = serde_json::from_value(json!({
+        "port": 0,
+        "host": "127.0.0.1",
+        "database_url": "postgres://unused",
+        "jwt_issuer": "test-issuer",
+        "jwt_audience": "test-audience",
+        "jwt_exp_secs": 300,
+        "trusted_proxies": ["10.0.0.2"],
+    }))
+    .unwrap();
+    let app = init_service(App::new().app_data(web::Data::new(config)).service(show_ip)).await;
+
+    for (peer, expected) in [("203.0.113.7", "203.0.113.7"), ("10.0.0.2", "198.51.100.1")] {
+        let req = TestRequest::get()
+            .uri("/ip")
+            .peer_addr(SocketAddr::new(ip(peer), 40000))
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .insert_header(("Forwarded", "for=192.0.2.60"))
+            .to_request();
+        let body = read_body(call_service(&app, req).await).await;
+        assert_eq!(body, expected.as_bytes());
+    }
+}
@@ -0,0 +1,73 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some
+//! of them, hence the `dead_code` allowance.
+#![allow(dead_code)]
+
+use diesel::r2d2::ConnectionManager;
+use diesel::PgConnection;
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::models::user::{NewUser, User};
+use ketchapp_auth_api::repositories::{users_repo, PgPool};
+use ketchapp_auth_api::services::jwt::JwtKeys;
+use serde_json::{json, Value};
+
+// * Configurazione minima del servizio, senza database reale
+pub fn app_config() -> AppConfig {
+    app_config_with(json!({}))
+}
+
+// * Configurazione minima con i campi di `overrides` aggiunti o sostituiti
+pub fn app_config_with(overrides: Value) -> AppConfig {
+    let mut config = json!({
+        "port": 0,
+        "host": "127.0.0.1",
+        "database_url": "postgres://unused",
+        "jwt_issuer": "test-issuer",
+        "jwt_audience": "test-audience",
+        "jwt_exp_secs": 300,
+    });
+    if let (Some(config), Some(overrides)) = (config.as_object_mut(), overrides.as_object()) {
+        config.extend(overrides.clone());
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+pub fn jwt_keys() -> JwtKeys {
+    JwtKeys::from_private_key(include_bytes!("../../private_key.pem")).unwrap()
+}
+
+// * I test che la usano richiedono un database con le migrazioni applicate, indicato da
+// * TEST_DATABASE_URL. Sono marcati #[ignore] così un'esecuzione senza database li segnala
+// * invece di farli passare: `cargo test -- --include-ignored`
+pub fn test_pool() -> PgPool {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL must point to a database with the migrations applied");
+    r2d2::Pool::builder()
+        .max_size(2)
+        .build(ConnectionManager::<PgConnection>::new(url))
+        .expect("TEST_DATABASE_URL is not reachable")
+}
+
+// * Username casuale di sole lettere, come richiesto dal vincolo della tabella
+pub fn random_username() -> String {
+    uuid::Uuid::new_v4()
+        .simple()
+        .to_string()
+        .chars()
+        .take(12)
+        .map(|c| (b'a' + c.to_digit(16).unwrap() as u8) as char)
+        .collect()
+}
+
+// * Utente con una password fittizia: i test non passano dal login
+pub fn create_user(pool: &PgPool) -> User {
+    let username = random_username();
+    users_repo::new_user(
+        pool,
+        NewUser {
+            email: format!("{}@example.com", username),
+            username,
+            password: Some("not-a-real-hash".into()),
+        },
+    )
+    .unwrap()
+}
@@ -0,0 +1,33 @@
+use ketchapp_auth_api::services::device_authorization::{
+    format_user_code, generate_user_code, normalize_user_code,
+};
+
+#[test]
+fn user_codes_use_only_unambiguous_consonants() {
+    for _ in 0..200 {
+        let code = generate_user_code();
+        assert_eq!(code.len(), 8);
+        assert!(
+            code.chars().all(|c| "BCDFGHJKLMNPQRSTVWXZ".contains(c)),
+            "unexpected character in {}",
+            code
+        );
+    }
+}
+
+#[test]
+fn user_codes_are_shown_in_two_groups() {
+    assert_eq!(format_user_code("WDJBMJHT"), "WDJB-MJHT");
+    // * Un valore inatteso viene mostrato così com'è
+    assert_eq!(format_user_code("ABC"), "ABC");
+}
+
+#[test]
+fn typed_user_codes_are_normalized() {
+    assert_eq!(normalize_user_code("wdjb-mjht"), "WDJBMJHT");
+    assert_eq!(normalize_user_code(" WDJB MJHT "), "WDJBMJHT");
+    assert_eq!(
+        normalize_user_code(&format_user_code("WDJBMJHT")),
+        "WDJBMJHT"
+    );
+}
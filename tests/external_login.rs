@@ -0,0 +1,475 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::errors::ServiceError;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::models::user_identity::{ExternalLoginCallback, ExternalLoginState};
+use ketchapp_auth_api::repositories::{establish_connection, user_identities_repo, PgPool};
+use ketchapp_auth_api::schema::{sessions, users};
+use ketchapp_auth_api::services::external_login::{
+    finish_login, start_login, unlink_identity, username_base, ExternalCallback, ExternalLogin,
+    ExternalProfile, IdentityProviders,
+};
+use ketchapp_auth_api::services::password::PasswordService;
+use ketchapp_auth_api::services::session;
+use serde_json::{json, Map, Value};
+
+mod common;
+use common::{app_config_with, jwt_keys, test_pool};
+
+const CLIENT_ID: &str = "ketchapp-test";
+
+// * Server HTTP minimale che risponde con `handler(metodo, path, header Authorization, corpo)`
+fn spawn_mock<F>(handler: F) -> String
+where
+    F: Fn(&str, &str, Option<&str>, &str) -> (u16, Value) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+            let mut content_length = 0;
+            let mut authorization = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').unwrap();
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => content_length = value.trim().parse().unwrap(),
+                    "authorization" => authorization = Some(value.trim().to_string()),
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let (status, response) = handler(
+                &method,
+                &path,
+                authorization.as_deref(),
+                &String::from_utf8_lossy(&body),
+            );
+            let response = response.to_string();
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    });
+    base
+}
+
+fn form_value(body: &str, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(body.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+// * Provider OpenID Connect finto. Il codice di autorizzazione è il JSON dei claims da mettere
+// * nell'ID token, così ogni test sceglie utente, nonce e audience
+fn spawn_oidc_provider() -> String {
+    let keys = jwt_keys();
+    let listener_base = std::sync::Arc::new(std::sync::OnceLock::<String>::new());
+    let base_for_handler = listener_base.clone();
+    let base = spawn_mock(move |method, path, _, body| {
+        let base = base_for_handler.get().unwrap();
+        match (method, path) {
+            ("GET", "/.well-known/openid-configuration") => (
+                200,
+                json!({
+                    "issuer": base,
+                    "authorization_endpoint": format!("{}/authorize", base),
+                    "token_endpoint": format!("{}/token", base),
+                    "userinfo_endpoint": format!("{}/userinfo", base),
+                    "jwks_uri": format!("{}/jwks", base),
+                }),
+            ),
+            ("GET", "/jwks") => (200, json!({ "keys": [keys.jwk()] })),
+            ("POST", "/token") => {
+                if form_value(body, "code_verifier").is_none() {
+                    return (400, json!({ "error": "invalid_grant" }));
+                }
+                let code = form_value(body, "code").unwrap();
+                let mut claims: Map<String, Value> = serde_json::from_str(&code).unwrap();
+                let now = Utc::now().timestamp();
+                claims.insert("iss".into(), json!(base));
+                claims.entry("aud").or_insert(json!(CLIENT_ID));
+                claims.insert("iat".into(), json!(now));
+                claims.insert("exp".into(), json!(now + 300));
+                (
+                    200,
+                    json!({
+                        "access_token": "upstream-access-token",
+                        "token_type": "Bearer",
+                        "id_token": keys.sign(&claims).unwrap(),
+                    }),
+                )
+            }
+            _ => (404, json!({})),
+        }
+    });
+    listener_base.set(base.clone()).unwrap();
+    base
+}
+
+fn app_config(providers: Value) -> AppConfig {
+    app_config_with(json!({
+        "oidc_issuer": "http://auth.ketchapp.test",
+        "identity_providers": providers,
+    }))
+}
+
+fn oidc_config(issuer: &str) -> AppConfig {
+    app_config(json!([{
+        "id": "school",
+        "name": "School",
+        "issuer": issuer,
+        "client_id": CLIENT_ID,
+    }]))
+}
+
+fn login_state(nonce: &str) -> ExternalLoginState {
+    ExternalLoginState {
+        state_hash: "unused".into(),
+        provider: "school".into(),
+        code_verifier: "v".repeat(64),
+        nonce: nonce.into(),
+        created_at: Utc::now().naive_utc(),
+        expires_at: Utc::now().naive_utc(),
+        link_user_id: None,
+    }
+}
+
+#[test]
+fn id_token_from_discovered_provider_gives_the_profile() {
+    let issuer = spawn_oidc_provider();
+    let config = oidc_config(&issuer);
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let provider = providers.get("school").unwrap();
+
+    let metadata = provider.metadata(providers.http()).unwrap();
+    assert_eq!(metadata.token_endpoint, format!("{}/token", issuer));
+
+    let code = json!({
+        "sub": "student-1",
+        "nonce": "nonce-1",
+        "email": "mario.rossi@school.example",
+        "email_verified": true,
+        "preferred_username": "mario.rossi",
+    });
+    let profile = provider
+        .fetch_profile(
+            providers.http(),
+            &code.to_string(),
+            &login_state("nonce-1"),
+            &config,
+        )
+        .unwrap();
+    assert_eq!(
+        profile,
+        ExternalProfile {
+            subject: "student-1".into(),
+            email: Some("mario.rossi@school.example".into()),
+            email_verified: true,
+            username_hint: Some("mario.rossi".into()),
+        }
+    );
+}
+
+#[test]
+fn id_token_with_wrong_nonce_or_audience_is_rejected() {
+    let issuer = spawn_oidc_provider();
+    let config = oidc_config(&issuer);
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let provider = providers.get("school").unwrap();
+
+    for code in [
+        json!({ "sub": "student-1", "nonce": "replayed", "email": "a@school.example" }),
+        json!({ "sub": "student-1", "nonce": "nonce-1", "aud": "another-client" }),
+    ] {
+        let result = provider.fetch_profile(
+            providers.http(),
+            &code.to_string(),
+            &login_state("nonce-1"),
+            &config,
+        );
+        assert!(
+            matches!(result, Err(ServiceError::IdentityProvider(_))),
+            "accepted {}",
+            code
+        );
+    }
+}
+
+#[test]
+fn oauth2_provider_profile_comes_from_userinfo() {
+    let base = spawn_mock(|method, path, authorization, _| match (method, path) {
+        ("POST", "/login/oauth/access_token") => (200, json!({ "access_token": "gh-token" })),
+        ("GET", "/user") if authorization == Some("Bearer gh-token") => (
+            200,
+            json!({ "id": 4242, "login": "octocat", "email": "octocat@example.com" }),
+        ),
+        _ => (401, json!({})),
+    });
+    let config = app_config(json!([{
+        "id": "forge",
+        "name": "Forge",
+        "authorization_endpoint": format!("{}/login/oauth/authorize", base),
+        "token_endpoint": format!("{}/login/oauth/access_token", base),
+        "userinfo_endpoint": format!("{}/user", base),
+        "client_id": CLIENT_ID,
+        "scopes": ["read:user", "user:email"],
+        "subject_claim": "id",
+    }]));
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let provider = providers.get("forge").unwrap();
+
+    let profile = provider
+        .fetch_profile(providers.http(), "code", &login_state("unused"), &config)
+        .unwrap();
+    assert_eq!(profile.subject, "4242");
+    assert_eq!(profile.username_hint.as_deref(), Some("octocat"));
+    // * Senza email_verified l'email non basta a collegare un account esistente
+    assert!(!profile.email_verified);
+}
+
+#[test]
+fn usernames_are_derived_from_the_profile() {
+    assert_eq!(username_base(Some("mario.rossi")), "mariorossi");
+    assert_eq!(username_base(Some("anna@school.example")), "annauser");
+    assert_eq!(
+        username_base(Some("Bartholomew-Montgomery")),
+        "bartholomewm"
+    );
+    assert_eq!(username_base(None), "useruser");
+}
+
+fn query_param(url: &str, name: &str) -> String {
+    url::Url::parse(url)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+}
+
+// * Completa un login (o un collegamento, con `link_user_id`) come farebbe il browser,
+// * con l'account `subject` del provider finto
+fn complete_login(
+    pool: &PgPool,
+    providers: &IdentityProviders,
+    config: &AppConfig,
+    subject: &str,
+    link_user_id: Option<uuid::Uuid>,
+    state_cookie: Option<&str>,
+) -> Result<ExternalCallback, ServiceError> {
+    let provider = providers.get("school").unwrap();
+    let started = start_login(pool, providers, provider, link_user_id, config).unwrap();
+    let nonce = query_param(&started.authorization_url, "nonce");
+    assert_eq!(
+        query_param(&started.authorization_url, "code_challenge_method"),
+        "S256"
+    );
+    let email = format!("{}@school.example", &subject[..8]);
+    let code = json!({ "sub": subject, "nonce": nonce, "email": email, "email_verified": true });
+    finish_login(
+        pool,
+        providers,
+        provider,
+        ExternalLoginCallback {
+            code: Some(code.to_string()),
+            state: Some(started.state.clone()),
+            error: None,
+            error_description: None,
+        },
+        Some(state_cookie.unwrap_or(&started.state)),
+        &ClientInfo::default(),
+        config,
+    )
+}
+
+fn signed_in(result: Result<ExternalCallback, ServiceError>) -> Box<ExternalLogin> {
+    match result {
+        Ok(ExternalCallback::SignedIn(login)) => login,
+        Ok(ExternalCallback::Linked(_)) => panic!("identity linked instead of signing in"),
+        Err(e) => panic!("login failed: {}", e),
+    }
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn first_login_creates_the_account_and_later_logins_reuse_it() {
+    let pool = test_pool();
+    let issuer = spawn_oidc_provider();
+    let config = oidc_config(&issuer);
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let subject = uuid::Uuid::new_v4().to_string();
+
+    let first = signed_in(complete_login(
+        &pool, &providers, &config, &subject, None, None,
+    ));
+    let second = signed_in(complete_login(
+        &pool, &providers, &config, &subject, None, None,
+    ));
+    let other_browser = complete_login(
+        &pool,
+        &providers,
+        &config,
+        &subject,
+        None,
+        Some("another-state"),
+    );
+    let mut conn = establish_connection(&pool).unwrap();
+    diesel::delete(users::table.find(first.user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(first.user.password.is_none());
+    assert_eq!(
+        first.user.email,
+        format!("{}@school.example", &subject[..8])
+    );
+    assert_eq!(first.grants.roles, vec!["student".to_string()]);
+    assert_eq!(first.grants.scopes, vec!["study.read", "study.write"]);
+    assert_eq!(second.user.id, first.user.id);
+    assert_ne!(second.issued.session.id, first.issued.session.id);
+    assert!(matches!(other_browser, Err(ServiceError::Unauthorized(_))));
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn identities_are_linked_and_the_last_login_method_is_kept() {
+    let pool = test_pool();
+    let issuer = spawn_oidc_provider();
+    let config = oidc_config(&issuer);
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let client = ClientInfo::default();
+    let owner_subject = uuid::Uuid::new_v4().to_string();
+    let second_subject = uuid::Uuid::new_v4().to_string();
+    let other_subject = uuid::Uuid::new_v4().to_string();
+
+    // * Account senza password creato dal primo login, poi un secondo account del provider
+    // * collegato dalle impostazioni
+    let owner = signed_in(complete_login(
+        &pool,
+        &providers,
+        &config,
+        &owner_subject,
+        None,
+        None,
+    ));
+    let linked = complete_login(
+        &pool,
+        &providers,
+        &config,
+        &second_subject,
+        Some(owner.user.id),
+        None,
+    );
+    let other = signed_in(complete_login(
+        &pool,
+        &providers,
+        &config,
+        &other_subject,
+        None,
+        None,
+    ));
+    // * L'identità di un altro utente non viene spostata
+    let stolen = complete_login(
+        &pool,
+        &providers,
+        &config,
+        &other_subject,
+        Some(owner.user.id),
+        None,
+    );
+
+    let Ok(ExternalCallback::Linked(linked)) = linked else {
+        panic!("identity not linked");
+    };
+    let first_unlink = unlink_identity(&pool, &providers, owner.user.id, linked.id, &client);
+    let mut conn = establish_connection(&pool).unwrap();
+    let remaining =
+        user_identities_repo::list_for_user_with_connection(&mut conn, owner.user.id).unwrap();
+    let last_unlink = unlink_identity(&pool, &providers, owner.user.id, remaining[0].id, &client);
+    let foreign_unlink =
+        unlink_identity(&pool, &providers, other.user.id, remaining[0].id, &client);
+    for user_id in [owner.user.id, other.user.id] {
+        diesel::delete(users::table.find(user_id))
+            .execute(&mut conn)
+            .unwrap();
+    }
+
+    assert_eq!(linked.user_id, owner.user.id);
+    assert!(matches!(stolen, Err(ServiceError::Conflict(_))));
+    assert!(first_unlink.is_ok());
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].subject, owner_subject);
+    assert!(matches!(last_unlink, Err(ServiceError::Conflict(_))));
+    assert!(matches!(foreign_unlink, Err(ServiceError::NotFound(_))));
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn passwordless_accounts_confirm_sensitive_actions_with_a_recent_login() {
+    let pool = test_pool();
+    let issuer = spawn_oidc_provider();
+    let config = oidc_config(&issuer);
+    let providers = IdentityProviders::from_config(&config).unwrap();
+    let password_service = PasswordService::default();
+    let subject = uuid::Uuid::new_v4().to_string();
+    let login = signed_in(complete_login(
+        &pool, &providers, &config, &subject, None, None,
+    ));
+    let session_id = login.issued.session.id;
+    let confirm = |password: Option<&str>, session_id| {
+        session::reauthentication_failure(
+            &pool,
+            &password_service,
+            &login.user,
+            password,
+            session_id,
+            &config,
+        )
+        .unwrap()
+    };
+
+    let recent = confirm(None, Some(session_id));
+    let with_password = confirm(Some("Secret123!"), Some(session_id));
+    let without_session = confirm(None, None);
+    let mut conn = establish_connection(&pool).unwrap();
+    let an_hour_ago = (Utc::now() - chrono::Duration::hours(1)).naive_utc();
+    diesel::update(sessions::table.find(session_id))
+        .set((
+            sessions::created_at.eq(an_hour_ago),
+            sessions::auth_time.eq(Some(an_hour_ago)),
+        ))
+        .execute(&mut conn)
+        .unwrap();
+    let stale = confirm(None, Some(session_id));
+    diesel::delete(users::table.find(login.user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(recent, None);
+    assert_eq!(with_password, None);
+    assert_eq!(without_session, Some("reauthentication_required"));
+    assert_eq!(stale, Some("reauthentication_required"));
+}
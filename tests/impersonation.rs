@@ -0,0 +1,315 @@
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{get, http::Method, http::StatusCode, web, App, HttpResponse};
+use chrono::NaiveDate;
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::errors::ServiceError;
+use ketchapp_auth_api::extractors::auth::AuthenticatedUser;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::handlers::{account, consents, data_export, oauth, sessions};
+use ketchapp_auth_api::models::audit_event::AuditLogEntry;
+use ketchapp_auth_api::models::claims::{Actor, Claims};
+use ketchapp_auth_api::models::data_export::ExportedAuditEvent;
+use ketchapp_auth_api::models::role::{RoleGrants, ADMIN_ROLE};
+use ketchapp_auth_api::models::user::AccountStatus;
+use ketchapp_auth_api::repositories::{
+    establish_connection, roles_repo, sessions_repo, users_repo, PgPool,
+};
+use ketchapp_auth_api::services::impersonation::{self, impersonation_claims};
+use ketchapp_auth_api::services::password::PasswordService;
+use serde_json::json;
+use uuid::Uuid;
+
+mod common;
+use common::{app_config_with, create_user, jwt_keys, test_pool};
+
+fn app_config() -> AppConfig {
+    app_config_with(json!({
+        "jwt_exp_secs": 3600,
+    }))
+}
+
+#[test]
+fn test_impersonation_tokens_are_short_lived_and_name_the_admin() {
+    let config = app_config();
+    let (user_id, admin_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let grants = RoleGrants {
+        roles: vec!["student".into()],
+        scopes: vec!["study.read".into()],
+        withheld_scopes: vec!["admin".into()],
+    };
+    let session_id = Uuid::new_v4();
+    let claims = impersonation_claims(user_id, grants, admin_id, session_id, &config);
+    assert_eq!(claims.roles, vec!["student"]);
+    assert_eq!(claims.sub, user_id.to_string());
+    assert_eq!(claims.exp - claims.iat, 900);
+    assert_eq!(claims.sid, Some(session_id.to_string()));
+    assert_eq!(claims.scope.as_deref(), Some("study.read"));
+    assert_eq!(claims.impersonator_id(), Some(admin_id));
+    assert_eq!(
+        serde_json::to_value(&claims.act).unwrap(),
+        json!({"sub": admin_id.to_string(), "impersonation": true})
+    );
+
+    // * Resta riconoscibile anche dopo uno scambio con un servizio a valle
+    let mut exchanged = Claims::new(user_id.to_string(), &config);
+    exchanged.act = Some(Actor {
+        sub: "reports".into(),
+        impersonation: false,
+        act: claims.act.map(Box::new),
+    });
+    assert_eq!(exchanged.impersonator_id(), Some(admin_id));
+    assert_eq!(
+        Claims::new(user_id.to_string(), &config).impersonator_id(),
+        None
+    );
+}
+
+#[test]
+fn test_exported_audit_events_do_not_attribute_impersonated_actions_to_the_user() {
+    let user_id = Uuid::new_v4();
+    let entry = AuditLogEntry {
+        id: Uuid::new_v4(),
+        occurred_at: NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap(),
+        action: "tokens.create".into(),
+        outcome: "success".into(),
+        actor_id: Some(user_id),
+        target_user_id: Some(user_id),
+        ip_address: Some("10.0.0.1".into()),
+        user_agent: None,
+        request_id: None,
+        details: json!({}),
+        impersonator_id: Some(Uuid::new_v4()),
+    };
+    let exported = ExportedAuditEvent::from_entry(entry, user_id);
+    assert!(exported.impersonated);
+    assert!(!exported.by_user);
+    assert_eq!(exported.ip_address, None);
+}
+
+#[get("/me/credentials")]
+async fn credentials(
+    auth: AuthenticatedUser,
+    client: ClientInfo,
+) -> Result<HttpResponse, ServiceError> {
+    let impersonator = client.impersonator_id.map(|id| id.to_string());
+    auth.ensure_not_impersonated()?;
+    Ok(HttpResponse::Ok().body(impersonator.unwrap_or_default()))
+}
+
+#[get("/me/audited")]
+async fn audited(client: ClientInfo, auth: AuthenticatedUser) -> HttpResponse {
+    let impersonator = client.impersonator_id.map(|id| id.to_string());
+    HttpResponse::Ok().body(format!(
+        "{} {}",
+        auth.user_id,
+        impersonator.unwrap_or_default()
+    ))
+}
+
+// * Impersonazione come la avvia l'endpoint admin: l'admin ha il ruolo e il token è legato a
+// * una sessione dell'utente
+fn start_impersonation(pool: &PgPool, config: &AppConfig) -> (Uuid, Uuid, Claims) {
+    let (user_id, admin_id) = (create_user(pool).id, create_user(pool).id);
+    let mut conn = establish_connection(pool).unwrap();
+    assert!(roles_repo::assign_role_with_connection(&mut conn, admin_id, ADMIN_ROLE).unwrap());
+    let session = impersonation::start_session_with_connection(
+        &mut conn,
+        user_id,
+        &ClientInfo::default(),
+        config,
+    )
+    .unwrap();
+    let claims = impersonation_claims(user_id, RoleGrants::default(), admin_id, session.id, config);
+    (user_id, admin_id, claims)
+}
+
+async fn call(pool: &PgPool, uri: &str, claims: &Claims) -> (StatusCode, String) {
+    let keys = jwt_keys();
+    let token = keys.sign(claims).unwrap();
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(app_config()))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool.clone()))
+            .service(credentials)
+            .service(audited),
+    )
+    .await;
+    let req = TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let response = call_service(&app, req).await;
+    let status = response.status();
+    let body = read_body(response).await;
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_impersonated_requests_are_flagged_and_cannot_change_credentials() {
+    let pool = test_pool();
+    let config = app_config();
+    let (user_id, admin_id, impersonated) = start_impersonation(&pool, &config);
+
+    // * L'admin è riportato qualunque sia l'ordine degli estrattori
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await,
+        (StatusCode::OK, format!("{} {}", user_id, admin_id))
+    );
+    assert_eq!(
+        call(&pool, "/me/credentials", &impersonated).await.0,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        call(
+            &pool,
+            "/me/credentials",
+            &Claims::new(user_id.to_string(), &config)
+        )
+        .await,
+        (StatusCode::OK, String::new())
+    );
+}
+
+// * Il token smette di valere quando si revoca la sessione, quando l'utente viene sospeso e
+// * quando l'admin perde il ruolo o viene sospeso
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_impersonation_tokens_stop_working_when_revoked_or_no_longer_allowed() {
+    let pool = test_pool();
+    let config = app_config();
+    let mut conn = establish_connection(&pool).unwrap();
+
+    let (user_id, _, impersonated) = start_impersonation(&pool, &config);
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await.0,
+        StatusCode::OK
+    );
+    let session_id = impersonated.sid.as_deref().unwrap().parse().unwrap();
+    sessions_repo::revoke_with_connection(&mut conn, user_id, session_id).unwrap();
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let (user_id, _, impersonated) = start_impersonation(&pool, &config);
+    users_repo::set_status_with_connection(
+        &mut conn,
+        user_id,
+        AccountStatus::Suspended,
+        Some("Spam"),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await.0,
+        StatusCode::FORBIDDEN
+    );
+
+    let (_, admin_id, impersonated) = start_impersonation(&pool, &config);
+    roles_repo::remove_role_with_connection(&mut conn, admin_id, ADMIN_ROLE).unwrap();
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let (_, admin_id, impersonated) = start_impersonation(&pool, &config);
+    users_repo::set_status_with_connection(
+        &mut conn,
+        admin_id,
+        AccountStatus::Suspended,
+        Some("Left the team"),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        call(&pool, "/me/audited", &impersonated).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_impersonating_admins_cannot_authorize_clients_or_manage_the_account() {
+    let pool = test_pool();
+    let config = app_config();
+    let keys = jwt_keys();
+    let (_, _, impersonated) = start_impersonation(&pool, &config);
+    let token = keys.sign(&impersonated).unwrap();
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(PasswordService::default()))
+            .service(
+                web::scope("/oauth")
+                    .service(oauth::authorize_handler)
+                    .service(oauth::consent_handler)
+                    .service(oauth::device_decision_handler),
+            )
+            .service(
+                web::scope("/api")
+                    .service(account::delete_account_handler)
+                    .service(sessions::revoke_other_sessions_handler)
+                    .service(sessions::revoke_session_handler)
+                    .service(consents::revoke_consent_handler)
+                    .service(data_export::export_personal_data_handler),
+            ),
+    )
+    .await;
+
+    // * Il controllo precede il lavoro di ogni endpoint, che non arriva a toccare i dati
+    let other = Uuid::new_v4();
+    let requests = [
+        (
+            Method::GET,
+            "/oauth/authorize?response_type=code&client_id=x".to_string(),
+            None,
+        ),
+        (
+            Method::POST,
+            "/oauth/device".into(),
+            Some(json!({"user_code": "ABCD-EFGH", "decision": "approve"})),
+        ),
+        (
+            Method::DELETE,
+            "/api/me".into(),
+            Some(json!({"password": "secret"})),
+        ),
+        (Method::DELETE, "/api/me/sessions".into(), None),
+        (Method::DELETE, format!("/api/me/sessions/{}", other), None),
+        (Method::DELETE, format!("/api/me/consents/{}", other), None),
+        (Method::GET, "/api/me/export".into(), None),
+    ];
+    for (method, uri, body) in requests {
+        let mut req = TestRequest::default()
+            .method(method)
+            .uri(&uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)));
+        if let Some(body) = body {
+            req = req.set_json(body);
+        }
+        let status = call_service(&app, req.to_request()).await.status();
+        let expected = if uri.starts_with("/oauth/authorize") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::FORBIDDEN
+        };
+        assert_eq!(status, expected, "{}", uri);
+    }
+
+    let req = TestRequest::post()
+        .uri("/oauth/authorize")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_form([("client_id", "x"), ("decision", "approve")])
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&read_body(response).await).unwrap();
+    assert_eq!(body["error"], "access_denied");
+}
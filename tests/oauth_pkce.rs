@@ -0,0 +1,49 @@
+use ketchapp_auth_api::services::oauth::{
+    is_valid_code_challenge, redirect_with, resolve_scopes, validate_redirect_uri, verify_pkce,
+};
+
+// * Esempio dell'appendice B della RFC 7636
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+#[test]
+fn test_pkce_s256_matches_rfc_example() {
+    assert!(is_valid_code_challenge(CHALLENGE));
+    assert!(verify_pkce(VERIFIER, CHALLENGE));
+    assert!(!verify_pkce(&VERIFIER.replace('d', "e"), CHALLENGE));
+    // * Il verifier in chiaro non è una challenge valida per sé stesso (niente metodo "plain")
+    assert!(!verify_pkce(VERIFIER, VERIFIER));
+    // * Verifier troppo corto
+    assert!(!verify_pkce("short", CHALLENGE));
+}
+
+#[test]
+fn test_redirect_uri_rules() {
+    assert!(validate_redirect_uri("https://app.ketchapp.example/callback").is_ok());
+    assert!(validate_redirect_uri("http://127.0.0.1:8080/callback").is_ok());
+    assert!(validate_redirect_uri("http://localhost/callback").is_ok());
+    assert!(validate_redirect_uri("com.ketchapp.mobile:/oauth").is_ok());
+    assert!(validate_redirect_uri("http://app.ketchapp.example/callback").is_err());
+    assert!(validate_redirect_uri("https://app.ketchapp.example/cb#frag").is_err());
+    assert!(validate_redirect_uri("javascript:alert(1)").is_err());
+    assert!(validate_redirect_uri("not a uri").is_err());
+}
+
+#[test]
+fn test_scopes_and_redirects() {
+    let allowed = vec!["profile".to_string(), "email".to_string()];
+    assert_eq!(resolve_scopes(None, &allowed).unwrap(), allowed);
+    assert_eq!(
+        resolve_scopes(Some("email  email"), &allowed).unwrap(),
+        vec!["email".to_string()]
+    );
+    assert!(resolve_scopes(Some("profile admin"), &allowed).is_err());
+
+    assert_eq!(
+        redirect_with(
+            "https://app.ketchapp.example/cb?tab=1",
+            &[("code", "abc"), ("state", "x y")]
+        ),
+        "https://app.ketchapp.example/cb?tab=1&code=abc&state=x+y"
+    );
+}
@@ -0,0 +1,89 @@
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::models::claims::Claims;
+use ketchapp_auth_api::models::oidc::UserInfo;
+use ketchapp_auth_api::models::user::User;
+use ketchapp_auth_api::services::oidc;
+
+mod common;
+use common::{app_config_with, jwt_keys};
+
+fn app_config() -> AppConfig {
+    app_config_with(serde_json::json!({
+        "oidc_issuer": "https://auth.ketchapp.example",
+    }))
+}
+
+fn user() -> User {
+    let now = Utc::now().naive_utc();
+    User {
+        id: uuid::Uuid::new_v4(),
+        username: "johndoe".into(),
+        email: "john_doe@example.com".into(),
+        password: None,
+        created_at: now,
+        updated_at: now,
+        password_changed_at: now,
+        must_change_password: false,
+        status: "active".into(),
+        failed_login_attempts: 0,
+        locked_until: None,
+        status_reason: None,
+        status_expires_at: None,
+        purge_after: None,
+    }
+}
+
+#[test]
+fn test_jwks_key_verifies_signed_tokens() {
+    let config = app_config();
+    let keys = jwt_keys();
+    let claims = Claims::new(uuid::Uuid::new_v4().to_string(), &config);
+    let token = keys.sign(&claims).unwrap();
+
+    let jwk = keys.jwk();
+    assert_eq!((jwk.kty, jwk.alg, jwk.key_use), ("RSA", "RS256", "sig"));
+    assert_eq!(jwk.e, "AQAB");
+    let header = decode_header(&token).unwrap();
+    assert_eq!(header.kid.as_deref(), Some(jwk.kid.as_str()));
+
+    // * Un client OIDC ricostruisce la chiave dal solo JWKS
+    let key = DecodingKey::from_rsa_components(&jwk.n, &jwk.e).unwrap();
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_issuer(&["test-issuer"]);
+    validation.set_audience(&["test-audience"]);
+    let decoded = decode::<Claims>(&token, &key, &validation).unwrap();
+    assert_eq!(decoded.claims.sub, claims.sub);
+}
+
+#[test]
+fn test_at_hash_matches_spec_example() {
+    // * Esempio dell'appendice A.4 di OpenID Connect Core 1.0
+    assert_eq!(
+        oidc::at_hash("jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y"),
+        "77QmUPtjPfzWtF2AnpK9RQ"
+    );
+}
+
+#[test]
+fn test_discovery_and_claims_follow_config_and_scopes() {
+    let config = app_config();
+    let document = oidc::discovery_document(&config);
+    assert_eq!(document.issuer, "https://auth.ketchapp.example");
+    assert_eq!(
+        document.jwks_uri,
+        "https://auth.ketchapp.example/.well-known/jwks.json"
+    );
+    assert_eq!(document.code_challenge_methods_supported, vec!["S256"]);
+
+    let user = user();
+    let minimal = UserInfo::for_scopes(&user, &["openid"]);
+    assert_eq!(minimal.sub, user.id.to_string());
+    assert!(minimal.preferred_username.is_none() && minimal.email.is_none());
+
+    let full = UserInfo::for_scopes(&user, &["openid", "profile", "email"]);
+    assert_eq!(full.preferred_username.as_deref(), Some("johndoe"));
+    assert_eq!(full.email.as_deref(), Some("john_doe@example.com"));
+    assert_eq!(full.email_verified, Some(false));
+}
@@ -0,0 +1,435 @@
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{http::StatusCode, web, App};
+use chrono::Utc;
+use diesel::prelude::*;
+use ketchapp_auth_api::config::app_config::{AppConfig, PasswordPolicyConfig};
+use ketchapp_auth_api::errors::ServiceError;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::handlers::organizations::switch_organization_handler;
+use ketchapp_auth_api::models::claims::{Actor, Claims};
+use ketchapp_auth_api::models::data_export::NewDataExport;
+use ketchapp_auth_api::models::oauth::NewOAuthClient;
+use ketchapp_auth_api::models::organization::{
+    validate_slug_logic, CreateOrganization, InviteMember,
+};
+use ketchapp_auth_api::models::role::{RoleGrants, ADMIN_ROLE};
+use ketchapp_auth_api::repositories::{
+    data_exports_repo, establish_connection, oauth_clients_repo, organizations_repo, roles_repo,
+    PgPool,
+};
+use ketchapp_auth_api::schema::{oauth_clients, organizations, users};
+use ketchapp_auth_api::services::breached_passwords::BreachedPasswords;
+use ketchapp_auth_api::services::data_export;
+use ketchapp_auth_api::services::impersonation;
+use ketchapp_auth_api::services::organization::{self, normalize_roles};
+use ketchapp_auth_api::services::password_policy::PasswordPolicy;
+use ketchapp_auth_api::services::session::{self, SessionGrant, TokenSettings};
+use serde_json::json;
+use uuid::Uuid;
+
+mod common;
+use common::{app_config, create_user, jwt_keys, test_pool};
+
+#[test]
+fn test_slugs_are_lowercase_words_separated_by_hyphens() {
+    assert!(validate_slug_logic("liceo-galilei").is_ok());
+    assert!(validate_slug_logic("itis-2").is_ok());
+    assert!(validate_slug_logic("ab").is_err());
+    assert!(validate_slug_logic("Liceo-Galilei").is_err());
+    assert!(validate_slug_logic("-liceo").is_err());
+    assert!(validate_slug_logic("liceo--galilei").is_err());
+    assert!(validate_slug_logic("liceo galilei").is_err());
+}
+
+#[test]
+fn test_roles_are_deduplicated_in_a_stable_order() {
+    let roles = vec!["student".to_string(), "owner".into(), "student".into()];
+    assert_eq!(normalize_roles(&roles), vec!["owner", "student"]);
+}
+
+#[test]
+fn test_org_claims_are_omitted_without_an_active_organization() {
+    let config = app_config();
+    let mut claims = Claims::new("user".into(), &config);
+    let value = serde_json::to_value(&claims).unwrap();
+    assert!(value.get("org_id").is_none());
+    assert!(value.get("org_roles").is_none());
+
+    claims.org_id = Some("2b1f6c0e-8f4e-4a57-9d55-0c7f0d7a8c11".into());
+    claims.org_roles = vec!["teacher".into()];
+    let value = serde_json::to_value(&claims).unwrap();
+    assert_eq!(value["org_roles"], serde_json::json!(["teacher"]));
+    assert!(claims.has_org_role("teacher"));
+    assert!(!claims.has_org_role("owner"));
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_members_get_org_claims_and_the_last_owner_stays() {
+    let pool = test_pool();
+    let config = app_config();
+    let policy = PasswordPolicy::new(
+        PasswordPolicyConfig::default(),
+        BreachedPasswords::default(),
+    );
+    let client = ClientInfo::default();
+    let (owner, teacher) = (create_user(&pool), create_user(&pool));
+    let slug = format!("school-{}", &teacher.username);
+
+    let (school, _) = organization::create(
+        &pool,
+        owner.id,
+        CreateOrganization {
+            name: "Liceo Galilei".into(),
+            slug: slug.clone(),
+        },
+        &client,
+    )
+    .unwrap();
+    let taken = organization::create(
+        &pool,
+        teacher.id,
+        CreateOrganization {
+            name: "Copy".into(),
+            slug,
+        },
+        &client,
+    );
+    let invite = |actor_id, user: &str, role: &str| {
+        organization::invite_member(
+            &pool,
+            school.id,
+            actor_id,
+            InviteMember {
+                user: user.into(),
+                roles: vec![role.into()],
+            },
+            &client,
+        )
+    };
+    let not_owner = invite(teacher.id, &teacher.username, "owner");
+    // * Stessa risposta per un account inesistente e per chi è già membro
+    let unknown = invite(owner.id, "nobody@example.com", "teacher");
+    let already_member = invite(owner.id, &owner.email, "student");
+    invite(owner.id, &teacher.email, "owner").unwrap();
+    // * Un nuovo invito sostituisce i ruoli offerti
+    invite(owner.id, &teacher.username, "teacher").unwrap();
+    // * Finché non accetta, l'invitato non è membro
+    let listed_before_accepting = organization::list_members(&pool, school.id, owner.id).unwrap();
+    let mut conn = establish_connection(&pool).unwrap();
+    let before_accepting =
+        session::start_session_with_connection(&mut conn, teacher.id, &client, &config).unwrap();
+    let (_, accepted) =
+        organization::accept_invitation(&pool, school.id, teacher.id, &client).unwrap();
+    let accepted_twice = organization::accept_invitation(&pool, school.id, teacher.id, &client);
+    let demote_last_owner = organization::update_member(
+        &pool,
+        school.id,
+        owner.id,
+        owner.id,
+        &["teacher".to_string()],
+        &client,
+    );
+    let leave_as_last_owner =
+        organization::remove_member(&pool, school.id, owner.id, owner.id, &client);
+
+    // * Con una sola organizzazione la sessione parte con quella attiva
+    let issued =
+        session::start_session_with_connection(&mut conn, teacher.id, &client, &config).unwrap();
+    let claims = session::access_claims(
+        &teacher,
+        RoleGrants::default(),
+        issued.session.id,
+        issued.organization.as_ref(),
+        &TokenSettings::global(&config),
+        &config,
+        &policy,
+    );
+    let listed_by_teacher = organization::list_members(&pool, school.id, teacher.id);
+
+    // * Uscito dall'organizzazione, i token rinnovati non la riportano più
+    organization::remove_member(&pool, school.id, teacher.id, teacher.id, &client).unwrap();
+    let refreshed =
+        session::refresh_with_connection(&mut conn, &issued.refresh_token, &client, None, &config)
+            .unwrap();
+
+    diesel::delete(organizations::table.find(school.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.filter(users::id.eq_any([owner.id, teacher.id])))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(matches!(taken, Err(ServiceError::Conflict(_))));
+    assert!(matches!(not_owner, Err(ServiceError::NotFound(_))));
+    assert!(unknown.is_ok());
+    assert!(already_member.is_ok());
+    assert_eq!(listed_before_accepting.len(), 1);
+    assert_eq!(before_accepting.organization, None);
+    assert_eq!(accepted.roles, vec!["teacher"]);
+    assert!(!accepted.is_pending());
+    assert!(matches!(accepted_twice, Err(ServiceError::NotFound(_))));
+    assert!(matches!(demote_last_owner, Err(ServiceError::Conflict(_))));
+    assert!(matches!(
+        leave_as_last_owner,
+        Err(ServiceError::Conflict(_))
+    ));
+    assert_eq!(issued.session.organization_id, Some(school.id));
+    assert_eq!(claims.org_id, Some(school.id.to_string()));
+    assert_eq!(claims.org_roles, vec!["teacher"]);
+    assert_eq!(listed_by_teacher.unwrap().len(), 2);
+    assert_eq!(refreshed.session.organization_id, Some(school.id));
+    assert_eq!(refreshed.organization, None);
+}
+
+// * Rifiutare l'invito lo elimina: non si può più accettare e l'organizzazione non resta
+// * tra quelle dell'utente
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_invited_users_can_decline() {
+    let pool = test_pool();
+    let client = ClientInfo::default();
+    let (owner, student) = (create_user(&pool), create_user(&pool));
+    let (school, _) = organization::create(
+        &pool,
+        owner.id,
+        CreateOrganization {
+            name: "Liceo Volta".into(),
+            slug: format!("school-{}", &student.username),
+        },
+        &client,
+    )
+    .unwrap();
+    organization::invite_member(
+        &pool,
+        school.id,
+        owner.id,
+        InviteMember {
+            user: student.username.clone(),
+            roles: vec!["student".into()],
+        },
+        &client,
+    )
+    .unwrap();
+    let invited = organizations_repo::list_for_user(&pool, student.id).unwrap();
+    organization::remove_member(&pool, school.id, student.id, student.id, &client).unwrap();
+    let accepted_after_declining =
+        organization::accept_invitation(&pool, school.id, student.id, &client);
+    let listed_after_declining = organizations_repo::list_for_user(&pool, student.id).unwrap();
+
+    let mut conn = establish_connection(&pool).unwrap();
+    diesel::delete(organizations::table.find(school.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.filter(users::id.eq_any([owner.id, student.id])))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(invited.len(), 1);
+    assert!(invited[0].1.is_pending());
+    assert!(matches!(
+        accepted_after_declining,
+        Err(ServiceError::NotFound(_))
+    ));
+    assert!(listed_after_declining.is_empty());
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_data_export_lists_memberships_and_pending_exports_resume() {
+    let pool = test_pool();
+    let owner = create_user(&pool);
+    let (school, _) = organization::create(
+        &pool,
+        owner.id,
+        CreateOrganization {
+            name: "Liceo Volta".into(),
+            slug: format!("school-{}", &owner.username),
+        },
+        &ClientInfo::default(),
+    )
+    .unwrap();
+    let archive = data_export::build_archive(&pool, owner.id).unwrap();
+    // * Un'esportazione rimasta in attesa, come dopo un riavvio durante la generazione
+    let export = data_exports_repo::create(
+        &pool,
+        NewDataExport {
+            user_id: owner.id,
+            expires_at: (chrono::Utc::now() + chrono::Duration::days(1)).naive_utc(),
+        },
+    )
+    .unwrap();
+    let pending = data_exports_repo::list_pending(&pool).unwrap();
+
+    let mut conn = establish_connection(&pool).unwrap();
+    diesel::delete(organizations::table.find(school.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.find(owner.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(archive.memberships.len(), 1);
+    assert_eq!(archive.memberships[0].organization_id, school.id);
+    assert_eq!(archive.memberships[0].slug, school.slug);
+    assert_eq!(archive.memberships[0].roles, vec!["owner"]);
+    assert!(pending.iter().any(|pending| pending.id == export.id));
+}
+
+async fn switch_organization(
+    pool: &PgPool,
+    config: &AppConfig,
+    policy: PasswordPolicy,
+    claims: &Claims,
+    organization_id: Uuid,
+) -> StatusCode {
+    let keys = jwt_keys();
+    let token = keys.sign(claims).unwrap();
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(policy))
+            .service(switch_organization_handler),
+    )
+    .await;
+    let req = TestRequest::put()
+        .uri("/me/organization")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "organization_id": organization_id }))
+        .to_request();
+    call_service(&app, req).await.status()
+}
+
+// * Solo il token di un login diretto viene riemesso: quelli emessi a un client OAuth, anche
+// * per scambio sulla sessione del login, e quelli di impersonazione non diventano un token di
+// * prima parte con tutti gli scope dell'utente e senza il claim act
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_only_direct_login_tokens_can_switch_organization() {
+    let pool = test_pool();
+    let config = app_config();
+    let policy = || {
+        PasswordPolicy::new(
+            PasswordPolicyConfig::default(),
+            BreachedPasswords::default(),
+        )
+    };
+    let client_info = ClientInfo::default();
+    let (user, admin) = (create_user(&pool), create_user(&pool));
+    let (school, _) = organization::create(
+        &pool,
+        user.id,
+        CreateOrganization {
+            name: "Liceo Fermi".into(),
+            slug: format!("school-{}", &user.username),
+        },
+        &client_info,
+    )
+    .unwrap();
+    let oauth_client = oauth_clients_repo::create(
+        &pool,
+        NewOAuthClient {
+            name: "Homework planner".into(),
+            client_secret_hash: None,
+            redirect_uris: Vec::new(),
+            allowed_scopes: Vec::new(),
+            first_party: false,
+            created_by: None,
+            audience: None,
+            access_token_ttl_secs: None,
+            refresh_token_ttl_secs: None,
+            idle_timeout_secs: None,
+            token_exchange_audiences: Vec::new(),
+        },
+    )
+    .unwrap();
+    let mut conn = establish_connection(&pool).unwrap();
+
+    let direct =
+        session::start_session_with_connection(&mut conn, user.id, &client_info, &config).unwrap();
+    let direct_claims = session::access_claims(
+        &user,
+        RoleGrants::default(),
+        direct.session.id,
+        direct.organization.as_ref(),
+        &TokenSettings::global(&config),
+        &config,
+        &policy(),
+    );
+    // * Token come lo emette /oauth/token dopo l'autorizzazione del client
+    let granted = session::start_client_session_with_connection(
+        &mut conn,
+        user.id,
+        &client_info,
+        &config,
+        Some(SessionGrant {
+            client: &oauth_client,
+            scope: "",
+            auth_time: Utc::now().naive_utc(),
+        }),
+    )
+    .unwrap();
+    let mut oauth_claims = session::access_claims(
+        &user,
+        RoleGrants::default(),
+        granted.session.id,
+        granted.organization.as_ref(),
+        &TokenSettings::for_client(&oauth_client, &config),
+        &config,
+        &policy(),
+    );
+    oauth_claims.client_id = Some(oauth_client.id.to_string());
+    oauth_claims.set_scopes(&[]);
+    // * Token scambiato dal client: conserva la sessione del login diretto
+    let mut exchanged_claims = direct_claims.clone();
+    exchanged_claims.client_id = Some(oauth_client.id.to_string());
+    exchanged_claims.act = Some(Actor {
+        sub: oauth_client.id.to_string(),
+        impersonation: false,
+        act: None,
+    });
+    exchanged_claims.set_scopes(&[]);
+    roles_repo::assign_role_with_connection(&mut conn, admin.id, ADMIN_ROLE).unwrap();
+    let impersonation_session =
+        impersonation::start_session_with_connection(&mut conn, user.id, &client_info, &config)
+            .unwrap();
+    let impersonated_claims = impersonation::impersonation_claims(
+        user.id,
+        RoleGrants::default(),
+        admin.id,
+        impersonation_session.id,
+        &config,
+    );
+
+    let mut statuses = Vec::new();
+    for claims in [
+        &oauth_claims,
+        &exchanged_claims,
+        &impersonated_claims,
+        &direct_claims,
+    ] {
+        statuses.push(switch_organization(&pool, &config, policy(), claims, school.id).await);
+    }
+
+    diesel::delete(oauth_clients::table.find(oauth_client.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(organizations::table.find(school.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.filter(users::id.eq_any([user.id, admin.id])))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(
+        statuses,
+        vec![
+            StatusCode::FORBIDDEN,
+            StatusCode::FORBIDDEN,
+            StatusCode::FORBIDDEN,
+            StatusCode::OK,
+        ]
+    );
+}
@@ -0,0 +1,108 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+
+use diesel::prelude::*;
+use ketchapp_auth_api::repositories::outbox_repo::{self, NewOutboxEvent};
+use ketchapp_auth_api::repositories::{establish_connection, PgPool};
+use ketchapp_auth_api::schema::outbox_events;
+use ketchapp_auth_api::services::http_client::HttpClient;
+use ketchapp_auth_api::services::outbox::{relay_pending, RelayError};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+mod common;
+use common::test_pool;
+
+// * Webhook finto: risponde `status` a ogni POST e inoltra il corpo ricevuto
+fn spawn_webhook(status: u16) -> (String, mpsc::Receiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let _ = sender.send(serde_json::from_slice(&body).unwrap());
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+fn published_at(pool: &PgPool, id: Uuid) -> Option<chrono::NaiveDateTime> {
+    let mut conn = establish_connection(pool).unwrap();
+    outbox_events::table
+        .find(id)
+        .select(outbox_events::published_at)
+        .first(&mut conn)
+        .unwrap()
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_relay_publishes_pending_events_until_the_webhook_fails() {
+    let pool = test_pool();
+    let http = HttpClient::default();
+    let aggregate_id = Uuid::new_v4();
+    let add = |event_type: &str| {
+        let mut conn = establish_connection(&pool).unwrap();
+        outbox_repo::add_with_connection(
+            &mut conn,
+            NewOutboxEvent {
+                event_type: event_type.into(),
+                aggregate_id,
+                payload: json!({ "user_id": aggregate_id }),
+            },
+        )
+        .unwrap()
+    };
+
+    let delivered = add("user.deletion_scheduled");
+    let (url, received) = spawn_webhook(200);
+    let published = relay_pending(&pool, &http, &url).unwrap();
+    let bodies: Vec<Value> = received.try_iter().collect();
+
+    let rejected = add("user.deleted");
+    let (failing_url, _) = spawn_webhook(503);
+    let failure = relay_pending(&pool, &http, &failing_url);
+    let delivered_at = published_at(&pool, delivered.id);
+    let rejected_at = published_at(&pool, rejected.id);
+
+    let mut conn = establish_connection(&pool).unwrap();
+    diesel::delete(outbox_events::table.filter(outbox_events::aggregate_id.eq(aggregate_id)))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(published >= 1);
+    let body = bodies
+        .iter()
+        .find(|body| body["id"] == json!(delivered.id))
+        .expect("the event reached the webhook");
+    assert_eq!(body["event_type"], "user.deletion_scheduled");
+    assert_eq!(body["payload"]["user_id"], json!(aggregate_id));
+    assert!(delivered_at.is_some());
+    assert!(matches!(failure, Err(RelayError::Delivery(_, _))));
+    assert_eq!(rejected_at, None);
+}
@@ -0,0 +1,179 @@
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{http::StatusCode, web, App};
+use ketchapp_auth_api::config::app_config::PasswordPolicyConfig;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::handlers::password::change_password_handler;
+use ketchapp_auth_api::models::role::RoleGrants;
+use ketchapp_auth_api::models::user::User;
+use ketchapp_auth_api::repositories::{establish_connection, users_repo, PgPool};
+use ketchapp_auth_api::services::breached_passwords::{BreachedPasswordFilter, BreachedPasswords};
+use ketchapp_auth_api::services::password::PasswordService;
+use ketchapp_auth_api::services::password_policy::PasswordPolicy;
+use ketchapp_auth_api::services::session::{self, TokenSettings};
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_config, create_user, jwt_keys, test_pool};
+
+const CURRENT_PASSWORD: &str = "Quiet-harbour-lantern-71";
+const BREACHED_PASSWORD: &str = "Purple-monkey-dishwasher-42";
+
+fn password_policy() -> PasswordPolicy {
+    let mut filter = BreachedPasswordFilter::with_capacity(10, 0.01);
+    filter.insert_digest(&sha1_digest(BREACHED_PASSWORD));
+    PasswordPolicy::new(
+        PasswordPolicyConfig {
+            history_depth: 3,
+            ..PasswordPolicyConfig::default()
+        },
+        BreachedPasswords::new(filter),
+    )
+}
+
+fn sha1_digest(password: &str) -> [u8; 20] {
+    use sha1::{Digest, Sha1};
+    Sha1::digest(password.as_bytes()).into()
+}
+
+// * Utente con password reale a cui un admin ha imposto il cambio: l'unico token che ottiene è
+// * quello limitato al cambio password, come dopo il login
+fn user_with_forced_change(pool: &PgPool, password_service: &PasswordService) -> (User, String) {
+    let config = app_config();
+    let user = create_user(pool);
+    let mut conn = establish_connection(pool).unwrap();
+    users_repo::change_password_with_connection(
+        &mut conn,
+        user.id,
+        &password_service.hash(CURRENT_PASSWORD).unwrap(),
+    )
+    .unwrap();
+    let user = users_repo::force_password_change(pool, user.id).unwrap();
+    let issued =
+        session::start_session_with_connection(&mut conn, user.id, &ClientInfo::default(), &config)
+            .unwrap();
+    let claims = session::access_claims(
+        &user,
+        RoleGrants::default(),
+        issued.session.id,
+        None,
+        &TokenSettings::global(&config),
+        &config,
+        &password_policy(),
+    );
+    assert!(claims.password_change_required);
+    (user, jwt_keys().sign(&claims).unwrap())
+}
+
+async fn change_password(
+    pool: &PgPool,
+    password_service: &PasswordService,
+    token: &str,
+    current_password: &str,
+    new_password: &str,
+) -> (StatusCode, Value) {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(app_config()))
+            .app_data(web::Data::new(jwt_keys()))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(password_service.clone()))
+            .app_data(web::Data::new(password_policy()))
+            .service(change_password_handler),
+    )
+    .await;
+    let req = TestRequest::post()
+        .uri("/me/password")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "current_password": current_password,
+            "new_password": new_password,
+        }))
+        .to_request();
+    let response = call_service(&app, req).await;
+    let status = response.status();
+    if status == StatusCode::NO_CONTENT {
+        return (status, Value::Null);
+    }
+    (status, read_body_json(response).await)
+}
+
+fn violated_rules(body: &Value) -> Vec<&str> {
+    body["violations"]
+        .as_array()
+        .map(|violations| {
+            violations
+                .iter()
+                .filter_map(|violation| violation["rule"].as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// * Non esiste un flusso di reset: il reset imposto dall'admin porta al cambio password con il
+// * token limitato, che deve applicare lo stesso controllo sulle password compromesse
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_forced_change_rejects_breached_passwords() {
+    let pool = test_pool();
+    let password_service = PasswordService::from_config(&app_config()).unwrap();
+    let (_, token) = user_with_forced_change(&pool, &password_service);
+
+    let (status, body) = change_password(
+        &pool,
+        &password_service,
+        &token,
+        CURRENT_PASSWORD,
+        BREACHED_PASSWORD,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(violated_rules(&body), vec!["breached"]);
+}
+
+// * Stesso percorso per la cronologia: né la password attuale né una precedente possono tornare
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_forced_change_rejects_reused_passwords() {
+    let pool = test_pool();
+    let password_service = PasswordService::from_config(&app_config()).unwrap();
+    let (user, token) = user_with_forced_change(&pool, &password_service);
+    let next_password = "Silver-meadow-compass-93";
+
+    let (status, body) = change_password(
+        &pool,
+        &password_service,
+        &token,
+        CURRENT_PASSWORD,
+        CURRENT_PASSWORD,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(violated_rules(&body), vec!["reused"]);
+
+    let (status, _) = change_password(
+        &pool,
+        &password_service,
+        &token,
+        CURRENT_PASSWORD,
+        next_password,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(
+        !users_repo::get_own_user(&pool, user.id)
+            .unwrap()
+            .must_change_password
+    );
+
+    // * La password appena sostituita è ora nella cronologia
+    let (status, body) = change_password(
+        &pool,
+        &password_service,
+        &token,
+        next_password,
+        CURRENT_PASSWORD,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(violated_rules(&body), vec!["reused"]);
+}
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use ketchapp_auth_api::services::password::PasswordService;
+
+fn service_with(peppers: &[(u32, &str)]) -> PasswordService {
+    PasswordService::new(
+        peppers
+            .iter()
+            .map(|(version, secret)| (*version, secret.as_bytes().to_vec()))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
+
+#[test]
+fn test_hash_without_pepper_is_plain_argon2() {
+    let service = PasswordService::default();
+    let hash = service.hash("Password123!").unwrap();
+    assert!(!hash.contains("keyid="));
+    assert!(service.verify("Password123!", &hash).is_ok());
+    assert!(service.verify("Password123?", &hash).is_err());
+    assert!(!service.needs_rehash(&hash));
+}
+
+#[test]
+fn test_peppered_hash_records_version_and_needs_pepper() {
+    let service = service_with(&[(1, "first-pepper")]);
+    let hash = service.hash("Password123!").unwrap();
+    assert!(hash.contains("keyid="));
+    assert!(service.verify("Password123!", &hash).is_ok());
+
+    // * Senza il pepper giusto la verifica deve fallire
+    let wrong = service_with(&[(1, "other-pepper")]);
+    assert!(wrong.verify("Password123!", &hash).is_err());
+    assert!(PasswordService::default()
+        .verify("Password123!", &hash)
+        .is_err());
+}
+
+#[test]
+fn test_rotation_verifies_old_versions_and_flags_rehash() {
+    let legacy = PasswordService::default().hash("Password123!").unwrap();
+    let v1 = service_with(&[(1, "first-pepper")])
+        .hash("Password123!")
+        .unwrap();
+
+    let rotated = service_with(&[(1, "first-pepper"), (2, "second-pepper")]);
+    assert_eq!(rotated.current_version(), Some(2));
+    assert!(rotated.verify("Password123!", &legacy).is_ok());
+    assert!(rotated.verify("Password123!", &v1).is_ok());
+    assert!(rotated.needs_rehash(&legacy));
+    assert!(rotated.needs_rehash(&v1));
+
+    let v2 = rotated.hash("Password123!").unwrap();
+    assert!(!rotated.needs_rehash(&v2));
+}
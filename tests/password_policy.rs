@@ -0,0 +1,98 @@
+use ketchapp_auth_api::config::app_config::PasswordPolicyConfig;
+use ketchapp_auth_api::services::breached_passwords::BreachedPasswords;
+use ketchapp_auth_api::services::password::PasswordService;
+use ketchapp_auth_api::services::password_policy::{
+    strength_score, PasswordContext, PasswordPolicy, PolicyRule,
+};
+
+fn rules(policy: &PasswordPolicy, password: &str, context: &PasswordContext) -> Vec<PolicyRule> {
+    match policy.check(password, context, &PasswordService::default()) {
+        Ok(()) => vec![],
+        Err(violations) => violations.into_iter().map(|v| v.rule).collect(),
+    }
+}
+
+fn context() -> PasswordContext<'static> {
+    PasswordContext {
+        username: "johndoe",
+        email: "john_doe@gmail.com",
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_default_policy_accepts_long_passphrases() {
+    let policy = PasswordPolicy::new(
+        PasswordPolicyConfig::default(),
+        BreachedPasswords::default(),
+    );
+    let passphrase = "Correct horse battery staple, 42 times over & over";
+    assert!(passphrase.len() > 32);
+    assert_eq!(rules(&policy, passphrase, &context()), vec![]);
+}
+
+#[test]
+fn test_default_policy_reports_every_violation() {
+    let policy = PasswordPolicy::new(
+        PasswordPolicyConfig::default(),
+        BreachedPasswords::default(),
+    );
+    let violations = rules(&policy, "abc", &context());
+    assert!(violations.contains(&PolicyRule::MinLength));
+    assert!(violations.contains(&PolicyRule::Uppercase));
+    assert!(violations.contains(&PolicyRule::Digit));
+    assert!(violations.contains(&PolicyRule::Symbol));
+    assert!(violations.contains(&PolicyRule::Strength));
+}
+
+#[test]
+fn test_policy_rejects_user_info_and_weak_patterns() {
+    let policy = PasswordPolicy::new(
+        PasswordPolicyConfig::default(),
+        BreachedPasswords::default(),
+    );
+    assert!(rules(&policy, "Johndoe2024!x", &context()).contains(&PolicyRule::ContainsUserInfo));
+    assert!(rules(&policy, "Password1!", &context()).contains(&PolicyRule::Strength));
+    assert!(rules(&policy, "Qwerty123456!", &context()).contains(&PolicyRule::Strength));
+}
+
+#[test]
+fn test_policy_rejects_reused_passwords() {
+    let password_service = PasswordService::default();
+    let previous = vec![password_service.hash("Tr0ub4dor&3-horse").unwrap()];
+    let policy = PasswordPolicy::new(
+        PasswordPolicyConfig::default(),
+        BreachedPasswords::default(),
+    );
+    let context = PasswordContext {
+        previous_hashes: &previous,
+        ..context()
+    };
+    let violations = policy
+        .check("Tr0ub4dor&3-horse", &context, &password_service)
+        .unwrap_err();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].rule, PolicyRule::Reused);
+}
+
+#[test]
+fn test_policy_rules_are_configurable() {
+    let config = PasswordPolicyConfig {
+        min_length: 4,
+        require_uppercase: false,
+        require_digit: false,
+        require_symbol: false,
+        min_strength_score: 0,
+        ..Default::default()
+    };
+    let policy = PasswordPolicy::new(config, BreachedPasswords::default());
+    assert_eq!(rules(&policy, "abcd", &context()), vec![]);
+}
+
+#[test]
+fn test_strength_score_orders_passwords() {
+    assert_eq!(strength_score("password", &[]), 0);
+    assert!(strength_score("Password1!", &[]) <= 1);
+    assert!(strength_score("correct horse battery staple", &[]) >= 4);
+    assert!(strength_score("kT9#vLq2!xWm", &[]) >= 3);
+}
@@ -0,0 +1,161 @@
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{get, http::StatusCode, web, App, HttpResponse};
+use diesel::prelude::*;
+use ketchapp_auth_api::config::app_config::{AppConfig, PasswordPolicyConfig};
+use ketchapp_auth_api::errors::ServiceError;
+use ketchapp_auth_api::extractors::{auth::AuthenticatedUser, client_info::ClientInfo};
+use ketchapp_auth_api::models::personal_access_token::CreatePersonalAccessToken;
+use ketchapp_auth_api::models::role::STUDENT_ROLE;
+use ketchapp_auth_api::repositories::{establish_connection, roles_repo, PgPool};
+use ketchapp_auth_api::schema::{personal_access_tokens, users};
+use ketchapp_auth_api::services::breached_passwords::BreachedPasswords;
+use ketchapp_auth_api::services::password_policy::PasswordPolicy;
+use ketchapp_auth_api::services::personal_access_token::{self, TOKEN_PREFIX};
+
+mod common;
+use common::{app_config_with, create_user, jwt_keys, test_pool};
+
+#[get("/whoami")]
+async fn whoami(auth: AuthenticatedUser) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{} {}",
+        auth.user_id,
+        auth.claims.scope.unwrap_or_default()
+    ))
+}
+
+fn app_config() -> AppConfig {
+    app_config_with(serde_json::json!({
+        "personal_access_token_scopes": ["study.read", "study.write"],
+    }))
+}
+
+#[test]
+fn test_tokens_are_prefixed_and_unique() {
+    let first = personal_access_token::generate_token();
+    let second = personal_access_token::generate_token();
+    assert!(first.starts_with("kapp_pat_"));
+    assert!(personal_access_token::is_personal_access_token(&first));
+    assert_eq!(first.len(), TOKEN_PREFIX.len() + 64);
+    assert_ne!(first, second);
+    // * Un JWT non viene scambiato per un personal access token
+    assert!(!personal_access_token::is_personal_access_token(
+        "eyJhbGciOiJSUzI1NiJ9.e30.sig"
+    ));
+}
+
+#[test]
+fn test_display_prefix_keeps_the_secret_hidden() {
+    let token = personal_access_token::generate_token();
+    let prefix = personal_access_token::display_prefix(&token);
+    assert_eq!(prefix.len(), TOKEN_PREFIX.len() + 8);
+    assert!(token.starts_with(&prefix));
+}
+
+async fn call_whoami(pool: &PgPool, token: &str) -> (StatusCode, String) {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(app_config()))
+            .app_data(web::Data::new(jwt_keys()))
+            .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(PasswordPolicy::new(
+                PasswordPolicyConfig {
+                    max_age_days: Some(90),
+                    ..PasswordPolicyConfig::default()
+                },
+                BreachedPasswords::default(),
+            )))
+            .service(whoami),
+    )
+    .await;
+    let req = TestRequest::get()
+        .uri("/whoami")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let response = call_service(&app, req).await;
+    let status = response.status();
+    let body = read_body(response).await;
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+fn request(scopes: &[&str], expires_in_days: Option<u32>) -> CreatePersonalAccessToken {
+    CreatePersonalAccessToken {
+        name: "Nightly sync".into(),
+        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        expires_in_days,
+    }
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_token_authenticates_until_revoked_or_expired() {
+    let pool = test_pool();
+    let config = app_config();
+    let client = ClientInfo::default();
+    let user = create_user(&pool);
+    // * Gli scope study.* restano nel token solo finché un ruolo dell'utente li concede
+    let mut conn = establish_connection(&pool).unwrap();
+    roles_repo::assign_role_with_connection(&mut conn, user.id, STUDENT_ROLE).unwrap();
+
+    let (token, secret) = personal_access_token::create(
+        &pool,
+        user.id,
+        request(&["study.write", "study.read", "study.read"], Some(30)),
+        &client,
+        &config,
+    )
+    .unwrap();
+    let (expiring, expiring_secret) = personal_access_token::create(
+        &pool,
+        user.id,
+        request(&["study.read"], None),
+        &client,
+        &config,
+    )
+    .unwrap();
+    let forbidden_scope =
+        personal_access_token::create(&pool, user.id, request(&["admin"], None), &client, &config);
+
+    let accepted = call_whoami(&pool, &secret).await;
+    // * Una password scaduta secondo la policy blocca il token come farebbe con una sessione
+    diesel::update(users::table.find(user.id))
+        .set(
+            users::password_changed_at
+                .eq(chrono::Utc::now().naive_utc() - chrono::Duration::days(91)),
+        )
+        .execute(&mut conn)
+        .unwrap();
+    let password_expired = call_whoami(&pool, &secret).await;
+    personal_access_token::revoke(&pool, user.id, token.id, &client).unwrap();
+    let revoked = call_whoami(&pool, &secret).await;
+    let revoked_twice = personal_access_token::revoke(&pool, user.id, token.id, &client);
+    diesel::update(personal_access_tokens::table.find(expiring.id))
+        .set(personal_access_tokens::expires_at.eq(Some(chrono::Utc::now().naive_utc())))
+        .execute(&mut conn)
+        .unwrap();
+    let expired = call_whoami(&pool, &expiring_secret).await;
+    let unknown = call_whoami(&pool, &personal_access_token::generate_token()).await;
+    diesel::delete(users::table.find(user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(token.scopes, vec!["study.read", "study.write"]);
+    assert!(token.expires_at.is_some());
+    assert!(!secret.contains(&token.token_hash));
+    assert!(matches!(
+        forbidden_scope,
+        Err(ServiceError::ValidationError(_))
+    ));
+    assert_eq!(
+        accepted,
+        (
+            StatusCode::OK,
+            format!("{} study.read study.write", user.id)
+        )
+    );
+    assert_eq!(password_expired.0, StatusCode::FORBIDDEN);
+    assert_eq!(revoked.0, StatusCode::UNAUTHORIZED);
+    assert!(matches!(revoked_twice, Err(ServiceError::NotFound(_))));
+    assert_eq!(expired.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown.0, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,51 @@
+use actix_web::{get, http::StatusCode, test, web, App, HttpResponse};
+use ketchapp_auth_api::extractors::role::{Admin, RequireRole};
+use ketchapp_auth_api::models::claims::Claims;
+
+mod common;
+use common::{app_config, create_user, jwt_keys, test_pool};
+
+#[get("/admin-only")]
+async fn admin_only(admin: RequireRole<Admin>) -> HttpResponse {
+    HttpResponse::Ok().body(admin.user_id.to_string())
+}
+
+// * Il token deve appartenere a un account attivo: i ruoli arrivano comunque dal claim
+async fn call_with_roles(roles: &[&str]) -> StatusCode {
+    let config = app_config();
+    let keys = jwt_keys();
+    let pool = test_pool();
+    let mut claims = Claims::new(create_user(&pool).id.to_string(), &config);
+    claims.roles = roles.iter().map(|r| r.to_string()).collect();
+    let token = keys.sign(&claims).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool))
+            .service(admin_only),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/admin-only")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    test::call_service(&app, req).await.status()
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_require_role_accepts_token_with_role() {
+    assert_eq!(call_with_roles(&["student", "admin"]).await, StatusCode::OK);
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_require_role_rejects_token_without_role() {
+    assert_eq!(
+        call_with_roles(&["student", "teacher"]).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(call_with_roles(&[]).await, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,144 @@
+use diesel::prelude::*;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::models::audit_event::AuditOutcome;
+use ketchapp_auth_api::models::personal_access_token::CreatePersonalAccessToken;
+use ketchapp_auth_api::models::user::User;
+use ketchapp_auth_api::repositories::{
+    as_user, establish_connection, personal_access_tokens_repo, sessions_repo, users_repo, PgPool,
+};
+use ketchapp_auth_api::schema::{personal_access_tokens, sessions, users};
+use ketchapp_auth_api::services::audit::{self, AuditEvent};
+use ketchapp_auth_api::services::{personal_access_token, session};
+
+mod common;
+use common::{app_config, create_user, test_pool};
+
+fn cleanup(pool: &PgPool, ids: &[uuid::Uuid]) {
+    let mut conn = establish_connection(pool).unwrap();
+    diesel::delete(users::table.filter(users::id.eq_any(ids)))
+        .execute(&mut conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_user_can_only_read_own_row() {
+    let pool = test_pool();
+    let alice = create_user(&pool);
+    let bob = create_user(&pool);
+
+    let visible = as_user(&pool, alice.id, |conn| {
+        users::table.select(users::id).load::<uuid::Uuid>(conn)
+    })
+    .unwrap();
+    let bob_as_alice = as_user(&pool, alice.id, |conn| {
+        users::table.find(bob.id).first::<User>(conn).optional()
+    })
+    .unwrap();
+    let own = users_repo::get_own_user(&pool, alice.id).map(|user| user.id);
+    cleanup(&pool, &[alice.id, bob.id]);
+
+    assert_eq!(visible, vec![alice.id]);
+    assert!(bob_as_alice.is_none());
+    assert_eq!(own.unwrap(), alice.id);
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_user_cannot_change_another_users_row() {
+    let pool = test_pool();
+    let alice = create_user(&pool);
+    let bob = create_user(&pool);
+
+    let updated = as_user(&pool, alice.id, |conn| {
+        diesel::update(users::table.find(bob.id))
+            .set(users::email.eq("stolen@example.com"))
+            .execute(conn)
+    })
+    .unwrap();
+    let deleted = as_user(&pool, alice.id, |conn| {
+        diesel::delete(users::table.find(bob.id)).execute(conn)
+    })
+    .unwrap();
+    // * La connessione torna nel pool con il ruolo originale: l'utente è ancora visibile a tutti
+    let bob_after = users_repo::get_user_by_id(&pool, bob.id).unwrap();
+    cleanup(&pool, &[alice.id, bob.id]);
+
+    assert_eq!(updated, 0);
+    assert_eq!(deleted, 0);
+    assert_eq!(bob_after.email, bob.email);
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_user_scoped_tables_only_expose_own_rows() {
+    let pool = test_pool();
+    let config = app_config();
+    let client = ClientInfo::default();
+    let alice = create_user(&pool);
+    let bob = create_user(&pool);
+    let mut conn = establish_connection(&pool).unwrap();
+    let alice_session =
+        session::start_session_with_connection(&mut conn, alice.id, &client, &config)
+            .unwrap()
+            .session;
+    let bob_session = session::start_session_with_connection(&mut conn, bob.id, &client, &config)
+        .unwrap()
+        .session;
+    // * La creazione passa anch'essa dal ruolo applicativo
+    let (bob_token, _) = personal_access_token::create(
+        &pool,
+        bob.id,
+        CreatePersonalAccessToken {
+            name: "script".into(),
+            scopes: Vec::new(),
+            expires_in_days: None,
+        },
+        &client,
+        &config,
+    )
+    .unwrap();
+
+    let visible_sessions = as_user(&pool, alice.id, |conn| {
+        sessions::table
+            .select(sessions::id)
+            .load::<uuid::Uuid>(conn)
+    })
+    .unwrap();
+    let revoked_foreign_session = as_user(&pool, alice.id, |conn| {
+        sessions_repo::revoke_with_connection(conn, bob.id, bob_session.id)
+    })
+    .unwrap();
+    let visible_tokens = as_user(&pool, alice.id, |conn| {
+        personal_access_tokens::table
+            .select(personal_access_tokens::id)
+            .load::<uuid::Uuid>(conn)
+    })
+    .unwrap();
+    let revoked_foreign_token = as_user(&pool, alice.id, |conn| {
+        personal_access_tokens_repo::revoke_with_connection(conn, bob.id, bob_token.id)
+    })
+    .unwrap();
+    let foreign_audit_event = as_user(&pool, alice.id, |conn| {
+        audit::record_with_connection(
+            conn,
+            AuditEvent {
+                action: "session.revoked",
+                outcome: AuditOutcome::Success,
+                actor_id: Some(alice.id),
+                target_user_id: Some(bob.id),
+                client: None,
+                details: serde_json::json!({}),
+            },
+        )
+    });
+    let own_tokens = personal_access_tokens_repo::list_for_user(&pool, bob.id).unwrap();
+    cleanup(&pool, &[alice.id, bob.id]);
+
+    assert_eq!(visible_sessions, vec![alice_session.id]);
+    assert_eq!(revoked_foreign_session, 0);
+    assert!(visible_tokens.is_empty());
+    assert!(revoked_foreign_token.is_none());
+    assert!(foreign_audit_event.is_err());
+    assert_eq!(own_tokens.len(), 1);
+}
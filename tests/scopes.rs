@@ -0,0 +1,197 @@
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{get, http::StatusCode, web, App, HttpResponse};
+use diesel::prelude::*;
+use ketchapp_auth_api::config::open_api::ApiDoc;
+use ketchapp_auth_api::extractors::role::{Admin, RequireRole};
+use ketchapp_auth_api::extractors::scope::{AdminScope, RequireScope, ScopeName};
+use ketchapp_auth_api::models::claims::Claims;
+use ketchapp_auth_api::models::role::{Permission, RoleGrants};
+use ketchapp_auth_api::repositories::{establish_connection, roles_repo};
+use ketchapp_auth_api::schema::users;
+use ketchapp_auth_api::services::scope;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+mod common;
+use common::{app_config, create_user, jwt_keys, test_pool};
+
+struct StudyWrite;
+
+impl ScopeName for StudyWrite {
+    const NAME: &'static str = "study.write";
+}
+
+#[get("/admin-only")]
+async fn admin_only(admin: RequireScope<AdminScope, RequireRole<Admin>>) -> HttpResponse {
+    HttpResponse::Ok().body(admin.user_id.to_string())
+}
+
+#[get("/study")]
+async fn study(user: RequireScope<StudyWrite>) -> HttpResponse {
+    HttpResponse::Ok().body(user.user_id.to_string())
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+fn permission(name: &str, description: &str) -> Permission {
+    Permission {
+        id: Uuid::new_v4(),
+        name: name.into(),
+        description: description.into(),
+    }
+}
+
+#[test]
+fn test_delegated_scopes_are_limited_by_grants() {
+    let teacher = RoleGrants {
+        roles: strings(&["teacher"]),
+        scopes: strings(&["study.read"]),
+        withheld_scopes: strings(&["admin", "analytics.ingest", "study.write"]),
+    };
+    // * Gli scope fuori dal catalogo (openid, scope di un client) non dipendono dai ruoli
+    assert_eq!(
+        scope::restrict_to_grants(
+            &teacher,
+            ["openid", "study.read", "study.write", "admin", "reports"]
+        ),
+        vec!["openid", "study.read", "reports"]
+    );
+    // * Uno scope che nessun ruolo concede non passa mai per un utente
+    assert!(scope::restrict_to_grants(&teacher, ["analytics.ingest"]).is_empty());
+}
+
+#[test]
+fn test_openapi_publishes_the_scope_catalogue() {
+    let catalogue = vec![
+        (
+            permission("admin", "Manage users, OAuth clients and the audit log"),
+            strings(&["admin"]),
+        ),
+        (permission("analytics.ingest", "Push events"), Vec::new()),
+        (
+            permission("study.read", "Read study sessions"),
+            strings(&["student", "teacher"]),
+        ),
+    ];
+    let mut openapi = ApiDoc::openapi();
+    scope::document(&mut openapi, &catalogue, &app_config());
+    let document = serde_json::to_value(&openapi).unwrap();
+    let flows = &document["components"]["securitySchemes"][scope::SECURITY_SCHEME]["flows"];
+    let scopes = &flows["authorizationCode"]["scopes"];
+    assert_eq!(
+        scopes["study.read"],
+        "Read study sessions (roles: student, teacher)"
+    );
+    assert_eq!(scopes["analytics.ingest"], "Push events");
+    assert!(scopes["openid"].is_string());
+    assert_eq!(
+        scopes["admin"],
+        "Manage users, OAuth clients and the audit log (roles: admin)"
+    );
+    assert_eq!(flows["clientCredentials"]["scopes"], *scopes);
+    assert_eq!(
+        document["paths"]["/api/admin/users"]["get"]["security"][0][scope::SECURITY_SCHEME][0],
+        "admin"
+    );
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_role_permissions_grant_the_scopes() {
+    let pool = test_pool();
+    let user = create_user(&pool);
+    let mut conn = establish_connection(&pool).unwrap();
+    let nobody = roles_repo::grants_for_user_with_connection(&mut conn, user.id).unwrap();
+    roles_repo::assign_role_with_connection(&mut conn, user.id, "teacher").unwrap();
+    let teacher = roles_repo::grants_for_user_with_connection(&mut conn, user.id).unwrap();
+    roles_repo::assign_role_with_connection(&mut conn, user.id, "student").unwrap();
+    let student = roles_repo::grants_for_user_with_connection(&mut conn, user.id).unwrap();
+    let catalogue = roles_repo::scope_catalogue(&pool).unwrap();
+    diesel::delete(users::table.find(user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(nobody.roles.is_empty() && nobody.scopes.is_empty());
+    assert_eq!(
+        nobody.withheld_scopes,
+        vec!["admin", "study.read", "study.write"]
+    );
+    assert_eq!(teacher.scopes, vec!["study.read"]);
+    assert_eq!(teacher.withheld_scopes, vec!["admin", "study.write"]);
+    assert_eq!(student.roles, vec!["student", "teacher"]);
+    assert_eq!(student.scopes, vec!["study.read", "study.write"]);
+    let roles_of = |name: &str| {
+        catalogue
+            .iter()
+            .find(|(permission, _)| permission.name == name)
+            .map(|(_, roles)| roles.clone())
+    };
+    assert_eq!(roles_of("admin"), Some(strings(&["admin"])));
+    assert_eq!(
+        roles_of("study.read"),
+        Some(strings(&["student", "teacher"]))
+    );
+}
+
+// * Il token deve appartenere a un account attivo: ruoli e scope arrivano comunque dai claim
+async fn call(uri: &str, roles: &[&str], scope: Option<&str>) -> StatusCode {
+    let config = app_config();
+    let keys = jwt_keys();
+    let pool = test_pool();
+    let mut claims = Claims::new(create_user(&pool).id.to_string(), &config);
+    claims.roles = strings(roles);
+    claims.scope = scope.map(str::to_string);
+    let token = keys.sign(&claims).unwrap();
+
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(pool))
+            .service(admin_only)
+            .service(study),
+    )
+    .await;
+    let req = TestRequest::get()
+        .uri(uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    call_service(&app, req).await.status()
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_require_scope_checks_the_scope_claim() {
+    assert_eq!(
+        call("/study", &[], Some("study.read study.write")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        call("/study", &["student"], Some("study.read")).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        call("/study", &["student"], None).await,
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[actix_web::test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+async fn test_require_scope_wraps_the_role_guard() {
+    assert_eq!(
+        call("/admin-only", &["admin"], Some("admin")).await,
+        StatusCode::OK
+    );
+    // * Il ruolo da solo non basta: ad esempio un client OAuth a cui non è stato concesso admin
+    assert_eq!(
+        call("/admin-only", &["admin"], Some("openid")).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        call("/admin-only", &["student"], Some("admin")).await,
+        StatusCode::FORBIDDEN
+    );
+}
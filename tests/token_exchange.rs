@@ -0,0 +1,143 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::models::claims::{Actor, Claims};
+use ketchapp_auth_api::models::oauth::{NewOAuthClient, TokenRequest};
+use ketchapp_auth_api::repositories::{establish_connection, oauth_clients_repo};
+use ketchapp_auth_api::schema::{oauth_clients, users};
+use ketchapp_auth_api::services::session;
+use ketchapp_auth_api::services::token_exchange::{self, ACCESS_TOKEN_TYPE};
+
+mod common;
+use common::{app_config, create_user, jwt_keys, test_pool};
+
+#[test]
+fn test_nested_actors_round_trip_through_the_act_claim() {
+    let config = app_config();
+    let mut claims = Claims::new("user".into(), &config);
+    assert!(!serde_json::to_value(&claims)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .contains_key("act"));
+
+    claims.act = Some(Actor {
+        sub: "reports".into(),
+        impersonation: false,
+        act: Some(Box::new(Actor {
+            sub: "gateway".into(),
+            impersonation: false,
+            act: None,
+        })),
+    });
+    let keys = jwt_keys();
+    let token = keys.sign(&claims).unwrap();
+    let decoded = keys.verify(&token, &config).unwrap();
+    assert_eq!(decoded.act, claims.act);
+    assert_eq!(
+        serde_json::to_value(&decoded.act).unwrap(),
+        serde_json::json!({"sub": "reports", "act": {"sub": "gateway"}})
+    );
+}
+
+fn exchange_request(subject_token: &str, audience: &str, scope: Option<&str>) -> TokenRequest {
+    TokenRequest {
+        grant_type: Some(token_exchange::GRANT_TYPE.into()),
+        subject_token: Some(subject_token.into()),
+        subject_token_type: Some(ACCESS_TOKEN_TYPE.into()),
+        audience: Some(audience.into()),
+        scope: scope.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_services_exchange_user_tokens_for_allowed_audiences() {
+    let pool = test_pool();
+    let config = app_config();
+    let keys = jwt_keys();
+    let client_info = ClientInfo::default();
+    let user = create_user(&pool);
+    let gateway = oauth_clients_repo::create(
+        &pool,
+        NewOAuthClient {
+            name: "Gateway".into(),
+            client_secret_hash: Some("0".repeat(64)),
+            redirect_uris: Vec::new(),
+            allowed_scopes: Vec::new(),
+            first_party: true,
+            created_by: None,
+            audience: None,
+            access_token_ttl_secs: Some(3600),
+            refresh_token_ttl_secs: None,
+            idle_timeout_secs: None,
+            token_exchange_audiences: vec!["reports".into()],
+        },
+    )
+    .unwrap();
+    let mut conn = establish_connection(&pool).unwrap();
+    let issued =
+        session::start_session_with_connection(&mut conn, user.id, &client_info, &config).unwrap();
+
+    let mut subject = Claims::new(user.id.to_string(), &config);
+    subject.sid = Some(issued.session.id.to_string());
+    subject.scope = Some("reports.read reports.write".into());
+    subject.act = Some(Actor {
+        sub: "mobile-app".into(),
+        impersonation: false,
+        act: None,
+    });
+    let subject_token = keys.sign(&subject).unwrap();
+
+    let not_allowed = token_exchange::exchange(
+        &pool,
+        &gateway,
+        &exchange_request(&subject_token, "billing", None),
+        &client_info,
+        &config,
+        &keys,
+    );
+    let broadened = token_exchange::exchange(
+        &pool,
+        &gateway,
+        &exchange_request(&subject_token, "reports", Some("reports.read admin")),
+        &client_info,
+        &config,
+        &keys,
+    );
+    let exchanged = token_exchange::exchange(
+        &pool,
+        &gateway,
+        &exchange_request(&subject_token, "reports", Some("reports.read")),
+        &client_info,
+        &config,
+        &keys,
+    );
+
+    diesel::delete(oauth_clients::table.find(gateway.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.find(user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert_eq!(not_allowed.unwrap_err().error, "invalid_target");
+    assert_eq!(broadened.unwrap_err().error, "invalid_scope");
+    let claims = exchanged.unwrap();
+    assert_eq!(claims.sub, user.id.to_string());
+    assert_eq!(claims.aud, "reports");
+    assert_eq!(claims.scope.as_deref(), Some("reports.read"));
+    assert_eq!(claims.sid, subject.sid);
+    // * Il token scambiato non sopravvive a quello presentato
+    assert_eq!(claims.exp, subject.exp);
+    assert!(claims.exp <= (Utc::now().timestamp() + 300) as usize);
+    assert_eq!(
+        claims.act,
+        Some(Actor {
+            sub: gateway.id.to_string(),
+            impersonation: false,
+            act: subject.act.map(Box::new),
+        })
+    );
+}
@@ -0,0 +1,221 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use ketchapp_auth_api::config::app_config::AppConfig;
+use ketchapp_auth_api::errors::ServiceError;
+use ketchapp_auth_api::extractors::client_info::ClientInfo;
+use ketchapp_auth_api::models::oauth::{NewOAuthClient, OAuthClient};
+use ketchapp_auth_api::repositories::{establish_connection, oauth_clients_repo, sessions_repo};
+use ketchapp_auth_api::schema::{oauth_clients, sessions, users};
+use ketchapp_auth_api::services::oauth::client_claims;
+use ketchapp_auth_api::services::session::{self, SessionGrant, TokenSettings};
+
+mod common;
+use common::{app_config_with, create_user, jwt_keys, test_pool};
+
+fn app_config() -> AppConfig {
+    app_config_with(serde_json::json!({
+        "refresh_token_exp_secs": 86400,
+        "session_idle_timeout_secs": 3600,
+    }))
+}
+
+fn client(audience: Option<&str>, access_ttl: Option<i32>, idle: Option<i32>) -> OAuthClient {
+    OAuthClient {
+        id: uuid::Uuid::new_v4(),
+        name: "Analytics".into(),
+        client_secret_hash: Some("0".repeat(64)),
+        redirect_uris: Vec::new(),
+        allowed_scopes: Vec::new(),
+        first_party: false,
+        created_by: None,
+        created_at: Utc::now().naive_utc(),
+        audience: audience.map(str::to_string),
+        access_token_ttl_secs: access_ttl,
+        refresh_token_ttl_secs: None,
+        idle_timeout_secs: idle,
+        token_exchange_audiences: Vec::new(),
+    }
+}
+
+#[test]
+fn test_client_settings_fall_back_to_the_global_configuration() {
+    let config = app_config();
+    let global = TokenSettings::for_client(&client(None, None, None), &config);
+    assert_eq!(global.audience, "test-audience");
+    assert_eq!(global.access_token_ttl_secs, 300);
+    assert_eq!(global.refresh_token_ttl_secs, 86400);
+    assert_eq!(global.idle_timeout_secs, Some(3600));
+
+    let own = TokenSettings::for_client(&client(Some("analytics"), Some(60), Some(600)), &config);
+    assert_eq!(own.audience, "analytics");
+    assert_eq!(own.access_token_ttl_secs, 60);
+    assert_eq!(own.refresh_token_ttl_secs, 86400);
+    assert_eq!(own.idle_timeout_secs, Some(600));
+}
+
+#[test]
+fn test_client_credentials_tokens_use_the_client_settings() {
+    let config = app_config();
+    let claims = client_claims(&client(Some("analytics"), Some(60), None), "", &config);
+    assert_eq!(claims.aud, "analytics");
+    assert_eq!(claims.exp - claims.iat, 60);
+
+    let claims = client_claims(&client(None, None, None), "", &config);
+    assert_eq!(claims.aud, "test-audience");
+    assert_eq!(claims.exp - claims.iat, 300);
+}
+
+#[test]
+fn test_tokens_for_another_audience_are_only_accepted_without_audience_check() {
+    let config = app_config();
+    let keys = jwt_keys();
+    let token = keys
+        .sign(&client_claims(
+            &client(Some("analytics"), None, None),
+            "",
+            &config,
+        ))
+        .unwrap();
+    assert!(matches!(
+        keys.verify(&token, &config),
+        Err(ServiceError::Unauthorized(_))
+    ));
+    assert_eq!(
+        keys.verify_any_audience(&token, &config).unwrap().aud,
+        "analytics"
+    );
+}
+
+fn idle_for(conn: &mut PgConnection, session_id: uuid::Uuid, idle: Duration) {
+    diesel::update(sessions::table.find(session_id))
+        .set(sessions::last_seen_at.eq((Utc::now() - idle).naive_utc()))
+        .execute(conn)
+        .unwrap();
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_idle_sessions_cannot_be_refreshed() {
+    let pool = test_pool();
+    let config = app_config();
+    let client_info = ClientInfo::default();
+    let user = create_user(&pool);
+    let oauth_client = oauth_clients_repo::create(
+        &pool,
+        NewOAuthClient {
+            name: "Classroom display".into(),
+            client_secret_hash: None,
+            redirect_uris: Vec::new(),
+            allowed_scopes: Vec::new(),
+            first_party: true,
+            created_by: None,
+            audience: Some("classroom".into()),
+            access_token_ttl_secs: Some(120),
+            refresh_token_ttl_secs: Some(7200),
+            idle_timeout_secs: Some(600),
+            token_exchange_audiences: Vec::new(),
+        },
+    )
+    .unwrap();
+    let mut conn = establish_connection(&pool).unwrap();
+
+    // * Login diretto: timeout globale di un'ora
+    let direct =
+        session::start_session_with_connection(&mut conn, user.id, &client_info, &config).unwrap();
+    idle_for(&mut conn, direct.session.id, Duration::minutes(30));
+    let refreshed = session::refresh_with_connection(
+        &mut conn,
+        &direct.refresh_token,
+        &client_info,
+        None,
+        &config,
+    )
+    .unwrap();
+    idle_for(&mut conn, direct.session.id, Duration::minutes(90));
+    let expired = session::refresh_with_connection(
+        &mut conn,
+        &refreshed.refresh_token,
+        &client_info,
+        None,
+        &config,
+    );
+    let direct_session = sessions_repo::get_session(&pool, direct.session.id).unwrap();
+
+    // * Sessione del client: durata e timeout del client
+    let granted = session::start_client_session_with_connection(
+        &mut conn,
+        user.id,
+        &client_info,
+        &config,
+        Some(SessionGrant {
+            client: &oauth_client,
+            scope: "",
+            auth_time: Utc::now().naive_utc(),
+        }),
+    )
+    .unwrap();
+    idle_for(&mut conn, granted.session.id, Duration::minutes(30));
+    let client_expired = session::refresh_with_connection(
+        &mut conn,
+        &granted.refresh_token,
+        &client_info,
+        Some(&oauth_client),
+        &config,
+    );
+
+    diesel::delete(oauth_clients::table.find(oauth_client.id))
+        .execute(&mut conn)
+        .unwrap();
+    diesel::delete(users::table.find(user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(matches!(expired, Err(ServiceError::Unauthorized(_))));
+    assert!(direct_session.revoked_at.is_some());
+    let lifetime = granted.session.expires_at - granted.session.created_at;
+    assert!((lifetime - Duration::hours(2)).num_seconds().abs() < 5);
+    assert!(matches!(client_expired, Err(ServiceError::Unauthorized(_))));
+}
+
+#[test]
+#[ignore = "needs a database with the migrations applied (TEST_DATABASE_URL)"]
+fn test_inactive_accounts_cannot_rotate_their_refresh_token() {
+    let pool = test_pool();
+    let config = app_config();
+    let client_info = ClientInfo::default();
+    let user = create_user(&pool);
+    let mut conn = establish_connection(&pool).unwrap();
+    let issued =
+        session::start_session_with_connection(&mut conn, user.id, &client_info, &config).unwrap();
+    let set_status = |conn: &mut PgConnection, status: &str| {
+        diesel::update(users::table.find(user.id))
+            .set(users::status.eq(status))
+            .execute(conn)
+            .unwrap();
+    };
+
+    set_status(&mut conn, "disabled");
+    let refused = session::refresh_with_connection(
+        &mut conn,
+        &issued.refresh_token,
+        &client_info,
+        None,
+        &config,
+    );
+    // * Il token non è stato ruotato: riattivato l'account, lo stesso token funziona ancora
+    set_status(&mut conn, "active");
+    let refreshed = session::refresh_with_connection(
+        &mut conn,
+        &issued.refresh_token,
+        &client_info,
+        None,
+        &config,
+    );
+
+    diesel::delete(users::table.find(user.id))
+        .execute(&mut conn)
+        .unwrap();
+
+    assert!(matches!(refused, Err(ServiceError::AccountInactive { .. })));
+    assert!(refreshed.is_ok());
+}
@@ -52,8 +52,8 @@ mod tests {
 
     #[actix_web::test]
     async fn test_register_handler_success() {
-        let pool = web::Data::new(MockDbPool::default());
-        let config = web::Data::new(AppConfig::default());
+        let pool = web::Data::new(MockDbPool);
+        let config = web::Data::new(AppConfig);
         let user = RegisterUser {
             username: "testuser".into(),
             email: "test@example.com".into(),
@@ -67,8 +67,8 @@ mod tests {
 
     #[actix_web::test]
     async fn test_login_handler_success() {
-        let pool = web::Data::new(MockDbPool::default());
-        let config = web::Data::new(AppConfig::default());
+        let pool = web::Data::new(MockDbPool);
+        let config = web::Data::new(AppConfig);
         let login = LoginUser {
             username: "testuser".into(),
             password: "Password123!".into(),
@@ -81,8 +81,8 @@ mod tests {
 
     #[actix_web::test]
     async fn test_login_handler_invalid_password() {
-        let pool = web::Data::new(MockDbPool::default());
-        let config = web::Data::new(AppConfig::default());
+        let pool = web::Data::new(MockDbPool);
+        let config = web::Data::new(AppConfig);
         let login = LoginUser {
             username: "testuser".into(),
             password: "wrongpassword".into(),